@@ -8,7 +8,9 @@ Generates all latency graphs from benchmark data:
 - N=20 varying R and W analysis
 """
 
-import re
+import glob
+import json
+import os
 import sys
 from collections import defaultdict
 import matplotlib.pyplot as plt
@@ -18,56 +20,45 @@ import numpy as np
 # Parsing Functions
 # ============================================================================
 
-def parse_basic_logs(log_file):
-    """Parse basic Dynamo logs and extract latency measurements."""
-    latencies = {'get': [], 'put': []}
-
-    patterns = [
-        re.compile(r'\[.*?\]\s+(GET|PUT)\s+key=(\S+)\s+latency=([\d.]+)ms'),
-        re.compile(r'(GET|PUT)\s+key=(\S+)\s+latency=([\d.]+)ms'),
-        re.compile(r'(GET|PUT)\s+key=\S+\s+latency=([\d.]+)'),
-    ]
-
+def load_results(path):
+    """Load one benchmark results file (schema in src/results.rs)."""
     try:
-        with open(log_file, 'r') as f:
-            for line in f:
-                for pattern in patterns:
-                    match = pattern.search(line)
-                    if match:
-                        op_type = match.group(1).lower()
-                        latency_str = match.group(3) if len(match.groups()) >= 3 else match.group(2)
-                        try:
-                            latency = float(latency_str)
-                            latencies[op_type].append(latency)
-                            break
-                        except (ValueError, IndexError):
-                            continue
+        with open(path, 'r') as f:
+            return json.load(f)
     except FileNotFoundError:
-        print(f"Warning: Log file '{log_file}' not found")
+        print(f"Warning: Results file '{path}' not found")
         return None
 
+def latencies_ms(results):
+    """Per-op latency samples in ms, e.g. {'get': [...], 'put': [...]}."""
+    latencies = {'get': [], 'put': []}
+    for op, data in results.get('ops', {}).items():
+        if not data.get('samples_us'):
+            print(f"Warning: run '{results['run_id']}' has no samples for {op}; rerun without --no-samples")
+        latencies.setdefault(op, []).extend(us / 1000.0 for us in data.get('samples_us', []))
     return latencies
 
-def parse_config_logs(log_file):
-    """Parse configuration benchmark logs."""
-    config_data = defaultdict(lambda: {'get': [], 'put': []})
+def parse_basic_results(path):
+    """Latency samples of the basic run."""
+    results = load_results(path)
+    return latencies_ms(results) if results else None
 
-    pattern = re.compile(r'\[(N\d+_W\d+_R\d+)\]\s+(GET|PUT)\s+key=\S+\s+latency=([\d.]+)ms\s+N=(\d+)\s+W=(\d+)\s+R=(\d+)')
+def parse_config_results(results_dir):
+    """Latency samples and (N, W, R) of every configuration run, keyed by run id."""
+    config_data = defaultdict(lambda: {'get': [], 'put': []})
 
-    try:
-        with open(log_file, 'r') as f:
-            for line in f:
-                match = pattern.search(line)
-                if match:
-                    config_name = match.group(1)
-                    op_type = match.group(2).lower()
-                    latency = float(match.group(3))
-                    n, w, r = int(match.group(4)), int(match.group(5)), int(match.group(6))
-
-                    config_data[config_name][op_type].append(latency)
-                    config_data[config_name]['params'] = (n, w, r)
-    except FileNotFoundError:
-        print(f"Warning: Log file '{log_file}' not found")
+    for path in sorted(glob.glob(os.path.join(results_dir, '*.json'))):
+        results = load_results(path)
+        if not results:
+            continue
+        cfg = results['config']
+        config_name = results['run_id']
+        for op, values in latencies_ms(results).items():
+            config_data[config_name].setdefault(op, []).extend(values)
+        config_data[config_name]['params'] = (cfg['n'], cfg['w'], cfg['r'])
+
+    if not config_data:
+        print(f"Warning: No results found in '{results_dir}'")
         return None
 
     return config_data
@@ -433,7 +424,7 @@ def main():
 
     # 1. Basic Latency Graphs
     print("1. Generating Basic Latency Graphs...")
-    latencies = parse_basic_logs('latency.log')
+    latencies = parse_basic_results('results/basic.json')
     if latencies and (latencies['get'] or latencies['put']):
         files = generate_basic_graphs(latencies)
         total_graphs += len(files)
@@ -444,7 +435,7 @@ def main():
 
     # 2. Configuration Comparison Graphs
     print("2. Generating Configuration Comparison Graphs...")
-    config_data = parse_config_logs('latency_configs.log')
+    config_data = parse_config_results('results/configs')
     if config_data:
         files = generate_config_graphs(config_data)
         total_graphs += len(files)
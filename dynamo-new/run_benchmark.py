@@ -6,14 +6,25 @@ Runs the real_benchmark Rust binary with different quorum configurations
 to collect 100% REAL latency data from actual Dynamo operations.
 
 NO simulated data - all operations execute real Dynamo logic.
+
+Extra arguments are passed to every real_benchmark run, e.g.
+`python3 run_benchmark.py --network --clients 4 --outstanding 8` runs each
+configuration against DynamoNode actors over localhost TCP.
 """
 
 import subprocess
 import sys
 import os
 
-def run_real_benchmark(num_ops, n=3, w=2, r=2):
-    """Run the real_benchmark binary with specific N/W/R configuration."""
+RESULTS_DIR = "results"
+SUMMARY_CSV = os.path.join(RESULTS_DIR, "summary.csv")
+EXTRA_ARGS = sys.argv[1:]
+
+def run_real_benchmark(num_ops, n=3, w=2, r=2, run_id=None, out=None):
+    """Run the real_benchmark binary with specific N/W/R configuration.
+
+    Returns the path of the JSON results file (see src/results.rs), or None on failure.
+    """
     binary_path = "./target/debug/real_benchmark"
 
     if not os.path.exists(binary_path):
@@ -21,10 +32,15 @@ def run_real_benchmark(num_ops, n=3, w=2, r=2):
         print("Run 'cargo build --bin real_benchmark' first")
         return None
 
+    run_id = run_id or f"N{n}_W{w}_R{r}"
+    out = out or os.path.join(RESULTS_DIR, f"{run_id}.json")
+    os.makedirs(os.path.dirname(out), exist_ok=True)
+
     try:
         # Run binary with N, W, R parameters
         result = subprocess.run(
-            [binary_path, str(num_ops), str(n), str(w), str(r)],
+            [binary_path, str(num_ops), str(n), str(w), str(r),
+             "--run-id", run_id, "--out", out, "--csv", SUMMARY_CSV] + EXTRA_ARGS,
             capture_output=True,
             text=True,
             timeout=300
@@ -32,11 +48,7 @@ def run_real_benchmark(num_ops, n=3, w=2, r=2):
 
         if result.returncode == 0:
             print(result.stdout)
-            # Read the generated latency.log
-            if os.path.exists("latency.log"):
-                with open("latency.log", 'r') as f:
-                    return f.readlines()
-            return None
+            return out if os.path.exists(out) else None
         else:
             print(f"Benchmark failed: {result.stderr}")
             return None
@@ -102,8 +114,12 @@ def run_all_benchmarks():
     print("   ✓ Quorum coordination")
     print()
 
-    basic_latencies = run_real_benchmark(1000, 3, 2, 2)
-    if not basic_latencies:
+    # start a fresh summary table for this collection
+    if os.path.exists(SUMMARY_CSV):
+        os.remove(SUMMARY_CSV)
+
+    basic_results = run_real_benchmark(1000, 3, 2, 2, run_id="basic", out=os.path.join(RESULTS_DIR, "basic.json"))
+    if not basic_results:
         print("✗ Basic benchmark failed")
         return 1
 
@@ -114,33 +130,20 @@ def run_all_benchmarks():
     print("   Each config runs REAL Dynamo operations")
     print()
 
-    with open("latency_configs.log", 'w') as config_log:
-        for i, (config_name, n, w, r) in enumerate(configs, 1):
-            print(f"   Config {config_name} (N={n}, W={w}, R={r}): {i}/{len(configs)}...", end='', flush=True)
-
-            # Run REAL benchmark with this configuration
-            config_latencies = run_real_benchmark(200, n, w, r)
-
-            if config_latencies:
-                # Write to config log with metadata
-                for line in config_latencies:
-                    line = line.strip()
-                    if line:
-                        # Add config metadata to each line
-                        config_log.write(f"[{config_name}] {line} N={n} W={w} R={r}\n")
-                print(" ✓")
-            else:
-                print(" ✗ FAILED")
+    for i, (config_name, n, w, r) in enumerate(configs, 1):
+        print(f"   Config {config_name} (N={n}, W={w}, R={r}): {i}/{len(configs)}...", end='', flush=True)
 
-    # Save basic latency test to latency.log (after all config runs)
-    with open("latency.log", 'w') as f:
-        f.writelines(basic_latencies)
+        # Run REAL benchmark with this configuration
+        config_results = run_real_benchmark(200, n, w, r, run_id=config_name,
+                                            out=os.path.join(RESULTS_DIR, "configs", f"{config_name}.json"))
+        print(" ✓" if config_results else " ✗ FAILED")
 
     print()
     print("="*60)
     print("✓ All benchmarks complete!")
-    print("  Basic latency: latency.log (1000 REAL ops)")
-    print("  Config comparison: latency_configs.log (4800 REAL ops)")
+    print("  Basic latency: results/basic.json (1000 REAL ops)")
+    print("  Config comparison: results/configs/*.json (4800 REAL ops)")
+    print("  Summary table: results/summary.csv")
     print("="*60)
     print()
     print("All data collected from REAL Dynamo operations:")
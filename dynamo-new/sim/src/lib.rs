@@ -0,0 +1,340 @@
+//! Deterministic in-process simulator for `dynamo-new`.
+//!
+//! Instantiates `DynamoNode`s and `DynamoClient`s in one process and drives them through
+//! `ActorProcess::process`, routing outputs the same way `DynamoNodeSender` and
+//! `DynamoClientSender` do. Every message gets a seeded delay and may be lost or cut off by a
+//! partition, so a run is fully determined by its `SimConfig`. All nodes share a `ManualClock`
+//! that follows virtual time, and the timers each node sets are queued on that time as well, so
+//! replica timeouts, retries and pings fire on simulated time even with no traffic left.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use log::debug;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use reactor_actor::{ActorProcess, CorrelationId, Msg, TimerCmd, TimerId, TimerQueue, timer_channel};
+
+use dynamo_new::client::DynamoClient;
+use dynamo_new::clock::{Clock, ManualClock};
+use dynamo_new::history::{HistoryEvent, HistoryRecorder};
+use dynamo_new::messages::{ClientToNode, DynamoClientIn, DynamoClientOut, DynamoNodeIn, DynamoNodeOut, GeneratorTrigger, NodeToClient};
+use dynamo_new::node::DynamoNode;
+use dynamo_new::session::SessionPolicy;
+use dynamo_new::vector_clock::VectorClock;
+
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    pub seed: u64,
+    /// Probability that any single message is dropped.
+    pub loss: f64,
+    /// Per-message delivery delay, drawn uniformly from `min_delay_ms..=max_delay_ms` of virtual time.
+    pub min_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub n: usize,
+    pub w: usize,
+    pub r: usize,
+    pub t: usize,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self { Self { seed: 1, loss: 0.0, min_delay_ms: 1, max_delay_ms: 10, n: 3, w: 2, r: 2, t: 10 } }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SimStats {
+    pub sent: u64,
+    pub delivered: u64,
+    pub lost: u64,
+    pub partitioned: u64,
+    pub to_stopped: u64,
+    pub undeliverable: u64,
+    pub timers_fired: u64,
+    /// Replies that arrived after their ask had timed out, dropped as the actor would.
+    pub late_replies: u64,
+}
+
+/// A PUT the cluster acknowledged to a client.
+#[derive(Debug, Clone)]
+pub struct AckedWrite { pub key: String, pub clock: VectorClock, pub at_ms: u64 }
+
+enum Payload { Node(DynamoNodeIn), Client(DynamoClientIn) }
+
+struct Envelope { from: String, to: String, payload: Payload }
+
+// A timer a node set on itself, due at its key in `Sim::timers`
+struct SimTimer { node: String, id: TimerId, period: Option<Duration>, msg: DynamoNodeIn, ask: Option<CorrelationId> }
+
+pub struct Sim {
+    cfg: SimConfig,
+    rng: StdRng,
+    now_ms: u64,
+    clock: ManualClock,
+    epoch: Instant, // virtual time zero, for client histories
+    seq: u64,
+    nodes: BTreeMap<String, DynamoNode>,
+    clients: BTreeMap<String, DynamoClient>,
+    queue: BTreeMap<(u64, u64), Envelope>, // (deliver_at, seq) -> message
+    timer_queues: BTreeMap<String, TimerQueue<DynamoNodeIn>>,
+    timers: BTreeMap<(u64, u64), SimTimer>, // (fire_at, seq) -> timer; seq shared with `queue`
+    timer_keys: HashMap<(String, TimerId), (u64, u64)>, // for cancelling
+    groups: HashMap<String, usize>, // partition group per actor; empty = fully connected
+    stopped: BTreeSet<String>,
+    requests: HashMap<(String, u64), u64>, // (client_addr, request_id) -> times sent
+    responses: HashMap<(String, u64), u64>, // (client_addr, request_id) -> times answered
+    acked: Vec<AckedWrite>,
+    trace: DefaultHasher,
+    pub stats: SimStats,
+}
+
+impl Sim {
+    pub fn new(cfg: SimConfig, node_ids: &[&str]) -> Self {
+        let ids: Vec<String> = node_ids.iter().map(|s| s.to_string()).collect();
+        let clock = ManualClock::new();
+        let mut timer_queues = BTreeMap::new();
+        let nodes = ids.iter().map(|id| {
+            let (timers, queue) = timer_channel();
+            timer_queues.insert(id.clone(), queue);
+            (id.clone(), DynamoNode::new(id.clone(), ids.clone(), cfg.n, cfg.w, cfg.r, cfg.t, Arc::new(clock.clone())).with_timers(timers))
+        }).collect();
+        let mut sim = Self {
+            rng: StdRng::seed_from_u64(cfg.seed),
+            cfg,
+            now_ms: 0,
+            epoch: clock.now(),
+            clock,
+            seq: 0,
+            nodes,
+            clients: BTreeMap::new(),
+            queue: BTreeMap::new(),
+            timer_queues,
+            timers: BTreeMap::new(),
+            timer_keys: HashMap::new(),
+            groups: HashMap::new(),
+            stopped: BTreeSet::new(),
+            requests: HashMap::new(),
+            responses: HashMap::new(),
+            acked: vec![],
+            trace: DefaultHasher::new(),
+            stats: SimStats::default(),
+        };
+        for id in ids.iter() { sim.take_timers(id); }
+        sim
+    }
+
+    pub fn now_ms(&self) -> u64 { self.now_ms }
+    pub fn node(&self, id: &str) -> &DynamoNode { &self.nodes[id] }
+    pub fn node_ids(&self) -> Vec<String> { self.nodes.keys().cloned().collect() }
+    pub fn client(&self, id: &str) -> &DynamoClient { &self.clients[id] }
+    pub fn acked_writes(&self) -> &[AckedWrite] { &self.acked }
+    pub fn in_flight(&self) -> usize { self.queue.len() }
+
+    /// Fingerprint of every delivery so far (time, endpoints, message kind); equal across runs with the same config.
+    pub fn trace_hash(&self) -> u64 { self.trace.finish() }
+
+    /// Merged history of all scripted clients, timestamped in virtual time; input for `checker::check`.
+    pub fn history(&self) -> Vec<HistoryEvent> {
+        let mut events: Vec<HistoryEvent> = self.clients.values().filter_map(|c| c.history()).flat_map(|h| h.events().iter().cloned()).collect();
+        events.sort_by_key(|e| e.ts_us());
+        events
+    }
+
+    /// Adds a scripted `DynamoClient` and kicks it off like the single generator trigger in `client_behaviour`.
+    pub fn add_client(&mut self, id: &str, reqs: Vec<ClientToNode>, session: Option<SessionPolicy>) {
+        let history = HistoryRecorder::in_memory().with_clock(Arc::new(self.clock.clone()), self.epoch);
+        let mut client = DynamoClient::new(id.to_string(), self.node_ids(), reqs).with_history(history);
+        if let Some(policy) = session { client = client.with_session(policy); }
+        let out = client.process(DynamoClientIn::GeneratorTrigger(GeneratorTrigger));
+        self.clients.insert(id.to_string(), client);
+        for o in out { self.route_client_out(id, o); }
+    }
+
+    /// Injects a client request at `via`, as if sent by an external client `client_addr` (no actor needed).
+    pub fn request(&mut self, via: &str, req: ClientToNode) {
+        let client_addr = match &req { ClientToNode::ClientPut{ client_addr, .. } | ClientToNode::ClientGet{ client_addr, .. } => client_addr.clone() };
+        self.note_request(&req);
+        self.send(client_addr, via.to_string(), Payload::Node(DynamoNodeIn::ClientToNode(req)));
+    }
+
+    /// Splits actors into groups that cannot reach each other; actors not listed form their own group.
+    pub fn partition(&mut self, groups: &[&[&str]]) {
+        self.groups.clear();
+        for (i, g) in groups.iter().enumerate() { for id in g.iter() { self.groups.insert(id.to_string(), i); } }
+    }
+
+    pub fn heal(&mut self) { self.groups.clear(); }
+
+    /// Stops delivering to and from `id`; the node keeps its in-memory state.
+    pub fn stop(&mut self, id: &str) { self.stopped.insert(id.to_string()); }
+
+    pub fn start(&mut self, id: &str) { self.stopped.remove(id); }
+
+    /// Delivers the next message, first firing any node timers due before it; returns false once
+    /// no message is in flight. Timers alone do not keep a run going, see `run_until`.
+    pub fn step(&mut self) -> bool {
+        let Some(&(at, _)) = self.queue.keys().next() else { return false };
+        if self.timers.keys().next().is_some_and(|(t, _)| *t <= at) { self.fire_timer(); } else { self.deliver(); }
+        true
+    }
+
+    fn deliver(&mut self) {
+        let Some(((at, _), env)) = self.queue.pop_first() else { return };
+        self.set_now(at);
+        if self.stopped.contains(&env.to) || self.stopped.contains(&env.from) { self.stats.to_stopped += 1; return; }
+        if !self.reachable(&env.from, &env.to) { self.stats.partitioned += 1; return; }
+        self.stats.delivered += 1;
+        (at, &env.from, &env.to).hash(&mut self.trace);
+        match env.payload {
+            Payload::Node(msg) => {
+                std::mem::discriminant(&msg).hash(&mut self.trace);
+                let Some(node) = self.nodes.get_mut(&env.to) else { self.stats.undeliverable += 1; return };
+                // a reply is let through only while its ask waits, as the actor does
+                if let Some(id) = msg.reply_to() && !self.timer_queues[&env.to].settle(id) { self.stats.late_replies += 1; return; }
+                let out = node.process(msg);
+                for o in out { self.route_node_out(&env.to, o); }
+                self.take_timers(&env.to);
+            }
+            Payload::Client(msg) => {
+                std::mem::discriminant(&msg).hash(&mut self.trace);
+                let Some(client) = self.clients.get_mut(&env.to) else { self.stats.undeliverable += 1; return };
+                let out = client.process(msg);
+                for o in out { self.route_client_out(&env.to, o); }
+            }
+        }
+    }
+
+    // The earliest timer; a periodic one is queued again for its next period
+    fn fire_timer(&mut self) {
+        let Some(((at, _), t)) = self.timers.pop_first() else { return };
+        self.timer_keys.remove(&(t.node.clone(), t.id));
+        self.set_now(at);
+        // an answered ask's timeout is left to expire here rather than cancelled
+        if let Some(ask) = t.ask && !self.timer_queues[&t.node].settle(ask) { return; }
+        if let Some(period) = t.period { self.schedule_timer(&t.node, t.id, period, Some(period), t.msg.clone(), None); }
+        self.stats.timers_fired += 1;
+        (at, &t.node, &t.node).hash(&mut self.trace);
+        std::mem::discriminant(&t.msg).hash(&mut self.trace);
+        let Some(node) = self.nodes.get_mut(&t.node) else { return };
+        let out = node.process(t.msg);
+        for o in out { self.route_node_out(&t.node, o); }
+        self.take_timers(&t.node);
+    }
+
+    // Applies the timers `node` set or cancelled while processing
+    fn take_timers(&mut self, node: &str) {
+        let Some(queue) = self.timer_queues.get_mut(node) else { return };
+        let cmds: Vec<TimerCmd<DynamoNodeIn>> = std::iter::from_fn(|| queue.try_next()).collect();
+        for cmd in cmds {
+            match cmd {
+                TimerCmd::Set{ id, delay, period, msg, ask } => self.schedule_timer(node, id, delay, period, msg, ask),
+                TimerCmd::Cancel(id) => { if let Some(key) = self.timer_keys.remove(&(node.to_string(), id)) { self.timers.remove(&key); } }
+            }
+        }
+    }
+
+    fn schedule_timer(&mut self, node: &str, id: TimerId, delay: Duration, period: Option<Duration>, msg: DynamoNodeIn, ask: Option<CorrelationId>) {
+        self.seq += 1;
+        let key = (self.now_ms + delay.as_millis() as u64, self.seq);
+        self.timer_keys.insert((node.to_string(), id), key);
+        self.timers.insert(key, SimTimer{ node: node.to_string(), id, period, msg, ask });
+    }
+
+    /// Runs until nothing is in flight or `max_steps` deliveries were made; returns the number of steps taken.
+    pub fn run(&mut self, max_steps: usize) -> usize {
+        let mut steps = 0;
+        while steps < max_steps && self.step() { steps += 1; }
+        steps
+    }
+
+    /// Runs every delivery and timer scheduled up to virtual time `until_ms`.
+    pub fn run_until(&mut self, until_ms: u64) {
+        loop {
+            let message = self.queue.keys().next().copied();
+            let timer = self.timers.keys().next().copied();
+            match (message, timer) {
+                (_, Some(t)) if t.0 <= until_ms && message.is_none_or(|m| t <= m) => self.fire_timer(),
+                (Some(m), _) if m.0 <= until_ms => self.deliver(),
+                _ => break,
+            }
+        }
+        self.set_now(until_ms);
+    }
+
+    /// Safety invariants that must hold at any point of any run:
+    /// - every acknowledged write is held (or superseded) by at least W replicas;
+    /// - no replica keeps a sibling that another of its siblings descends from;
+    /// - no client request is answered more often than it was sent.
+    pub fn check_invariants(&self) -> Result<(), Vec<String>> {
+        let mut errs = vec![];
+        for a in self.acked.iter() {
+            let holders = self.nodes.values().filter(|n| n.local(&a.key).is_some_and(|vs| vs.versions.iter().any(|v| v.clock.descends(&a.clock)))).count();
+            if holders < self.cfg.w { errs.push(format!("acked write key={} clock={:?} held by {} < W={} replicas", a.key, a.clock.clock, holders, self.cfg.w)); }
+        }
+        for (id, node) in self.nodes.iter() {
+            for key in node.local_keys() {
+                let vs = &node.local(key).unwrap().versions;
+                for (i, a) in vs.iter().enumerate() {
+                    if vs.iter().enumerate().any(|(j, b)| i != j && b.clock.descends(&a.clock)) {
+                        errs.push(format!("node={} key={} keeps dominated sibling {:?}", id, key, a.clock.clock));
+                    }
+                }
+            }
+        }
+        for (req, answered) in self.responses.iter() {
+            let sent = self.requests.get(req).copied().unwrap_or(0);
+            if *answered > sent { errs.push(format!("client={} request={} answered {} times but sent {}", req.0, req.1, answered, sent)); }
+        }
+        if errs.is_empty() { Ok(()) } else { Err(errs) }
+    }
+
+    fn set_now(&mut self, ms: u64) {
+        self.now_ms = self.now_ms.max(ms);
+        self.clock.set_elapsed(Duration::from_millis(self.now_ms));
+    }
+
+    fn reachable(&self, a: &str, b: &str) -> bool {
+        if self.groups.is_empty() { return true; }
+        self.groups.get(a) == self.groups.get(b)
+    }
+
+    fn note_request(&mut self, req: &ClientToNode) {
+        let (ClientToNode::ClientPut{ client_addr, request_id, .. } | ClientToNode::ClientGet{ client_addr, request_id, .. }) = req;
+        *self.requests.entry((client_addr.clone(), *request_id)).or_default() += 1;
+    }
+
+    fn send(&mut self, from: String, to: String, payload: Payload) {
+        self.stats.sent += 1;
+        if self.cfg.loss > 0.0 && self.rng.random::<f64>() < self.cfg.loss {
+            self.stats.lost += 1;
+            debug!("[sim] t={} lost {} -> {}", self.now_ms, from, to);
+            return;
+        }
+        let delay = self.rng.random_range(self.cfg.min_delay_ms..=self.cfg.max_delay_ms.max(self.cfg.min_delay_ms));
+        self.seq += 1;
+        self.queue.insert((self.now_ms + delay, self.seq), Envelope{ from, to, payload });
+    }
+
+    fn route_node_out(&mut self, from: &str, o: DynamoNodeOut) {
+        let to = o.destination().to_string();
+        match o {
+            DynamoNodeOut::NodeToNode(m) => self.send(from.to_string(), to, Payload::Node(DynamoNodeIn::NodeToNode(m))),
+            DynamoNodeOut::NodeToClient(m) => {
+                let (NodeToClient::ClientPutRsp{ request_id, .. } | NodeToClient::ClientGetRsp{ request_id, .. } | NodeToClient::ClientPutRejected{ request_id, .. }) = &m;
+                *self.responses.entry((to.clone(), *request_id)).or_default() += 1;
+                if let NodeToClient::ClientPutRsp{ key, clock, .. } = &m { self.acked.push(AckedWrite{ key: key.clone(), clock: clock.clone(), at_ms: self.now_ms }); }
+                // responses to external clients (see `request`) are only recorded
+                if self.clients.contains_key(&to) { self.send(from.to_string(), to, Payload::Client(DynamoClientIn::NodeToClient(m))); }
+            }
+        }
+    }
+
+    fn route_client_out(&mut self, from: &str, o: DynamoClientOut) {
+        let DynamoClientOut::ClientToNode(req) = o;
+        self.note_request(&req);
+        // DynamoClientSender picks a random node; here the pick comes from the seeded rng
+        let ids = self.node_ids();
+        let to = ids[self.rng.random_range(0..ids.len())].clone();
+        self.send(from.to_string(), to, Payload::Node(DynamoNodeIn::ClientToNode(req)));
+    }
+}
@@ -0,0 +1,123 @@
+// Simulator Tests
+// Whole-cluster runs under seeded loss, delay and partitions
+
+use dynamo_new::checker::{check, CheckOptions};
+use dynamo_new::messages::ClientToNode;
+use dynamo_sim::{Sim, SimConfig};
+
+const NODES: [&str; 5] = ["nodeA", "nodeB", "nodeC", "nodeD", "nodeE"];
+
+fn put(client: &str, request_id: u64, key: &str, value: &str) -> ClientToNode {
+    ClientToNode::ClientPut { key: key.to_string(), value: value.to_string(), metadata: vec![], client_addr: client.to_string(), request_id }
+}
+
+fn get(client: &str, request_id: u64, key: &str) -> ClientToNode {
+    ClientToNode::ClientGet { key: key.to_string(), client_addr: client.to_string(), request_id }
+}
+
+fn script(client: &str, ops: u64) -> Vec<ClientToNode> {
+    (0..ops).map(|i| if i % 2 == 0 { put(client, i + 1, &format!("key{}", i % 7), &format!("{}-{}", client, i)) } else { get(client, i + 1, &format!("key{}", (i - 1) % 7)) }).collect()
+}
+
+fn workload(cfg: SimConfig) -> Sim {
+    let mut sim = Sim::new(cfg, &NODES);
+    for c in ["client1", "client2", "client3"] { sim.add_client(c, script(c, 40), None); }
+    sim.run(100_000);
+    sim
+}
+
+#[cfg(test)]
+mod sim_tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_network_completes_workload() {
+        let sim = workload(SimConfig::default());
+        assert_eq!(sim.in_flight(), 0);
+        assert_eq!(sim.acked_writes().len(), 60);
+        assert_eq!(sim.stats.lost, 0);
+        sim.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_same_seed_same_run() {
+        let cfg = SimConfig { seed: 7, loss: 0.05, ..SimConfig::default() };
+        let a = workload(cfg.clone());
+        let b = workload(cfg);
+        assert_eq!(a.trace_hash(), b.trace_hash());
+        assert_eq!(a.stats, b.stats);
+        assert_eq!(a.acked_writes().len(), b.acked_writes().len());
+
+        let c = workload(SimConfig { seed: 8, loss: 0.05, ..SimConfig::default() });
+        assert_ne!(a.trace_hash(), c.trace_hash());
+    }
+
+    #[test]
+    fn test_invariants_hold_under_loss() {
+        for seed in 0..20 {
+            let sim = workload(SimConfig { seed, loss: 0.1, min_delay_ms: 1, max_delay_ms: 50, ..SimConfig::default() });
+            assert!(sim.stats.lost > 0);
+            if let Err(errs) = sim.check_invariants() { panic!("seed {}: {:?}", seed, errs); }
+        }
+    }
+
+    #[test]
+    fn test_minority_partition_blocks_quorum() {
+        let mut sim = Sim::new(SimConfig::default(), &NODES);
+        // client and its coordinator are cut off from every other replica
+        sim.partition(&[&["ext", "nodeA"], &["nodeB", "nodeC", "nodeD", "nodeE"]]);
+        for i in 0..10 { sim.request("nodeA", put("ext", i, &format!("key{}", i), "v")); }
+        sim.run(10_000);
+        let acked_in_partition = sim.acked_writes().len();
+        assert!(sim.stats.partitioned > 0);
+        // only keys nodeA can satisfy alone would be acked, and W=2 needs a second replica
+        assert_eq!(acked_in_partition, 0);
+        sim.check_invariants().unwrap();
+
+        sim.heal();
+        for i in 10..20 { sim.request("nodeA", put("ext", i, &format!("key{}", i), "v")); }
+        sim.run(10_000);
+        assert!(sim.acked_writes().len() > acked_in_partition);
+        sim.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_isolated_coordinator_times_out_without_traffic() {
+        let mut sim = Sim::new(SimConfig::default(), &NODES);
+        sim.partition(&[&["ext", "nodeA"], &["nodeB", "nodeC", "nodeD", "nodeE"]]);
+        for i in 0..10 { sim.request("nodeA", put("ext", i, &format!("key{}", i), "v")); }
+        sim.run(10_000);
+        assert_eq!(sim.in_flight(), 0);
+        assert_eq!(sim.node("nodeA").metrics().replica_timeouts, 0, "no timeout is due yet");
+
+        // nothing else is sent; only nodeA's own timers can notice the silent replicas
+        let sent = sim.stats.sent;
+        sim.run_until(sim.now_ms() + 2_000);
+        assert!(sim.stats.timers_fired > 0);
+        assert!(sim.node("nodeA").metrics().replica_timeouts > 0);
+        assert!(!sim.node("nodeA").stats().failed.is_empty());
+        assert!(sim.stats.sent > sent, "timed-out requests are retried elsewhere");
+        assert!(sim.acked_writes().is_empty());
+        sim.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn test_stopped_node_drops_traffic() {
+        let mut sim = Sim::new(SimConfig::default(), &NODES);
+        sim.stop("nodeC");
+        sim.add_client("client1", script("client1", 10), None);
+        sim.run(10_000);
+        sim.check_invariants().unwrap();
+        assert!(sim.node("nodeC").local_keys().next().is_none());
+    }
+
+    #[test]
+    fn test_recorded_history_passes_checker() {
+        let sim = workload(SimConfig::default());
+        let history = sim.history();
+        assert_eq!(history.len(), 240, "an invoke and a completion per request");
+        let report = check(&history, &CheckOptions::default());
+        assert_eq!((report.ops, report.incomplete), (120, 0));
+        assert!(report.is_ok(), "{:?}", report.anomalies);
+    }
+}
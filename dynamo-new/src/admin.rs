@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::time::Duration;
+use log::{info, warn};
+use reactor_actor::{ActorProcess, BehaviourBuilder, RuntimeCtx, TimerHandle, timer_channel};
+use reactor_actor::codec::BincodeCodec;
+
+use crate::config::TuningUpdate;
+use crate::messages::{DynamoAdminIn, DynamoNodeOut, GeneratorTrigger, NodeToNode};
+use crate::node::DynamoNodeSender;
+use crate::snapshot::Snapshot;
+use crate::versioned_value::VersionedValues;
+
+/// Snapshot keys an import keeps in flight before waiting for `ImportAck`s.
+pub const IMPORT_WINDOW: usize = 64;
+
+/// How often an admin that polls only once re-asks for missing snapshot chunks and import acks.
+pub const SNAPSHOT_RETRY_MS: u64 = 1000;
+
+// A snapshot being collected from the nodes that have not sent their last chunk yet
+struct Export { path: PathBuf, snapshot: Snapshot, waiting: HashSet<String>, requested: bool }
+
+// A snapshot being loaded; unacked keys are resent on every poll
+struct Import { queue: VecDeque<(String, VersionedValues)>, in_flight: BTreeMap<u64, (String, VersionedValues)>, next_id: u64, acked: u64, total: u64 }
+
+/// Admin actor that periodically polls every node for its stats and, if configured,
+/// writes each node's metrics to `<metrics_dir>/<node>.prom` in Prometheus text format.
+/// A configured `TuningUpdate` is sent once, before the first poll. Snapshot exports and imports
+/// start on the first poll; later polls re-ask nodes that have not answered.
+pub struct DynamoAdmin {
+    admin_id: String,
+    nodes: Vec<String>,
+    metrics_dir: Option<PathBuf>,
+    reconfigure: Option<(TuningUpdate, bool)>,
+    export: Option<Export>,
+    import: Option<Import>,
+    timers: Option<TimerHandle<DynamoAdminIn>>,
+    // zero polls the stats once
+    interval: Duration,
+    polled: bool,
+}
+
+impl DynamoAdmin {
+    pub fn new(admin_id: String, nodes: Vec<String>, metrics_dir: Option<PathBuf>) -> Self {
+        Self { admin_id, nodes, metrics_dir, reconfigure: None, export: None, import: None, timers: None, interval: Duration::ZERO, polled: false }
+    }
+
+    /// Schedules each poll with `timers`: every `interval_ms`, or with `interval_ms == 0` once,
+    /// then only every `SNAPSHOT_RETRY_MS` while a snapshot export or import is unfinished.
+    pub fn with_timers(mut self, timers: TimerHandle<DynamoAdminIn>, interval_ms: u64) -> Self {
+        self.timers = Some(timers);
+        self.interval = Duration::from_millis(interval_ms);
+        self
+    }
+
+    /// Exports every node's store, merged per key, to `path` (see `Snapshot`).
+    pub fn with_export(mut self, path: PathBuf) -> Self {
+        let waiting = self.nodes.iter().cloned().collect();
+        self.export = Some(Export { path, snapshot: Snapshot::new(), waiting, requested: false });
+        self
+    }
+
+    /// Loads `snapshot` into the cluster: each key is sent to a node, round robin, which writes it
+    /// to the key's replicas. Keys keep their sibling versions and vector clocks.
+    pub fn with_import(mut self, snapshot: &Snapshot) -> Self {
+        let queue: VecDeque<(String, VersionedValues)> = snapshot.entries().map(|(k, v)| (k.clone(), v.clone())).collect();
+        let total = queue.len() as u64;
+        self.import = Some(Import { queue, in_flight: BTreeMap::new(), next_id: 0, acked: 0, total });
+        self
+    }
+
+    /// True once the configured export has been written and the import fully acknowledged.
+    pub fn snapshot_done(&self) -> bool { self.export.is_none() && self.import.is_none() }
+
+    fn export_poll(&mut self) -> Vec<DynamoNodeOut> {
+        let Some(export) = self.export.as_mut() else { return vec![] };
+        if export.requested {
+            warn!("[admin] snapshot still waiting for {:?}; asking again", export.waiting);
+        }
+        export.requested = true;
+        let mut waiting: Vec<&String> = export.waiting.iter().collect();
+        waiting.sort();
+        waiting.into_iter().map(|n| DynamoNodeOut::NodeToNode(NodeToNode::SnapshotReq{ from: self.admin_id.clone(), to: n.clone() })).collect()
+    }
+
+    fn on_snapshot_chunk(&mut self, from: String, entries: Vec<(String, VersionedValues)>, last: bool) {
+        let Some(export) = self.export.as_mut() else { return };
+        if !export.waiting.contains(&from) { return; }
+        export.snapshot.add_node(&from);
+        for (key, values) in entries { export.snapshot.add(&from, key, &values); }
+        if !last { return; }
+        export.waiting.remove(&from);
+        if !export.waiting.is_empty() { return; }
+        let export = self.export.take().unwrap();
+        match export.snapshot.write(&export.path) {
+            Ok(()) => info!("[admin] snapshot of {} keys from {} nodes written to {}", export.snapshot.len(), export.snapshot.nodes().len(), export.path.display()),
+            Err(e) => warn!("[admin] failed to write snapshot {}: {}", export.path.display(), e),
+        }
+    }
+
+    fn import_key(&self, import_id: u64, key: &str, values: &VersionedValues) -> DynamoNodeOut {
+        let to = self.nodes[import_id as usize % self.nodes.len()].clone();
+        DynamoNodeOut::NodeToNode(NodeToNode::ImportKey{ from: self.admin_id.clone(), to, key: key.to_string(), values: values.clone(), import_id })
+    }
+
+    // Tops the window up to IMPORT_WINDOW keys in flight
+    fn import_fill(&mut self) -> Vec<DynamoNodeOut> {
+        let mut sent = vec![];
+        if let Some(import) = self.import.as_mut() {
+            while import.in_flight.len() < IMPORT_WINDOW && let Some((key, values)) = import.queue.pop_front() {
+                let id = import.next_id;
+                import.next_id += 1;
+                import.in_flight.insert(id, (key.clone(), values.clone()));
+                sent.push((id, key, values));
+            }
+        }
+        sent.iter().map(|(id, key, values)| self.import_key(*id, key, values)).collect()
+    }
+
+    fn import_poll(&mut self) -> Vec<DynamoNodeOut> {
+        if self.nodes.is_empty() { return vec![]; }
+        let Some(import) = self.import.as_ref() else { return vec![] };
+        if import.next_id > 0 {
+            info!("[admin] import acked={}/{} resending={}", import.acked, import.total, import.in_flight.len());
+        }
+        let mut out: Vec<DynamoNodeOut> = import.in_flight.iter().map(|(id, (key, values))| self.import_key(*id, key, values)).collect();
+        out.extend(self.import_fill());
+        self.import_finish();
+        out
+    }
+
+    fn on_import_ack(&mut self, import_id: u64) -> Vec<DynamoNodeOut> {
+        let Some(import) = self.import.as_mut() else { return vec![] };
+        if import.in_flight.remove(&import_id).is_none() { return vec![]; }
+        import.acked += 1;
+        let out = self.import_fill();
+        self.import_finish();
+        out
+    }
+
+    fn import_finish(&mut self) {
+        if let Some(import) = self.import.as_ref() && import.queue.is_empty() && import.in_flight.is_empty() {
+            info!("[admin] import of {} keys complete", import.total);
+            self.import = None;
+        }
+    }
+
+    fn arm_next_poll(&self) {
+        let Some(timers) = self.timers.as_ref() else { return };
+        let next = if !self.interval.is_zero() { self.interval } else if !self.snapshot_done() { Duration::from_millis(SNAPSHOT_RETRY_MS) } else { return };
+        timers.set_once(next, DynamoAdminIn::GeneratorTrigger(GeneratorTrigger));
+    }
+
+    /// With `propagate` the update goes to the first node, which forwards it to the rest of the
+    /// cluster; otherwise it is sent to every node in `nodes`.
+    pub fn with_reconfigure(mut self, update: TuningUpdate, propagate: bool) -> Self {
+        self.reconfigure = Some((update, propagate));
+        self
+    }
+}
+
+impl ActorProcess for DynamoAdmin {
+    type IMsg = DynamoAdminIn;
+    type OMsg = DynamoNodeOut;
+
+    fn process(&mut self, input: Self::IMsg) -> Vec<Self::OMsg> {
+        match input {
+            DynamoAdminIn::GeneratorTrigger(_) => {
+                let mut out = vec![];
+                if let Some((update, propagate)) = self.reconfigure.take() {
+                    let targets = if propagate { &self.nodes[..self.nodes.len().min(1)] } else { &self.nodes[..] };
+                    info!("[admin] reconfigure {:?} via {:?} propagate={}", update, targets, propagate);
+                    out.extend(targets.iter().map(|n| DynamoNodeOut::NodeToNode(NodeToNode::Reconfigure{ from: self.admin_id.clone(), to: n.clone(), update: update.clone(), propagate })));
+                }
+                out.extend(self.export_poll());
+                out.extend(self.import_poll());
+                if !self.polled || !self.interval.is_zero() {
+                    out.extend(self.nodes.iter().map(|n| DynamoNodeOut::NodeToNode(NodeToNode::StatsReq{ from: self.admin_id.clone(), to: n.clone() })));
+                }
+                self.polled = true;
+                self.arm_next_poll();
+                out
+            }
+            DynamoAdminIn::NodeToNode(NodeToNode::StatsRsp{ stats, .. }) => {
+                let m = &stats.metrics;
+                info!("[admin] stats node={} keys={} failed={:?} tuning={:?} puts={} gets={} put_p99<={}ms get_p99<={}ms repairs={} repairs_acked={} repairs_failed={} repairs_pending={} conflict_rate={:.3} keys_with_siblings={} most_siblings={} sibling_hot_keys={:?}",
+                    stats.node_id, stats.keys, stats.failed, stats.tuning, m.puts_completed, m.gets_completed, m.put_latency.quantile_ms(0.99), m.get_latency.quantile_ms(0.99), m.read_repairs, m.repairs_acked, m.repairs_failed, stats.pending_repairs, m.conflict_rate(), stats.keys_with_siblings, stats.most_siblings, stats.sibling_hot_keys);
+                if let Some(dir) = &self.metrics_dir {
+                    let path = dir.join(format!("{}.prom", stats.node_id));
+                    if let Err(e) = std::fs::write(&path, stats.to_prometheus()) {
+                        warn!("[admin] failed to write {:?}: {}", path, e);
+                    }
+                }
+                vec![]
+            }
+            DynamoAdminIn::NodeToNode(NodeToNode::ReconfigureRsp{ from, result, .. }) => {
+                match result {
+                    Ok(tuning) => info!("[admin] reconfigured node={} tuning={:?}", from, tuning),
+                    Err(e) => warn!("[admin] node={} rejected reconfigure: {}", from, e),
+                }
+                vec![]
+            }
+            DynamoAdminIn::NodeToNode(NodeToNode::SnapshotChunk{ from, entries, last, .. }) => {
+                self.on_snapshot_chunk(from, entries, last);
+                vec![]
+            }
+            DynamoAdminIn::NodeToNode(NodeToNode::ImportAck{ import_id, .. }) => self.on_import_ack(import_id),
+            DynamoAdminIn::NodeToNode(other) => {
+                info!("[admin] {:?}", other);
+                vec![]
+            }
+        }
+    }
+}
+
+pub async fn admin_behaviour(
+    ctx: RuntimeCtx,
+    proc: DynamoAdmin,
+    interval_ms: u64,
+    decoder: reactor_actor::SubDecoderStore<DynamoAdminIn>,
+) {
+    if let Some(dir) = &proc.metrics_dir { let _ = std::fs::create_dir_all(dir); }
+    let (timers, timer_queue) = timer_channel();
+    // the first poll is immediate; each poll schedules the next
+    let behaviour = BehaviourBuilder::new(proc.with_timers(timers, interval_ms), BincodeCodec::default())
+        .send(DynamoNodeSender::new())
+        .sub_decoders(decoder)
+        .ask_receiver_to_adapt()
+        .generator(std::iter::once(DynamoAdminIn::GeneratorTrigger(GeneratorTrigger)))
+        .timers(timer_queue)
+        .build();
+    let addr = ctx.addr;
+    if let Err(e) = behaviour.run(ctx).await {
+        log::error!("[admin] {} exited: {}", addr, e);
+    }
+}
@@ -1,97 +1,327 @@
-use std::time::Instant;
-use std::fs::OpenOptions;
-use std::io::Write;
-use log::info;
-use reactor_actor::{ActorProcess, ActorSend, BehaviourBuilder, RouteTo, RuntimeCtx};
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use log::{debug, info, warn};
+use reactor_actor::{ActorProcess, ActorSend, BehaviourBuilder, RouteTo, RuntimeCtx, TimerHandle, timer_channel};
 use reactor_actor::codec::BincodeCodec;
+use tokio::sync::mpsc::UnboundedSender;
 
+use crate::clock::{Clock, SystemClock};
+use crate::history::HistoryRecorder;
 use crate::messages::{DynamoClientIn, DynamoClientOut, GeneratorTrigger, ClientToNode, NodeToClient};
+use crate::results::{self, BenchResults, LatencySummary, OpStats, RunConfig};
 use crate::vector_clock::VectorClock;
+use crate::workload::{OpType, Workload, WorkloadConfig, WorkloadOp};
 
-/// Benchmark client that measures GET/PUT latencies
-/// Writes directly to latency.log file
+// A request in flight; scans send one GET per key and complete when all have answered
+struct Inflight {
+    op: OpType,
+    key: String,
+    intended: Instant,
+    scan: Option<u64>,
+}
+
+/// Handed to `with_report` when a run completes, so a driver can merge several clients into one run.
+pub struct ClientReport {
+    pub client_id: String,
+    pub elapsed: Duration,
+    pub stats: HashMap<OpType, OpStats>,
+    pub unknown_responses: u64,
+    /// PUTs refused because their key was at its sibling limit; they count as completed, without a latency.
+    pub rejected_puts: u64,
+}
+
+/// Benchmark client driven by a YCSB-style `WorkloadConfig`.
+///
+/// Closed-loop (no target rate) it keeps `max_outstanding` operations in flight. Open-loop it
+/// schedules arrivals at `target_ops_per_sec` regardless of completions; arrivals that find no free
+/// slot queue up, and their latency is measured from the intended arrival time so a slow cluster
+/// is not hidden by a slow client (coordinated omission). Latencies go to HdrHistograms and, once
+/// the run completes, to a `BenchResults` JSON file and/or CSV rows (see `with_output`).
 pub struct BenchClient {
     client_id: String,
     nodes: Vec<String>,
-    num_ops: usize,
-    current_op: usize,
-    inflight_key: Option<String>,
-    inflight_start: Option<Instant>,
-    inflight_type: Option<String>, // "GET" or "PUT"
+    workload: Workload,
+    clock: Arc<dyn Clock>,
+    start: Option<Instant>,
+    arrivals: usize, // open-loop arrivals admitted so far
+    backlog: VecDeque<(WorkloadOp, Instant)>,
+    inflight: HashMap<u64, Inflight>,
+    scans: HashMap<u64, usize>, // scan id -> GETs still outstanding
+    outstanding: usize,
+    completed: usize,
+    next_request_id: u64,
     local_vc: VectorClock,
+    latencies: HashMap<OpType, OpStats>,
+    unknown_responses: u64,
+    rejected_puts: u64,
+    run_id: String,
+    run_config: RunConfig,
+    keep_samples: bool,
+    json_out: Option<PathBuf>,
+    csv_out: Option<PathBuf>,
+    done: Arc<AtomicBool>,
+    history: Option<HistoryRecorder>,
+    report_tx: Option<UnboundedSender<ClientReport>>,
+    timers: Option<TimerHandle<DynamoClientIn>>,
 }
 
 impl BenchClient {
-    pub fn new(client_id: String, nodes: Vec<String>, num_ops: usize) -> Self {
+    pub fn new(client_id: String, nodes: Vec<String>, workload: WorkloadConfig) -> Self {
+        let run_config = RunConfig { operations: workload.operations, workload: Some(workload.clone()), ..RunConfig::default() };
+        let workload = Workload::new(workload, &client_id);
         Self {
+            run_id: format!("{}-{}", client_id, results::now_ms()),
             client_id,
             nodes,
-            num_ops,
-            current_op: 0,
-            inflight_key: None,
-            inflight_start: None,
-            inflight_type: None,
+            workload,
+            clock: Arc::new(SystemClock),
+            start: None,
+            arrivals: 0,
+            backlog: VecDeque::new(),
+            inflight: HashMap::new(),
+            scans: HashMap::new(),
+            outstanding: 0,
+            completed: 0,
+            next_request_id: 0,
             local_vc: VectorClock::new(),
+            latencies: HashMap::new(),
+            unknown_responses: 0,
+            rejected_puts: 0,
+            run_config,
+            keep_samples: false,
+            json_out: None,
+            csv_out: None,
+            done: Arc::new(AtomicBool::new(false)),
+            history: None,
+            report_tx: None,
+            timers: None,
         }
     }
 
-    fn next_operation(&mut self) -> Option<ClientToNode> {
-        if self.current_op >= self.num_ops {
-            return None;
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Records every request and response (see `history`).
+    pub fn with_history(mut self, history: HistoryRecorder) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Labels the results; `n`/`w`/`r`/`t` describe the cluster under test, which the client cannot see.
+    pub fn with_run(mut self, run_id: Option<String>, n: Option<usize>, w: Option<usize>, r: Option<usize>, t: Option<usize>) -> Self {
+        if let Some(id) = run_id {
+            self.run_id = id;
         }
+        self.run_config = RunConfig { n, w, r, t, ..self.run_config };
+        self
+    }
 
-        let key = format!("key{}", self.current_op % 100);
-        let op_type = if self.current_op % 2 == 0 { "PUT" } else { "GET" };
+    /// Writes the results as JSON and/or appends CSV rows when the run completes.
+    pub fn with_output(mut self, json: Option<PathBuf>, csv: Option<PathBuf>, keep_samples: bool) -> Self {
+        self.json_out = json;
+        self.csv_out = csv;
+        self.keep_samples = keep_samples;
+        self
+    }
 
-        self.inflight_key = Some(key.clone());
-        self.inflight_start = Some(Instant::now());
-        self.inflight_type = Some(op_type.to_string());
-        self.current_op += 1;
+    /// Ticks open-loop runs every `ARRIVAL_TICK` with `timers` to admit the arrivals that became due.
+    pub fn with_timers(mut self, timers: TimerHandle<DynamoClientIn>) -> Self {
+        self.timers = Some(timers);
+        self
+    }
 
-        if op_type == "PUT" {
-            let value = format!("value_{}", self.current_op);
-            self.local_vc.increment(&self.client_id);
-            Some(ClientToNode::ClientPut {
-                key,
-                value,
-                request_id: self.current_op as u64,
-                metadata: vec![self.local_vc.clone()],
-                client_addr: self.client_id.clone(),
-            })
-        } else {
-            Some(ClientToNode::ClientGet {
-                key,
-                request_id: self.current_op as u64,
-                client_addr: self.client_id.clone(),
+    /// Sends a `ClientReport` on `tx` when the run completes.
+    pub fn with_report(mut self, tx: UnboundedSender<ClientReport>) -> Self {
+        self.report_tx = Some(tx);
+        self
+    }
+
+    pub fn workload(&self) -> &WorkloadConfig {
+        self.workload.config()
+    }
+
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+
+    pub fn outstanding(&self) -> usize {
+        self.outstanding
+    }
+
+    /// Set once every operation has completed.
+    pub fn done_flag(&self) -> Arc<AtomicBool> {
+        self.done.clone()
+    }
+
+    pub fn summary(&self, op: OpType) -> LatencySummary {
+        self.latencies.get(&op).map(OpStats::summary).unwrap_or_default()
+    }
+
+    /// Results of the run so far.
+    pub fn results(&self) -> BenchResults {
+        let mut res = BenchResults::new(self.run_id.clone(), format!("bench_client:{}", self.client_id), self.run_config.clone());
+        let elapsed = self.start.map(|s| self.clock.now().saturating_duration_since(s)).unwrap_or_default();
+        res.started_at_ms = results::now_ms().saturating_sub(elapsed.as_millis() as u64);
+        res.finish(elapsed, self.latencies.iter().map(|(op, s)| (op.results_key(), s)));
+        if self.unknown_responses > 0 {
+            res.errors.push(format!("{} responses to unknown requests", self.unknown_responses));
+        }
+        if self.rejected_puts > 0 {
+            res.errors.push(format!("{} PUTs rejected at the sibling limit", self.rejected_puts));
+        }
+        res
+    }
+
+    // Queues every open-loop arrival due by `now`
+    fn admit_arrivals(&mut self, now: Instant) {
+        let rate = self.workload.config().target_ops_per_sec;
+        let Some(start) = self.start else { return };
+        if rate <= 0.0 {
+            return;
+        }
+        let due = (now.saturating_duration_since(start).as_secs_f64() * rate) as usize + 1;
+        while self.arrivals < due {
+            let Some(op) = self.workload.next_op() else { break };
+            let intended = start + Duration::from_secs_f64(self.arrivals as f64 / rate);
+            self.backlog.push_back((op, intended));
+            self.arrivals += 1;
+        }
+    }
+
+    // Issues queued (open-loop) or fresh (closed-loop) operations while slots are free
+    fn fill(&mut self, now: Instant) -> Vec<DynamoClientOut> {
+        let mut out = vec![];
+        let closed_loop = self.workload.config().target_ops_per_sec <= 0.0;
+        while self.outstanding < self.workload.config().max_outstanding {
+            let next = if closed_loop { self.workload.next_op().map(|op| (op, now)) } else { self.backlog.pop_front() };
+            let Some((op, intended)) = next else { break };
+            self.outstanding += 1;
+            out.extend(self.issue(op, intended));
+        }
+        out
+    }
+
+    fn issue(&mut self, op: WorkloadOp, intended: Instant) -> Vec<DynamoClientOut> {
+        let op_type = op.op_type();
+        let reqs: Vec<(String, ClientToNode)> = match op {
+            WorkloadOp::Read { key } => vec![(key.clone(), self.get(key))],
+            WorkloadOp::Update { key, value_len } => {
+                self.next_request_id += 1;
+                // client id keeps values unique per key across bench clients
+                let mut value = format!("{}_value_{}", self.client_id, self.next_request_id);
+                if value.len() < value_len {
+                    value.push_str(&"x".repeat(value_len - value.len()));
+                }
+                self.local_vc.increment(&self.client_id);
+                let req = ClientToNode::ClientPut {
+                    key: key.clone(),
+                    value,
+                    request_id: self.next_request_id,
+                    metadata: vec![self.local_vc.clone()],
+                    client_addr: self.client_id.clone(),
+                };
+                vec![(key, req)]
+            }
+            WorkloadOp::Scan { keys } => keys.into_iter().map(|k| (k.clone(), self.get(k))).collect(),
+        };
+        let scan = (op_type == OpType::Scan).then(|| {
+            self.scans.insert(self.next_request_id, reqs.len());
+            self.next_request_id
+        });
+        reqs.into_iter()
+            .map(|(key, req)| {
+                let (ClientToNode::ClientPut { request_id, .. } | ClientToNode::ClientGet { request_id, .. }) = &req;
+                self.inflight.insert(*request_id, Inflight { op: op_type, key, intended, scan });
+                if let Some(h) = self.history.as_mut() {
+                    h.invoke(&self.client_id, &req);
+                }
+                DynamoClientOut::ClientToNode(req)
             })
+            .collect()
+    }
+
+    fn get(&mut self, key: String) -> ClientToNode {
+        self.next_request_id += 1;
+        ClientToNode::ClientGet { key, request_id: self.next_request_id, client_addr: self.client_id.clone() }
+    }
+
+    fn record_latency(&mut self, op: OpType, key: &str, latency: Duration) {
+        let keep_samples = self.keep_samples;
+        self.latencies.entry(op).or_insert_with(|| OpStats::new(keep_samples)).record(latency);
+        debug!("{} key={} latency={:.2}ms", op.label(), key, latency.as_secs_f64() * 1000.0);
+    }
+
+    fn on_response(&mut self, resp: NodeToClient) {
+        let (NodeToClient::ClientPutRsp { request_id, .. } | NodeToClient::ClientGetRsp { request_id, .. } | NodeToClient::ClientPutRejected { request_id, .. }) = &resp;
+        let Some(req) = self.inflight.remove(request_id) else {
+            warn!("[bench_client] {} ignoring response to unknown request {}", self.client_id, request_id);
+            self.unknown_responses += 1;
+            return;
+        };
+        if let Some(h) = self.history.as_mut() {
+            h.complete(&self.client_id, &resp);
+        }
+        if let NodeToClient::ClientPutRejected { key, reason, .. } = &resp {
+            debug!("[bench_client] {} PUT key={} rejected: {}", self.client_id, key, reason);
+            self.rejected_puts += 1;
+            self.outstanding -= 1;
+            self.completed += 1;
+            return;
+        }
+        if let NodeToClient::ClientGetRsp { metadata, .. } = resp {
+            // Update local clock with returned metadata
+            if !metadata.is_empty() {
+                self.local_vc = VectorClock::converge(metadata);
+            }
+        }
+        if let Some(scan) = req.scan {
+            let remaining = self.scans.get_mut(&scan).map(|r| {
+                *r -= 1;
+                *r
+            });
+            if remaining != Some(0) {
+                return;
+            }
+            self.scans.remove(&scan);
         }
+        let latency = self.clock.now().saturating_duration_since(req.intended);
+        self.record_latency(req.op, &req.key, latency);
+        self.outstanding -= 1;
+        self.completed += 1;
     }
 
-    fn record_latency(&mut self) {
-        if let (Some(key), Some(start), Some(op_type)) =
-            (&self.inflight_key, self.inflight_start, &self.inflight_type)
+    fn report(&self) {
+        let res = self.results();
+        info!("[bench_client] {} run={} completed {} operations in {:.2}s ({:.1} ops/s)", self.client_id, res.run_id, self.completed, res.duration_ms as f64 / 1000.0, res.throughput_ops_per_sec);
+        for (op, o) in res.ops.iter() {
+            let s = &o.latency;
+            info!(
+                "[bench_client] {} count={} mean={:.2}ms p50={:.2}ms p90={:.2}ms p99={:.2}ms p99.9={:.2}ms max={:.2}ms",
+                op, s.count, s.mean_us / 1000.0, s.p50_us as f64 / 1000.0, s.p90_us as f64 / 1000.0, s.p99_us as f64 / 1000.0, s.p999_us as f64 / 1000.0, s.max_us as f64 / 1000.0
+            );
+        }
+        if let Some(path) = &self.json_out {
+            match res.write_json(path) {
+                Ok(()) => info!("[bench_client] results written to {}", path.display()),
+                Err(e) => warn!("[bench_client] failed to write results to {}: {}", path.display(), e),
+            }
+        }
+        if let Some(path) = &self.csv_out
+            && let Err(e) = res.append_csv(path)
         {
-            let latency = start.elapsed();
-            let latency_ms = latency.as_secs_f64() * 1000.0;
-
-            // Write directly to latency.log file for reliable capture
-            let log_line = format!("{} key={} latency={:.2}ms\n", op_type, key, latency_ms);
-
-            // Also log via info! for console output
-            info!("{} key={} latency={:.2}ms", op_type, key, latency_ms);
-
-            // Write to file
-            if let Ok(mut file) = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open("latency.log")
-            {
-                let _ = file.write_all(log_line.as_bytes());
+            warn!("[bench_client] failed to append results to {}: {}", path.display(), e);
+        }
+        if let Some(tx) = &self.report_tx {
+            let elapsed = self.start.map(|s| self.clock.now().saturating_duration_since(s)).unwrap_or_default();
+            let report = ClientReport { client_id: self.client_id.clone(), elapsed, stats: self.latencies.clone(), unknown_responses: self.unknown_responses, rejected_puts: self.rejected_puts };
+            if tx.send(report).is_err() {
+                warn!("[bench_client] {} report receiver is gone", self.client_id);
             }
-
-            self.inflight_key = None;
-            self.inflight_start = None;
-            self.inflight_type = None;
         }
     }
 }
@@ -101,39 +331,27 @@ impl ActorProcess for BenchClient {
     type OMsg = DynamoClientOut;
 
     fn process(&mut self, input: Self::IMsg) -> Vec<Self::OMsg> {
+        if self.done.load(Ordering::Relaxed) {
+            return vec![];
+        }
+        let now = self.clock.now();
         match input {
             DynamoClientIn::GeneratorTrigger(_) => {
-                // Start first operation
-                if let Some(req) = self.next_operation() {
-                    vec![DynamoClientOut::ClientToNode(req)]
-                } else {
-                    vec![]
-                }
-            }
-            DynamoClientIn::NodeToClient(resp) => {
-                // Record latency for completed operation
-                self.record_latency();
-
-                // Process response
-                match resp {
-                    NodeToClient::ClientPutRsp { .. } => {}
-                    NodeToClient::ClientGetRsp { metadata, .. } => {
-                        // Update local clock with returned metadata
-                        if !metadata.is_empty() {
-                            self.local_vc = VectorClock::converge(metadata);
-                        }
-                    }
-                }
-
-                // Issue next operation
-                if let Some(req) = self.next_operation() {
-                    vec![DynamoClientOut::ClientToNode(req)]
-                } else {
-                    info!("[bench_client] Completed {} operations", self.num_ops);
-                    vec![]
+                // first trigger starts the run; open-loop runs get one per tick
+                self.start.get_or_insert(now);
+                if let Some(timers) = self.timers.as_ref() && self.workload.config().target_ops_per_sec > 0.0 {
+                    timers.set_once(ARRIVAL_TICK, DynamoClientIn::GeneratorTrigger(GeneratorTrigger));
                 }
             }
+            DynamoClientIn::NodeToClient(resp) => self.on_response(resp),
+        }
+        self.admit_arrivals(now);
+        let out = self.fill(now);
+        if self.outstanding == 0 && self.start.is_some() && self.workload.generated() >= self.workload.config().operations {
+            self.done.store(true, Ordering::Relaxed);
+            self.report();
         }
+        out
     }
 }
 
@@ -163,23 +381,32 @@ impl ActorSend for BenchClientSender {
     }
 }
 
+// Open-loop clients are ticked this often to admit the arrivals that became due
+const ARRIVAL_TICK: Duration = Duration::from_millis(1);
+
 pub async fn bench_client_behaviour(
     ctx: RuntimeCtx,
-    client_id: String,
-    nodes: Vec<String>,
-    num_ops: usize,
+    proc: BenchClient,
     decoder: reactor_actor::SubDecoderStore<DynamoClientIn>,
 ) {
-    info!("[bench_client] Starting benchmark: {} operations", num_ops);
+    let workload = proc.workload();
+    info!(
+        "[bench_client] Starting benchmark: {} operations, target={} ops/s, max_outstanding={}",
+        workload.operations, workload.target_ops_per_sec, workload.max_outstanding
+    );
 
-    let proc = BenchClient::new(client_id, nodes.clone(), num_ops);
-    BehaviourBuilder::new(proc, BincodeCodec::default())
-        .send(BenchClientSender::new(nodes))
+    // requests go round-robin to the nodes the client was given
+    let sender = BenchClientSender::new(proc.nodes.clone());
+    let (timers, timer_queue) = timer_channel();
+    let behaviour = BehaviourBuilder::new(proc.with_timers(timers), BincodeCodec::default())
+        .send(sender)
         .sub_decoders(decoder)
         .ask_receiver_to_adapt()
-        .generator_if(true, || vec![DynamoClientIn::GeneratorTrigger(GeneratorTrigger)].into_iter())
-        .build()
-        .run(ctx)
-        .await
-        .unwrap();
+        .generator(std::iter::once(DynamoClientIn::GeneratorTrigger(GeneratorTrigger)))
+        .timers(timer_queue)
+        .build();
+    let addr = ctx.addr;
+    if let Err(e) = behaviour.run(ctx).await {
+        log::error!("[bench-client] {} exited: {}", addr, e);
+    }
 }
@@ -0,0 +1,51 @@
+//! Compares two benchmark runs
+//!
+//! Usage: bench_compare [--threshold 0.1] <base.json> <new.json>
+//!
+//! Prints latency percentiles, errors and throughput of both runs side by side and flags
+//! regressions: latencies that grew, or throughput that dropped, by more than the threshold
+//! (a fraction, default 10%), and any new errors. Exits with status 1 if there are regressions.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use dynamo_new::results::{compare, BenchResults};
+
+fn main() -> ExitCode {
+    let mut threshold = 0.1;
+    let mut paths = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threshold" => match args.next().and_then(|v| v.parse::<f64>().ok()) {
+                Some(t) if t >= 0.0 => threshold = t,
+                _ => { eprintln!("--threshold expects a non-negative fraction, e.g. 0.1"); return ExitCode::from(2); }
+            },
+            _ => paths.push(arg),
+        }
+    }
+    let [base, new] = paths.as_slice() else {
+        eprintln!("usage: bench_compare [--threshold 0.1] <base.json> <new.json>");
+        return ExitCode::from(2);
+    };
+    let (base, new) = match (BenchResults::load(Path::new(base)), BenchResults::load(Path::new(new))) {
+        (Ok(b), Ok(n)) => (b, n),
+        (Err(e), _) | (_, Err(e)) => { eprintln!("failed to read results: {}", e); return ExitCode::from(2); }
+    };
+
+    println!("base: {} ({})  new: {} ({})  threshold: {:.0}%", base.run_id, base.source, new.run_id, new.source, threshold * 100.0);
+    println!("{:<6} {:<24} {:>14} {:>14} {:>9}", "op", "metric", "base", "new", "change");
+    let rows = compare(&base, &new, threshold);
+    for c in rows.iter() {
+        let change = if c.change.is_finite() { format!("{:+.1}%", c.change * 100.0) } else { "new".to_string() };
+        println!("{:<6} {:<24} {:>14.1} {:>14.1} {:>9}{}", c.op, c.metric, c.base, c.new, change, if c.regression { "  REGRESSION" } else { "" });
+    }
+    let regressions = rows.iter().filter(|c| c.regression).count();
+    if regressions == 0 {
+        println!("no regressions");
+        ExitCode::SUCCESS
+    } else {
+        println!("{} regression(s)", regressions);
+        ExitCode::from(1)
+    }
+}
@@ -0,0 +1,41 @@
+//! Consistency checker for recorded client histories
+//!
+//! Usage: check_history [--settle-ms N] [--no-sessions] <history.jsonl>...
+//!
+//! Merges the history files written by clients started with `history_path` and reports lost
+//! acknowledged writes, siblings of causally ordered writes, values nobody wrote and (unless
+//! disabled) session guarantee violations. Exits with status 1 if any anomaly is found.
+
+use std::process::ExitCode;
+
+use dynamo_new::checker::{check, CheckOptions};
+use dynamo_new::history;
+
+fn main() -> ExitCode {
+    let mut opts = CheckOptions::default();
+    let mut paths = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--settle-ms" => match args.next().and_then(|v| v.parse::<u64>().ok()) {
+                Some(ms) => opts.settle_after_us = ms * 1000,
+                None => { eprintln!("--settle-ms expects a number of milliseconds"); return ExitCode::from(2); }
+            },
+            "--no-sessions" => opts.sessions = false,
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("usage: check_history [--settle-ms N] [--no-sessions] <history.jsonl>...");
+        return ExitCode::from(2);
+    }
+
+    let events = match history::load(&paths) {
+        Ok(events) => events,
+        Err(e) => { eprintln!("failed to read history: {}", e); return ExitCode::from(2); }
+    };
+    let report = check(&events, &opts);
+    for a in report.anomalies.iter() { println!("{}", a); }
+    println!("checked {} operations ({} incomplete), {} anomalies", report.ops, report.incomplete, report.anomalies.len());
+    if report.is_ok() { ExitCode::SUCCESS } else { ExitCode::from(1) }
+}
@@ -1,23 +1,42 @@
-/// Real Dynamo Benchmark - Direct Logic Execution
-///
-/// This benchmark uses ACTUAL Dynamo business logic:
-/// - Real ConsistentHash for key routing
-/// - Real VectorClock operations
-/// - Real VersionedValue storage and conflict resolution
-/// - Real quorum logic
-/// - Real HashMap storage operations
-///
-/// All operations execute real Dynamo code, not simulations.
-/// Latencies are measured from actual computation time.
+//! Real Dynamo Benchmark - Direct Logic Execution
+//!
+//! This benchmark uses ACTUAL Dynamo business logic:
+//! - Real ConsistentHash for key routing
+//! - Real VectorClock operations
+//! - Real VersionedValue storage and conflict resolution
+//! - Real quorum logic
+//! - Real HashMap storage operations
+//!
+//! All operations execute real Dynamo code, not simulations.
+//! Latencies are measured from actual computation time.
+//!
+//! Usage: real_benchmark [num_ops] [n] [w] [r] [--run-id ID] [--out results.json] [--csv results.csv] [--no-samples]
+//!                       [--network [--nodes M] [--clients C] [--outstanding K] [--rate OPS] [--keys K]
+//!                                  [--read-proportion P] [--dist uniform|zipfian|latest] [--base-port PORT]
+//!                                  [--timeout-secs S] [--wal-dir DIR [--wal-fsync always|interval|none]]]
+//!
+//! With `--network` the operations instead go through real `DynamoNode` actors, each listening on its
+//! own localhost TCP port under the reactor runtime, driven by `C` bench clients (see `BenchClient`)
+//! that split the operations between them. Latencies then include message encoding, the transport
+//! and the coordinator's quorum round trips.
+//!
+//! Results are written as a `BenchResults` JSON document (per-op percentiles, histogram and, unless
+//! `--no-samples`, every latency), and optionally appended as CSV rows.
 
 use std::collections::HashMap;
-use std::time::Instant;
-use std::fs::OpenOptions;
-use std::io::Write as IoWrite;
-
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use dynamo_new::bench_client::BenchClient;
+use dynamo_new::config::DynamoConfig;
+use dynamo_new::local_cluster::LocalCluster;
+use dynamo_new::workload::{KeyDistribution, OpType, WorkloadConfig};
 use dynamo_new::vector_clock::VectorClock;
 use dynamo_new::versioned_value::{VersionedValue, VersionedValues};
 use dynamo_new::consistent_hash::ConsistentHash;
+use dynamo_new::results::{BenchResults, OpStats, RunConfig};
+use dynamo_new::wal::FsyncMode;
 
 /// Minimal DynamoStore that uses REAL Dynamo logic
 struct RealDynamoStore {
@@ -44,6 +63,7 @@ impl RealDynamoStore {
         let versioned = VersionedValue {
             value,
             clock: self.clock.clone(),
+            ts_ms: 0,
         };
 
         // Real storage with conflict detection
@@ -163,34 +183,98 @@ impl RealQuorumCoordinator {
     }
 }
 
-#[tokio::main]
-async fn main() {
-    let args: Vec<String> = std::env::args().collect();
+/// Settings of the `--network` mode
+struct NetworkOpts {
+    nodes: Option<usize>,
+    clients: usize,
+    workload: WorkloadConfig,
+    base_port: Option<u16>,
+    timeout: Duration,
+    wal_dir: Option<PathBuf>,
+    wal_fsync: FsyncMode,
+}
 
-    // Parse arguments: num_ops [n] [w] [r]
-    let num_ops = if args.len() > 1 {
-        args[1].parse().unwrap_or(1000)
-    } else {
-        1000
-    };
+impl Default for NetworkOpts {
+    fn default() -> Self {
+        Self { nodes: None, clients: 1, workload: WorkloadConfig::default(), base_port: None, timeout: Duration::from_secs(60), wal_dir: None, wal_fsync: FsyncMode::default() }
+    }
+}
 
-    let n = if args.len() > 2 {
-        args[2].parse().unwrap_or(3)
-    } else {
-        3
-    };
+fn parse_flag<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    value.as_deref().and_then(|v| v.parse().ok()).ok_or_else(|| format!("{} expects a value, got {:?}", flag, value.unwrap_or_default()))
+}
 
-    let w = if args.len() > 3 {
-        args[3].parse().unwrap_or(2)
-    } else {
-        2
-    };
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::init();
+    let mut positional: Vec<String> = vec![];
+    let mut run_id: Option<String> = None;
+    let mut out = PathBuf::from("results.json");
+    let mut csv: Option<PathBuf> = None;
+    let mut keep_samples = true;
+    let mut network: Option<NetworkOpts> = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let net = || NetworkOpts::default();
+        let parsed: Result<(), String> = match arg.as_str() {
+            "--run-id" => { run_id = args.next(); Ok(()) }
+            "--out" => { out = args.next().map(PathBuf::from).unwrap_or(out); Ok(()) }
+            "--csv" => { csv = args.next().map(PathBuf::from); Ok(()) }
+            "--no-samples" => { keep_samples = false; Ok(()) }
+            "--network" => { network.get_or_insert_with(net); Ok(()) }
+            "--nodes" => parse_flag(&arg, args.next()).map(|v| network.get_or_insert_with(net).nodes = Some(v)),
+            "--clients" => parse_flag(&arg, args.next()).map(|v| network.get_or_insert_with(net).clients = v),
+            "--outstanding" => parse_flag(&arg, args.next()).map(|v| network.get_or_insert_with(net).workload.max_outstanding = v),
+            "--rate" => parse_flag(&arg, args.next()).map(|v| network.get_or_insert_with(net).workload.target_ops_per_sec = v),
+            "--keys" => parse_flag(&arg, args.next()).map(|v| network.get_or_insert_with(net).workload.record_count = v),
+            "--read-proportion" => parse_flag(&arg, args.next()).map(|p: f64| {
+                let w = &mut network.get_or_insert_with(net).workload;
+                (w.read_proportion, w.update_proportion) = (p, 1.0 - p);
+            }),
+            "--dist" => match args.next().as_deref() {
+                Some("uniform") => { network.get_or_insert_with(net).workload.key_distribution = KeyDistribution::Uniform; Ok(()) }
+                Some("zipfian") => { network.get_or_insert_with(net).workload.key_distribution = KeyDistribution::Zipfian; Ok(()) }
+                Some("latest") => { network.get_or_insert_with(net).workload.key_distribution = KeyDistribution::Latest; Ok(()) }
+                other => Err(format!("--dist expects uniform, zipfian or latest, got {:?}", other.unwrap_or_default())),
+            },
+            "--base-port" => parse_flag(&arg, args.next()).map(|v| network.get_or_insert_with(net).base_port = Some(v)),
+            "--timeout-secs" => parse_flag(&arg, args.next()).map(|v| network.get_or_insert_with(net).timeout = Duration::from_secs(v)),
+            "--wal-dir" => parse_flag(&arg, args.next()).map(|v| network.get_or_insert_with(net).wal_dir = Some(v)),
+            "--wal-fsync" => match args.next().as_deref() {
+                Some("always") => { network.get_or_insert_with(net).wal_fsync = FsyncMode::Always; Ok(()) }
+                Some("interval") => { network.get_or_insert_with(net).wal_fsync = FsyncMode::Interval; Ok(()) }
+                Some("none") => { network.get_or_insert_with(net).wal_fsync = FsyncMode::None; Ok(()) }
+                other => Err(format!("--wal-fsync expects always, interval or none, got {:?}", other.unwrap_or_default())),
+            },
+            _ => { positional.push(arg); Ok(()) }
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    }
 
-    let r = if args.len() > 4 {
-        args[4].parse().unwrap_or(2)
-    } else {
-        2
-    };
+    // Parse arguments: num_ops [n] [w] [r]
+    let arg = |i: usize, default: usize| positional.get(i).and_then(|a| a.parse().ok()).unwrap_or(default);
+    let (num_ops, n, w, r) = (arg(0, 1000), arg(1, 3), arg(2, 2), arg(3, 2));
+    let t = 10;
+    let run_id = run_id.unwrap_or_else(|| format!("N{}_W{}_R{}", n, w, r));
+
+    if let Some(opts) = network {
+        return match run_network(opts, num_ops, n, w, r, t, run_id, keep_samples).await {
+            Ok(results) => {
+                if let Err(e) = write_results(&results, &out, csv.as_deref()) {
+                    eprintln!("Failed to write results: {}", e);
+                    return ExitCode::FAILURE;
+                }
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("Networked benchmark failed: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
 
     println!("========================================");
     println!("Real Dynamo Benchmark");
@@ -212,53 +296,139 @@ async fn main() {
         .map(|i| format!("node{}", (b'A' + i as u8) as char))
         .collect();
 
-    let mut coordinator = RealQuorumCoordinator::new(node_names, n, w, r, 10);
-
-    // Open output file
-    let mut file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open("latency.log")
-        .expect("Failed to create latency.log");
+    let mut coordinator = RealQuorumCoordinator::new(node_names, n, w, r, t);
+    let mut results = BenchResults::new(run_id, "real_benchmark".to_string(), RunConfig { n: Some(n), w: Some(w), r: Some(r), t: Some(t), operations: num_ops, workload: None });
+    let (mut puts, mut gets) = (OpStats::new(keep_samples), OpStats::new(keep_samples));
+    let run_start = Instant::now();
 
     // Run REAL operations with REAL timing
     for i in 0..num_ops {
         let key = format!("key{}", i % 100);
-        let op_type = if i % 2 == 0 { "PUT" } else { "GET" };
-
         let start = Instant::now();
 
-        if op_type == "PUT" {
+        if i % 2 == 0 {
             let value = format!("value_{}", i);
             // REAL PUT: executes actual consistent hashing, vector clock increment,
             // versioned storage, and quorum write
-            coordinator.quorum_put(key.clone(), value);
+            coordinator.quorum_put(key, value);
+            puts.record(start.elapsed());
         } else {
             // REAL GET: executes actual consistent hashing, quorum read,
             // version reconciliation, and read repair
             let _ = coordinator.quorum_get(&key);
+            gets.record(start.elapsed());
         }
 
-        // Measure REAL computation time
-        let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
-
-        let log_line = format!("{} key={} latency={:.2}ms\n", op_type, key, latency_ms);
-        file.write_all(log_line.as_bytes())
-            .expect("Failed to write to latency.log");
-
         if (i + 1) % 200 == 0 {
             println!("  Progress: {}/{} operations", i + 1, num_ops);
         }
     }
 
-    println!();
-    println!("✓ Benchmark complete!");
-    println!("  Output: latency.log");
+    results.finish(run_start.elapsed(), [("get", &gets), ("put", &puts)]);
+    write_results(&results, &out, csv.as_deref()).expect("Failed to write results");
     println!();
     println!("All operations executed REAL Dynamo code:");
     println!("  ✓ {} ConsistentHash lookups", num_ops);
     println!("  ✓ {} VectorClock operations", num_ops / 2);
     println!("  ✓ {} VersionedValue storage ops", num_ops / 2);
     println!("  ✓ {} Quorum reads with read repair", num_ops / 2);
+    ExitCode::SUCCESS
+}
+
+fn write_results(results: &BenchResults, out: &Path, csv: Option<&Path>) -> std::io::Result<()> {
+    results.write_json(out)?;
+    if let Some(csv) = csv {
+        results.append_csv(csv)?;
+    }
+    println!();
+    println!("✓ Benchmark complete!");
+    println!("  Output: {}", out.display());
+    for (op, o) in results.ops.iter() {
+        println!("  {} p50={}us p99={}us max={}us errors={}", op.to_uppercase(), o.latency.p50_us, o.latency.p99_us, o.latency.max_us, o.errors);
+    }
+    println!("  Throughput: {:.1} ops/s", results.throughput_ops_per_sec);
+    Ok(())
+}
+
+/// Runs the operations through `DynamoNode` actors over localhost TCP and merges every client's
+/// latencies into one result.
+#[allow(clippy::too_many_arguments)]
+async fn run_network(opts: NetworkOpts, num_ops: usize, n: usize, w: usize, r: usize, t: usize, run_id: String, keep_samples: bool) -> Result<BenchResults, String> {
+    let num_nodes = opts.nodes.unwrap_or(n.max(5));
+    let node_names: Vec<String> = (0..num_nodes).map(|i| format!("node{}", i)).collect();
+    let node_cfg = DynamoConfig { node_id: node_names[0].clone(), nodes: node_names.clone(), n, w, r, t, wal_dir: opts.wal_dir.clone(), wal_fsync: opts.wal_fsync, ..DynamoConfig::default() };
+    node_cfg.validate().map_err(|e| e.to_string())?;
+    if opts.clients == 0 {
+        return Err("--clients must be at least 1".to_string());
+    }
+    let workload = WorkloadConfig { operations: num_ops, ..opts.workload };
+    workload.validate()?;
+
+    println!("========================================");
+    println!("Networked Dynamo Benchmark");
+    println!("========================================");
+    println!();
+    println!("Operations: {} from {} client(s), {} in flight each", num_ops, opts.clients, workload.max_outstanding);
+    println!("Cluster: {} DynamoNode actors over localhost TCP, N={}, W={}, R={}", num_nodes, n, w, r);
+    if workload.target_ops_per_sec > 0.0 {
+        println!("Open loop at {} ops/s", workload.target_ops_per_sec);
+    }
+    println!();
+
+    let mut cluster = match opts.base_port {
+        Some(p) => LocalCluster::new().with_base_port(p),
+        None => LocalCluster::new(),
+    };
+    for name in node_names.iter() {
+        cluster.spawn_node(DynamoConfig { node_id: name.clone(), ..node_cfg.clone() }).await.map_err(|e| e.to_string())?;
+    }
+
+    let (report_tx, mut report_rx) = tokio::sync::mpsc::unbounded_channel();
+    let run_start = Instant::now();
+    for i in 0..opts.clients {
+        let client_id = format!("bench{}", i);
+        // the first clients take the remainder; the target rate is split evenly
+        let operations = num_ops / opts.clients + usize::from(i < num_ops % opts.clients);
+        let cfg = WorkloadConfig { operations, target_ops_per_sec: workload.target_ops_per_sec / opts.clients as f64, ..workload.clone() };
+        // start each client at a different node so coordinators share the load
+        let mut nodes = node_names.clone();
+        nodes.rotate_left(i % num_nodes);
+        let proc = BenchClient::new(client_id.clone(), nodes, cfg).with_output(None, None, keep_samples).with_report(report_tx.clone());
+        cluster.spawn_bench_client(&client_id, proc).await.map_err(|e| e.to_string())?;
+    }
+    drop(report_tx);
+
+    let mut stats: HashMap<OpType, OpStats> = HashMap::new();
+    let mut errors = vec![];
+    let deadline = tokio::time::Instant::now() + opts.timeout;
+    for _ in 0..opts.clients {
+        let report = match tokio::time::timeout_at(deadline, report_rx.recv()).await {
+            Ok(Some(report)) => report,
+            Ok(None) => return Err("a bench client exited without reporting".to_string()),
+            Err(_) => {
+                cluster.stop(Duration::from_secs(1)).await;
+                return Err(format!("clients did not finish within {:?}; is W or R larger than the live replicas?", opts.timeout));
+            }
+        };
+        println!("  {} finished in {:.2}s", report.client_id, report.elapsed.as_secs_f64());
+        if report.unknown_responses > 0 {
+            errors.push(format!("{}: {} responses to unknown requests", report.client_id, report.unknown_responses));
+        }
+        if report.rejected_puts > 0 {
+            errors.push(format!("{}: {} PUTs rejected at the sibling limit", report.client_id, report.rejected_puts));
+        }
+        for (op, s) in report.stats {
+            match stats.get_mut(&op) {
+                Some(merged) => merged.merge(&s),
+                None => { stats.insert(op, s); }
+            }
+        }
+    }
+    let elapsed = run_start.elapsed();
+    cluster.stop(Duration::from_secs(5)).await;
+
+    let mut results = BenchResults::new(run_id, "real_benchmark:network".to_string(), RunConfig { n: Some(n), w: Some(w), r: Some(r), t: Some(t), operations: num_ops, workload: Some(workload) });
+    results.finish(elapsed, stats.iter().map(|(op, s)| (op.results_key(), s)));
+    results.errors = errors;
+    Ok(results)
 }
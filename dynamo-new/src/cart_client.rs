@@ -1,7 +1,7 @@
 use std::collections::{BTreeMap};
 use serde::{Deserialize, Serialize};
 use rand::prelude::IndexedRandom;
-use log::info;
+use log::{info, warn};
 use reactor_actor::{ActorProcess, ActorSend, BehaviourBuilder, RouteTo, RuntimeCtx};
 use reactor_actor::codec::BincodeCodec;
 
@@ -122,6 +122,12 @@ impl ActorProcess for CartClient {
                         }
                         return vec![];
                     }
+                    NodeToClient::ClientPutRejected{ key, request_id, reason, .. } => {
+                        // the GET of the retry returns every sibling, and the merged cart written back replaces them
+                        warn!("[cart] {} PutRejected key={} req_id={}: {}; retrying step", self.client_id, key, request_id, reason);
+                        let cart = match &self.steps[self.next] { CartStep::Get{cart}|CartStep::Add{cart,..}|CartStep::Remove{cart,..} => cart.clone() };
+                        vec![DynamoClientOut::ClientToNode(ClientToNode::ClientGet{ key: format!("cart:{}", cart), client_addr: self.client_id.clone(), request_id: (self.next as u64)*2 + 1 })]
+                    }
                 }
             }
         }
@@ -151,13 +157,14 @@ pub async fn cart_client_behaviour(
 ) {
     let proc = CartClient::new(client_id.clone(), nodes.clone(), steps);
     // drive with a single trigger; the client chains GET->(PUT?) per step
-    BehaviourBuilder::new(proc, BincodeCodec::default())
+    let behaviour = BehaviourBuilder::new(proc, BincodeCodec::default())
         .send(CartClientSender::new(nodes))
         .sub_decoders(decoder)
         .ask_receiver_to_adapt()
         .generator_if(true, move || { vec![DynamoClientIn::GeneratorTrigger(GeneratorTrigger)].into_iter() })
-        .build()
-        .run(ctx)
-        .await
-        .unwrap();
+        .build();
+    let addr = ctx.addr;
+    if let Err(e) = behaviour.run(ctx).await {
+        log::error!("[cart-client] {} exited: {}", addr, e);
+    }
 }
@@ -0,0 +1,148 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::history::{HistoryEvent, OpKind};
+use crate::session::{Guarantee, Session, SessionCheck};
+use crate::vector_clock::{ClockOrdering, VectorClock};
+
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    /// Check read-your-writes and monotonic reads per client.
+    pub sessions: bool,
+    /// How long after an acknowledged PUT the cluster may still return reads that miss it.
+    pub settle_after_us: u64,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self { Self { sessions: true, settle_after_us: 0 } }
+}
+
+/// A request as `client/request_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OpRef { pub client: String, pub request_id: u64 }
+
+impl fmt::Display for OpRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}/{}", self.client, self.request_id) }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Anomaly {
+    /// A read issued after the settle time of an acknowledged PUT returned nothing that descends it.
+    LostWrite { key: String, write: OpRef, read: OpRef },
+    /// A read returned two siblings whose clocks are ordered, or whose writes were causally ordered
+    /// (the later write was sent with a context that already covered the earlier one).
+    OrderedSiblings { key: String, values: (String, String), read: OpRef },
+    /// A read returned a value no recorded PUT wrote.
+    UnknownValue { key: String, value: String, read: OpRef },
+    Session { key: String, guarantee: Guarantee, read: OpRef },
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anomaly::LostWrite{ key, write, read } => write!(f, "lost write: key={} write={} missing from read={}", key, write, read),
+            Anomaly::OrderedSiblings{ key, values, read } => write!(f, "ordered siblings: key={} values=({}, {}) read={}", key, values.0, values.1, read),
+            Anomaly::UnknownValue{ key, value, read } => write!(f, "unknown value: key={} value={} read={}", key, value, read),
+            Anomaly::Session{ key, guarantee, read } => write!(f, "session violation: key={} guarantee={:?} read={}", key, guarantee, read),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CheckReport {
+    /// Completed operations checked.
+    pub ops: usize,
+    /// Invocations that never completed; they are not checked.
+    pub incomplete: usize,
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool { self.anomalies.is_empty() }
+}
+
+// An invocation paired with its completion
+struct Op {
+    at: OpRef,
+    kind: OpKind,
+    key: String,
+    value: Option<String>,
+    context: Vec<VectorClock>,
+    invoke_us: u64,
+    complete_us: u64,
+    values: Vec<String>,
+    clocks: Vec<VectorClock>,
+}
+
+/// Checks a merged client history (see `history::load`). Written values are assumed to be unique
+/// per key, which is how a read is traced back to the PUT that produced it.
+pub fn check(events: &[HistoryEvent], opts: &CheckOptions) -> CheckReport {
+    let (ops, incomplete) = pair(events);
+    let mut report = CheckReport { ops: ops.len(), incomplete, anomalies: vec![] };
+    let writes: HashMap<(&str, &str), &Op> = ops.iter().filter(|o| o.kind == OpKind::Put).filter_map(|o| o.value.as_deref().map(|v| ((o.key.as_str(), v), o))).collect();
+    let mut puts: HashMap<&str, Vec<&Op>> = HashMap::new();
+    for o in ops.iter().filter(|o| o.kind == OpKind::Put) { puts.entry(o.key.as_str()).or_default().push(o); }
+    let mut sessions: BTreeMap<&str, Session> = BTreeMap::new();
+
+    for op in ops.iter() {
+        if op.kind == OpKind::Put {
+            if opts.sessions { sessions.entry(&op.at.client).or_default().record_write(&op.key, &VectorClock::converge(op.clocks.iter().cloned())); }
+            continue;
+        }
+        // no lost writes: every PUT acknowledged long enough before this read is visible in it
+        let settled = puts.get(op.key.as_str()).map(|ws| ws.iter().take_while(|w| w.complete_us + opts.settle_after_us <= op.invoke_us)).into_iter().flatten();
+        for w in settled {
+            let Some(acked) = w.clocks.first() else { continue };
+            if !op.clocks.iter().any(|c| c.descends(acked)) { report.anomalies.push(Anomaly::LostWrite{ key: op.key.clone(), write: w.at.clone(), read: op.at.clone() }); }
+        }
+        for v in op.values.iter() {
+            if !writes.contains_key(&(op.key.as_str(), v.as_str())) { report.anomalies.push(Anomaly::UnknownValue{ key: op.key.clone(), value: v.clone(), read: op.at.clone() }); }
+        }
+        // siblings only for concurrent writes
+        for i in 0..op.values.len() {
+            for j in i + 1..op.values.len() {
+                let (a, b) = (&op.values[i], &op.values[j]);
+                let clocks_ordered = match (op.clocks.get(i), op.clocks.get(j)) { (Some(ca), Some(cb)) => ca.compare(cb) != ClockOrdering::Concurrent, _ => false };
+                let writes_ordered = match (writes.get(&(op.key.as_str(), a.as_str())), writes.get(&(op.key.as_str(), b.as_str()))) {
+                    (Some(wa), Some(wb)) => covers(wa, wb) || covers(wb, wa),
+                    _ => false,
+                };
+                if clocks_ordered || writes_ordered { report.anomalies.push(Anomaly::OrderedSiblings{ key: op.key.clone(), values: (a.clone(), b.clone()), read: op.at.clone() }); }
+            }
+        }
+        if opts.sessions {
+            let session = sessions.entry(&op.at.client).or_default();
+            match session.check_read(&op.key, &op.clocks) {
+                SessionCheck::Ok => session.record_read(&op.key, &op.clocks),
+                SessionCheck::Violation(guarantee) => report.anomalies.push(Anomaly::Session{ key: op.key.clone(), guarantee, read: op.at.clone() }),
+            }
+        }
+    }
+    report
+}
+
+// True if `later` was sent with a context that already covered what `earlier` wrote
+fn covers(later: &Op, earlier: &Op) -> bool {
+    let Some(written) = earlier.clocks.first() else { return false };
+    !later.context.is_empty() && VectorClock::converge(later.context.iter().cloned()).descends(written)
+}
+
+// Pairs each completion with the latest invocation of the same request (a session retry re-sends
+// the same request id); returns completed operations in completion order and the count of the rest.
+fn pair(events: &[HistoryEvent]) -> (Vec<Op>, usize) {
+    let mut order: Vec<&HistoryEvent> = events.iter().collect();
+    order.sort_by_key(|e| e.ts_us());
+    let mut pending: HashMap<OpRef, &HistoryEvent> = HashMap::new();
+    let mut ops = vec![];
+    for ev in order {
+        match ev {
+            HistoryEvent::Invoke{ client, request_id, .. } => { pending.insert(OpRef{ client: client.clone(), request_id: *request_id }, ev); }
+            HistoryEvent::Complete{ client, request_id, values, clocks, ts_us, .. } => {
+                let at = OpRef{ client: client.clone(), request_id: *request_id };
+                let Some(HistoryEvent::Invoke{ op, key, value, context, ts_us: invoke_us, .. }) = pending.remove(&at) else { continue };
+                ops.push(Op{ at, kind: *op, key: key.clone(), value: value.clone(), context: context.clone(), invoke_us: *invoke_us, complete_us: *ts_us, values: values.clone(), clocks: clocks.clone() });
+            }
+        }
+    }
+    (ops, pending.len())
+}
@@ -1,9 +1,11 @@
 use rand::prelude::IndexedRandom;
-use log::info;
+use log::{info, warn};
 use reactor_actor::{ActorProcess, ActorSend, BehaviourBuilder, RouteTo, RuntimeCtx};
 use reactor_actor::codec::BincodeCodec;
 
+use crate::history::HistoryRecorder;
 use crate::messages::{DynamoClientIn, DynamoClientOut, GeneratorTrigger, ClientToNode, NodeToClient};
+use crate::session::{Session, SessionCheck, SessionPolicy, SessionStats};
 use crate::vector_clock::VectorClock;
 
 pub struct DynamoClient {
@@ -14,11 +16,58 @@ pub struct DynamoClient {
     last_metadata: Vec<VectorClock>,
     local_vc: VectorClock,
     inflight: bool,
+    session: Option<Session>,
+    retries: u32, // session retries of the current GET
+    session_stats: SessionStats,
+    history: Option<HistoryRecorder>,
 }
 
 impl DynamoClient {
     pub fn new(client_id: String, nodes: Vec<String>, reqs: Vec<ClientToNode>) -> Self {
-        Self { client_id, nodes, reqs, next: 0, last_metadata: vec![], local_vc: VectorClock::new(), inflight: false }
+        Self { client_id, nodes, reqs, next: 0, last_metadata: vec![], local_vc: VectorClock::new(), inflight: false, session: None, retries: 0, session_stats: SessionStats::default(), history: None }
+    }
+
+    pub fn with_session(mut self, policy: SessionPolicy) -> Self { self.session = Some(Session::new(policy)); self }
+
+    pub fn session_stats(&self) -> &SessionStats { &self.session_stats }
+
+    /// Records every request and response (see `history`).
+    pub fn with_history(mut self, history: HistoryRecorder) -> Self { self.history = Some(history); self }
+
+    pub fn history(&self) -> Option<&HistoryRecorder> { self.history.as_ref() }
+
+    fn send(&mut self, req: ClientToNode) -> Vec<DynamoClientOut> {
+        if let Some(h) = self.history.as_mut() { h.invoke(&self.client_id, &req); }
+        vec![DynamoClientOut::ClientToNode(req)]
+    }
+
+    // Applies the session policy to a GET result; returns None if the GET should be re-sent,
+    // otherwise whether the result is accepted as the client's view of the key.
+    fn check_session(&mut self, key: &str, request_id: u64, metadata: &[VectorClock]) -> Option<bool> {
+        let Some(session) = self.session.as_mut() else { return Some(true) };
+        let guarantee = match session.check_read(key, metadata) {
+            SessionCheck::Ok => { session.record_read(key, metadata); return Some(true); }
+            SessionCheck::Violation(g) => g,
+        };
+        self.session_stats.violations += 1;
+        match session.policy {
+            SessionPolicy::Retry{ max_retries } if self.retries < max_retries => {
+                self.retries += 1;
+                self.session_stats.retries += 1;
+                warn!("[client-session] {} stale GET key={} req_id={} violates {:?}; retry {}/{}", self.client_id, key, request_id, guarantee, self.retries, max_retries);
+                None
+            }
+            SessionPolicy::Warn => {
+                warn!("[client-session] {} stale GET key={} req_id={} violates {:?}; accepting", self.client_id, key, request_id, guarantee);
+                session.record_read(key, metadata);
+                Some(true)
+            }
+            _ => {
+                self.session_stats.rejected += 1;
+                warn!("[client-session] {} stale GET key={} req_id={} violates {:?}; rejected", self.client_id, key, request_id, guarantee);
+                Some(false)
+            }
+        }
     }
 }
 
@@ -40,20 +89,34 @@ impl ActorProcess for DynamoClient {
                 }
                 self.inflight = true;
                 info!("[client] sending {:?}", &req);
-                vec![DynamoClientOut::ClientToNode(req)]
+                self.send(req)
             }
             DynamoClientIn::NodeToClient(resp) => {
+                if let Some(h) = self.history.as_mut() { h.complete(&self.client_id, &resp); }
                 match resp {
-                    NodeToClient::ClientPutRsp{ key, request_id, .. } => {
+                    NodeToClient::ClientPutRsp{ key, request_id, clock, .. } => {
                         info!("[client] PutOk key={} req_id={}", key, request_id);
+                        if let Some(session) = self.session.as_mut() { session.record_write(&key, &clock); }
+                    }
+                    NodeToClient::ClientPutRejected{ key, request_id, reason, .. } => {
+                        warn!("[client] PutRejected key={} req_id={}: {}", key, request_id, reason);
                     }
                     NodeToClient::ClientGetRsp{ key, request_id, values, metadata, .. } => {
                         // Zip values with their clocks for clearer debugging
                         let pairs: Vec<String> = values.iter().zip(metadata.iter()).map(|(v, vc)| format!("({},{:?})", v, vc.clock)).collect();
                         info!("[client] GetOk key={} req_id={} versions={} entries={:?}", key, request_id, metadata.len(), pairs);
-                        self.last_metadata = metadata;
+                        match self.check_session(&key, request_id, &metadata) {
+                            None => {
+                                let req = self.reqs[self.next].clone();
+                                info!("[client] re-sending {:?}", &req);
+                                return self.send(req);
+                            }
+                            Some(true) => self.last_metadata = metadata,
+                            Some(false) => {}
+                        }
                     }
                 }
+                self.retries = 0;
                 // Chain next request after a response
                 if self.next + 1 <= self.reqs.len() {
                     self.next += 1;
@@ -70,7 +133,7 @@ impl ActorProcess for DynamoClient {
                     }
                     self.inflight = true;
                     info!("[client] sending {:?}", &req);
-                    vec![DynamoClientOut::ClientToNode(req)]
+                    self.send(req)
                 } else {
                     vec![]
                 }
@@ -98,17 +161,22 @@ pub async fn client_behaviour(
     client_id: String,
     nodes: Vec<String>,
     reqs: Vec<ClientToNode>,
+    session: Option<SessionPolicy>,
+    history: Option<HistoryRecorder>,
     decoder: reactor_actor::SubDecoderStore<DynamoClientIn>,
 ) {
     let reqs_len = 1usize; // single trigger to kick off serialized flow
-    let proc = DynamoClient::new(client_id.clone(), nodes.clone(), reqs);
-    BehaviourBuilder::new(proc, BincodeCodec::default())
+    let mut proc = DynamoClient::new(client_id.clone(), nodes.clone(), reqs);
+    if let Some(policy) = session { proc = proc.with_session(policy); }
+    if let Some(history) = history { proc = proc.with_history(history); }
+    let behaviour = BehaviourBuilder::new(proc, BincodeCodec::default())
         .send(DynamoClientSender::new(nodes))
         .sub_decoders(decoder)
         .ask_receiver_to_adapt()
         .generator_if(true, move || { vec![DynamoClientIn::GeneratorTrigger(GeneratorTrigger); reqs_len].into_iter() })
-        .build()
-        .run(ctx)
-        .await
-        .unwrap();
+        .build();
+    let addr = ctx.addr;
+    if let Err(e) = behaviour.run(ctx).await {
+        log::error!("[client] {} exited: {}", addr, e);
+    }
 }
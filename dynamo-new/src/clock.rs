@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use reactor_actor::{CorrelationId, TimerCmd, TimerId, TimerQueue};
+
+/// Source of time for `DynamoNode` deadlines, pings and latency measurements.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+    /// Milliseconds since the Unix epoch, stamped on writes to order siblings.
+    fn unix_ms(&self) -> u64;
+}
+
+/// Wall clock; what the deployed actors use.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant { Instant::now() }
+
+    fn unix_ms(&self) -> u64 { std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0) }
+}
+
+/// Clock that only moves when told to. Clones share the same time, so one handle can be kept by
+/// a test or simulator while nodes hold the others.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    base: Instant,
+    elapsed_us: Arc<AtomicU64>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self { Self::new() }
+}
+
+impl ManualClock {
+    pub fn new() -> Self { Self { base: Instant::now(), elapsed_us: Arc::new(AtomicU64::new(0)) } }
+
+    pub fn advance(&self, d: Duration) { self.elapsed_us.fetch_add(d.as_micros() as u64, Ordering::SeqCst); }
+
+    /// Moves time forward to `elapsed` since creation; never moves it backwards.
+    pub fn set_elapsed(&self, elapsed: Duration) { self.elapsed_us.fetch_max(elapsed.as_micros() as u64, Ordering::SeqCst); }
+
+    pub fn elapsed(&self) -> Duration { Duration::from_micros(self.elapsed_us.load(Ordering::SeqCst)) }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant { self.base + self.elapsed() }
+
+    // time since creation, so runs are reproducible
+    fn unix_ms(&self) -> u64 { self.elapsed().as_millis() as u64 }
+}
+
+// A timer set through the handle of a `ClockTimers`
+struct ClockTimer<M> { id: TimerId, period: Option<Duration>, msg: M, ask: Option<CorrelationId> }
+
+/// Serves a `TimerQueue` on a `Clock` instead of the runtime, for a processor no actor serves
+/// timers for. Nothing fires by itself: the owner calls `arm` after each step of the processor,
+/// and `due` hands over what is due whenever it asks.
+pub struct ClockTimers<M> {
+    queue: TimerQueue<M>,
+    clock: Arc<dyn Clock>,
+    timers: BTreeMap<(Instant, u64), ClockTimer<M>>, // (due, seq) -> timer
+    seq: u64,
+}
+
+impl<M: Clone> ClockTimers<M> {
+    pub fn new(queue: TimerQueue<M>, clock: Arc<dyn Clock>) -> Self { Self { queue, clock, timers: BTreeMap::new(), seq: 0 } }
+
+    /// Messages of the timers due by now, earliest first. An ask's timeout comes only while the
+    /// ask waits, and a periodic timer skips the periods it missed.
+    pub fn due(&mut self) -> Vec<M> {
+        self.arm();
+        let now = self.clock.now();
+        let mut out = vec![];
+        while let Some(entry) = self.timers.first_entry() && entry.key().0 <= now {
+            let ((at, _), t) = entry.remove_entry();
+            if let Some(ask) = t.ask && !self.queue.settle(ask) { continue; }
+            if let Some(period) = t.period {
+                let next = if at + period > now { at + period } else { now + period };
+                self.schedule(t.id, next, Some(period), t.msg.clone(), None);
+            }
+            out.push(t.msg);
+        }
+        out
+    }
+
+    /// Settles the ask `id` for its reply, see `TimerQueue::settle`.
+    pub fn settle(&self, id: CorrelationId) -> bool { self.queue.settle(id) }
+
+    /// Applies the timers set or cancelled through the handle since the last call; their delays
+    /// count from now.
+    pub fn arm(&mut self) {
+        let now = self.clock.now();
+        while let Some(cmd) = self.queue.try_next() {
+            match cmd {
+                TimerCmd::Set{ id, delay, period, msg, ask } => self.schedule(id, now + delay, period, msg, ask),
+                TimerCmd::Cancel(id) => self.timers.retain(|_, t| t.id != id),
+            }
+        }
+    }
+
+    fn schedule(&mut self, id: TimerId, at: Instant, period: Option<Duration>, msg: M, ask: Option<CorrelationId>) {
+        self.seq += 1;
+        self.timers.insert((at, self.seq), ClockTimer{ id, period, msg, ask });
+    }
+}
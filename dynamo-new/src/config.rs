@@ -0,0 +1,212 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::node::ReadRepairConfig;
+use crate::versioned_value::SiblingPolicy;
+use crate::wal::FsyncMode;
+
+/// Settings of one `dynamo_node`, from its actor payload and/or a TOML file.
+///
+/// The payload may name a file with `config_path`; keys set in the payload override the file, so
+/// cluster-wide settings can live in one file while each placement only sets its `node_id`.
+/// Unknown keys are rejected rather than ignored, and `validate` runs before the node starts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DynamoConfig {
+    /// Defaults to the actor address.
+    pub node_id: String,
+    /// Every node on the ring, including this one.
+    pub nodes: Vec<String>,
+    /// Replicas per key.
+    #[serde(rename = "N")]
+    pub n: usize,
+    /// Replica acks a PUT waits for.
+    #[serde(rename = "W")]
+    pub w: usize,
+    /// Replica responses a GET waits for.
+    #[serde(rename = "R")]
+    pub r: usize,
+    /// Virtual nodes per node on the ring.
+    #[serde(rename = "T")]
+    pub t: usize,
+    /// How long a replica gets to answer before it is marked failed.
+    pub timeout_ms: u64,
+    /// How often failed nodes are pinged and anti-entropy runs.
+    pub ping_interval_ms: u64,
+    /// Keys pushed to their replicas per anti-entropy round.
+    pub sync_batch: usize,
+    /// Sends of one repair write before it is dropped.
+    pub repair_max_attempts: u32,
+    pub read_repair_window_ms: u64,
+    pub read_repair_chance: f64,
+    /// Directory of the write-ahead log `<node_id>.wal`; without it the store lives only in memory.
+    pub wal_dir: Option<PathBuf>,
+    pub wal_fsync: FsyncMode,
+    /// How long `wal_fsync = "interval"` holds acks to share one fsync.
+    pub wal_group_commit_ms: u64,
+    /// Concurrent versions a replica keeps per key before `sibling_policy` applies; 0 = no limit.
+    pub max_siblings: usize,
+    pub sibling_policy: SiblingPolicy,
+}
+
+impl Default for DynamoConfig {
+    fn default() -> Self {
+        let read_repair = ReadRepairConfig::default();
+        Self {
+            node_id: String::new(),
+            nodes: vec![],
+            n: 3,
+            w: 2,
+            r: 2,
+            t: 10,
+            timeout_ms: 800,
+            ping_interval_ms: 1000,
+            sync_batch: 2,
+            repair_max_attempts: 3,
+            read_repair_window_ms: read_repair.late_window_ms,
+            read_repair_chance: read_repair.full_repair_chance,
+            wal_dir: None,
+            wal_fsync: FsyncMode::default(),
+            wal_group_commit_ms: 10,
+            max_siblings: 0,
+            sibling_policy: SiblingPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("cannot read {}: {source}", path.display())]
+    Io { path: PathBuf, source: std::io::Error },
+    #[error("cannot parse {origin}: {reason}")]
+    Parse { origin: String, reason: String },
+    #[error("invalid `{field}`: {reason}")]
+    Invalid { field: &'static str, reason: String },
+}
+
+fn invalid(field: &'static str, reason: String) -> ConfigError {
+    ConfigError::Invalid { field, reason }
+}
+
+impl DynamoConfig {
+    pub fn from_toml_str(s: &str) -> Result<Self, ConfigError> {
+        toml::from_str(s).map_err(|e| ConfigError::Parse { origin: "TOML config".to_string(), reason: e.message().to_string() })
+    }
+
+    pub fn from_toml_file(path: &Path) -> Result<Self, ConfigError> {
+        let s = std::fs::read_to_string(path).map_err(|source| ConfigError::Io { path: path.to_path_buf(), source })?;
+        toml::from_str(&s).map_err(|e| ConfigError::Parse { origin: path.display().to_string(), reason: e.message().to_string() })
+    }
+
+    /// Builds the config of a `dynamo_node` from its payload: the file at `config_path` if given,
+    /// overridden by the other payload keys. `node_id` falls back to `default_node_id`. Not validated.
+    pub fn from_payload(mut payload: HashMap<String, serde_json::Value>, default_node_id: &str) -> Result<Self, ConfigError> {
+        let base = match payload.remove("config_path") {
+            Some(serde_json::Value::String(path)) => Self::from_toml_file(Path::new(&path))?,
+            Some(other) => return Err(invalid("config_path", format!("expected a path, got {}", other))),
+            None => Self::default(),
+        };
+        let mut merged = match serde_json::to_value(base) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => unreachable!("DynamoConfig serializes to a map"),
+        };
+        merged.extend(payload);
+        let mut cfg: Self = serde_json::from_value(serde_json::Value::Object(merged)).map_err(|e| ConfigError::Parse { origin: "actor payload".to_string(), reason: e.to_string() })?;
+        if cfg.node_id.is_empty() {
+            cfg.node_id = default_node_id.to_string();
+        }
+        Ok(cfg)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.node_id.is_empty() {
+            return Err(invalid("node_id", "must not be empty".to_string()));
+        }
+        if self.nodes.is_empty() {
+            return Err(invalid("nodes", "must list at least this node".to_string()));
+        }
+        let mut seen = HashSet::new();
+        if let Some(dup) = self.nodes.iter().find(|n| !seen.insert(n.as_str())) {
+            return Err(invalid("nodes", format!("{} is listed twice", dup)));
+        }
+        if !self.nodes.contains(&self.node_id) {
+            return Err(invalid("node_id", format!("{} is not in nodes {:?}", self.node_id, self.nodes)));
+        }
+        if self.n == 0 || self.n > self.nodes.len() {
+            return Err(invalid("N", format!("must be between 1 and the number of nodes ({}), got {}", self.nodes.len(), self.n)));
+        }
+        if self.w == 0 || self.w > self.n {
+            return Err(invalid("W", format!("must be between 1 and N ({}), got {}", self.n, self.w)));
+        }
+        if self.r == 0 || self.r > self.n {
+            return Err(invalid("R", format!("must be between 1 and N ({}), got {}", self.n, self.r)));
+        }
+        if self.t == 0 {
+            return Err(invalid("T", "must be at least 1".to_string()));
+        }
+        for (field, v) in [("timeout_ms", self.timeout_ms), ("ping_interval_ms", self.ping_interval_ms), ("sync_batch", self.sync_batch as u64), ("repair_max_attempts", self.repair_max_attempts as u64), ("wal_group_commit_ms", self.wal_group_commit_ms)] {
+            if v == 0 {
+                return Err(invalid(field, "must be at least 1".to_string()));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.read_repair_chance) {
+            return Err(invalid("read_repair_chance", format!("must be between 0 and 1, got {}", self.read_repair_chance)));
+        }
+        Ok(())
+    }
+
+    pub fn read_repair(&self) -> ReadRepairConfig {
+        ReadRepairConfig { late_window_ms: self.read_repair_window_ms, full_repair_chance: self.read_repair_chance }
+    }
+
+    pub fn wal_path(&self) -> Option<PathBuf> {
+        self.wal_dir.as_ref().map(|dir| dir.join(format!("{}.wal", self.node_id)))
+    }
+
+    pub fn tuning(&self) -> Tuning {
+        Tuning { w: self.w, r: self.r, timeout_ms: self.timeout_ms, ping_interval_ms: self.ping_interval_ms, sync_batch: self.sync_batch }
+    }
+}
+
+/// Parameters of a running node that can be changed without restarting it; reported in `NodeStats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct Tuning {
+    pub w: usize,
+    pub r: usize,
+    pub timeout_ms: u64,
+    pub ping_interval_ms: u64,
+    pub sync_batch: usize,
+}
+
+/// A change to some of a node's `Tuning`, sent in `NodeToNode::Reconfigure`. Unset fields keep
+/// their current value; uses the same key names as `DynamoConfig`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, Encode, Decode)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuningUpdate {
+    #[serde(rename = "W")]
+    pub w: Option<usize>,
+    #[serde(rename = "R")]
+    pub r: Option<usize>,
+    pub timeout_ms: Option<u64>,
+    pub ping_interval_ms: Option<u64>,
+    pub sync_batch: Option<usize>,
+}
+
+impl TuningUpdate {
+    pub fn is_empty(&self) -> bool { *self == Self::default() }
+
+    /// Applies the update to `cfg` and validates the result, leaving `cfg` untouched on error.
+    pub fn apply(&self, cfg: &mut DynamoConfig) -> Result<(), ConfigError> {
+        let mut next = cfg.clone();
+        if let Some(w) = self.w { next.w = w; }
+        if let Some(r) = self.r { next.r = r; }
+        if let Some(ms) = self.timeout_ms { next.timeout_ms = ms; }
+        if let Some(ms) = self.ping_interval_ms { next.ping_interval_ms = ms; }
+        if let Some(b) = self.sync_batch { next.sync_batch = b; }
+        next.validate()?;
+        *cfg = next;
+        Ok(())
+    }
+}
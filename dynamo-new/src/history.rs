@@ -0,0 +1,112 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Clock;
+use crate::messages::{ClientToNode, NodeToClient};
+use crate::vector_clock::VectorClock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpKind { Put, Get }
+
+/// One line of a client history file (JSON Lines). Every request produces an `Invoke` when it is
+/// sent and, if the cluster answers, a `Complete` with what came back. Timestamps are wall-clock
+/// microseconds since the Unix epoch so histories from several clients can be merged (or time on an
+/// injected `Clock`, see `HistoryRecorder::with_clock`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HistoryEvent {
+    /// `context` is the causal context sent with a PUT.
+    Invoke { client: String, request_id: u64, op: OpKind, key: String, value: Option<String>, context: Vec<VectorClock>, ts_us: u64 },
+    /// For a PUT, `clocks` holds the single clock the coordinator wrote; for a GET, one clock per returned value.
+    Complete { client: String, request_id: u64, op: OpKind, key: String, values: Vec<String>, clocks: Vec<VectorClock>, ts_us: u64 },
+}
+
+impl HistoryEvent {
+    pub fn client(&self) -> &str {
+        match self { HistoryEvent::Invoke{ client, .. } | HistoryEvent::Complete{ client, .. } => client }
+    }
+
+    pub fn ts_us(&self) -> u64 {
+        match self { HistoryEvent::Invoke{ ts_us, .. } | HistoryEvent::Complete{ ts_us, .. } => *ts_us }
+    }
+}
+
+fn now_us() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_micros() as u64).unwrap_or(0)
+}
+
+/// Records a client's history, either appended to a JSONL file or kept in memory.
+pub struct HistoryRecorder {
+    out: Option<BufWriter<File>>,
+    events: Vec<HistoryEvent>,
+    time: Option<(Arc<dyn Clock>, Instant)>,
+}
+
+impl HistoryRecorder {
+    pub fn to_file(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { out: Some(BufWriter::new(file)), events: vec![], time: None })
+    }
+
+    pub fn in_memory() -> Self { Self { out: None, events: vec![], time: None } }
+
+    /// Timestamps events with `clock`, as microseconds since `epoch`, instead of wall-clock time.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>, epoch: Instant) -> Self { self.time = Some((clock, epoch)); self }
+
+    /// Events recorded so far; empty when writing to a file.
+    pub fn events(&self) -> &[HistoryEvent] { &self.events }
+
+    /// Records an `Invoke` for a request about to be sent.
+    pub fn invoke(&mut self, client: &str, req: &ClientToNode) {
+        let ts_us = self.now();
+        let ev = match req {
+            ClientToNode::ClientPut{ key, value, metadata, request_id, .. } => HistoryEvent::Invoke{ client: client.to_string(), request_id: *request_id, op: OpKind::Put, key: key.clone(), value: Some(value.clone()), context: metadata.clone(), ts_us },
+            ClientToNode::ClientGet{ key, request_id, .. } => HistoryEvent::Invoke{ client: client.to_string(), request_id: *request_id, op: OpKind::Get, key: key.clone(), value: None, context: vec![], ts_us },
+        };
+        self.record(ev);
+    }
+
+    /// Records a `Complete` for a response from the cluster.
+    pub fn complete(&mut self, client: &str, rsp: &NodeToClient) {
+        let ts_us = self.now();
+        let ev = match rsp {
+            NodeToClient::ClientPutRsp{ key, request_id, clock, .. } => HistoryEvent::Complete{ client: client.to_string(), request_id: *request_id, op: OpKind::Put, key: key.clone(), values: vec![], clocks: vec![clock.clone()], ts_us },
+            NodeToClient::ClientGetRsp{ key, request_id, values, metadata, .. } => HistoryEvent::Complete{ client: client.to_string(), request_id: *request_id, op: OpKind::Get, key: key.clone(), values: values.clone(), clocks: metadata.clone(), ts_us },
+            // some replicas may have stored the write, so like a timeout it stays indeterminate
+            NodeToClient::ClientPutRejected{ .. } => return,
+        };
+        self.record(ev);
+    }
+
+    fn now(&self) -> u64 {
+        match &self.time { Some((clock, epoch)) => clock.now().saturating_duration_since(*epoch).as_micros() as u64, None => now_us() }
+    }
+
+    fn record(&mut self, ev: HistoryEvent) {
+        let Some(out) = self.out.as_mut() else { self.events.push(ev); return };
+        // flushed per event so the file is complete even if the actor is killed
+        let res = serde_json::to_writer(&mut *out, &ev).map_err(io::Error::other).and_then(|_| out.write_all(b"\n")).and_then(|_| out.flush());
+        if let Err(e) = res { warn!("[history] failed to record event: {}", e); }
+    }
+}
+
+/// Reads one or more history files and returns their events ordered by timestamp.
+pub fn load<P: AsRef<Path>>(paths: &[P]) -> io::Result<Vec<HistoryEvent>> {
+    let mut events = vec![];
+    for p in paths {
+        for (i, line) in BufReader::new(File::open(p)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            let ev: HistoryEvent = serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", p.as_ref().display(), i + 1, e)))?;
+            events.push(ev);
+        }
+    }
+    events.sort_by_key(|e| e.ts_us());
+    Ok(events)
+}
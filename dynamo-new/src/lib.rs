@@ -3,9 +3,21 @@ pub mod vector_clock;
 pub mod versioned_value;
 pub mod consistent_hash;
 pub mod node;
-mod client;
+pub mod clock;
+pub mod config;
+pub mod metrics;
+pub mod session;
+pub mod history;
+pub mod checker;
+pub mod admin;
+pub mod client;
 mod cart_client;
-mod bench_client;
+pub mod bench_client;
+pub mod workload;
+pub mod results;
+pub mod snapshot;
+pub mod wal;
+pub mod local_cluster;
 
 pub use reactor_actor::setup_shared_logger_ref;
 
@@ -13,7 +25,7 @@ use reactor_actor::RuntimeCtx;
 use reactor_actor::actor;
 use std::collections::HashMap;
 
-use messages::{DynamoNodeIn, DynamoClientIn, ClientToNode, NodeToNode, NodeToClient, DynamoClientOut, DynamoNodeOut};
+use messages::{DynamoNodeIn, DynamoClientIn, DynamoAdminIn, ClientToNode, NodeToNode, NodeToClient, DynamoClientOut, DynamoNodeOut};
 use cart_client::CartStep;
 use reactor_macros::msg_converter;
 
@@ -24,6 +36,8 @@ msg_converter! {
         dynamo_node_decoder can decode DynamoClientOut, DynamoNodeOut to DynamoNodeIn;
         // Client accepts messages from nodes
         dynamo_client_decoder can decode DynamoNodeOut to DynamoClientIn;
+        // Admin accepts node-to-node replies (stats)
+        dynamo_admin_decoder can decode DynamoNodeOut to DynamoAdminIn;
     ];
 }
 
@@ -32,14 +46,17 @@ lazy_static::lazy_static! {
 }
 
 #[actor]
-fn dynamo_node(ctx: RuntimeCtx, mut payload: HashMap<String, serde_json::Value>) {
-    let node_id = payload.remove("node_id").and_then(|v| v.as_str().map(|s| s.to_string())).unwrap_or_else(|| ctx.addr.to_string());
-    let nodes: Vec<String> = payload.remove("nodes").and_then(|v| serde_json::from_value(v).ok()).unwrap_or_else(Vec::new);
-    let n = payload.remove("N").and_then(|v| v.as_u64()).unwrap_or(3) as usize;
-    let w = payload.remove("W").and_then(|v| v.as_u64()).unwrap_or(2) as usize;
-    let r = payload.remove("R").and_then(|v| v.as_u64()).unwrap_or(2) as usize;
-    let t = payload.remove("T").and_then(|v| v.as_u64()).unwrap_or(10) as usize;
-    RUNTIME.spawn(node::node_behaviour(ctx, node_id, nodes, n, w, r, t, dynamo_node_decoder));
+fn dynamo_node(ctx: RuntimeCtx, payload: HashMap<String, serde_json::Value>) {
+    // payload keys are `DynamoConfig` fields, optionally on top of the TOML file at `config_path`
+    let cfg = match config::DynamoConfig::from_payload(payload, ctx.addr).and_then(|cfg| cfg.validate().map(|_| cfg)) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            log::error!("[node-init] {} not started: {}", ctx.addr, e);
+            return;
+        }
+    };
+    log::info!("[node-init] {} N={} W={} R={} T={} nodes={}", cfg.node_id, cfg.n, cfg.w, cfg.r, cfg.t, cfg.nodes.len());
+    RUNTIME.spawn(node::node_behaviour(ctx, cfg, dynamo_node_decoder));
 }
 
 #[actor]
@@ -88,7 +105,11 @@ fn dynamo_client(ctx: RuntimeCtx, mut payload: HashMap<String, serde_json::Value
         rid += 1;
         reqs.push(ClientToNode::ClientGet { key: "user:1".into(), client_addr: client_id.clone(), request_id: rid });
     }
-    RUNTIME.spawn(client::client_behaviour(ctx, client_id, nodes, reqs, dynamo_client_decoder));
+    // Optional session guarantees: { policy = "retry"|"reject"|"warn", max_retries? }
+    let session: Option<session::SessionPolicy> = payload.remove("session").and_then(|v| serde_json::from_value(v).map_err(|e| log::warn!("[client-init] {} ignoring invalid session config: {}", client_id, e)).ok());
+    if let Some(policy) = &session { log::info!("[client-init] {} session guarantees enabled policy={:?}", client_id, policy); }
+    let history = history_recorder(&client_id, payload.remove("history_path"));
+    RUNTIME.spawn(client::client_behaviour(ctx, client_id, nodes, reqs, session, history, dynamo_client_decoder));
 }
 
 #[actor]
@@ -105,7 +126,60 @@ fn dynamo_cart_client(ctx: RuntimeCtx, mut payload: HashMap<String, serde_json::
 fn dynamo_bench_client(ctx: RuntimeCtx, mut payload: HashMap<String, serde_json::Value>) {
     let client_id = payload.remove("client_id").and_then(|v| v.as_str().map(|s| s.to_string())).unwrap_or_else(|| ctx.addr.to_string());
     let nodes: Vec<String> = payload.remove("nodes").and_then(|v| serde_json::from_value(v).ok()).unwrap_or_else(Vec::new);
-    let num_ops = payload.remove("num_ops").and_then(|v| v.as_u64()).unwrap_or(1000) as usize;
-    log::info!("[bench-init] {} running {} operations", client_id, num_ops);
-    RUNTIME.spawn(bench_client::bench_client_behaviour(ctx, client_id, nodes, num_ops, dynamo_client_decoder));
+    // Optional YCSB-style workload table; `num_ops` still sets the operation count
+    let mut workload: workload::WorkloadConfig = payload.remove("workload").and_then(|v| serde_json::from_value(v).map_err(|e| log::warn!("[bench-init] {} ignoring invalid workload: {}", client_id, e)).ok()).unwrap_or_default();
+    if let Some(n) = payload.remove("num_ops").and_then(|v| v.as_u64()) { workload.operations = n as usize; }
+    if let Err(e) = workload.validate() {
+        log::error!("[bench-init] {} invalid workload: {}", client_id, e);
+        return;
+    }
+    log::info!("[bench-init] {} running {} operations", client_id, workload.operations);
+    let history = history_recorder(&client_id, payload.remove("history_path"));
+    // Results: `run_id`, `results_path` (JSON), `results_csv` (appended), `keep_samples`; N/W/R/T label the cluster
+    let run_id = payload.remove("run_id").and_then(|v| v.as_str().map(|s| s.to_string()));
+    let json = payload.remove("results_path").and_then(|v| v.as_str().map(std::path::PathBuf::from));
+    let csv = payload.remove("results_csv").and_then(|v| v.as_str().map(std::path::PathBuf::from));
+    let keep_samples = payload.remove("keep_samples").and_then(|v| v.as_bool()).unwrap_or(false);
+    let [n, w, r, t] = ["N", "W", "R", "T"].map(|k| payload.remove(k).and_then(|v| v.as_u64()).map(|v| v as usize));
+    let mut proc = bench_client::BenchClient::new(client_id, nodes, workload).with_run(run_id, n, w, r, t).with_output(json, csv, keep_samples);
+    if let Some(h) = history { proc = proc.with_history(h); }
+    RUNTIME.spawn(bench_client::bench_client_behaviour(ctx, proc, dynamo_client_decoder));
+}
+
+// Optional history file for the consistency checker (`check_history`)
+fn history_recorder(client_id: &str, path: Option<serde_json::Value>) -> Option<history::HistoryRecorder> {
+    let path = path.and_then(|v| v.as_str().map(std::path::PathBuf::from))?;
+    match history::HistoryRecorder::to_file(&path) {
+        Ok(h) => { log::info!("[client-init] {} recording history to {}", client_id, path.display()); Some(h) }
+        Err(e) => { log::warn!("[client-init] {} cannot open history file {}: {}", client_id, path.display(), e); None }
+    }
+}
+
+#[actor]
+fn dynamo_admin(ctx: RuntimeCtx, mut payload: HashMap<String, serde_json::Value>) {
+    let admin_id = payload.remove("admin_id").and_then(|v| v.as_str().map(|s| s.to_string())).unwrap_or_else(|| ctx.addr.to_string());
+    let nodes: Vec<String> = payload.remove("nodes").and_then(|v| serde_json::from_value(v).ok()).unwrap_or_default();
+    let metrics_dir = payload.remove("metrics_dir").and_then(|v| v.as_str().map(std::path::PathBuf::from));
+    let interval_ms = payload.remove("interval_ms").and_then(|v| v.as_u64()).unwrap_or(5000);
+    // reconfigure = { W = 1, timeout_ms = 300 }, forwarded cluster-wide unless propagate = false
+    let propagate = payload.remove("propagate").and_then(|v| v.as_bool()).unwrap_or(true);
+    let reconfigure = match payload.remove("reconfigure").map(serde_json::from_value::<config::TuningUpdate>) {
+        Some(Ok(update)) => (!update.is_empty()).then_some((update, propagate)),
+        Some(Err(e)) => { log::error!("[admin-init] {} not started: invalid reconfigure: {}", admin_id, e); return; }
+        None => None,
+    };
+    log::info!("[admin-init] {} polling {} nodes every {}ms", admin_id, nodes.len(), interval_ms);
+    let mut proc = admin::DynamoAdmin::new(admin_id.clone(), nodes, metrics_dir);
+    if let Some((update, propagate)) = reconfigure { proc = proc.with_reconfigure(update, propagate); }
+    if let Some(path) = payload.remove("snapshot_path").and_then(|v| v.as_str().map(std::path::PathBuf::from)) {
+        log::info!("[admin-init] {} exporting a snapshot to {}", admin_id, path.display());
+        proc = proc.with_export(path);
+    }
+    if let Some(path) = payload.remove("import_path").and_then(|v| v.as_str().map(std::path::PathBuf::from)) {
+        match snapshot::Snapshot::load(&path) {
+            Ok((header, snap)) => { log::info!("[admin-init] {} importing {} keys from {} (exported from {:?})", admin_id, header.keys, path.display(), header.nodes); proc = proc.with_import(&snap); }
+            Err(e) => { log::error!("[admin-init] {} not started: cannot load snapshot: {}", admin_id, e); return; }
+        }
+    }
+    RUNTIME.spawn(admin::admin_behaviour(ctx, proc, interval_ms, dynamo_admin_decoder));
 }
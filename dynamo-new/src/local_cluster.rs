@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use log::{info, warn};
+use reactor_actor::{Connection, ControlInst, ControlReq, NodeComm, RuntimeCtx};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::bench_client::{self, BenchClient};
+use crate::config::DynamoConfig;
+use crate::node;
+
+// How long a freshly started actor gets to open its listener
+const LISTEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// DynamoNode and client actors running in this process under the reactor runtime, each listening
+/// on its own localhost TCP port, so every message goes through the real codecs and transport.
+///
+/// Stands in for the node controller: it hands each actor its port and answers address lookups.
+/// Must be created inside a tokio runtime.
+pub struct LocalCluster {
+    addrs: Arc<Mutex<HashMap<String, SocketAddr>>>,
+    controller_tx: mpsc::Sender<ControlReq>,
+    actors: Vec<(String, mpsc::Sender<ControlInst>, JoinHandle<()>)>,
+    next_port: Option<u16>,
+}
+
+impl Default for LocalCluster {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocalCluster {
+    /// Actors get free ports picked by the OS.
+    pub fn new() -> Self {
+        let addrs: Arc<Mutex<HashMap<String, SocketAddr>>> = Arc::new(Mutex::new(HashMap::new()));
+        let (controller_tx, mut controller_rx) = mpsc::channel::<ControlReq>(1024);
+        let known = addrs.clone();
+        tokio::spawn(async move {
+            while let Some(req) = controller_rx.recv().await {
+                match req {
+                    ControlReq::Resolve { addr, resp_tx } => {
+                        let conn = known.lock().unwrap().get(&addr).map(|a| Connection::Remote(*a)).unwrap_or(Connection::CouldntResolve);
+                        let _ = resp_tx.send(conn);
+                    }
+                    // actors are not restarted here, a failure shows up in the test instead
+                    ControlReq::Exited { addr, exit } => info!("[local_cluster] {} exited: {:?}", addr, exit),
+                }
+            }
+        });
+        Self { addrs, controller_tx, actors: vec![], next_port: None }
+    }
+
+    /// Actors get consecutive ports from `base_port` on.
+    pub fn with_base_port(mut self, base_port: u16) -> Self {
+        self.next_port = Some(base_port);
+        self
+    }
+
+    /// Address each actor listens on.
+    pub fn addrs(&self) -> HashMap<String, SocketAddr> {
+        self.addrs.lock().unwrap().clone()
+    }
+
+    pub async fn spawn_node(&mut self, cfg: DynamoConfig) -> io::Result<SocketAddr> {
+        cfg.validate().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let node_id = cfg.node_id.clone();
+        let (ctx, control, addr) = self.register(&node_id)?;
+        let handle = tokio::spawn(node::node_behaviour(ctx, cfg, crate::dynamo_node_decoder));
+        self.start(&node_id, control, addr, handle).await
+    }
+
+    pub async fn spawn_bench_client(&mut self, client_id: &str, proc: BenchClient) -> io::Result<SocketAddr> {
+        let (ctx, control, addr) = self.register(client_id)?;
+        let handle = tokio::spawn(bench_client::bench_client_behaviour(ctx, proc, crate::dynamo_client_decoder));
+        self.start(client_id, control, addr, handle).await
+    }
+
+    /// Stops every actor and waits up to `timeout` for them to wind down.
+    pub async fn stop(self, timeout: Duration) {
+        for (name, control, _) in self.actors.iter() {
+            if control.send(ControlInst::Stop).await.is_err() {
+                warn!("[local_cluster] {} already stopped", name);
+            }
+        }
+        let deadline = Instant::now() + timeout;
+        for (name, _, handle) in self.actors {
+            if tokio::time::timeout(deadline.saturating_duration_since(Instant::now()), handle).await.is_err() {
+                warn!("[local_cluster] {} did not stop within {:?}", name, timeout);
+            }
+        }
+    }
+
+    fn register(&mut self, name: &str) -> io::Result<(RuntimeCtx, mpsc::Sender<ControlInst>, SocketAddr)> {
+        if self.addrs.lock().unwrap().contains_key(name) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("actor {} already exists", name)));
+        }
+        let port = match self.next_port.as_mut() {
+            Some(p) => { *p += 1; *p - 1 }
+            None => TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?.local_addr()?.port(),
+        };
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let (control_tx, control_rx) = mpsc::channel(20);
+        let ctx = RuntimeCtx::new(name.to_string().leak(), NodeComm::new(control_rx, self.controller_tx.clone()));
+        Ok((ctx, control_tx, addr))
+    }
+
+    async fn start(&mut self, name: &str, control: mpsc::Sender<ControlInst>, addr: SocketAddr, handle: JoinHandle<()>) -> io::Result<SocketAddr> {
+        control.send(ControlInst::StartTcpRecv(addr.port())).await.map_err(|_| io::Error::other(format!("{} exited before listening", name)))?;
+        self.actors.push((name.to_string(), control, handle));
+        wait_listening(name, addr).await?;
+        self.addrs.lock().unwrap().insert(name.to_string(), addr);
+        info!("[local_cluster] {} listening on {}", name, addr);
+        Ok(addr)
+    }
+}
+
+// Senders retry a refused connection with backoff, but handing out an address only once its
+// listener is up keeps startup from burning through those retries.
+async fn wait_listening(name: &str, addr: SocketAddr) -> io::Result<()> {
+    let deadline = Instant::now() + LISTEN_TIMEOUT;
+    loop {
+        match reactor_actor::probe_tcp(addr, "local_cluster_probe").await {
+            Ok(()) => return Ok(()),
+            Err(e) if Instant::now() >= deadline => return Err(io::Error::new(e.kind(), format!("{} is not listening on {}: {}", name, addr, e))),
+            Err(_) => tokio::time::sleep(Duration::from_millis(5)).await,
+        }
+    }
+}
@@ -1,11 +1,30 @@
 use bincode::{Decode, Encode};
+use reactor_actor::CorrelationId;
 use reactor_macros::{DefaultPrio, Msg as DeriveMsg, msg_converter};
 use crate::vector_clock::VectorClock;
 use crate::versioned_value::VersionedValues;
+use crate::metrics::NodeStats;
+use crate::config::{Tuning, TuningUpdate};
 
 #[derive(Debug, Clone, Encode, Decode, DefaultPrio, DeriveMsg)]
 pub struct GeneratorTrigger;
 
+// A node's own timer: its earliest pending deadline, ping round or group commit is due
+#[derive(Debug, Clone, Encode, Decode, DefaultPrio, DeriveMsg)]
+pub struct Wake;
+
+// A node's own timer: the RepairReq sent as `repair_id` went unanswered for the timeout
+#[derive(Debug, Clone, Encode, Decode, DefaultPrio, DeriveMsg)]
+pub struct RepairTimeout { pub repair_id: CorrelationId }
+
+// A node's own timer: the replica sent the PutReq `msg_id` did not answer within the timeout
+#[derive(Debug, Clone, Encode, Decode, DefaultPrio, DeriveMsg)]
+pub struct PutTimeout { pub msg_id: CorrelationId }
+
+// A node's own timer: the replica sent the GetReq `msg_id` did not answer within the timeout
+#[derive(Debug, Clone, Encode, Decode, DefaultPrio, DeriveMsg)]
+pub struct GetTimeout { pub msg_id: CorrelationId }
+
 // Client -> Node
 #[derive(Debug, Clone, Encode, Decode, DefaultPrio, DeriveMsg)]
 pub enum ClientToNode {
@@ -16,20 +35,35 @@ pub enum ClientToNode {
 // Node -> Client
 #[derive(Debug, Clone, Encode, Decode, DefaultPrio, DeriveMsg)]
 pub enum NodeToClient {
-    ClientPutRsp { key: String, request_id: u64, client_addr: String },
+    ClientPutRsp { key: String, request_id: u64, clock: VectorClock, client_addr: String },
+    // the key is at its sibling limit and `sibling_policy = "reject_write"`; replicas that
+    // answered before the rejection may still hold the write
+    ClientPutRejected { key: String, request_id: u64, reason: String, client_addr: String },
     ClientGetRsp { key: String, request_id: u64, values: Vec<String>, metadata: Vec<VectorClock>, client_addr: String },
 }
 
+// Why a RepairReq was sent; reported in logs and failure counts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encode, Decode)]
+pub enum RepairKind { ReadRepair, Handoff, Redistribution, Import }
+
 // Node <-> Node
 #[derive(Debug, Clone, Encode, Decode, DefaultPrio, DeriveMsg)]
 pub enum NodeToNode {
     ForwardClientPut { coordinator: String, key: String, value: String, metadata: Vec<VectorClock>, client_addr: String, request_id: u64 },
     ForwardClientGet { coordinator: String, key: String, client_addr: String, request_id: u64 },
 
-    PutReq { from: String, to: String, key: String, value: String, clock: VectorClock, msg_id: u64, handoff: Option<Vec<String>> },
-    PutRsp { from: String, to: String, msg_id: u64 },
-    GetReq { from: String, to: String, key: String, msg_id: u64 },
-    GetRsp { from: String, to: String, key: String, values: VersionedValues, msg_id: u64 },
+    // Each replica request is an ask of the coordinator, which the replica's answer settles
+    PutReq { from: String, to: String, key: String, value: String, clock: VectorClock, ts_ms: u64, msg_id: CorrelationId, handoff: Option<Vec<String>> },
+    PutRsp { from: String, to: String, #[reply_to] msg_id: CorrelationId },
+    // the write would put the key over the replica's sibling limit
+    PutRej { from: String, to: String, #[reply_to] msg_id: CorrelationId, reason: String },
+    GetReq { from: String, to: String, key: String, msg_id: CorrelationId },
+    GetRsp { from: String, to: String, key: String, values: VersionedValues, #[reply_to] msg_id: CorrelationId },
+
+    // Read repair, hinted-handoff replay and redistribution; acknowledged and retried until max attempts.
+    // Each attempt is an ask of the sender, which the RepairRsp answers
+    RepairReq { from: String, to: String, key: String, values: VersionedValues, repair_id: CorrelationId, kind: RepairKind },
+    RepairRsp { from: String, to: String, #[reply_to] repair_id: CorrelationId },
 
     // background anti-entropy: push local view of a key to a replica for merge
     SyncKey { from: String, to: String, key: String, values: VersionedValues },
@@ -40,18 +74,36 @@ pub enum NodeToNode {
     // Dynamic membership: add a new node to the cluster
     AddNode { from: String, to: String, new_node: String },
     AddNodeAck { from: String, to: String, new_node: String },
+
+    // Admin: fetch a node's metrics and state
+    StatsReq { from: String, to: String },
+    StatsRsp { from: String, to: String, stats: Box<NodeStats> },
+
+    // Admin: change tuning on a live node; with `propagate` the node forwards it to every other node,
+    // which all answer `from` with their new tuning or why the update was rejected
+    Reconfigure { from: String, to: String, update: TuningUpdate, propagate: bool },
+    ReconfigureRsp { from: String, to: String, result: Result<Tuning, String> },
+
+    // Admin: export every key in a node's store, streamed in chunks until `last`
+    SnapshotReq { from: String, to: String },
+    SnapshotChunk { from: String, to: String, entries: Vec<(String, VersionedValues)>, last: bool },
+    // Admin: load one snapshot key; the node writes it to the key's replicas with RepairReq
+    ImportKey { from: String, to: String, key: String, values: VersionedValues, import_id: u64 },
+    ImportAck { from: String, to: String, import_id: u64 },
 }
 
 msg_converter! {
     Unions: [
-        DynamoNodeIn = ClientToNode, NodeToNode;
+        DynamoNodeIn = ClientToNode, NodeToNode, Wake, RepairTimeout, PutTimeout, GetTimeout;
         DynamoNodeOut = NodeToNode, NodeToClient;
         DynamoClientIn = NodeToClient, GeneratorTrigger;
         DynamoClientOut = ClientToNode;
+        DynamoAdminIn = NodeToNode, GeneratorTrigger;
     ];
     Adapters: [
         DynamoNodeIn from DynamoClientOut via ClientToNode;
         DynamoNodeIn from DynamoNodeOut via NodeToNode;
         DynamoClientIn from DynamoNodeOut via NodeToClient;
+        DynamoAdminIn from DynamoNodeOut via NodeToNode;
     ];
 }
@@ -0,0 +1,201 @@
+use std::fmt::Write as _;
+use std::time::Duration;
+use bincode::{Decode, Encode};
+
+use crate::config::Tuning;
+
+/// Upper bounds (in milliseconds) of the latency histogram buckets; the last bucket is +Inf.
+pub const LATENCY_BUCKETS_MS: [f64; 13] = [0.5, 1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0];
+
+/// Fixed-bucket latency histogram. `counts[i]` holds observations `<= LATENCY_BUCKETS_MS[i]`
+/// (non-cumulative), the extra trailing slot holds everything above the last bound.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct Histogram {
+    pub counts: Vec<u64>,
+    pub sum_us: u64,
+    pub count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self { Self { counts: vec![0; LATENCY_BUCKETS_MS.len() + 1], sum_us: 0, count: 0 } }
+}
+
+impl Histogram {
+    pub fn observe(&mut self, d: Duration) {
+        let ms = d.as_secs_f64() * 1000.0;
+        let idx = LATENCY_BUCKETS_MS.iter().position(|&b| ms <= b).unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.counts[idx] += 1;
+        self.sum_us += d.as_micros() as u64;
+        self.count += 1;
+    }
+
+    pub fn mean_ms(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.sum_us as f64 / self.count as f64 / 1000.0 }
+    }
+
+    /// Upper bound of the bucket containing the q-th quantile (0.0..=1.0); +Inf bucket reports the last finite bound.
+    pub fn quantile_ms(&self, q: f64) -> f64 {
+        if self.count == 0 { return 0.0; }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0u64;
+        for (i, c) in self.counts.iter().enumerate() {
+            seen += c;
+            if seen >= rank { return LATENCY_BUCKETS_MS[i.min(LATENCY_BUCKETS_MS.len() - 1)]; }
+        }
+        LATENCY_BUCKETS_MS[LATENCY_BUCKETS_MS.len() - 1]
+    }
+}
+
+/// Counters and histograms tracked by a single `DynamoNode`.
+///
+/// Latencies are measured at the coordinator, from the moment it accepts a client request until
+/// it answers the client. Quorum wait is the time between the first replica response and the
+/// response that completed the W (or R) quorum, i.e. how long the coordinator waited on the
+/// slower replicas.
+#[derive(Debug, Clone, Default, PartialEq, Encode, Decode)]
+pub struct NodeMetrics {
+    pub put_latency: Histogram,
+    pub get_latency: Histogram,
+    pub put_quorum_wait: Histogram,
+    pub get_quorum_wait: Histogram,
+    pub puts_coordinated: u64,
+    pub gets_coordinated: u64,
+    pub puts_completed: u64,
+    pub gets_completed: u64,
+    pub puts_forwarded: u64,
+    pub gets_forwarded: u64,
+    pub read_repairs: u64,
+    pub late_read_repairs: u64,
+    pub full_read_repairs: u64,
+    pub late_get_responses: u64,
+    pub repairs_acked: u64,
+    pub repairs_retried: u64,
+    pub repairs_failed: u64,
+    pub hints_stored: u64,
+    pub handoffs_replayed: u64,
+    pub gets_with_conflict: u64,
+    pub replica_timeouts: u64,
+    pub wal_appends: u64,
+    pub wal_syncs: u64,
+    pub wal_errors: u64,
+    pub siblings_dropped: u64,
+    pub writes_rejected: u64,
+    pub puts_rejected: u64,
+}
+
+impl NodeMetrics {
+    /// Fraction of completed GETs that returned more than one sibling.
+    pub fn conflict_rate(&self) -> f64 {
+        if self.gets_completed == 0 { 0.0 } else { self.gets_with_conflict as f64 / self.gets_completed as f64 }
+    }
+
+    /// Render in the Prometheus text exposition format, labelling every sample with `node`.
+    pub fn to_prometheus(&self, node_id: &str) -> String {
+        let mut s = String::new();
+        let histograms = [
+            ("dynamo_put_latency_ms", "Coordinator PUT latency in milliseconds", &self.put_latency),
+            ("dynamo_get_latency_ms", "Coordinator GET latency in milliseconds", &self.get_latency),
+            ("dynamo_put_quorum_wait_ms", "Time from first replica ack to W acks in milliseconds", &self.put_quorum_wait),
+            ("dynamo_get_quorum_wait_ms", "Time from first replica response to R responses in milliseconds", &self.get_quorum_wait),
+        ];
+        for (name, help, h) in histograms {
+            let _ = writeln!(s, "# HELP {} {}", name, help);
+            let _ = writeln!(s, "# TYPE {} histogram", name);
+            let mut cumulative = 0u64;
+            for (i, bound) in LATENCY_BUCKETS_MS.iter().enumerate() {
+                cumulative += h.counts[i];
+                let _ = writeln!(s, "{}_bucket{{node=\"{}\",le=\"{}\"}} {}", name, node_id, bound, cumulative);
+            }
+            let _ = writeln!(s, "{}_bucket{{node=\"{}\",le=\"+Inf\"}} {}", name, node_id, h.count);
+            let _ = writeln!(s, "{}_sum{{node=\"{}\"}} {}", name, node_id, h.sum_us as f64 / 1000.0);
+            let _ = writeln!(s, "{}_count{{node=\"{}\"}} {}", name, node_id, h.count);
+        }
+        let counters = [
+            ("dynamo_puts_coordinated_total", "PUTs accepted as coordinator", self.puts_coordinated),
+            ("dynamo_gets_coordinated_total", "GETs accepted as coordinator", self.gets_coordinated),
+            ("dynamo_puts_completed_total", "PUTs answered to the client", self.puts_completed),
+            ("dynamo_gets_completed_total", "GETs answered to the client", self.gets_completed),
+            ("dynamo_puts_forwarded_total", "PUTs forwarded to another coordinator", self.puts_forwarded),
+            ("dynamo_gets_forwarded_total", "GETs forwarded to another coordinator", self.gets_forwarded),
+            ("dynamo_read_repairs_total", "Read-repair writes sent to stale replicas", self.read_repairs),
+            ("dynamo_late_read_repairs_total", "Read-repair writes triggered by replicas answering after quorum", self.late_read_repairs),
+            ("dynamo_full_read_repairs_total", "Read-repair writes sent to replicas that never answered", self.full_read_repairs),
+            ("dynamo_late_get_responses_total", "GET responses received after the R quorum was reached", self.late_get_responses),
+            ("dynamo_repairs_acked_total", "Repair, handoff and redistribution writes acknowledged by the target", self.repairs_acked),
+            ("dynamo_repairs_retried_total", "Repair writes resent after a timeout", self.repairs_retried),
+            ("dynamo_repairs_failed_total", "Repair writes abandoned after the maximum number of attempts", self.repairs_failed),
+            ("dynamo_hints_stored_total", "Hinted-handoff hints stored for failed nodes", self.hints_stored),
+            ("dynamo_handoffs_replayed_total", "Hinted-handoff writes replayed to recovered nodes", self.handoffs_replayed),
+            ("dynamo_gets_with_conflict_total", "GETs that returned more than one sibling", self.gets_with_conflict),
+            ("dynamo_replica_timeouts_total", "Replica requests that timed out", self.replica_timeouts),
+            ("dynamo_wal_appends_total", "Replica writes appended to the write-ahead log", self.wal_appends),
+            ("dynamo_wal_syncs_total", "Write-ahead log fsyncs", self.wal_syncs),
+            ("dynamo_wal_errors_total", "Failed write-ahead log appends and fsyncs; their writes were not acknowledged", self.wal_errors),
+            ("dynamo_siblings_dropped_total", "Versions pruned or resolved away by the per-key sibling limit", self.siblings_dropped),
+            ("dynamo_writes_rejected_total", "Replica writes refused because the key was at its sibling limit", self.writes_rejected),
+            ("dynamo_puts_rejected_total", "Coordinated PUTs failed because a replica refused them", self.puts_rejected),
+        ];
+        for (name, help, v) in counters {
+            let _ = writeln!(s, "# HELP {} {}", name, help);
+            let _ = writeln!(s, "# TYPE {} counter", name);
+            let _ = writeln!(s, "{}{{node=\"{}\"}} {}", name, node_id, v);
+        }
+        let _ = writeln!(s, "# HELP dynamo_conflict_rate Fraction of GETs that returned siblings");
+        let _ = writeln!(s, "# TYPE dynamo_conflict_rate gauge");
+        let _ = writeln!(s, "dynamo_conflict_rate{{node=\"{}\"}} {}", node_id, self.conflict_rate());
+        s
+    }
+}
+
+/// Snapshot of a node's state returned through the admin `StatsReq`/`StatsRsp` exchange.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct NodeStats {
+    pub node_id: String,
+    pub keys: u64,
+    pub failed: Vec<String>,
+    pub pending_repairs: u64,
+    pub tuning: Tuning,
+    /// Keys in the local store holding more than one version.
+    pub keys_with_siblings: u64,
+    /// Largest number of versions held for one key.
+    pub most_siblings: u64,
+    /// Keys with the most siblings and their counts, most first; at most `SIBLING_HOT_KEYS`.
+    pub sibling_hot_keys: Vec<(String, u64)>,
+    pub metrics: NodeMetrics,
+}
+
+impl NodeStats {
+    pub fn to_prometheus(&self) -> String {
+        let mut s = self.metrics.to_prometheus(&self.node_id);
+        let _ = writeln!(s, "# HELP dynamo_keys Keys held in the local store");
+        let _ = writeln!(s, "# TYPE dynamo_keys gauge");
+        let _ = writeln!(s, "dynamo_keys{{node=\"{}\"}} {}", self.node_id, self.keys);
+        let _ = writeln!(s, "# HELP dynamo_failed_nodes Nodes currently suspected as failed");
+        let _ = writeln!(s, "# TYPE dynamo_failed_nodes gauge");
+        let _ = writeln!(s, "dynamo_failed_nodes{{node=\"{}\"}} {}", self.node_id, self.failed.len());
+        let _ = writeln!(s, "# HELP dynamo_pending_repairs Repair writes awaiting acknowledgement");
+        let _ = writeln!(s, "# TYPE dynamo_pending_repairs gauge");
+        let _ = writeln!(s, "dynamo_pending_repairs{{node=\"{}\"}} {}", self.node_id, self.pending_repairs);
+        let t = &self.tuning;
+        let gauges = [
+            ("dynamo_write_quorum", "Replica acks a PUT waits for (W)", t.w as u64),
+            ("dynamo_read_quorum", "Replica responses a GET waits for (R)", t.r as u64),
+            ("dynamo_timeout_ms", "Replica response timeout in milliseconds", t.timeout_ms),
+            ("dynamo_ping_interval_ms", "Interval between failure pings and anti-entropy rounds in milliseconds", t.ping_interval_ms),
+            ("dynamo_sync_batch", "Keys pushed per anti-entropy round", t.sync_batch as u64),
+            ("dynamo_keys_with_siblings", "Keys in the local store holding more than one version", self.keys_with_siblings),
+            ("dynamo_most_siblings", "Largest number of versions held for one key", self.most_siblings),
+        ];
+        for (name, help, v) in gauges {
+            let _ = writeln!(s, "# HELP {} {}", name, help);
+            let _ = writeln!(s, "# TYPE {} gauge", name);
+            let _ = writeln!(s, "{}{{node=\"{}\"}} {}", name, self.node_id, v);
+        }
+        let _ = writeln!(s, "# HELP dynamo_key_siblings Versions held for the keys with the most siblings");
+        let _ = writeln!(s, "# TYPE dynamo_key_siblings gauge");
+        for (key, count) in self.sibling_hot_keys.iter() {
+            let _ = writeln!(s, "dynamo_key_siblings{{node=\"{}\",key=\"{}\"}} {}", self.node_id, key.replace('\\', "\\\\").replace('"', "\\\""), count);
+        }
+        s
+    }
+}
@@ -1,18 +1,55 @@
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use log::{info, debug, warn};
-use reactor_actor::{ActorProcess, ActorSend, BehaviourBuilder, RouteTo, RuntimeCtx};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use reactor_actor::{ActorProcess, ActorSend, BehaviourBuilder, CorrelationId, Msg, RouteTo, RuntimeCtx, TimerHandle, TimerId, timer_channel};
 use reactor_actor::codec::BincodeCodec;
 
+use crate::clock::{Clock, ClockTimers, SystemClock};
+use crate::config::{DynamoConfig, TuningUpdate};
 use crate::consistent_hash::ConsistentHash;
-use crate::messages::{DynamoNodeIn, DynamoNodeOut, NodeToNode, NodeToClient, ClientToNode};
+use crate::messages::{DynamoNodeIn, DynamoNodeOut, NodeToNode, NodeToClient, ClientToNode, GetTimeout, PutTimeout, RepairKind, RepairTimeout, Wake};
+use crate::metrics::{NodeMetrics, NodeStats};
 use crate::vector_clock::VectorClock;
-use crate::versioned_value::{VersionedValue, VersionedValues};
+use crate::versioned_value::{SiblingPolicy, VersionedValue, VersionedValues};
+use crate::wal::{FsyncMode, Wal, WalRecord};
+
+/// Keys per `SnapshotChunk`, so a large store is not sent as one message.
+pub const SNAPSHOT_CHUNK_KEYS: usize = 256;
+
+/// Keys reported in `NodeStats::sibling_hot_keys`.
+pub const SIBLING_HOT_KEYS: usize = 10;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 enum ReqKind { Put, Get }
 
-struct Deadline { to: String, kind: ReqKind, seq: u64, at: Instant, key: String }
+/// Read-repair tuning. After a GET reaches its R quorum the coordinator keeps listening for
+/// `late_window_ms` and repairs replicas that answer late with stale data. With probability
+/// `full_repair_chance` a GET also repairs every replica that never answered once the window closes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadRepairConfig {
+    pub late_window_ms: u64,
+    pub full_repair_chance: f64,
+}
+
+impl Default for ReadRepairConfig {
+    fn default() -> Self { Self { late_window_ms: 200, full_repair_chance: 0.0 } }
+}
+
+// A GET that already answered its client but is still collecting late replica responses
+struct LateRead { key: String, merged: VersionedValues, responded: HashSet<String>, sent: HashMap<String, CorrelationId>, until: Instant, full_repair: bool }
+
+// A repair write waiting for its RepairRsp or its RepairTimeout
+struct PendingRepair { to: String, key: String, values: VersionedValues, kind: RepairKind, attempts: u32 }
+
+// Versions in `merged` that a replica holding `have` is missing
+fn missing_versions(have: &VersionedValues, merged: &VersionedValues) -> VersionedValues {
+    let mut out = VersionedValues::new();
+    for vv in merged.versions.iter() { if !have.contains(vv) { out.add_version(vv.clone()); } }
+    out
+}
 
 pub struct DynamoNode {
     node_id: String,
@@ -26,111 +63,443 @@ pub struct DynamoNode {
     store: HashMap<String, VersionedValues>,
     // pending
     pending_put_rsp: HashMap<u64, HashSet<String>>, // seq -> acks
+    pending_put_rej: HashMap<u64, HashSet<String>>, // seq -> replicas that refused the write
     pending_put_msg: HashMap<u64, (String, String, u64)>, // seq -> (client_addr, key, client_req_id)
-    pending_put_data: HashMap<u64, (String, String, VectorClock)>, // seq -> (key, value, clock)
+    pending_put_data: HashMap<u64, (String, String, VectorClock, u64)>, // seq -> (key, value, clock, ts_ms)
     pending_get_rsp: HashMap<u64, Vec<(String, VersionedValues)>>, // seq -> list of (from, values)
     pending_get_msg: HashMap<u64, (String, String, u64)>, // seq -> (client_addr, key, client_req_id)
-    pending_req: HashMap<(ReqKind, u64), HashSet<String>>, // (kind, seq) -> sent nodes
+    pending_req: HashMap<(ReqKind, u64), HashMap<String, CorrelationId>>, // (kind, seq) -> sent node -> its ask
+    replica_reqs: HashMap<CorrelationId, (ReqKind, u64, String)>, // msg_id -> (kind, seq, replica) until the operation is done
+    pending_start: HashMap<(ReqKind, u64), (Instant, Option<Instant>)>, // (kind, seq) -> (accepted, first replica response)
     failed: HashSet<String>,
     handoffs: HashMap<String, HashSet<String>>, // failed_node -> keys to handoff
-    deadlines: Vec<Deadline>,
     timeout_ms: u64,
     last_ping: Instant,
     ping_interval_ms: u64,
     // anti-entropy sweep
     sync_cursor: usize,
     sync_batch: usize,
+    read_repair: ReadRepairConfig,
+    late_reads: HashMap<u64, LateRead>, // seq -> GET past quorum, still accepting late responses
+    pending_repairs: HashMap<CorrelationId, PendingRepair>, // repair_id -> unacknowledged repair
+    repair_max_attempts: u32,
+    max_siblings: usize, // 0 = no limit
+    sibling_policy: SiblingPolicy,
+    rng: StdRng,
+    clock: Arc<dyn Clock>,
+    metrics: NodeMetrics,
+    wal: Option<Wal>,
+    held_acks: Vec<DynamoNodeOut>, // PutRsp/RepairRsp waiting for the WAL group commit
+    timers: TimerHandle<DynamoNodeIn>,
+    clock_timers: Option<ClockTimers<DynamoNodeIn>>, // without `with_timers`: the node fires its own on its clock
+    armed: Option<(Instant, TimerId)>, // wake-up timer for the earliest pending deadline
 }
 
 impl DynamoNode {
-    pub fn new(node_id: String, nodes: Vec<String>, n: usize, w: usize, r: usize, t: usize) -> Self {
+    pub fn new(node_id: String, nodes: Vec<String>, n: usize, w: usize, r: usize, t: usize, clock: Arc<dyn Clock>) -> Self {
         let ring = ConsistentHash::new(&nodes, t);
+        // seeded per node so repair decisions are reproducible in tests
+        let seed = node_id.bytes().fold(0u64, |h, b| h.wrapping_mul(31).wrapping_add(b as u64));
+        let (timers, queue) = timer_channel();
+        let clock_timers = Some(ClockTimers::new(queue, clock.clone()));
         Self {
             node_id, nodes, ring, n, w, r, t,
             seq: 0,
             store: HashMap::new(),
             pending_put_rsp: HashMap::new(),
+            pending_put_rej: HashMap::new(),
             pending_put_msg: HashMap::new(),
             pending_get_rsp: HashMap::new(),
             pending_get_msg: HashMap::new(),
             pending_req: HashMap::new(),
+            replica_reqs: HashMap::new(),
+            pending_start: HashMap::new(),
             pending_put_data: HashMap::new(),
             failed: HashSet::new(),
             handoffs: HashMap::new(),
-            deadlines: vec![],
             timeout_ms: 800,
-            last_ping: Instant::now(),
+            last_ping: clock.now(),
             ping_interval_ms: 1000,
             sync_cursor: 0,
             sync_batch: 2,
+            read_repair: ReadRepairConfig::default(),
+            late_reads: HashMap::new(),
+            pending_repairs: HashMap::new(),
+            repair_max_attempts: 3,
+            max_siblings: 0,
+            sibling_policy: SiblingPolicy::default(),
+            rng: StdRng::seed_from_u64(seed),
+            clock,
+            metrics: NodeMetrics::default(),
+            wal: None,
+            held_acks: vec![],
+            timers,
+            clock_timers,
+            armed: None,
+        }
+    }
+
+    /// `from_config`, plus the write-ahead log if `wal_dir` is set: the store is rebuilt from the
+    /// log and every later replica write is logged before it is acknowledged.
+    pub fn open(cfg: &DynamoConfig, clock: Arc<dyn Clock>) -> std::io::Result<Self> {
+        let mut node = Self::from_config(cfg, clock);
+        if let Some(path) = cfg.wal_path() {
+            let (wal, records) = Wal::open(&path, cfg.wal_fsync, Duration::from_millis(cfg.wal_group_commit_ms))?;
+            let count = records.len();
+            for rec in records {
+                let entry = node.store.entry(rec.key.clone()).or_default();
+                entry.merge(&rec.values);
+                // the limit is deterministic, so replaying gives the store the node had
+                entry.limit_siblings(node.max_siblings, node.sibling_policy);
+                for failed in rec.hints { node.failed.insert(failed.clone()); node.handoffs.entry(failed).or_default().insert(rec.key.clone()); }
+            }
+            info!("[wal-recover] node={} path={} records={} keys={} hinted_nodes={}", node.node_id, path.display(), count, node.store.len(), node.handoffs.len());
+            node.wal = Some(wal);
+        }
+        Ok(node)
+    }
+
+    /// A node with every tunable taken from `cfg`, which should have passed `DynamoConfig::validate`.
+    pub fn from_config(cfg: &DynamoConfig, clock: Arc<dyn Clock>) -> Self {
+        let mut node = Self::new(cfg.node_id.clone(), cfg.nodes.clone(), cfg.n, cfg.w, cfg.r, cfg.t, clock).with_read_repair(cfg.read_repair()).with_timeout_ms(cfg.timeout_ms);
+        node.ping_interval_ms = cfg.ping_interval_ms;
+        node.sync_batch = cfg.sync_batch;
+        node.repair_max_attempts = cfg.repair_max_attempts;
+        node.with_sibling_limit(cfg.max_siblings, cfg.sibling_policy)
+    }
+
+    pub fn with_sibling_limit(mut self, max: usize, policy: SiblingPolicy) -> Self { self.max_siblings = max; self.sibling_policy = policy; self }
+
+    /// Sends every replica request and repair as an ask, so unanswered ones time out and are
+    /// retried, and wakes the node with a timer whenever a ping round, read-repair window or group
+    /// commit is due, so those happen on an idle cluster too. Without it the node only acts when
+    /// a message arrives, and fires its own timers against its clock then.
+    pub fn with_timers(mut self, timers: TimerHandle<DynamoNodeIn>) -> Self {
+        self.timers = timers;
+        self.clock_timers = None;
+        self.arm_timer();
+        self
+    }
+
+    pub fn with_read_repair(mut self, cfg: ReadRepairConfig) -> Self { self.read_repair = cfg; self }
+
+    pub fn with_timeout_ms(mut self, ms: u64) -> Self { self.timeout_ms = ms; self }
+
+    pub fn metrics(&self) -> &NodeMetrics { &self.metrics }
+
+    /// Versions of `key` held in this node's local store.
+    pub fn local(&self, key: &str) -> Option<&VersionedValues> { self.store.get(key) }
+
+    pub fn local_keys(&self) -> impl Iterator<Item = &String> { self.store.keys() }
+
+    pub fn stats(&self) -> NodeStats {
+        let mut failed: Vec<String> = self.failed.iter().cloned().collect();
+        failed.sort();
+        let mut siblings: Vec<(String, u64)> = self.store.iter().filter(|(_, vs)| vs.has_conflict()).map(|(k, vs)| (k.clone(), vs.versions.len() as u64)).collect();
+        siblings.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let keys_with_siblings = siblings.len() as u64;
+        let most_siblings = siblings.first().map(|(_, c)| *c).unwrap_or(if self.store.is_empty() { 0 } else { 1 });
+        siblings.truncate(SIBLING_HOT_KEYS);
+        NodeStats {
+            node_id: self.node_id.clone(), keys: self.store.len() as u64, failed, pending_repairs: self.pending_repairs.len() as u64, tuning: self.config().tuning(),
+            keys_with_siblings, most_siblings, sibling_hot_keys: siblings, metrics: self.metrics.clone(),
+        }
+    }
+
+    /// Current settings of this node, including changes made by `Reconfigure`.
+    pub fn config(&self) -> DynamoConfig {
+        DynamoConfig {
+            node_id: self.node_id.clone(), nodes: self.nodes.clone(), n: self.n, w: self.w, r: self.r, t: self.t,
+            timeout_ms: self.timeout_ms, ping_interval_ms: self.ping_interval_ms, sync_batch: self.sync_batch, repair_max_attempts: self.repair_max_attempts,
+            read_repair_window_ms: self.read_repair.late_window_ms, read_repair_chance: self.read_repair.full_repair_chance,
+            wal_dir: self.wal.as_ref().and_then(|w| w.path().parent().map(|d| d.to_path_buf())),
+            wal_fsync: self.wal.as_ref().map(|w| w.mode()).unwrap_or_default(),
+            wal_group_commit_ms: self.wal.as_ref().map(|w| w.group_commit().as_millis() as u64).unwrap_or(DynamoConfig::default().wal_group_commit_ms),
+            max_siblings: self.max_siblings, sibling_policy: self.sibling_policy,
         }
     }
 
     fn next_seq(&mut self) -> u64 { self.seq += 1; self.seq }
 
-    fn sweep_timeouts(&mut self) -> Vec<DynamoNodeOut> {
-        let now = Instant::now();
-        let mut out = vec![];
-        let mut expired: Vec<usize> = vec![];
-        for (i, d) in self.deadlines.iter().enumerate() {
-            if now >= d.at {
-                expired.push(i);
+    fn note_replica_response(&mut self, kind: ReqKind, seq: u64) {
+        let now = self.clock.now();
+        if let Some((_, first)) = self.pending_start.get_mut(&(kind, seq)) {
+            first.get_or_insert(now);
+        }
+    }
+
+    fn observe_completion(&mut self, kind: ReqKind, seq: u64) {
+        if let Some((started, first)) = self.pending_start.remove(&(kind, seq)) {
+            let now = self.clock.now();
+            let wait = first.map(|f| now.duration_since(f)).unwrap_or_default();
+            match kind {
+                ReqKind::Put => { self.metrics.put_latency.observe(now.duration_since(started)); self.metrics.put_quorum_wait.observe(wait); self.metrics.puts_completed += 1; }
+                ReqKind::Get => { self.metrics.get_latency.observe(now.duration_since(started)); self.metrics.get_quorum_wait.observe(wait); self.metrics.gets_completed += 1; }
             }
         }
-        // remove in reverse to keep indices valid
-        expired.sort(); expired.reverse();
-        for idx in expired {
-            let d = self.deadlines.remove(idx);
-            if self.failed.insert(d.to.clone()) {
-                warn!("node {} timeout to {} on {:?} seq {}", self.node_id, d.to, d.kind, d.seq);
+    }
+
+    // An ask whose timeout comes back unless its reply does first
+    fn ask(&mut self, on_timeout: impl FnOnce(CorrelationId) -> DynamoNodeIn) -> CorrelationId {
+        self.timers.ask(Duration::from_millis(self.timeout_ms), on_timeout)
+    }
+
+    // Without `with_timers`: handles the timers due on the node's clock, earliest first
+    fn fire_clock_timers(&mut self) -> Vec<DynamoNodeOut> {
+        let Some(timers) = self.clock_timers.as_mut() else { return vec![] };
+        let mut out = vec![];
+        for msg in timers.due() { out.extend(self.process(msg)); }
+        out
+    }
+
+    // Asks `to` for its part of operation `seq`; its answer or its timeout comes back with the id
+    fn ask_replica(&mut self, kind: ReqKind, seq: u64, to: &str) -> CorrelationId {
+        let msg_id = match kind {
+            ReqKind::Put => self.ask(|msg_id| DynamoNodeIn::PutTimeout(PutTimeout{ msg_id })),
+            ReqKind::Get => self.ask(|msg_id| DynamoNodeIn::GetTimeout(GetTimeout{ msg_id })),
+        };
+        self.pending_req.entry((kind, seq)).or_default().insert(to.to_string(), msg_id);
+        self.replica_reqs.insert(msg_id, (kind, seq, to.to_string()));
+        msg_id
+    }
+
+    // Forgets the replica requests of a finished operation
+    fn finish_replica_reqs(&mut self, sent: &HashMap<String, CorrelationId>) {
+        for msg_id in sent.values() { self.replica_reqs.remove(msg_id); }
+    }
+
+    // The operation `msg_id` belongs to, while it is not done
+    fn replica_req_seq(&self, kind: ReqKind, msg_id: CorrelationId) -> Option<u64> {
+        self.replica_reqs.get(&msg_id).filter(|(k, _, _)| *k == kind).map(|(_, seq, _)| *seq)
+    }
+
+    // Sends `values` for `key` to `to` and tracks it until acknowledged
+    fn send_repair(&mut self, to: String, key: String, values: VersionedValues, kind: RepairKind) -> DynamoNodeOut {
+        self.send_repair_attempt(PendingRepair{ to, key, values, kind, attempts: 1 })
+    }
+
+    // Asks for the repair's RepairRsp, which the actor lets through only until the timeout fires
+    fn send_repair_attempt(&mut self, p: PendingRepair) -> DynamoNodeOut {
+        let repair_id = self.ask(|repair_id| DynamoNodeIn::RepairTimeout(RepairTimeout{ repair_id }));
+        let req = NodeToNode::RepairReq{ from: self.node_id.clone(), to: p.to.clone(), key: p.key.clone(), values: p.values.clone(), repair_id, kind: p.kind };
+        self.pending_repairs.insert(repair_id, p);
+        DynamoNodeOut::NodeToNode(req)
+    }
+
+    fn on_repair_timeout(&mut self, repair_id: CorrelationId) -> Vec<DynamoNodeOut> {
+        let Some(mut p) = self.pending_repairs.remove(&repair_id) else { return vec![] };
+        if p.attempts >= self.repair_max_attempts {
+            self.metrics.repairs_failed += 1;
+            warn!("[repair-failed] node={} to={} key={} kind={:?} id={} attempts={}", self.node_id, p.to, p.key, p.kind, repair_id.0, p.attempts);
+            // keep the hint so the replay is attempted again once the node answers a ping
+            if p.kind == RepairKind::Handoff {
+                self.failed.insert(p.to.clone());
+                self.handoffs.entry(p.to).or_default().insert(p.key);
             }
-            // retry to an additional node not yet used
-            let (pref, _avoided) = self.ring.find_nodes(&d.key, self.n, &self.failed.iter().cloned().collect::<Vec<_>>() );
-            let key = d.key.clone();
-            let sent = self.pending_req.entry((d.kind, d.seq)).or_default().clone();
-            for node in pref {
-                if !sent.contains(&node) {
-                    match d.kind {
-                        ReqKind::Put => {
-                            if let Some((k, v, clk)) = self.pending_put_data.get(&d.seq).cloned() {
-                                info!("[put-timeout-retry] coord={} key={} retry_to={} seq={}", self.node_id, k, node, d.seq);
-                                out.push(DynamoNodeOut::NodeToNode(NodeToNode::PutReq{
-                                    from: self.node_id.clone(), to: node.clone(), key: k.clone(), value: v.clone(), clock: clk.clone(), msg_id: d.seq, handoff: None
-                                }));
-                                self.pending_req.entry((ReqKind::Put, d.seq)).or_default().insert(node.clone());
-                                self.deadlines.push(Deadline{ to: node, kind: ReqKind::Put, seq: d.seq, at: Instant::now() + Duration::from_millis(self.timeout_ms), key: key.clone() });
-                            }
-                        }
-                        ReqKind::Get => {
-                            out.push(DynamoNodeOut::NodeToNode(NodeToNode::GetReq{ from: self.node_id.clone(), to: node.clone(), key: key.clone(), msg_id: d.seq }));
-                            self.pending_req.entry((ReqKind::Get, d.seq)).or_default().insert(node.clone());
-                            self.deadlines.push(Deadline{ to: node.clone(), kind: ReqKind::Get, seq: d.seq, at: Instant::now() + Duration::from_millis(self.timeout_ms), key: key.clone() });
-                        }
-                    }
-                    break;
+            return vec![];
+        }
+        p.attempts += 1;
+        self.metrics.repairs_retried += 1;
+        debug!("[repair-retry] node={} to={} key={} kind={:?} id={} attempt={}", self.node_id, p.to, p.key, p.kind, repair_id.0, p.attempts);
+        vec![self.send_repair_attempt(p)]
+    }
+
+    fn on_repair_req(&mut self, from: String, key: String, values: VersionedValues, repair_id: CorrelationId, kind: RepairKind) -> Vec<DynamoNodeOut> {
+        let rec = self.new_versions(&key, &values).map(|new| WalRecord{ key: key.clone(), values: new, hints: vec![] });
+        let ack = DynamoNodeOut::NodeToNode(NodeToNode::RepairRsp{ from: self.node_id.clone(), to: from, repair_id });
+        let Some(out) = self.log_then_ack(rec, Some(ack)) else { return vec![] };
+        self.merge_into_store(&key, &values);
+        debug!("[store-repair] node={} key={} kind={:?} versions_now={}", self.node_id, key, kind, self.store[&key].versions.len());
+        out
+    }
+
+    // The versions of `values` the store lacks, which are what the WAL needs
+    fn new_versions(&self, key: &str, values: &VersionedValues) -> Option<VersionedValues> {
+        let new = match self.store.get(key) { Some(entry) => missing_versions(entry, values), None => missing_versions(&VersionedValues::new(), values) };
+        (!new.versions.is_empty()).then_some(new)
+    }
+
+    fn merge_into_store(&mut self, key: &str, values: &VersionedValues) {
+        let entry = self.store.entry(key.to_string()).or_default();
+        entry.merge(values);
+        let dropped = entry.limit_siblings(self.max_siblings, self.sibling_policy);
+        if dropped > 0 {
+            self.metrics.siblings_dropped += dropped as u64;
+            debug!("[sibling-limit] node={} key={} policy={:?} dropped={} versions_now={}", self.node_id, key, self.sibling_policy, dropped, entry.versions.len());
+        }
+    }
+
+    // Logs `rec` and returns `ack` once the write is durable under the fsync mode. With group
+    // commit the ack is held until the next sync; acks for writes that were already stored wait
+    // behind any held ones, since the earlier copy may not be durable yet. A failed append returns
+    // `None`: the caller leaves the write out of the store and the sender times out and retries
+    // elsewhere.
+    fn log_then_ack(&mut self, rec: Option<WalRecord>, ack: Option<DynamoNodeOut>) -> Option<Vec<DynamoNodeOut>> {
+        let Some(wal) = self.wal.as_mut() else { return Some(ack.into_iter().collect()) };
+        let durable = match rec {
+            Some(rec) => match wal.append(&rec, self.clock.now()) {
+                Ok(durable) => {
+                    self.metrics.wal_appends += 1;
+                    if durable && wal.mode() == FsyncMode::Always { self.metrics.wal_syncs += 1; }
+                    durable
+                }
+                Err(e) => {
+                    self.metrics.wal_errors += 1;
+                    warn!("[wal] node={} append failed for key={}: {}; not storing or acknowledging", self.node_id, rec.key, e);
+                    return None;
                 }
+            },
+            None => self.held_acks.is_empty(),
+        };
+        match ack {
+            Some(ack) if !durable => { self.held_acks.push(ack); Some(vec![]) }
+            ack => Some(ack.into_iter().collect()),
+        }
+    }
+
+    // Earliest time a sweep in `process` has work to do
+    fn next_wakeup(&self) -> Option<Instant> {
+        let ping = (!self.failed.is_empty() || !self.store.is_empty()).then(|| self.last_ping + Duration::from_millis(self.ping_interval_ms));
+        self.late_reads.values().map(|l| l.until)
+            .chain(self.wal.as_ref().and_then(|w| w.sync_deadline()))
+            .chain(ping)
+            .min()
+    }
+
+    // Keeps one timer set for the next wake-up; a later one is left to fire early and re-arm.
+    // Timers on the node's own clock only fire as messages arrive, when the sweeps run anyway,
+    // so those just start counting what this step set.
+    fn arm_timer(&mut self) {
+        if let Some(timers) = self.clock_timers.as_mut() { timers.arm(); return; }
+        let timers = &self.timers;
+        let Some(at) = self.next_wakeup() else { return };
+        if self.armed.is_some_and(|(armed_at, _)| armed_at <= at) { return; }
+        if let Some((_, id)) = self.armed.take() { timers.cancel(id); }
+        let id = timers.set_once(at.saturating_duration_since(self.clock.now()), DynamoNodeIn::Wake(Wake));
+        self.armed = Some((at, id));
+    }
+
+    // Group commit: fsync once the oldest unsynced write has waited long enough and release its acks
+    fn sweep_wal(&mut self) -> Vec<DynamoNodeOut> {
+        let now = self.clock.now();
+        let Some(wal) = self.wal.as_mut().filter(|w| w.sync_due(now)) else { return vec![] };
+        match wal.sync() {
+            Ok(()) => {
+                self.metrics.wal_syncs += 1;
+                debug!("[wal-sync] node={} released_acks={}", self.node_id, self.held_acks.len());
+                std::mem::take(&mut self.held_acks)
             }
+            Err(e) => {
+                self.metrics.wal_errors += 1;
+                warn!("[wal] node={} fsync failed: {}; dropping {} acks", self.node_id, e, self.held_acks.len());
+                self.held_acks.clear();
+                vec![]
+            }
+        }
+    }
+
+    fn on_repair_rsp(&mut self, from: String, repair_id: CorrelationId) -> Vec<DynamoNodeOut> {
+        if let Some(p) = self.pending_repairs.remove(&repair_id) {
+            self.metrics.repairs_acked += 1;
+            debug!("[repair-ack] node={} from={} key={} kind={:?} id={}", self.node_id, from, p.key, p.kind, repair_id.0);
+        }
+        vec![]
+    }
+
+    fn sweep_late_reads(&mut self) -> Vec<DynamoNodeOut> {
+        let now = self.clock.now();
+        let mut expired: Vec<u64> = self.late_reads.iter().filter(|(_, l)| now >= l.until).map(|(seq, _)| *seq).collect();
+        expired.sort();
+        let mut out = vec![];
+        for seq in expired {
+            let Some(late) = self.late_reads.remove(&seq) else { continue };
+            self.finish_replica_reqs(&late.sent);
+            if !late.full_repair { continue; }
+            // full repair: push the merged view to every replica that never answered
+            let (home, _) = self.ring.find_nodes(&late.key, self.n, &[]);
+            let mut targets: Vec<String> = home.into_iter().chain(late.sent.keys().cloned()).filter(|n| !late.responded.contains(n)).collect();
+            targets.sort(); targets.dedup();
+            for node in targets.iter() { out.push(self.send_repair(node.clone(), late.key.clone(), late.merged.clone(), RepairKind::ReadRepair)); }
+            let sent = targets.len();
+            self.metrics.read_repairs += sent as u64;
+            self.metrics.full_read_repairs += sent as u64;
+            info!("[full-read-repair] coord={} key={} seq={} non_responders={:?} repairs_sent={}", self.node_id, late.key, seq, targets, sent);
         }
         out
     }
 
+    fn on_late_get_rsp(&mut self, from: String, key: String, values: VersionedValues, seq: u64) -> Vec<DynamoNodeOut> {
+        let Some(late) = self.late_reads.get_mut(&seq) else { return vec![] };
+        if !late.responded.insert(from.clone()) { return vec![]; }
+        self.metrics.late_get_responses += 1;
+        // versions only the late replica had must reach the earlier responders too
+        let fresh = missing_versions(&late.merged, &values);
+        late.merged.merge(&values);
+        let stale = missing_versions(&values, &late.merged);
+        let mut earlier: Vec<String> = late.responded.iter().filter(|n| **n != from).cloned().collect();
+        earlier.sort();
+        let mut out = vec![];
+        if !stale.versions.is_empty() { out.push(self.send_repair(from.clone(), key.clone(), stale, RepairKind::ReadRepair)); }
+        if !fresh.versions.is_empty() {
+            for node in earlier { out.push(self.send_repair(node, key.clone(), fresh.clone(), RepairKind::ReadRepair)); }
+        }
+        self.metrics.read_repairs += out.len() as u64;
+        self.metrics.late_read_repairs += out.len() as u64;
+        if !out.is_empty() { info!("[late-read-repair] coord={} key={} seq={} late_replica={} repairs_sent={}", self.node_id, key, seq, from, out.len()); }
+        out
+    }
+
+    // The replica asked with `msg_id` did not answer: suspect it and ask the next node in the
+    // preference list that was not asked yet. Operations already done ignore their timeouts.
+    fn on_replica_timeout(&mut self, msg_id: CorrelationId) -> Vec<DynamoNodeOut> {
+        let Some((kind, seq, to)) = self.replica_reqs.remove(&msg_id) else { return vec![] };
+        let key = match kind {
+            ReqKind::Put => self.pending_put_msg.get(&seq).map(|(_, k, _)| k.clone()),
+            ReqKind::Get => self.pending_get_msg.get(&seq).map(|(_, k, _)| k.clone()),
+        };
+        let Some(key) = key else { return vec![] };
+        self.metrics.replica_timeouts += 1;
+        if self.failed.insert(to.clone()) {
+            warn!("node {} timeout to {} on {:?} seq {}", self.node_id, to, kind, seq);
+        }
+        let (pref, _avoided) = self.ring.find_nodes(&key, self.n, &self.failed.iter().cloned().collect::<Vec<_>>() );
+        let sent = self.pending_req.get(&(kind, seq)).cloned().unwrap_or_default();
+        let Some(node) = pref.into_iter().find(|n| !sent.contains_key(n)) else { return vec![] };
+        let req = match kind {
+            ReqKind::Put => {
+                let Some((k, v, clk, ts_ms)) = self.pending_put_data.get(&seq).cloned() else { return vec![] };
+                info!("[put-timeout-retry] coord={} key={} retry_to={} seq={}", self.node_id, k, node, seq);
+                let msg_id = self.ask_replica(ReqKind::Put, seq, &node);
+                NodeToNode::PutReq{ from: self.node_id.clone(), to: node, key: k, value: v, clock: clk, ts_ms, msg_id, handoff: None }
+            }
+            ReqKind::Get => {
+                let msg_id = self.ask_replica(ReqKind::Get, seq, &node);
+                NodeToNode::GetReq{ from: self.node_id.clone(), to: node, key, msg_id }
+            }
+        };
+        vec![DynamoNodeOut::NodeToNode(req)]
+    }
+
     fn on_client_put(&mut self, key: String, value: String, mut meta: Vec<VectorClock>, client_addr: String, request_id: u64) -> Vec<DynamoNodeOut> {
         let (pref, avoided) = self.ring.find_nodes(&key, self.n, &self.failed.iter().cloned().collect::<Vec<_>>() );
         if !pref.contains(&self.node_id) {
             let coord = pref.get(0).cloned().unwrap_or_else(|| self.node_id.clone());
             info!("[forward-put] at={} forwarding key={} to coordinator={} pref={:?}", self.node_id, key, coord, pref);
+            self.metrics.puts_forwarded += 1;
             return vec![DynamoNodeOut::NodeToNode(NodeToNode::ForwardClientPut{ coordinator: coord, key, value, metadata: meta, client_addr, request_id })];
         }
         // coordinator path
         let seq = self.next_seq();
+        self.metrics.puts_coordinated += 1;
+        self.pending_start.insert((ReqKind::Put, seq), (self.clock.now(), None));
         self.pending_put_rsp.insert(seq, HashSet::new());
         self.pending_put_msg.insert(seq, (client_addr.clone(), key.clone(), request_id));
-        self.pending_req.entry((ReqKind::Put, seq)).or_default();
 
         // build clock: converge metadata if provided, then update this node with seq
         let mut clock = if meta.is_empty() { VectorClock::new() } else { VectorClock::converge(meta.drain(..)) };
         clock.update(&self.node_id, seq);
-        self.pending_put_data.insert(seq, (key.clone(), value.clone(), clock.clone()));
+        let ts_ms = self.clock.unix_ms();
+        self.pending_put_data.insert(seq, (key.clone(), value.clone(), clock.clone(), ts_ms));
 
         let avoided_top_n: Vec<String> = avoided.into_iter().take(self.n).collect();
         let non_extra = self.n.saturating_sub(avoided_top_n.len());
@@ -138,11 +507,10 @@ impl DynamoNode {
         info!("[coord-put] coord={} key={} seq={} pref={:?} avoided_top_n={:?} clock={:?}", self.node_id, key, seq, pref, avoided_top_n, clock.clock);
         for (i, node) in pref.into_iter().enumerate() {
             let handoff = if i >= non_extra && !avoided_top_n.is_empty() { Some(avoided_top_n.clone()) } else { None };
+            let msg_id = self.ask_replica(ReqKind::Put, seq, &node);
             out.push(DynamoNodeOut::NodeToNode(NodeToNode::PutReq{
-                from: self.node_id.clone(), to: node.clone(), key: key.clone(), value: value.clone(), clock: clock.clone(), msg_id: seq, handoff
+                from: self.node_id.clone(), to: node, key: key.clone(), value: value.clone(), clock: clock.clone(), ts_ms, msg_id, handoff
             }));
-            self.pending_req.get_mut(&(ReqKind::Put, seq)).unwrap().insert(node.clone());
-            self.deadlines.push(Deadline{ to: node, kind: ReqKind::Put, seq, at: Instant::now() + Duration::from_millis(self.timeout_ms), key: key.clone() });
         }
         out
     }
@@ -152,50 +520,92 @@ impl DynamoNode {
         self.on_client_put(key, value, metadata, client_addr, request_id)
     }
 
-    fn on_put_req(&mut self, from: String, key: String, value: String, clock: VectorClock, handoff: Option<Vec<String>>, msg_id: u64) -> Vec<DynamoNodeOut> {
-        let entry = self.store.entry(key.clone()).or_insert_with(VersionedValues::new);
-        entry.add_version(VersionedValue::new(value, clock));
-        debug!("[store-put] node={} key={} versions_now={}", self.node_id, key, entry.versions.len());
-    if let Some(ref h) = handoff { for failed in h.iter() { self.failed.insert(failed.clone()); self.handoffs.entry(failed.clone()).or_default().insert(key.clone()); } }
+    fn on_put_req(&mut self, from: String, key: String, vv: VersionedValue, handoff: Option<Vec<String>>, msg_id: CorrelationId) -> Vec<DynamoNodeOut> {
+        if self.sibling_policy == SiblingPolicy::RejectWrite && self.max_siblings > 0
+            && let Some(entry) = self.store.get(&key)
+            && entry.versions.len() >= self.max_siblings && entry.adds_sibling(&vv) {
+            self.metrics.writes_rejected += 1;
+            let reason = format!("key {} is at its limit of {} siblings", key, self.max_siblings);
+            info!("[put-reject] node={} key={} siblings={} from={} id={}", self.node_id, key, entry.versions.len(), from, msg_id.0);
+            return vec![DynamoNodeOut::NodeToNode(NodeToNode::PutRej{ from: self.node_id.clone(), to: from, msg_id, reason })];
+        }
+        let mut values = VersionedValues::new();
+        values.add_version(vv);
+        // a hint is new state even when the version is not
+        let rec = (self.new_versions(&key, &values).is_some() || handoff.is_some()).then(|| WalRecord{ key: key.clone(), values: values.clone(), hints: handoff.clone().unwrap_or_default() });
+        let ack = DynamoNodeOut::NodeToNode(NodeToNode::PutRsp{ from: self.node_id.clone(), to: from, msg_id });
+        let Some(out) = self.log_then_ack(rec, Some(ack)) else { return vec![] };
+        self.merge_into_store(&key, &values);
+        debug!("[store-put] node={} key={} versions_now={}", self.node_id, key, self.store[&key].versions.len());
+    if let Some(ref h) = handoff { self.metrics.hints_stored += h.len() as u64; for failed in h.iter() { self.failed.insert(failed.clone()); self.handoffs.entry(failed.clone()).or_default().insert(key.clone()); } }
     if let Some(h) = &handoff { info!("[hinted-handoff] node={} storing hints for failed={:?} key={}", self.node_id, h, key); }
-        vec![DynamoNodeOut::NodeToNode(NodeToNode::PutRsp{ from: self.node_id.clone(), to: from, msg_id })]
+        out
     }
 
-    fn on_put_rsp(&mut self, from: String, msg_id: u64) -> Vec<DynamoNodeOut> {
-        if let Some(acks) = self.pending_put_rsp.get_mut(&msg_id) {
+    fn on_put_rsp(&mut self, from: String, msg_id: CorrelationId) -> Vec<DynamoNodeOut> {
+        let Some(seq) = self.replica_req_seq(ReqKind::Put, msg_id) else { return vec![] };
+        if self.pending_put_rsp.contains_key(&seq) { self.note_replica_response(ReqKind::Put, seq); }
+        if let Some(acks) = self.pending_put_rsp.get_mut(&seq) {
             acks.insert(from);
-            info!("[put-ack] coord={} seq={} acks={}/{}", self.node_id, msg_id, acks.len(), self.w);
-            if acks.len() >= self.w {
-                if let Some((client, key, client_req_id)) = self.pending_put_msg.remove(&msg_id) {
-                    self.pending_put_rsp.remove(&msg_id);
-                    self.pending_req.remove(&(ReqKind::Put, msg_id));
-                    self.pending_put_data.remove(&msg_id);
-                    // clear deadlines for this seq
-                    self.deadlines.retain(|d| !(d.seq==msg_id && matches!(d.kind, ReqKind::Put)));
-                    return vec![DynamoNodeOut::NodeToClient(NodeToClient::ClientPutRsp{ key, request_id: client_req_id, client_addr: client })];
-                }
+            info!("[put-ack] coord={} seq={} acks={}/{}", self.node_id, seq, acks.len(), self.w);
+            if acks.len() >= self.w && let Some((client, key, client_req_id)) = self.pending_put_msg.remove(&seq) {
+                self.observe_completion(ReqKind::Put, seq);
+                self.pending_put_rsp.remove(&seq);
+                self.pending_put_rej.remove(&seq);
+                let sent = self.pending_req.remove(&(ReqKind::Put, seq)).unwrap_or_default();
+                self.finish_replica_reqs(&sent);
+                let clock = self.pending_put_data.remove(&seq).map(|(_, _, c, _)| c).unwrap_or_default();
+                return vec![DynamoNodeOut::NodeToClient(NodeToClient::ClientPutRsp{ key, request_id: client_req_id, clock, client_addr: client })];
             }
         }
         vec![]
     }
 
+    // The PUT fails once so many replicas refused it that W acks can no longer arrive. The replicas
+    // that did accept it keep the write, and read repair spreads it from there, so a rejected PUT
+    // may still become visible, like any PUT that failed short of W.
+    fn on_put_rej(&mut self, from: String, msg_id: CorrelationId, reason: String) -> Vec<DynamoNodeOut> {
+        let Some(seq) = self.replica_req_seq(ReqKind::Put, msg_id) else {
+            debug!("[put-reject] coord={} id={} from={} after the PUT completed", self.node_id, msg_id.0, from);
+            return vec![];
+        };
+        let replicas = self.pending_req.get(&(ReqKind::Put, seq)).map(|r| r.len()).unwrap_or(0);
+        let rejects = self.pending_put_rej.entry(seq).or_default();
+        rejects.insert(from.clone());
+        if rejects.len() + self.w <= replicas {
+            info!("[put-reject] coord={} seq={} by={} rejects={}/{}: {}", self.node_id, seq, from, rejects.len(), replicas, reason);
+            return vec![];
+        }
+        let Some((client, key, client_req_id)) = self.pending_put_msg.remove(&seq) else { return vec![] };
+        self.pending_put_rsp.remove(&seq);
+        self.pending_put_rej.remove(&seq);
+        self.pending_put_data.remove(&seq);
+        let sent = self.pending_req.remove(&(ReqKind::Put, seq)).unwrap_or_default();
+        self.finish_replica_reqs(&sent);
+        self.pending_start.remove(&(ReqKind::Put, seq));
+        self.metrics.puts_rejected += 1;
+        warn!("[put-rejected] coord={} key={} seq={} by={}: {}", self.node_id, key, seq, from, reason);
+        vec![DynamoNodeOut::NodeToClient(NodeToClient::ClientPutRejected{ key, request_id: client_req_id, reason, client_addr: client })]
+    }
+
     fn on_client_get(&mut self, key: String, client_addr: String, request_id: u64) -> Vec<DynamoNodeOut> {
         let (pref, _avoided) = self.ring.find_nodes(&key, self.n, &self.failed.iter().cloned().collect::<Vec<_>>() );
         if !pref.contains(&self.node_id) {
             let coord = pref.get(0).cloned().unwrap_or_else(|| self.node_id.clone());
             info!("[forward-get] at={} forwarding key={} to coordinator={} pref={:?}", self.node_id, key, coord, pref);
+            self.metrics.gets_forwarded += 1;
             return vec![DynamoNodeOut::NodeToNode(NodeToNode::ForwardClientGet{ coordinator: coord, key, client_addr, request_id })];
         }
         let seq = self.next_seq();
+        self.metrics.gets_coordinated += 1;
+        self.pending_start.insert((ReqKind::Get, seq), (self.clock.now(), None));
     self.pending_get_msg.insert(seq, (client_addr.clone(), key.clone(), request_id));
         self.pending_get_rsp.insert(seq, vec![]);
-        self.pending_req.entry((ReqKind::Get, seq)).or_default();
         let mut out = vec![];
         info!("[coord-get] coord={} key={} seq={} pref={:?}", self.node_id, key, seq, pref);
         for node in pref.into_iter() {
-            out.push(DynamoNodeOut::NodeToNode(NodeToNode::GetReq{ from: self.node_id.clone(), to: node.clone(), key: key.clone(), msg_id: seq }));
-            self.pending_req.get_mut(&(ReqKind::Get, seq)).unwrap().insert(node.clone());
-            self.deadlines.push(Deadline{ to: node.clone(), kind: ReqKind::Get, seq, at: Instant::now() + Duration::from_millis(self.timeout_ms), key: key.clone() });
+            let msg_id = self.ask_replica(ReqKind::Get, seq, &node);
+            out.push(DynamoNodeOut::NodeToNode(NodeToNode::GetReq{ from: self.node_id.clone(), to: node, key: key.clone(), msg_id }));
         }
         out
     }
@@ -205,48 +615,102 @@ impl DynamoNode {
         self.on_client_get(key, client_addr, request_id)
     }
 
-    fn on_get_req(&mut self, from: String, key: String, msg_id: u64) -> Vec<DynamoNodeOut> {
+    fn on_get_req(&mut self, from: String, key: String, msg_id: CorrelationId) -> Vec<DynamoNodeOut> {
         let values = self.store.get(&key).cloned().unwrap_or_default();
         debug!("[serve-get] node={} key={} versions={} to={}", self.node_id, key, values.versions.len(), from);
         vec![DynamoNodeOut::NodeToNode(NodeToNode::GetRsp{ from: self.node_id.clone(), to: from, key, values, msg_id })]
     }
 
-    fn on_get_rsp(&mut self, from: String, key: String, values: VersionedValues, msg_id: u64) -> Vec<DynamoNodeOut> {
-        if let Some(vs) = self.pending_get_rsp.get_mut(&msg_id) {
+    fn on_get_rsp(&mut self, from: String, key: String, values: VersionedValues, msg_id: CorrelationId) -> Vec<DynamoNodeOut> {
+        let Some(seq) = self.replica_req_seq(ReqKind::Get, msg_id) else { return vec![] };
+        if self.late_reads.contains_key(&seq) { return self.on_late_get_rsp(from, key, values, seq); }
+        if self.pending_get_rsp.contains_key(&seq) { self.note_replica_response(ReqKind::Get, seq); }
+        if let Some(vs) = self.pending_get_rsp.get_mut(&seq) {
             vs.push((from, values));
-            debug!("[get-rsp] coord={} seq={} collected={}", self.node_id, msg_id, vs.len());
-            if vs.len() >= self.r {
-                if let Some((client, _k, client_req_id)) = self.pending_get_msg.remove(&msg_id) {
-                    let mut merged = VersionedValues::new();
-                    for (_n, v) in vs.iter() { merged.merge(v); }
-                    // read-repair: for each replica that responded, if missing any merged versions, send repairs
-                    let mut repairs: Vec<DynamoNodeOut> = vec![];
-                    let mut repair_count = 0usize;
-                    for (replica, vset) in vs.iter() {
-                        for vv in merged.versions.iter() {
-                            if !vset.contains(vv) {
-                                repairs.push(DynamoNodeOut::NodeToNode(NodeToNode::PutReq{
-                                    from: self.node_id.clone(), to: replica.clone(), key: key.clone(), value: vv.value.clone(), clock: vv.clock.clone(), msg_id: 0, handoff: None
-                                }));
-                                repair_count += 1;
-                            }
-                        }
-                    }
-                    let vals: Vec<String> = merged.versions.iter().map(|v| v.value.clone()).collect();
-                    let meta: Vec<VectorClock> = merged.versions.iter().map(|v| v.clock.clone()).collect();
-                    self.pending_get_rsp.remove(&msg_id);
-                    self.pending_req.remove(&(ReqKind::Get, msg_id));
-                    self.deadlines.retain(|d| !(d.seq==msg_id && matches!(d.kind, ReqKind::Get)));
-                    info!("[coord-get-rsp] coord={} key={} seq={} merged_versions={} repairs_sent={}", self.node_id, key, msg_id, vals.len(), repair_count);
-                    let mut out = vec![DynamoNodeOut::NodeToClient(NodeToClient::ClientGetRsp{ key: key.clone(), request_id: client_req_id, values: vals, metadata: meta, client_addr: client })];
-                    out.extend(repairs);
-                    return out;
+            debug!("[get-rsp] coord={} seq={} collected={}", self.node_id, seq, vs.len());
+            if vs.len() >= self.r && let Some((client, _k, client_req_id)) = self.pending_get_msg.remove(&seq) {
+                let mut merged = VersionedValues::new();
+                for (_n, v) in vs.iter() { merged.merge(v); }
+                // read-repair: for each replica that responded, if missing any merged versions, send repairs
+                let stale: Vec<(String, VersionedValues)> = vs.iter().map(|(n, v)| (n.clone(), missing_versions(v, &merged))).filter(|(_, m)| !m.versions.is_empty()).collect();
+                let responded: HashSet<String> = vs.iter().map(|(n, _)| n.clone()).collect();
+                let repairs: Vec<DynamoNodeOut> = stale.into_iter().map(|(n, m)| self.send_repair(n, key.clone(), m, RepairKind::ReadRepair)).collect();
+                let repair_count = repairs.len();
+                let vals: Vec<String> = merged.versions.iter().map(|v| v.value.clone()).collect();
+                let meta: Vec<VectorClock> = merged.versions.iter().map(|v| v.clock.clone()).collect();
+                self.metrics.read_repairs += repair_count as u64;
+                if merged.versions.len() > 1 { self.metrics.gets_with_conflict += 1; }
+                self.observe_completion(ReqKind::Get, seq);
+                self.pending_get_rsp.remove(&seq);
+                let sent = self.pending_req.remove(&(ReqKind::Get, seq)).unwrap_or_default();
+                let full_repair = self.read_repair.full_repair_chance > 0.0 && self.rng.random::<f64>() < self.read_repair.full_repair_chance;
+                // late answers keep coming in on their own asks until the window closes
+                if self.read_repair.late_window_ms > 0 || full_repair {
+                    let until = self.clock.now() + Duration::from_millis(self.read_repair.late_window_ms);
+                    self.late_reads.insert(seq, LateRead{ key: key.clone(), merged: merged.clone(), responded, sent, until, full_repair });
+                } else {
+                    self.finish_replica_reqs(&sent);
                 }
+                info!("[coord-get-rsp] coord={} key={} seq={} merged_versions={} repairs_sent={}", self.node_id, key, seq, vals.len(), repair_count);
+                let mut out = vec![DynamoNodeOut::NodeToClient(NodeToClient::ClientGetRsp{ key: key.clone(), request_id: client_req_id, values: vals, metadata: meta, client_addr: client })];
+                out.extend(repairs);
+                return out;
             }
         }
         vec![]
     }
 
+    // The whole store in one actor step, so each node's part of a snapshot is consistent
+    fn on_snapshot_req(&self, from: String) -> Vec<DynamoNodeOut> {
+        let mut keys: Vec<&String> = self.store.keys().collect();
+        keys.sort();
+        let chunks: Vec<&[&String]> = if keys.is_empty() { vec![&[]] } else { keys.chunks(SNAPSHOT_CHUNK_KEYS).collect() };
+        info!("[snapshot] node={} exporting keys={} chunks={} to={}", self.node_id, keys.len(), chunks.len(), from);
+        let last = chunks.len() - 1;
+        chunks.into_iter().enumerate().map(|(i, chunk)| {
+            let entries = chunk.iter().map(|k| ((*k).clone(), self.store[*k].clone())).collect();
+            DynamoNodeOut::NodeToNode(NodeToNode::SnapshotChunk{ from: self.node_id.clone(), to: from.clone(), entries, last: i == last })
+        }).collect()
+    }
+
+    // Acked once the repairs are queued; they are retried like any other repair write
+    fn on_import_key(&mut self, from: String, key: String, values: VersionedValues, import_id: u64) -> Vec<DynamoNodeOut> {
+        let (pref, _) = self.ring.find_nodes(&key, self.n, &self.failed.iter().cloned().collect::<Vec<_>>());
+        debug!("[import] node={} key={} versions={} pref={:?}", self.node_id, key, values.versions.len(), pref);
+        let mut out: Vec<DynamoNodeOut> = pref.into_iter().map(|node| self.send_repair(node, key.clone(), values.clone(), RepairKind::Import)).collect();
+        out.push(DynamoNodeOut::NodeToNode(NodeToNode::ImportAck{ from: self.node_id.clone(), to: from, import_id }));
+        out
+    }
+
+    // Requests already in flight keep their timeouts; new quorums and timeouts apply from the next request
+    fn on_reconfigure(&mut self, from: String, update: TuningUpdate, propagate: bool) -> Vec<DynamoNodeOut> {
+        let mut cfg = self.config();
+        let result = match update.apply(&mut cfg) {
+            Ok(()) => {
+                self.w = cfg.w;
+                self.r = cfg.r;
+                self.timeout_ms = cfg.timeout_ms;
+                self.ping_interval_ms = cfg.ping_interval_ms;
+                self.sync_batch = cfg.sync_batch;
+                info!("[reconfigure] node={} from={} applied {:?}", self.node_id, from, cfg.tuning());
+                Ok(cfg.tuning())
+            }
+            Err(e) => {
+                warn!("[reconfigure] node={} from={} rejected {:?}: {}", self.node_id, from, update, e);
+                Err(e.to_string())
+            }
+        };
+        let mut out = vec![];
+        // forwarded even if rejected here; each node validates against its own N and membership
+        if propagate {
+            for node in self.nodes.iter().filter(|n| **n != self.node_id) {
+                out.push(DynamoNodeOut::NodeToNode(NodeToNode::Reconfigure{ from: from.clone(), to: node.clone(), update: update.clone(), propagate: false }));
+            }
+        }
+        out.push(DynamoNodeOut::NodeToNode(NodeToNode::ReconfigureRsp{ from: self.node_id.clone(), to: from, result }));
+        out
+    }
+
     fn on_add_node(&mut self, from: String, new_node: String) -> Vec<DynamoNodeOut> {
         // Check if node already exists
         if self.nodes.contains(&new_node) {
@@ -267,26 +731,15 @@ impl DynamoNode {
 
         // Redistribute data: for each key, check if new node should now own it
         let mut redistribution_msgs = vec![];
-        let keys_to_check: Vec<String> = self.store.keys().cloned().collect();
+        let mut keys_to_check: Vec<String> = self.store.keys().cloned().collect();
+        keys_to_check.sort();
 
         for key in keys_to_check {
             let (pref, _) = self.ring.find_nodes(&key, self.n, &[]);
 
             // If new node is now in preference list, send it the data
-            if pref.contains(&new_node) {
-                if let Some(vs) = self.store.get(&key) {
-                    for v in &vs.versions {
-                        redistribution_msgs.push(DynamoNodeOut::NodeToNode(NodeToNode::PutReq{
-                            from: self.node_id.clone(),
-                            to: new_node.clone(),
-                            key: key.clone(),
-                            value: v.value.clone(),
-                            clock: v.clock.clone(),
-                            msg_id: 0,
-                            handoff: None
-                        }));
-                    }
-                }
+            if pref.contains(&new_node) && let Some(vs) = self.store.get(&key).cloned() {
+                redistribution_msgs.push(self.send_repair(new_node.clone(), key, vs, RepairKind::Redistribution));
             }
         }
 
@@ -309,17 +762,28 @@ impl ActorProcess for DynamoNode {
 
     fn process(&mut self, input: Self::IMsg) -> Vec<Self::OMsg> {
         // sweep deadlines each event
-        let mut out = self.sweep_timeouts();
+        let mut out = self.fire_clock_timers();
+        out.extend(self.sweep_late_reads());
+        // without timers the node settles its own asks: a reply after its timeout, or a repeated
+        // one, is dropped as the behaviour would
+        let settled = match (self.clock_timers.as_ref(), input.reply_to()) {
+            (Some(timers), Some(id)) => timers.settle(id),
+            _ => true,
+        };
         // periodic pings to failed nodes for liveness probing
-        let now = Instant::now();
+        let now = self.clock.now();
         if now.duration_since(self.last_ping).as_millis() as u64 >= self.ping_interval_ms {
             self.last_ping = now;
-            for n in self.failed.clone().into_iter() {
+            let mut failed: Vec<String> = self.failed.iter().cloned().collect();
+            failed.sort();
+            for n in failed {
                 out.push(DynamoNodeOut::NodeToNode(NodeToNode::PingReq{ from: self.node_id.clone(), to: n }));
             }
             // background anti-entropy: sync a couple of keys each tick to their replicas
             if !self.store.is_empty() {
-                let keys: Vec<String> = self.store.keys().cloned().collect();
+                // sorted so the cursor walks a stable order across ticks
+                let mut keys: Vec<String> = self.store.keys().cloned().collect();
+                keys.sort();
                 let total = keys.len();
                 for i in 0..self.sync_batch.min(total) {
                     let idx = (self.sync_cursor + i) % total;
@@ -338,6 +802,14 @@ impl ActorProcess for DynamoNode {
             }
         }
         let more = match input {
+            _ if !settled => vec![],
+            // only makes the sweeps above run
+            DynamoNodeIn::Wake(_) => {
+                if self.armed.is_some_and(|(at, _)| at <= now) { self.armed = None; }
+                vec![]
+            }
+            DynamoNodeIn::RepairTimeout(RepairTimeout{ repair_id }) => self.on_repair_timeout(repair_id),
+            DynamoNodeIn::PutTimeout(PutTimeout{ msg_id }) | DynamoNodeIn::GetTimeout(GetTimeout{ msg_id }) => self.on_replica_timeout(msg_id),
             DynamoNodeIn::ClientToNode(c) => match c {
                 ClientToNode::ClientPut{ key, value, metadata, client_addr, request_id } => self.on_client_put(key, value, metadata, client_addr, request_id),
                 ClientToNode::ClientGet{ key, client_addr, request_id } => self.on_client_get(key, client_addr, request_id),
@@ -345,30 +817,39 @@ impl ActorProcess for DynamoNode {
             DynamoNodeIn::NodeToNode(n2n) => match n2n {
                 NodeToNode::ForwardClientPut{ coordinator, key, value, metadata, client_addr, request_id } => self.on_forward_client_put(coordinator, key, value, metadata, client_addr, request_id),
                 NodeToNode::ForwardClientGet{ coordinator, key, client_addr, request_id } => self.on_forward_client_get(coordinator, key, client_addr, request_id),
-                NodeToNode::PutReq{ from, to:_, key, value, clock, msg_id, handoff } => self.on_put_req(from, key, value, clock, handoff, msg_id),
+                NodeToNode::PutReq{ from, to:_, key, value, clock, ts_ms, msg_id, handoff } => self.on_put_req(from, key, VersionedValue::new(value, clock).with_ts(ts_ms), handoff, msg_id),
                 NodeToNode::PutRsp{ from, to:_, msg_id } => self.on_put_rsp(from, msg_id),
+                NodeToNode::PutRej{ from, to:_, msg_id, reason } => self.on_put_rej(from, msg_id, reason),
                 NodeToNode::GetReq{ from, to:_, key, msg_id } => self.on_get_req(from, key, msg_id),
                 NodeToNode::GetRsp{ from, to:_, key, values, msg_id } => self.on_get_rsp(from, key, values, msg_id),
+                NodeToNode::RepairReq{ from, to:_, key, values, repair_id, kind } => self.on_repair_req(from, key, values, repair_id, kind),
+                NodeToNode::RepairRsp{ from, to:_, repair_id } => self.on_repair_rsp(from, repair_id),
                 NodeToNode::SyncKey{ from:_, to:_, key, values } => {
-                    let entry = self.store.entry(key.clone()).or_insert_with(VersionedValues::new);
-                    let before = entry.versions.len();
-                    entry.merge(&values);
-                    let after = entry.versions.len();
-                    if after != before { info!("[anti-entropy-merge] node={} key={} versions {}->{}", self.node_id, key, before, after); }
-                    vec![]
+                    let before = self.store.get(&key).map(|e| e.versions.len()).unwrap_or(0);
+                    let rec = self.new_versions(&key, &values).map(|new| WalRecord{ key: key.clone(), values: new, hints: vec![] });
+                    match self.log_then_ack(rec, None) {
+                        Some(out) => {
+                            self.merge_into_store(&key, &values);
+                            let after = self.store[&key].versions.len();
+                            if after != before { info!("[anti-entropy-merge] node={} key={} versions {}->{}", self.node_id, key, before, after); }
+                            out
+                        }
+                        None => vec![],
+                    }
                 },
                 NodeToNode::PingReq{ from, to:_ } => vec![DynamoNodeOut::NodeToNode(NodeToNode::PingRsp{ from: self.node_id.clone(), to: from })],
                 NodeToNode::PingRsp{ from, to:_ } => {
                     // recovered
                     self.failed.remove(&from);
                     if let Some(keys) = self.handoffs.remove(&from) {
+                        let mut keys: Vec<String> = keys.into_iter().collect();
+                        keys.sort();
                         info!("[recovery] node={} recovered={}; replaying_handoffs keys={:?}", self.node_id, from, keys);
                         let mut msgs = vec![];
-                        for k in keys { if let Some(vs) = self.store.get(&k) {
-                            for v in &vs.versions {
-                                msgs.push(DynamoNodeOut::NodeToNode(NodeToNode::PutReq{ from: self.node_id.clone(), to: from.clone(), key: k.clone(), value: v.value.clone(), clock: v.clock.clone(), msg_id: 0, handoff: None }));
-                            }
+                        for k in keys { if let Some(vs) = self.store.get(&k).cloned() {
+                            msgs.push(self.send_repair(from.clone(), k, vs, RepairKind::Handoff));
                         }}
+                        self.metrics.handoffs_replayed += msgs.len() as u64;
                         msgs
                     } else { vec![] }
                 },
@@ -377,9 +858,18 @@ impl ActorProcess for DynamoNode {
                     info!("[add-node-ack] node={} received ack from {} for new_node={}", self.node_id, from, new_node);
                     vec![]
                 },
+                NodeToNode::StatsReq{ from, to:_ } => vec![DynamoNodeOut::NodeToNode(NodeToNode::StatsRsp{ from: self.node_id.clone(), to: from, stats: Box::new(self.stats()) })],
+                NodeToNode::StatsRsp{ from, .. } => { warn!("[stats] node={} ignoring stray stats response from {}", self.node_id, from); vec![] },
+                NodeToNode::Reconfigure{ from, to:_, update, propagate } => self.on_reconfigure(from, update, propagate),
+                NodeToNode::ReconfigureRsp{ from, .. } => { warn!("[reconfigure] node={} ignoring stray reconfigure response from {}", self.node_id, from); vec![] },
+                NodeToNode::SnapshotReq{ from, to:_ } => self.on_snapshot_req(from),
+                NodeToNode::ImportKey{ from, to:_, key, values, import_id } => self.on_import_key(from, key, values, import_id),
+                NodeToNode::SnapshotChunk{ from, .. } | NodeToNode::ImportAck{ from, .. } => { warn!("[snapshot] node={} ignoring stray admin response from {}", self.node_id, from); vec![] },
             },
         };
         out.extend(more);
+        out.extend(self.sweep_wal());
+        self.arm_timer();
         out
     }
 }
@@ -389,52 +879,72 @@ pub struct DynamoNodeSender {
 }
 impl DynamoNodeSender { pub fn new() -> Self { Self{} } }
 
-impl ActorSend for DynamoNodeSender {
-    type OMsg = DynamoNodeOut;
-    async fn before_send<'a>(&'a mut self, o: &Self::OMsg) -> RouteTo<'a> {
-        match o {
+impl DynamoNodeOut {
+    /// Actor address this message is delivered to; shared by `DynamoNodeSender` and in-process harnesses.
+    pub fn destination(&self) -> &str {
+        match self {
             DynamoNodeOut::NodeToNode(n2n) => {
                 match n2n {
-                    NodeToNode::ForwardClientPut{ coordinator, .. } => RouteTo::from(coordinator.clone()),
-                    NodeToNode::ForwardClientGet{ coordinator, .. } => RouteTo::from(coordinator.clone()),
-                    NodeToNode::PutReq{ to, .. } => RouteTo::from(to.clone()),
-                    NodeToNode::PutRsp{ to, .. } => RouteTo::from(to.clone()),
-                    NodeToNode::GetReq{ to, .. } => RouteTo::from(to.clone()),
-                    NodeToNode::GetRsp{ to, .. } => RouteTo::from(to.clone()),
-                    NodeToNode::SyncKey{ to, .. } => RouteTo::from(to.clone()),
-                    NodeToNode::PingReq{ to, .. } => RouteTo::from(to.clone()),
-                    NodeToNode::PingRsp{ to, .. } => RouteTo::from(to.clone()),
-                    NodeToNode::AddNode{ to, .. } => RouteTo::from(to.clone()),
-                    NodeToNode::AddNodeAck{ to, .. } => RouteTo::from(to.clone()),
+                    NodeToNode::ForwardClientPut{ coordinator, .. } => coordinator,
+                    NodeToNode::ForwardClientGet{ coordinator, .. } => coordinator,
+                    NodeToNode::PutReq{ to, .. } => to,
+                    NodeToNode::PutRsp{ to, .. } => to,
+                    NodeToNode::PutRej{ to, .. } => to,
+                    NodeToNode::GetReq{ to, .. } => to,
+                    NodeToNode::GetRsp{ to, .. } => to,
+                    NodeToNode::RepairReq{ to, .. } => to,
+                    NodeToNode::RepairRsp{ to, .. } => to,
+                    NodeToNode::SyncKey{ to, .. } => to,
+                    NodeToNode::PingReq{ to, .. } => to,
+                    NodeToNode::PingRsp{ to, .. } => to,
+                    NodeToNode::AddNode{ to, .. } => to,
+                    NodeToNode::AddNodeAck{ to, .. } => to,
+                    NodeToNode::StatsReq{ to, .. } => to,
+                    NodeToNode::StatsRsp{ to, .. } => to,
+                    NodeToNode::Reconfigure{ to, .. } => to,
+                    NodeToNode::ReconfigureRsp{ to, .. } => to,
+                    NodeToNode::SnapshotReq{ to, .. } => to,
+                    NodeToNode::SnapshotChunk{ to, .. } => to,
+                    NodeToNode::ImportKey{ to, .. } => to,
+                    NodeToNode::ImportAck{ to, .. } => to,
                 }
             }
             DynamoNodeOut::NodeToClient(c) => {
                 match c {
-                    NodeToClient::ClientPutRsp{ client_addr, .. } => RouteTo::from(client_addr.clone()),
-                    NodeToClient::ClientGetRsp{ client_addr, .. } => RouteTo::from(client_addr.clone()),
+                    NodeToClient::ClientPutRsp{ client_addr, .. } => client_addr,
+                    NodeToClient::ClientPutRejected{ client_addr, .. } => client_addr,
+                    NodeToClient::ClientGetRsp{ client_addr, .. } => client_addr,
                 }
             }
         }
     }
 }
 
+impl ActorSend for DynamoNodeSender {
+    type OMsg = DynamoNodeOut;
+    async fn before_send<'a>(&'a mut self, o: &Self::OMsg) -> RouteTo<'a> {
+        RouteTo::from(o.destination().to_string())
+    }
+}
+
 pub async fn node_behaviour(
     ctx: RuntimeCtx,
-    node_id: String,
-    nodes: Vec<String>,
-    n: usize,
-    w: usize,
-    r: usize,
-    t: usize,
+    cfg: DynamoConfig,
     decoder: reactor_actor::SubDecoderStore<DynamoNodeIn>,
 ) {
-    let proc = DynamoNode::new(node_id.clone(), nodes.clone(), n, w, r, t);
-    BehaviourBuilder::new(proc, BincodeCodec::default())
+    let proc = match DynamoNode::open(&cfg, Arc::new(SystemClock)) {
+        Ok(proc) => proc,
+        Err(e) => { log::error!("[node-init] {} not started: cannot open WAL: {}", cfg.node_id, e); return; }
+    };
+    let (timers, timer_queue) = timer_channel();
+    let behaviour = BehaviourBuilder::new(proc.with_timers(timers), BincodeCodec::default())
         .send(DynamoNodeSender::new())
         .sub_decoders(decoder)
         .ask_receiver_to_adapt()
-        .build()
-        .run(ctx)
-        .await
-        .unwrap();
+        .timers(timer_queue)
+        .build();
+    let addr = ctx.addr;
+    if let Err(e) = behaviour.run(ctx).await {
+        log::error!("[node] {} exited: {}", addr, e);
+    }
 }
@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use hdrhistogram::Histogram;
+use serde::{Deserialize, Serialize};
+
+use crate::workload::WorkloadConfig;
+
+/// Bumped whenever a field changes meaning or is removed.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Results of one benchmark run, written by `real_benchmark` and `dynamo_bench_client` and read
+/// by `bench_compare` and the graph scripts. Operations are keyed `get`, `put` and `scan`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchResults {
+    pub schema_version: u32,
+    pub run_id: String,
+    /// What produced the run, e.g. `real_benchmark` or `bench_client:bench_client1`.
+    pub source: String,
+    pub started_at_ms: u64,
+    pub duration_ms: u64,
+    pub config: RunConfig,
+    pub throughput_ops_per_sec: f64,
+    pub ops: BTreeMap<String, OpResults>,
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+/// Cluster and workload settings of a run; quorum settings are unknown to clients unless given.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunConfig {
+    pub n: Option<usize>,
+    pub w: Option<usize>,
+    pub r: Option<usize>,
+    pub t: Option<usize>,
+    pub operations: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workload: Option<WorkloadConfig>,
+}
+
+/// Latency percentiles of one operation type, in microseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub mean_us: f64,
+    pub p50_us: u64,
+    pub p90_us: u64,
+    pub p99_us: u64,
+    pub p999_us: u64,
+    pub max_us: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpResults {
+    pub errors: u64,
+    pub latency: LatencySummary,
+    /// Non-empty buckets of the latency histogram as (upper bound in us, count), doubling from 100us.
+    pub histogram: Vec<(u64, u64)>,
+    /// Every latency in us, in completion order; only kept when asked for.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub samples_us: Vec<u64>,
+}
+
+/// Collects latencies of one operation type.
+#[derive(Clone)]
+pub struct OpStats {
+    hist: Histogram<u64>,
+    samples: Option<Vec<u64>>,
+    errors: u64,
+}
+
+impl OpStats {
+    pub fn new(keep_samples: bool) -> Self {
+        Self { hist: Histogram::new_with_bounds(1, 3_600_000_000, 3).unwrap(), samples: keep_samples.then(Vec::new), errors: 0 }
+    }
+
+    pub fn record(&mut self, latency: Duration) {
+        let us = latency.as_micros().max(1) as u64;
+        self.hist.saturating_record(us);
+        if let Some(s) = self.samples.as_mut() { s.push(us); }
+    }
+
+    pub fn record_error(&mut self) { self.errors += 1; }
+
+    /// Adds the latencies and errors of `other`, e.g. to combine several clients into one run.
+    pub fn merge(&mut self, other: &OpStats) {
+        self.hist.add(&other.hist).expect("histograms share bounds");
+        if let (Some(s), Some(o)) = (self.samples.as_mut(), other.samples.as_ref()) { s.extend_from_slice(o); }
+        self.errors += other.errors;
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        let h = &self.hist;
+        LatencySummary { count: h.len(), mean_us: h.mean(), p50_us: h.value_at_quantile(0.5), p90_us: h.value_at_quantile(0.9), p99_us: h.value_at_quantile(0.99), p999_us: h.value_at_quantile(0.999), max_us: h.max() }
+    }
+
+    pub fn results(&self) -> OpResults {
+        let histogram = if self.hist.is_empty() { vec![] } else { self.hist.iter_log(100, 2.0).filter(|v| v.count_since_last_iteration() > 0).map(|v| (v.value_iterated_to(), v.count_since_last_iteration())).collect() };
+        OpResults { errors: self.errors, latency: self.summary(), histogram, samples_us: self.samples.clone().unwrap_or_default() }
+    }
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+impl BenchResults {
+    pub fn new(run_id: String, source: String, config: RunConfig) -> Self {
+        Self { schema_version: SCHEMA_VERSION, run_id, source, started_at_ms: now_ms(), duration_ms: 0, config, throughput_ops_per_sec: 0.0, ops: BTreeMap::new(), errors: vec![] }
+    }
+
+    /// Fills in the duration, per-op results and throughput from completed `stats`.
+    pub fn finish<'a>(&mut self, elapsed: Duration, stats: impl IntoIterator<Item = (&'a str, &'a OpStats)>) {
+        self.duration_ms = elapsed.as_millis() as u64;
+        self.ops = stats.into_iter().map(|(op, s)| (op.to_string(), s.results())).collect();
+        let completed: u64 = self.ops.values().map(|o| o.latency.count).sum();
+        self.throughput_ops_per_sec = completed as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+    }
+
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) { std::fs::create_dir_all(dir)?; }
+        let mut out = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut out, self).map_err(io::Error::other)?;
+        out.write_all(b"\n")?;
+        out.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let results: Self = serde_json::from_reader(BufReader::new(File::open(path)?)).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+        if results.schema_version != SCHEMA_VERSION { return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: schema version {} (expected {})", path.display(), results.schema_version, SCHEMA_VERSION))); }
+        Ok(results)
+    }
+
+    pub const CSV_HEADER: &'static str = "run_id,source,n,w,r,t,op,count,errors,mean_us,p50_us,p90_us,p99_us,p999_us,max_us,throughput_ops_per_sec";
+
+    /// One CSV row per operation type; rows of several runs can share a file (see `append_csv`).
+    pub fn csv_rows(&self) -> Vec<String> {
+        let opt = |v: Option<usize>| v.map(|v| v.to_string()).unwrap_or_default();
+        let c = &self.config;
+        self.ops.iter().map(|(op, o)| {
+            let l = &o.latency;
+            format!("{},{},{},{},{},{},{},{},{},{:.1},{},{},{},{},{},{:.1}", self.run_id, self.source, opt(c.n), opt(c.w), opt(c.r), opt(c.t), op, l.count, o.errors, l.mean_us, l.p50_us, l.p90_us, l.p99_us, l.p999_us, l.max_us, self.throughput_ops_per_sec)
+        }).collect()
+    }
+
+    /// Appends this run's rows to a CSV file, writing the header if the file is new.
+    pub fn append_csv(&self, path: &Path) -> io::Result<()> {
+        let new = !path.exists();
+        let mut out = OpenOptions::new().create(true).append(true).open(path)?;
+        if new { writeln!(out, "{}", Self::CSV_HEADER)?; }
+        for row in self.csv_rows() { writeln!(out, "{}", row)?; }
+        Ok(())
+    }
+}
+
+/// One metric of one operation compared between two runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub op: String,
+    pub metric: &'static str,
+    pub base: f64,
+    pub new: f64,
+    /// Relative change, (new - base) / base.
+    pub change: f64,
+    pub regression: bool,
+}
+
+/// Compares latency percentiles, errors and throughput of `new` against `base`. Latencies that grow
+/// and throughput that drops by more than `threshold` (e.g. 0.1 = 10%), and any new errors, are
+/// flagged as regressions.
+pub fn compare(base: &BenchResults, new: &BenchResults, threshold: f64) -> Vec<Comparison> {
+    let change = |b: f64, n: f64| if b == 0.0 { if n == 0.0 { 0.0 } else { f64::INFINITY } } else { (n - b) / b };
+    let mut out = vec![];
+    for (op, b) in base.ops.iter() {
+        let Some(n) = new.ops.get(op) else { continue };
+        let metrics: [(&'static str, f64, f64); 4] = [
+            ("p50_us", b.latency.p50_us as f64, n.latency.p50_us as f64),
+            ("p99_us", b.latency.p99_us as f64, n.latency.p99_us as f64),
+            ("p999_us", b.latency.p999_us as f64, n.latency.p999_us as f64),
+            ("mean_us", b.latency.mean_us, n.latency.mean_us),
+        ];
+        for (metric, bv, nv) in metrics {
+            let c = change(bv, nv);
+            out.push(Comparison { op: op.clone(), metric, base: bv, new: nv, change: c, regression: c > threshold });
+        }
+        let c = change(b.errors as f64, n.errors as f64);
+        out.push(Comparison { op: op.clone(), metric: "errors", base: b.errors as f64, new: n.errors as f64, change: c, regression: n.errors > b.errors });
+    }
+    let c = change(base.throughput_ops_per_sec, new.throughput_ops_per_sec);
+    out.push(Comparison { op: "all".to_string(), metric: "throughput_ops_per_sec", base: base.throughput_ops_per_sec, new: new.throughput_ops_per_sec, change: c, regression: c < -threshold });
+    out
+}
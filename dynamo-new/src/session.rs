@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use serde::Deserialize;
+
+use crate::vector_clock::VectorClock;
+
+/// What the client does when a GET result does not dominate the session's clock for that key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum SessionPolicy {
+    /// Re-issue the GET (usually landing on another coordinator) up to `max_retries` times, then reject.
+    Retry { #[serde(default = "default_max_retries")] max_retries: u32 },
+    /// Drop the stale result; the session keeps its previous view of the key.
+    Reject,
+    /// Accept the stale result and log it.
+    Warn,
+}
+
+fn default_max_retries() -> u32 { 3 }
+
+impl Default for SessionPolicy {
+    fn default() -> Self { SessionPolicy::Retry { max_retries: default_max_retries() } }
+}
+
+/// The guarantee a stale read would break.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Guarantee { ReadYourWrites, MonotonicReads }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionCheck {
+    Ok,
+    Violation(Guarantee),
+}
+
+/// Per-client counts of session violations and how they were handled.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionStats {
+    pub violations: u64,
+    pub retries: u64,
+    pub rejected: u64,
+}
+
+/// Opt-in client session: remembers, per key, the clock of the last acknowledged write and the
+/// causal context of the last accepted read. A later read must return a context that descends both.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pub policy: SessionPolicy,
+    written: HashMap<String, VectorClock>,
+    read: HashMap<String, VectorClock>,
+}
+
+impl Session {
+    pub fn new(policy: SessionPolicy) -> Self {
+        Self { policy, written: HashMap::new(), read: HashMap::new() }
+    }
+
+    pub fn record_write(&mut self, key: &str, clock: &VectorClock) {
+        self.written.entry(key.to_string()).or_default().merge(clock);
+    }
+
+    pub fn record_read(&mut self, key: &str, metadata: &[VectorClock]) {
+        self.read.entry(key.to_string()).or_default().merge(&VectorClock::converge(metadata.iter().cloned()));
+    }
+
+    pub fn check_read(&self, key: &str, metadata: &[VectorClock]) -> SessionCheck {
+        let seen = VectorClock::converge(metadata.iter().cloned());
+        if self.written.get(key).is_some_and(|w| !seen.descends(w)) { return SessionCheck::Violation(Guarantee::ReadYourWrites); }
+        if self.read.get(key).is_some_and(|r| !seen.descends(r)) { return SessionCheck::Violation(Guarantee::MonotonicReads); }
+        SessionCheck::Ok
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::versioned_value::{VersionedValue, VersionedValues};
+
+pub const SNAPSHOT_FORMAT: &str = "dynamo-snapshot";
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// First line of a snapshot file; `keys` lets `load` detect a truncated file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    pub format: String,
+    pub version: u32,
+    pub created_at_ms: u64,
+    /// Nodes whose stores went into the snapshot.
+    pub nodes: Vec<String>,
+    pub keys: u64,
+}
+
+/// One line per key after the header, with every sibling and its vector clock.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub key: String,
+    pub versions: Vec<VersionedValue>,
+}
+
+/// Every key of a cluster with all sibling versions, merged from the stores of its nodes.
+///
+/// Each node's part is taken in one step of its actor loop, but nodes are read one after the
+/// other, so writes racing the export may be in some nodes' parts and not others. Stored as JSON
+/// Lines: a `SnapshotHeader`, then one `SnapshotEntry` per key in key order.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    nodes: Vec<String>,
+    entries: BTreeMap<String, VersionedValues>,
+}
+
+impl Snapshot {
+    pub fn new() -> Self { Self::default() }
+
+    /// Records `node` as a source even if its store is empty.
+    pub fn add_node(&mut self, node: &str) {
+        if !self.nodes.iter().any(|n| n == node) { self.nodes.push(node.to_string()); }
+    }
+
+    /// Adds `from`'s copy of `key`, merging it with the copies of other replicas.
+    pub fn add(&mut self, from: &str, key: String, values: &VersionedValues) {
+        self.add_node(from);
+        self.entries.entry(key).or_default().merge(values);
+    }
+
+    pub fn nodes(&self) -> &[String] { &self.nodes }
+
+    pub fn len(&self) -> usize { self.entries.len() }
+
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    pub fn get(&self, key: &str) -> Option<&VersionedValues> { self.entries.get(key) }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &VersionedValues)> { self.entries.iter() }
+
+    /// Writes to a temporary file next to `path` and renames it, so a crash never leaves a partial snapshot.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) { std::fs::create_dir_all(dir)?; }
+        let tmp = path.with_extension("tmp");
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        let created_at_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+        let header = SnapshotHeader { format: SNAPSHOT_FORMAT.to_string(), version: SNAPSHOT_VERSION, created_at_ms, nodes: self.nodes.clone(), keys: self.entries.len() as u64 };
+        serde_json::to_writer(&mut out, &header)?;
+        writeln!(out)?;
+        for (key, values) in self.entries.iter() {
+            serde_json::to_writer(&mut out, &SnapshotEntry { key: key.clone(), versions: values.versions.clone() })?;
+            writeln!(out)?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&tmp, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<(SnapshotHeader, Self)> {
+        let invalid = |line: usize, msg: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", path.display(), line, msg));
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header: SnapshotHeader = match lines.next() {
+            Some(line) => serde_json::from_str(&line?).map_err(|e| invalid(1, e.to_string()))?,
+            None => return Err(invalid(1, "empty file".to_string())),
+        };
+        if header.format != SNAPSHOT_FORMAT { return Err(invalid(1, format!("not a snapshot (format {:?})", header.format))); }
+        if header.version != SNAPSHOT_VERSION { return Err(invalid(1, format!("snapshot version {} (expected {})", header.version, SNAPSHOT_VERSION))); }
+        let mut snap = Self { nodes: header.nodes.clone(), entries: BTreeMap::new() };
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            let entry: SnapshotEntry = serde_json::from_str(&line).map_err(|e| invalid(i + 2, e.to_string()))?;
+            let values = snap.entries.entry(entry.key).or_default();
+            for v in entry.versions { values.add_version(v); }
+        }
+        if snap.entries.len() as u64 != header.keys {
+            return Err(invalid(1, format!("header lists {} keys but the file holds {}", header.keys, snap.entries.len())));
+        }
+        Ok((header, snap))
+    }
+}
@@ -1,7 +1,8 @@
 use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, Default, Serialize, Deserialize)]
 pub struct VectorClock {
     pub clock: HashMap<String, u64>,
 }
@@ -51,6 +52,9 @@ impl VectorClock {
 
     pub fn happens_before(&self, other: &VectorClock) -> bool { matches!(self.compare(other), ClockOrdering::Before) }
 
+    /// True if this clock has seen everything `other` has (equal or after).
+    pub fn descends(&self, other: &VectorClock) -> bool { matches!(self.compare(other), ClockOrdering::After | ClockOrdering::Equal) }
+
     pub fn converge<I: IntoIterator<Item = VectorClock>>(vcs: I) -> VectorClock {
         let mut r = VectorClock::new();
         for vc in vcs { r.merge(&vc); }
@@ -1,13 +1,36 @@
 use crate::vector_clock::{VectorClock};
 use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
 pub struct VersionedValue {
     pub value: String,
     pub clock: VectorClock,
+    /// Wall-clock time (ms since the Unix epoch) the coordinator accepted the write; only orders
+    /// siblings when a key is over its sibling limit. 0 if unknown.
+    #[serde(default)]
+    pub ts_ms: u64,
 }
 
-impl VersionedValue { pub fn new(value: String, clock: VectorClock) -> Self { Self { value, clock } } }
+impl VersionedValue {
+    pub fn new(value: String, clock: VectorClock) -> Self { Self { value, clock, ts_ms: 0 } }
+
+    pub fn with_ts(mut self, ts_ms: u64) -> Self { self.ts_ms = ts_ms; self }
+}
+
+/// What a replica does when a key would hold more than `max_siblings` concurrent versions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Encode, Decode, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SiblingPolicy {
+    /// Keep the `max_siblings` newest by write timestamp and drop the rest.
+    #[default]
+    KeepNewest,
+    /// Refuse client writes that would add a sibling; the PUT fails with `ClientPutRejected`.
+    /// Repairs and anti-entropy are still merged, so a key can end up over the limit.
+    RejectWrite,
+    /// Collapse all siblings into the newest value, under a clock that descends from every one of them.
+    Resolve,
+}
 
 #[derive(Debug, Clone, Encode, Decode, Default)]
 pub struct VersionedValues { pub versions: Vec<VersionedValue> }
@@ -27,4 +50,34 @@ impl VersionedValues {
     pub fn merge(&mut self, other: &VersionedValues) { for v in &other.versions { self.add_version(v.clone()); } }
     pub fn has_conflict(&self) -> bool { self.versions.len() > 1 }
     pub fn contains(&self, vv: &VersionedValue) -> bool { self.versions.iter().any(|v| v.value == vv.value && v.clock == vv.clock) }
+
+    /// Whether adding `new_v` would leave more versions than there are now.
+    pub fn adds_sibling(&self, new_v: &VersionedValue) -> bool {
+        let mut after = self.clone();
+        after.add_version(new_v.clone());
+        after.versions.len() > self.versions.len()
+    }
+
+    /// Applies `policy` if there are more than `max` siblings (0 = no limit) and returns how many
+    /// versions were dropped. Ties on the timestamp are broken by value and clock so every replica
+    /// keeps the same versions.
+    pub fn limit_siblings(&mut self, max: usize, policy: SiblingPolicy) -> usize {
+        if max == 0 || self.versions.len() <= max || policy == SiblingPolicy::RejectWrite { return 0; }
+        let before = self.versions.len();
+        self.versions.sort_by_cached_key(|v| {
+            let mut clock: Vec<(String, u64)> = v.clock.clock.iter().map(|(n, c)| (n.clone(), *c)).collect();
+            clock.sort();
+            std::cmp::Reverse((v.ts_ms, v.value.clone(), clock))
+        });
+        match policy {
+            SiblingPolicy::RejectWrite => {}
+            SiblingPolicy::KeepNewest => self.versions.truncate(max),
+            SiblingPolicy::Resolve => {
+                let clock = VectorClock::converge(self.versions.iter().map(|v| v.clock.clone()));
+                let newest = &self.versions[0];
+                self.versions = vec![VersionedValue { value: newest.value.clone(), clock, ts_ms: newest.ts_ms }];
+            }
+        }
+        before - self.versions.len()
+    }
 }
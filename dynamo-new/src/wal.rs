@@ -0,0 +1,169 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use bincode::{Decode, Encode};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::versioned_value::VersionedValues;
+
+/// When appended records are fsynced, and so when the writes they carry are acknowledged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FsyncMode {
+    /// fsync before acknowledging every write.
+    #[default]
+    Always,
+    /// Group commit: one fsync covers every write of the last `wal_group_commit_ms`, whose acks are held until then.
+    Interval,
+    /// Never fsync; writes are acknowledged once handed to the OS and are lost if the machine crashes.
+    None,
+}
+
+/// Versions merged into `key`, plus the failed nodes this replica holds them for as hinted handoff.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct WalRecord {
+    pub key: String,
+    pub values: VersionedValues,
+    pub hints: Vec<String>,
+}
+
+// length and CRC32 of the payload, both little-endian u32
+const HEADER_LEN: usize = 8;
+
+/// The file a `Wal` appends to, opened for reading and appending. Tests wrap a `File` to make
+/// appends fail.
+pub trait WalFile: Read + Write + Seek {
+    fn file_len(&self) -> io::Result<u64>;
+    fn set_len(&self, len: u64) -> io::Result<()>;
+    fn sync_data(&self) -> io::Result<()>;
+}
+
+impl WalFile for File {
+    fn file_len(&self) -> io::Result<u64> { Ok(self.metadata()?.len()) }
+    fn set_len(&self, len: u64) -> io::Result<()> { File::set_len(self, len) }
+    fn sync_data(&self) -> io::Result<()> { File::sync_data(self) }
+}
+
+/// Append-only log of a node's replica writes, replayed into the store on restart.
+///
+/// Each record is framed with its length and CRC32, so a record torn by a crash mid-append is
+/// detected on `open` and cut off along with anything after it. A record torn by a failed append
+/// is cut off at once, so the appends after it stay readable.
+pub struct Wal<F: WalFile = File> {
+    file: F,
+    path: PathBuf,
+    mode: FsyncMode,
+    group_commit: Duration,
+    // end of the last complete record
+    len: u64,
+    // set when a torn record could not be cut off; every later append fails
+    broken: bool,
+    // time of the oldest append not yet fsynced
+    unsynced_since: Option<Instant>,
+}
+
+impl Wal {
+    /// Opens or creates the log at `path` and returns the records it holds.
+    pub fn open(path: &Path, mode: FsyncMode, group_commit: Duration) -> io::Result<(Self, Vec<WalRecord>)> {
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) { std::fs::create_dir_all(dir)?; }
+        let file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        Self::from_file(file, path, mode, group_commit)
+    }
+}
+
+impl<F: WalFile> Wal<F> {
+    /// `open` on an already opened `file`, which `path` only names in logs and `config`.
+    pub fn from_file(mut file: F, path: &Path, mode: FsyncMode, group_commit: Duration) -> io::Result<(Self, Vec<WalRecord>)> {
+        let len = file.file_len()?;
+        let (records, valid_len) = read_records(&mut file, len)?;
+        if valid_len < len {
+            warn!("[wal] {}: dropping {} bytes after the last complete record", path.display(), len - valid_len);
+            file.set_len(valid_len)?;
+            file.sync_data()?;
+        }
+        file.seek(SeekFrom::End(0))?;
+        Ok((Self { file, path: path.to_path_buf(), mode, group_commit, len: valid_len, broken: false, unsynced_since: None }, records))
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    pub fn mode(&self) -> FsyncMode { self.mode }
+
+    pub fn group_commit(&self) -> Duration { self.group_commit }
+
+    /// Appends `rec` and returns whether it is already durable under the fsync mode; if not, it
+    /// becomes durable at the next `sync`. On error nothing of `rec` is left in the log.
+    pub fn append(&mut self, rec: &WalRecord, now: Instant) -> io::Result<bool> {
+        if self.broken { return Err(io::Error::other("an earlier failed append could not be cut off")); }
+        let payload = bincode::encode_to_vec(rec, bincode::config::standard()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mut buf = Vec::with_capacity(HEADER_LEN + payload.len());
+        buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        buf.extend_from_slice(&payload);
+        let written = self.file.write_all(&buf).and_then(|()| if self.mode == FsyncMode::Always { self.file.sync_data() } else { Ok(()) });
+        if let Err(e) = written {
+            // left in place, the torn bytes would end recovery before any later append
+            if let Err(cut) = self.file.set_len(self.len) {
+                warn!("[wal] {}: cannot cut off a failed append: {}; failing every later append", self.path.display(), cut);
+                self.broken = true;
+            }
+            return Err(e);
+        }
+        self.len += buf.len() as u64;
+        match self.mode {
+            FsyncMode::Always | FsyncMode::None => Ok(true),
+            FsyncMode::Interval => { self.unsynced_since.get_or_insert(now); Ok(false) }
+        }
+    }
+
+    /// True once the oldest unsynced append has waited `group_commit`.
+    pub fn sync_due(&self, now: Instant) -> bool {
+        self.unsynced_since.is_some_and(|t| now.duration_since(t) >= self.group_commit)
+    }
+
+    /// When `sync_due` becomes true, if anything is waiting for an fsync.
+    pub fn sync_deadline(&self) -> Option<Instant> { self.unsynced_since.map(|t| t + self.group_commit) }
+
+    pub fn sync(&mut self) -> io::Result<()> {
+        if self.unsynced_since.is_some() {
+            self.file.sync_data()?;
+            self.unsynced_since = None;
+        }
+        Ok(())
+    }
+}
+
+// Reads records up to the first incomplete or corrupt one; returns them and the length they span
+fn read_records(file: &mut impl WalFile, file_len: u64) -> io::Result<(Vec<WalRecord>, u64)> {
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(file);
+    let mut records = vec![];
+    let mut valid_len = 0u64;
+    loop {
+        let mut header = [0u8; HEADER_LEN];
+        if !read_full(&mut reader, &mut header)? { break; }
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+        // a torn length field must not make us allocate past the end of the file
+        if valid_len + (HEADER_LEN + len) as u64 > file_len { break; }
+        let mut payload = vec![0u8; len];
+        if !read_full(&mut reader, &mut payload)? || crc32fast::hash(&payload) != crc { break; }
+        match bincode::decode_from_slice::<WalRecord, _>(&payload, bincode::config::standard()) {
+            Ok((rec, _)) => records.push(rec),
+            Err(_) => break,
+        }
+        valid_len += (HEADER_LEN + len) as u64;
+    }
+    Ok((records, valid_len))
+}
+
+// false if the input ends before `buf` is filled
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> io::Result<bool> {
+    match r.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
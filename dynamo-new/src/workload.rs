@@ -0,0 +1,208 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+/// YCSB-style workload for `dynamo_bench_client`, read from the `workload` payload key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkloadConfig {
+    /// Operations to run; a scan counts as one.
+    pub operations: usize,
+    /// Keys are `key0..key{record_count-1}`.
+    pub record_count: usize,
+    pub read_proportion: f64,
+    pub update_proportion: f64,
+    /// Scans read `1..=max_scan_length` consecutive keys with one GET each.
+    pub scan_proportion: f64,
+    pub max_scan_length: usize,
+    pub key_distribution: KeyDistribution,
+    /// Skew of the zipfian and latest distributions.
+    pub zipfian_constant: f64,
+    pub value_size: ValueSize,
+    /// Open-loop arrival rate; 0 runs closed-loop, issuing the next operation as soon as a slot frees.
+    pub target_ops_per_sec: f64,
+    /// Operations in flight at once; open-loop arrivals beyond this queue up.
+    pub max_outstanding: usize,
+    /// Defaults to a hash of the client id.
+    pub seed: Option<u64>,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        Self {
+            operations: 1000,
+            record_count: 100,
+            read_proportion: 0.5,
+            update_proportion: 0.5,
+            scan_proportion: 0.0,
+            max_scan_length: 10,
+            key_distribution: KeyDistribution::Uniform,
+            zipfian_constant: 0.99,
+            value_size: ValueSize::Constant { bytes: 32 },
+            target_ops_per_sec: 0.0,
+            max_outstanding: 1,
+            seed: None,
+        }
+    }
+}
+
+impl WorkloadConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let total = self.read_proportion + self.update_proportion + self.scan_proportion;
+        if [self.read_proportion, self.update_proportion, self.scan_proportion].iter().any(|p| *p < 0.0) || total <= 0.0 { return Err(format!("operation proportions must be non-negative with a positive sum, got {}", total)); }
+        if self.record_count == 0 { return Err("record_count must be at least 1".to_string()); }
+        if self.max_outstanding == 0 { return Err("max_outstanding must be at least 1".to_string()); }
+        if self.scan_proportion > 0.0 && self.max_scan_length == 0 { return Err("max_scan_length must be at least 1 when scans are enabled".to_string()); }
+        if !(self.zipfian_constant > 0.0 && self.zipfian_constant < 1.0) { return Err(format!("zipfian_constant must be in (0, 1), got {}", self.zipfian_constant)); }
+        if self.target_ops_per_sec < 0.0 { return Err("target_ops_per_sec must not be negative".to_string()); }
+        match self.value_size {
+            ValueSize::Uniform{ min, max } | ValueSize::Zipfian{ min, max } if min > max => Err(format!("value_size min {} exceeds max {}", min, max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyDistribution {
+    Uniform,
+    /// Popular keys follow a zipfian distribution, scattered over the key space.
+    Zipfian,
+    /// Recently updated keys are the most popular.
+    Latest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "dist", rename_all = "snake_case")]
+pub enum ValueSize {
+    Constant { bytes: usize },
+    Uniform { min: usize, max: usize },
+    /// Sizes near `min` are the most common.
+    Zipfian { min: usize, max: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OpType { Read, Update, Scan }
+
+impl OpType {
+    pub fn label(&self) -> &'static str {
+        match self { OpType::Read => "READ", OpType::Update => "UPDATE", OpType::Scan => "SCAN" }
+    }
+
+    /// Key of this operation in `results::BenchResults::ops`.
+    pub fn results_key(&self) -> &'static str {
+        match self { OpType::Read => "get", OpType::Update => "put", OpType::Scan => "scan" }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkloadOp {
+    Read { key: String },
+    Update { key: String, value_len: usize },
+    Scan { keys: Vec<String> },
+}
+
+impl WorkloadOp {
+    pub fn op_type(&self) -> OpType {
+        match self { WorkloadOp::Read{ .. } => OpType::Read, WorkloadOp::Update{ .. } => OpType::Update, WorkloadOp::Scan{ .. } => OpType::Scan }
+    }
+}
+
+/// Zipfian ranks over `0..items` (Gray et al., "Quickly generating billion-record synthetic
+/// databases", as used by YCSB); rank 0 is the most popular.
+#[derive(Debug, Clone)]
+pub struct Zipfian {
+    items: u64,
+    theta: f64,
+    alpha: f64,
+    zetan: f64,
+    eta: f64,
+}
+
+impl Zipfian {
+    pub fn new(items: u64, theta: f64) -> Self {
+        let items = items.max(1);
+        let zeta = |n: u64| (1..=n).map(|i| 1.0 / (i as f64).powf(theta)).sum::<f64>();
+        let (zeta2, zetan) = (zeta(2.min(items)), zeta(items));
+        let eta = if items < 2 { 0.0 } else { (1.0 - (2.0 / items as f64).powf(1.0 - theta)) / (1.0 - zeta2 / zetan) };
+        Self { items, theta, alpha: 1.0 / (1.0 - theta), zetan, eta }
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> u64 {
+        let u: f64 = rng.random();
+        let uz = u * self.zetan;
+        if uz < 1.0 { return 0; }
+        if uz < 1.0 + 0.5f64.powf(self.theta) { return 1.min(self.items - 1); }
+        ((self.items as f64 * (self.eta * u - self.eta + 1.0).powf(self.alpha)) as u64).min(self.items - 1)
+    }
+}
+
+/// Generates the operations of a `WorkloadConfig`.
+pub struct Workload {
+    cfg: WorkloadConfig,
+    rng: StdRng,
+    keys: Option<Zipfian>,
+    sizes: Option<Zipfian>,
+    latest: u64, // most recently updated key, for `KeyDistribution::Latest`
+    generated: usize,
+}
+
+impl Workload {
+    pub fn new(cfg: WorkloadConfig, client_id: &str) -> Self {
+        let seed = cfg.seed.unwrap_or_else(|| { let mut h = DefaultHasher::new(); client_id.hash(&mut h); h.finish() });
+        let keys = (cfg.key_distribution != KeyDistribution::Uniform).then(|| Zipfian::new(cfg.record_count as u64, cfg.zipfian_constant));
+        let sizes = match cfg.value_size { ValueSize::Zipfian{ min, max } => Some(Zipfian::new((max - min + 1) as u64, cfg.zipfian_constant)), _ => None };
+        let latest = cfg.record_count.saturating_sub(1) as u64;
+        Self { rng: StdRng::seed_from_u64(seed), keys, sizes, latest, generated: 0, cfg }
+    }
+
+    pub fn config(&self) -> &WorkloadConfig { &self.cfg }
+
+    /// Operations generated so far.
+    pub fn generated(&self) -> usize { self.generated }
+
+    /// The next operation, or None once `operations` have been generated.
+    pub fn next_op(&mut self) -> Option<WorkloadOp> {
+        if self.generated >= self.cfg.operations { return None; }
+        self.generated += 1;
+        let total = self.cfg.read_proportion + self.cfg.update_proportion + self.cfg.scan_proportion;
+        let pick = self.rng.random::<f64>() * total;
+        let idx = self.next_key_index();
+        let op = if pick < self.cfg.read_proportion {
+            WorkloadOp::Read{ key: key_name(idx) }
+        } else if pick < self.cfg.read_proportion + self.cfg.update_proportion {
+            self.latest = idx;
+            WorkloadOp::Update{ key: key_name(idx), value_len: self.next_value_len() }
+        } else {
+            let len = self.rng.random_range(1..=self.cfg.max_scan_length.max(1));
+            let n = self.cfg.record_count as u64;
+            WorkloadOp::Scan{ keys: (0..len as u64).map(|i| key_name((idx + i) % n)).collect() }
+        };
+        Some(op)
+    }
+
+    fn next_key_index(&mut self) -> u64 {
+        let n = self.cfg.record_count as u64;
+        match (self.cfg.key_distribution, &self.keys) {
+            (KeyDistribution::Zipfian, Some(z)) => {
+                // scatter popular ranks so hot keys do not sit next to each other on the ring
+                let mut h = DefaultHasher::new();
+                z.sample(&mut self.rng).hash(&mut h);
+                h.finish() % n
+            }
+            (KeyDistribution::Latest, Some(z)) => (self.latest + n - z.sample(&mut self.rng) % n) % n,
+            _ => self.rng.random_range(0..n),
+        }
+    }
+
+    fn next_value_len(&mut self) -> usize {
+        match self.cfg.value_size {
+            ValueSize::Constant{ bytes } => bytes,
+            ValueSize::Uniform{ min, max } => self.rng.random_range(min..=max),
+            ValueSize::Zipfian{ min, .. } => min + self.sizes.as_ref().map(|z| z.sample(&mut self.rng) as usize).unwrap_or(0),
+        }
+    }
+}
+
+fn key_name(idx: u64) -> String { format!("key{}", idx) }
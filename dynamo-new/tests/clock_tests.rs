@@ -0,0 +1,222 @@
+// Clock Tests
+// Timeout, retry, ping and hinted-handoff behaviour; the node's timers fire on a ManualClock
+
+mod common;
+use common::{tick};
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use dynamo_new::clock::{ClockTimers, ManualClock};
+use dynamo_new::consistent_hash::ConsistentHash;
+use dynamo_new::node::DynamoNode;
+use dynamo_new::messages::{ClientToNode, DynamoNodeIn, DynamoNodeOut, NodeToNode};
+use reactor_actor::{ActorProcess, CorrelationId, Msg, TimerCmd, TimerId, TimerQueue, timer_channel};
+
+fn five_nodes() -> Vec<String> {
+    ["nodeA", "nodeB", "nodeC", "nodeD", "nodeE"].iter().map(|s| s.to_string()).collect()
+}
+
+// A key whose preference list starts at nodeA, so nodeA coordinates it
+fn key_coordinated_by_a() -> (String, Vec<String>) {
+    let ring = ConsistentHash::new(&five_nodes(), 10);
+    (0..).map(|i| format!("key{}", i)).map(|k| { let (pref, _) = ring.find_nodes(&k, 3, &[]); (k, pref) }).find(|(_, pref)| pref[0] == "nodeA").unwrap()
+}
+
+fn client_put(key: &str, request_id: u64) -> DynamoNodeIn {
+    DynamoNodeIn::ClientToNode(ClientToNode::ClientPut { key: key.to_string(), value: "v".to_string(), metadata: vec![], client_addr: "client1".to_string(), request_id })
+}
+
+fn put_reqs(out: &[DynamoNodeOut]) -> Vec<(String, CorrelationId, Option<Vec<String>>)> {
+    out.iter().filter_map(|m| match m {
+        DynamoNodeOut::NodeToNode(NodeToNode::PutReq{ to, msg_id, handoff, .. }) => Some((to.clone(), *msg_id, handoff.clone())),
+        _ => None,
+    }).collect()
+}
+
+// A timer nodeA set, due at its key in `TimedNode::timers`
+struct Timer { id: TimerId, period: Option<Duration>, msg: DynamoNodeIn, ask: Option<CorrelationId> }
+
+// nodeA with its TimerQueue served on a ManualClock, as dynamo-sim serves it on virtual time
+struct TimedNode {
+    node: DynamoNode,
+    clock: ManualClock,
+    queue: TimerQueue<DynamoNodeIn>,
+    timers: BTreeMap<(Duration, u64), Timer>, // (due, seq) -> timer
+    seq: u64,
+}
+
+impl TimedNode {
+    fn new() -> Self {
+        let clock = ManualClock::new();
+        let (timers, queue) = timer_channel();
+        let node = DynamoNode::new("nodeA".to_string(), five_nodes(), 3, 2, 2, 10, Arc::new(clock.clone())).with_timers(timers);
+        let mut t = TimedNode { node, clock, queue, timers: BTreeMap::new(), seq: 0 };
+        t.take_timers();
+        t
+    }
+
+    // Delivers `msg`; a reply is let through only while its ask waits, as the actor does
+    fn process(&mut self, msg: DynamoNodeIn) -> Vec<DynamoNodeOut> {
+        if let Some(id) = msg.reply_to() && !self.queue.settle(id) { return vec![]; }
+        let out = self.node.process(msg);
+        self.take_timers();
+        out
+    }
+
+    // Moves the clock on by `d`, firing every timer due on the way
+    fn advance(&mut self, d: Duration) -> Vec<DynamoNodeOut> {
+        let until = self.clock.elapsed() + d;
+        let mut out = vec![];
+        while let Some(entry) = self.timers.first_entry() && entry.key().0 <= until {
+            let ((at, _), t) = entry.remove_entry();
+            self.clock.set_elapsed(at);
+            // an answered ask's timeout is left to expire here rather than cancelled
+            if let Some(ask) = t.ask && !self.queue.settle(ask) { continue; }
+            if let Some(period) = t.period { self.schedule(t.id, period, Some(period), t.msg.clone(), None); }
+            out.extend(self.node.process(t.msg));
+            self.take_timers();
+        }
+        self.clock.set_elapsed(until);
+        out
+    }
+
+    // Applies the timers the node set or cancelled
+    fn take_timers(&mut self) {
+        while let Some(cmd) = self.queue.try_next() {
+            match cmd {
+                TimerCmd::Set{ id, delay, period, msg, ask } => self.schedule(id, delay, period, msg, ask),
+                TimerCmd::Cancel(id) => self.timers.retain(|_, t| t.id != id),
+            }
+        }
+    }
+
+    fn schedule(&mut self, id: TimerId, delay: Duration, period: Option<Duration>, msg: DynamoNodeIn, ask: Option<CorrelationId>) {
+        self.seq += 1;
+        self.timers.insert((self.clock.elapsed() + delay, self.seq), Timer{ id, period, msg, ask });
+    }
+}
+
+fn put_rsp(node: &mut TimedNode, from: &str, msg_id: CorrelationId) -> Vec<DynamoNodeOut> {
+    node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutRsp { from: from.to_string(), to: "nodeA".to_string(), msg_id }))
+}
+
+// The nodes' default timeout_ms
+const TIMEOUT: Duration = Duration::from_millis(800);
+
+fn pings(out: &[DynamoNodeOut]) -> Vec<String> {
+    let mut p: Vec<String> = out.iter().filter_map(|m| match m {
+        DynamoNodeOut::NodeToNode(NodeToNode::PingReq{ to, .. }) => Some(to.clone()),
+        _ => None,
+    }).collect();
+    p.sort();
+    p
+}
+
+#[cfg(test)]
+mod clock_tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock_only_moves_when_told() {
+        let clock = ManualClock::new();
+        let shared = clock.clone();
+        assert_eq!(clock.elapsed(), Duration::ZERO);
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(shared.elapsed(), Duration::from_millis(5));
+        clock.set_elapsed(Duration::from_millis(2));
+        assert_eq!(shared.elapsed(), Duration::from_millis(5), "never goes backwards");
+    }
+
+    #[test]
+    fn test_clock_timers_fire_only_when_due() {
+        let clock = ManualClock::new();
+        let (handle, queue) = timer_channel::<u32>();
+        let mut timers = ClockTimers::new(queue, Arc::new(clock.clone()));
+        handle.set_periodic(Duration::from_millis(10), 1);
+        let answered = handle.ask(Duration::from_millis(5), |_| 2);
+        handle.ask(Duration::from_millis(5), |_| 3);
+        let cancelled = handle.set_once(Duration::from_millis(1), 4);
+        handle.cancel(cancelled);
+        timers.arm();
+
+        assert!(timers.settle(answered));
+        clock.advance(Duration::from_millis(4));
+        assert!(timers.due().is_empty(), "not due yet");
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(timers.due(), vec![3], "an answered ask does not time out");
+        // missed periods are skipped, not delivered in a burst
+        clock.advance(Duration::from_millis(30));
+        assert_eq!(timers.due(), vec![1]);
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(timers.due(), vec![1]);
+    }
+
+    #[test]
+    fn test_put_timeout_retries_on_next_node() {
+        let mut node = TimedNode::new();
+        let (key, pref) = key_coordinated_by_a();
+        let sent = put_reqs(&node.process(client_put(&key, 1)));
+        assert_eq!(sent.len(), 3);
+        put_rsp(&mut node, "nodeA", sent[0].1);
+
+        assert!(put_reqs(&node.advance(TIMEOUT - Duration::from_millis(1))).is_empty(), "not due yet");
+        assert_eq!(node.node.metrics().replica_timeouts, 0);
+        let retries = put_reqs(&node.advance(Duration::from_millis(1)));
+        assert_eq!(node.node.metrics().replica_timeouts, 2);
+        assert!(!node.node.stats().failed.contains(&"nodeA".to_string()), "a replica that answered is not suspected");
+        assert!(!retries.is_empty());
+        assert!(retries.iter().all(|(to, msg_id, _)| !pref.contains(to) && sent.iter().all(|(_, asked, _)| asked != msg_id)), "retries are new asks to other nodes");
+        for silent in &pref[1..] { assert!(node.node.stats().failed.contains(silent)); }
+
+        // an answer after its timeout is dropped before the node
+        put_rsp(&mut node, &pref[1], sent[1].1);
+        assert!(node.node.stats().failed.contains(&pref[1]));
+    }
+
+    #[test]
+    fn test_failed_nodes_are_pinged_and_recover() {
+        let mut node = TimedNode::new();
+        let (key, pref) = key_coordinated_by_a();
+        node.process(client_put(&key, 1));
+        assert!(pings(&node.advance(TIMEOUT)).is_empty());
+        assert!(!node.node.stats().failed.is_empty());
+
+        // pings go out once the ping interval has passed since the last round
+        let pinged = pings(&node.advance(Duration::from_millis(1000) - TIMEOUT));
+        assert!(pref[1..].iter().all(|n| pinged.contains(n)));
+        assert!(pings(&tick(&mut node.node)).is_empty());
+
+        for n in pinged { node.process(DynamoNodeIn::NodeToNode(NodeToNode::PingRsp { from: n, to: "nodeA".to_string() })); }
+        assert!(node.node.stats().failed.is_empty());
+    }
+
+    #[test]
+    fn test_put_to_failed_replica_carries_handoff_hint() {
+        let mut node = TimedNode::new();
+        let (key, pref) = key_coordinated_by_a();
+        let ask_to = |sent: &[(String, CorrelationId, Option<Vec<String>>)], node: &str| sent.iter().find(|(to, _, _)| to == node).unwrap().1;
+        let sent = put_reqs(&node.process(client_put(&key, 1)));
+        // nodeA and the second replica answer, the third never does
+        for from in ["nodeA", pref[1].as_str()] { put_rsp(&mut node, from, ask_to(&sent, from)); }
+        let sent2 = put_reqs(&node.process(client_put(&key, 2)));
+        for from in ["nodeA", pref[1].as_str()] { put_rsp(&mut node, from, ask_to(&sent2, from)); }
+        node.advance(TIMEOUT);
+        assert!(node.node.stats().failed.is_empty(), "completed writes ignore their timeouts");
+
+        // the other replicas go silent on a write that is still waiting for quorum
+        let sent3 = put_reqs(&node.process(client_put(&key, 3)));
+        put_rsp(&mut node, "nodeA", ask_to(&sent3, "nodeA"));
+        node.advance(TIMEOUT);
+        assert!(pref[1..].iter().all(|n| node.node.stats().failed.contains(n)));
+        // the second one is back before the next write
+        node.process(DynamoNodeIn::NodeToNode(NodeToNode::PingRsp { from: pref[1].clone(), to: "nodeA".to_string() }));
+        assert_eq!(node.node.stats().failed, vec![pref[2].clone()]);
+
+        let sent = put_reqs(&node.process(client_put(&key, 4)));
+        assert!(!sent.iter().any(|(to, _, _)| *to == pref[2]));
+        let hinted: Vec<&(String, CorrelationId, Option<Vec<String>>)> = sent.iter().filter(|(_, _, h)| h.is_some()).collect();
+        assert!(!hinted.is_empty());
+        assert!(hinted.iter().all(|(_, _, h)| h.as_ref().unwrap().contains(&pref[2])));
+    }
+}
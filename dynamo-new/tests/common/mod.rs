@@ -0,0 +1,34 @@
+// Fixtures shared by the integration tests; each test binary uses only some of them
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use dynamo_new::messages::{DynamoNodeIn, DynamoNodeOut, NodeToNode};
+use dynamo_new::node::DynamoNode;
+use dynamo_new::vector_clock::VectorClock;
+use dynamo_new::versioned_value::{VersionedValue, VersionedValues};
+use reactor_actor::ActorProcess;
+
+pub fn three_nodes() -> Vec<String> {
+    vec!["nodeA".to_string(), "nodeB".to_string(), "nodeC".to_string()]
+}
+
+// One sibling per (value, writer), each written once by its writer
+pub fn versions(values: &[(&str, &str)]) -> VersionedValues {
+    let mut vs = VersionedValues::new();
+    for (value, writer) in values {
+        let mut vc = VectorClock::new();
+        vc.increment(writer);
+        vs.add_version(VersionedValue::new(value.to_string(), vc));
+    }
+    vs
+}
+
+// Unique per test binary run, so parallel runs don't share files
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("dynamo_test_{}_{}", std::process::id(), name))
+}
+
+// Any message makes the node look at its clock; a stats request changes nothing else
+pub fn tick(node: &mut DynamoNode) -> Vec<DynamoNodeOut> {
+    node.process(DynamoNodeIn::NodeToNode(NodeToNode::StatsReq { from: "test".to_string(), to: "nodeA".to_string() }))
+}
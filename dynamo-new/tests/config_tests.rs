@@ -0,0 +1,138 @@
+// Config Tests
+// DynamoConfig parsing from TOML and actor payloads, validation, and applying it to a node
+
+mod common;
+use common::{three_nodes, temp_path};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use dynamo_new::clock::ManualClock;
+use dynamo_new::config::{ConfigError, DynamoConfig};
+use dynamo_new::messages::{ClientToNode, DynamoNodeIn, DynamoNodeOut, GetTimeout, NodeToNode};
+use dynamo_new::node::DynamoNode;
+use reactor_actor::{ActorProcess, TimerCmd, timer_channel};
+
+fn valid() -> DynamoConfig {
+    DynamoConfig { node_id: "nodeA".to_string(), nodes: three_nodes(), ..DynamoConfig::default() }
+}
+
+fn payload(json: serde_json::Value) -> HashMap<String, serde_json::Value> {
+    serde_json::from_value(json).unwrap()
+}
+
+// The field a validation error names, panicking on any other outcome
+fn invalid_field(cfg: &DynamoConfig) -> &'static str {
+    match cfg.validate() {
+        Err(ConfigError::Invalid { field, .. }) => field,
+        other => panic!("expected a validation error, got {:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_uses_quorum_names() {
+        let cfg = DynamoConfig::from_toml_str(r#"
+            node_id = "nodeA"
+            nodes = ["nodeA", "nodeB", "nodeC"]
+            N = 3
+            W = 1
+            R = 3
+            timeout_ms = 250
+        "#).unwrap();
+        assert_eq!((cfg.n, cfg.w, cfg.r, cfg.t), (3, 1, 3, 10));
+        assert_eq!(cfg.timeout_ms, 250);
+        assert_eq!(cfg.ping_interval_ms, DynamoConfig::default().ping_interval_ms, "unset keys keep their defaults");
+        cfg.validate().unwrap();
+    }
+
+    #[test]
+    fn test_unknown_and_mistyped_keys_rejected() {
+        let err = DynamoConfig::from_toml_str("timeout = 10").unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { .. }), "{:?}", err);
+        assert!(err.to_string().contains("timeout"), "{}", err);
+
+        let err = DynamoConfig::from_payload(payload(serde_json::json!({ "nodes": ["nodeA"], "W": "two" })), "nodeA").unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { .. }), "{:?}", err);
+        assert!(err.to_string().contains("actor payload"), "{}", err);
+    }
+
+    #[test]
+    fn test_payload_overrides_config_file() {
+        let path = temp_path("cluster.toml");
+        std::fs::write(&path, "nodes = [\"nodeA\", \"nodeB\", \"nodeC\"]\nW = 3\ntimeout_ms = 300\n").unwrap();
+        let cfg = DynamoConfig::from_payload(payload(serde_json::json!({ "config_path": path.to_str().unwrap(), "W": 1 })), "nodeB").unwrap();
+        assert_eq!(cfg.nodes, three_nodes());
+        assert_eq!(cfg.w, 1, "payload wins over the file");
+        assert_eq!(cfg.timeout_ms, 300);
+        assert_eq!(cfg.node_id, "nodeB", "node_id defaults to the actor address");
+        cfg.validate().unwrap();
+
+        let cfg = DynamoConfig::from_payload(payload(serde_json::json!({ "node_id": "nodeC", "nodes": three_nodes() })), "actor1").unwrap();
+        assert_eq!(cfg.node_id, "nodeC");
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_missing_config_file() {
+        let path = temp_path("missing.toml");
+        let err = DynamoConfig::from_payload(payload(serde_json::json!({ "config_path": path.to_str().unwrap() })), "nodeA").unwrap_err();
+        assert!(matches!(err, ConfigError::Io { .. }), "{:?}", err);
+        assert!(err.to_string().contains("missing.toml"), "{}", err);
+
+        let err = DynamoConfig::from_payload(payload(serde_json::json!({ "config_path": 3 })), "nodeA").unwrap_err();
+        assert!(matches!(err, ConfigError::Invalid { field: "config_path", .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_validation_names_the_field() {
+        valid().validate().unwrap();
+        assert_eq!(invalid_field(&DynamoConfig::default()), "node_id");
+        assert_eq!(invalid_field(&DynamoConfig { nodes: vec![], ..valid() }), "nodes");
+        assert_eq!(invalid_field(&DynamoConfig { nodes: vec!["nodeA".to_string(), "nodeB".to_string(), "nodeA".to_string()], ..valid() }), "nodes");
+        assert_eq!(invalid_field(&DynamoConfig { node_id: "nodeD".to_string(), ..valid() }), "node_id");
+        assert_eq!(invalid_field(&DynamoConfig { n: 4, ..valid() }), "N");
+        assert_eq!(invalid_field(&DynamoConfig { n: 2, w: 3, ..valid() }), "W");
+        assert_eq!(invalid_field(&DynamoConfig { r: 0, ..valid() }), "R");
+        assert_eq!(invalid_field(&DynamoConfig { t: 0, ..valid() }), "T");
+        assert_eq!(invalid_field(&DynamoConfig { timeout_ms: 0, ..valid() }), "timeout_ms");
+        assert_eq!(invalid_field(&DynamoConfig { sync_batch: 0, ..valid() }), "sync_batch");
+        assert_eq!(invalid_field(&DynamoConfig { read_repair_chance: 1.5, ..valid() }), "read_repair_chance");
+
+        let err = DynamoConfig { n: 2, w: 3, ..valid() }.validate().unwrap_err();
+        assert_eq!(err.to_string(), "invalid `W`: must be between 1 and N (2), got 3");
+    }
+
+    #[test]
+    fn test_node_applies_timeouts() {
+        let clock = ManualClock::new();
+        let cfg = DynamoConfig { timeout_ms: 10, ping_interval_ms: 50, ..valid() };
+        let (timers, mut queue) = timer_channel();
+        let mut node = DynamoNode::from_config(&cfg, Arc::new(clock.clone())).with_timers(timers);
+        node.process(DynamoNodeIn::ClientToNode(ClientToNode::ClientGet { key: "k".to_string(), client_addr: "client1".to_string(), request_id: 1 }));
+
+        let timeouts: Vec<(Duration, DynamoNodeIn)> = std::iter::from_fn(|| queue.try_next()).filter_map(|cmd| match cmd {
+            TimerCmd::Set{ delay, msg, ask: Some(_), .. } => Some((delay, msg)),
+            _ => None,
+        }).collect();
+        assert_eq!(timeouts.len(), 3);
+        for (delay, msg) in timeouts {
+            assert_eq!(delay, Duration::from_millis(10));
+            assert!(matches!(msg, DynamoNodeIn::GetTimeout(GetTimeout{ .. })));
+            node.process(msg);
+        }
+        let failed = node.stats().failed;
+        assert!(!failed.is_empty(), "unanswered replicas time out after timeout_ms");
+
+        clock.advance(Duration::from_millis(50));
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::StatsReq { from: "nodeB".to_string(), to: "nodeA".to_string() }));
+        let pinged: Vec<&String> = out.iter().filter_map(|m| match m {
+            DynamoNodeOut::NodeToNode(NodeToNode::PingReq{ to, .. }) => Some(to),
+            _ => None,
+        }).collect();
+        assert_eq!(pinged.len(), failed.len(), "failed nodes are pinged every ping_interval_ms");
+    }
+}
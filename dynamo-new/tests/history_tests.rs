@@ -0,0 +1,127 @@
+// History and Checker Tests
+// Recording client histories to JSONL and checking hand-built histories for anomalies
+
+use dynamo_new::checker::{check, Anomaly, CheckOptions, OpRef};
+use dynamo_new::history::{self, HistoryEvent, HistoryRecorder, OpKind};
+use dynamo_new::messages::{ClientToNode, NodeToClient};
+use dynamo_new::session::Guarantee;
+use dynamo_new::vector_clock::VectorClock;
+
+fn vc(entries: &[(&str, u64)]) -> VectorClock {
+    let mut c = VectorClock::new();
+    for (n, v) in entries { c.update(n, *v); }
+    c
+}
+
+fn put(client: &str, request_id: u64, key: &str, value: &str, context: Vec<VectorClock>, clock: VectorClock, (invoke_us, complete_us): (u64, u64)) -> Vec<HistoryEvent> {
+    vec![
+        HistoryEvent::Invoke { client: client.to_string(), request_id, op: OpKind::Put, key: key.to_string(), value: Some(value.to_string()), context, ts_us: invoke_us },
+        HistoryEvent::Complete { client: client.to_string(), request_id, op: OpKind::Put, key: key.to_string(), values: vec![], clocks: vec![clock], ts_us: complete_us },
+    ]
+}
+
+fn get(client: &str, request_id: u64, key: &str, returned: &[(&str, VectorClock)], (invoke_us, complete_us): (u64, u64)) -> Vec<HistoryEvent> {
+    vec![
+        HistoryEvent::Invoke { client: client.to_string(), request_id, op: OpKind::Get, key: key.to_string(), value: None, context: vec![], ts_us: invoke_us },
+        HistoryEvent::Complete { client: client.to_string(), request_id, op: OpKind::Get, key: key.to_string(), values: returned.iter().map(|(v, _)| v.to_string()).collect(), clocks: returned.iter().map(|(_, c)| c.clone()).collect(), ts_us: complete_us },
+    ]
+}
+
+fn op_ref(client: &str, request_id: u64) -> OpRef {
+    OpRef { client: client.to_string(), request_id }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use super::*;
+
+    #[test]
+    fn test_recorder_round_trips_through_jsonl() {
+        let path = std::env::temp_dir().join(format!("dynamo_history_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let mut rec = HistoryRecorder::to_file(&path).unwrap();
+            rec.invoke("c1", &ClientToNode::ClientPut { key: "k".to_string(), value: "v1".to_string(), metadata: vec![vc(&[("c1", 1)])], client_addr: "c1".to_string(), request_id: 1 });
+            rec.complete("c1", &NodeToClient::ClientPutRsp { key: "k".to_string(), request_id: 1, clock: vc(&[("c1", 1), ("nodeA", 1)]), client_addr: "c1".to_string() });
+            assert!(rec.events().is_empty(), "file recorders do not buffer events");
+        }
+        let events = history::load(&[&path]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(&events[0], HistoryEvent::Invoke { op: OpKind::Put, value: Some(v), context, .. } if v == "v1" && context.len() == 1));
+        assert!(matches!(&events[1], HistoryEvent::Complete { clocks, .. } if clocks[0] == vc(&[("c1", 1), ("nodeA", 1)])));
+    }
+
+    #[test]
+    fn test_clean_history_passes() {
+        let mut h = put("c1", 1, "k", "v1", vec![], vc(&[("nodeA", 1)]), (0, 10));
+        h.extend(put("c1", 2, "k", "v2", vec![vc(&[("nodeA", 1)])], vc(&[("nodeA", 2)]), (20, 30)));
+        h.extend(get("c2", 1, "k", &[("v2", vc(&[("nodeA", 2)]))], (40, 50)));
+        let report = check(&h, &CheckOptions::default());
+        assert_eq!((report.ops, report.incomplete), (3, 0));
+        assert!(report.is_ok(), "{:?}", report.anomalies);
+    }
+
+    #[test]
+    fn test_lost_write_after_settle_time() {
+        let mut h = put("c1", 1, "k", "v1", vec![], vc(&[("nodeA", 1)]), (0, 10));
+        h.extend(get("c2", 1, "k", &[], (1_000, 1_010)));
+        let report = check(&h, &CheckOptions::default());
+        assert_eq!(report.anomalies, vec![Anomaly::LostWrite { key: "k".to_string(), write: op_ref("c1", 1), read: op_ref("c2", 1) }]);
+
+        // still converging: reads within the settle window may miss the write
+        let report = check(&h, &CheckOptions { settle_after_us: 5_000, ..CheckOptions::default() });
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn test_siblings_must_come_from_concurrent_writes() {
+        // two blind writes are concurrent, so siblings are expected
+        let mut h = put("c1", 1, "k", "a", vec![], vc(&[("nodeA", 1)]), (0, 10));
+        h.extend(put("c2", 1, "k", "b", vec![], vc(&[("nodeB", 1)]), (0, 10)));
+        h.extend(get("c3", 1, "k", &[("a", vc(&[("nodeA", 1)])), ("b", vc(&[("nodeB", 1)]))], (20, 30)));
+        assert!(check(&h, &CheckOptions::default()).is_ok());
+
+        // c2 wrote with a context that covered "a", yet the cluster kept both
+        let mut h = put("c1", 1, "k", "a", vec![], vc(&[("nodeA", 1)]), (0, 10));
+        h.extend(put("c2", 1, "k", "b", vec![vc(&[("nodeA", 1)])], vc(&[("nodeB", 1)]), (20, 30)));
+        h.extend(get("c3", 1, "k", &[("a", vc(&[("nodeA", 1)])), ("b", vc(&[("nodeB", 1)]))], (40, 50)));
+        let report = check(&h, &CheckOptions::default());
+        assert!(matches!(&report.anomalies[..], [Anomaly::OrderedSiblings { values, .. }] if values == &("a".to_string(), "b".to_string())));
+    }
+
+    #[test]
+    fn test_unknown_value_is_reported() {
+        let h = get("c1", 1, "k", &[("ghost", vc(&[("nodeA", 1)]))], (0, 10));
+        let report = check(&h, &CheckOptions::default());
+        assert!(matches!(&report.anomalies[..], [Anomaly::UnknownValue { value, .. }] if value == "ghost"));
+    }
+
+    #[test]
+    fn test_session_violations_are_checked_per_client() {
+        // c1's read misses its own write on a lagging replica, but the write is not "lost" yet
+        let mut h = put("c1", 1, "k", "v1", vec![], vc(&[("nodeA", 1)]), (0, 10));
+        h.extend(get("c1", 2, "k", &[], (20, 30)));
+        let opts = CheckOptions { settle_after_us: 1_000, ..CheckOptions::default() };
+        let report = check(&h, &opts);
+        assert_eq!(report.anomalies, vec![Anomaly::Session { key: "k".to_string(), guarantee: Guarantee::ReadYourWrites, read: op_ref("c1", 2) }]);
+        assert!(check(&h, &CheckOptions { sessions: false, ..opts.clone() }).is_ok());
+
+        // another client has no session with that write
+        let mut h = put("c1", 1, "k", "v1", vec![], vc(&[("nodeA", 1)]), (0, 10));
+        h.extend(get("c2", 1, "k", &[], (20, 30)));
+        assert!(check(&h, &opts).is_ok());
+    }
+
+    #[test]
+    fn test_retried_requests_pair_with_latest_invoke() {
+        let mut h = put("c1", 1, "k", "v1", vec![], vc(&[("nodeA", 1)]), (0, 10));
+        // GET sent twice with the same id (session retry); only the second completes
+        h.push(HistoryEvent::Invoke { client: "c1".to_string(), request_id: 2, op: OpKind::Get, key: "k".to_string(), value: None, context: vec![], ts_us: 20 });
+        h.extend(get("c1", 2, "k", &[("v1", vc(&[("nodeA", 1)]))], (500, 510)));
+        h.push(HistoryEvent::Invoke { client: "c1".to_string(), request_id: 3, op: OpKind::Get, key: "k".to_string(), value: None, context: vec![], ts_us: 600 });
+        let report = check(&h, &CheckOptions::default());
+        assert_eq!((report.ops, report.incomplete), (2, 1));
+        assert!(report.is_ok(), "{:?}", report.anomalies);
+    }
+}
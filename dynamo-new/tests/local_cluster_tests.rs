@@ -0,0 +1,99 @@
+// Local Cluster Tests
+// DynamoNode and bench client actors talking over localhost TCP through the reactor runtime
+
+use std::time::Duration;
+use dynamo_new::bench_client::{BenchClient, ClientReport};
+use dynamo_new::config::DynamoConfig;
+use dynamo_new::local_cluster::LocalCluster;
+use dynamo_new::workload::{OpType, WorkloadConfig};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+async fn start_nodes(cluster: &mut LocalCluster, count: usize) -> Vec<String> {
+    let nodes: Vec<String> = (0..count).map(|i| format!("node{}", i)).collect();
+    for name in nodes.iter() {
+        let n = count.min(3);
+        cluster.spawn_node(DynamoConfig { node_id: name.clone(), nodes: nodes.clone(), n, w: n.min(2), r: n.min(2), ..DynamoConfig::default() }).await.unwrap();
+    }
+    nodes
+}
+
+async fn next_report(rx: &mut UnboundedReceiver<ClientReport>) -> ClientReport {
+    tokio::time::timeout(Duration::from_secs(30), rx.recv()).await.expect("client did not finish").expect("client exited without reporting")
+}
+
+#[cfg(test)]
+mod local_cluster_tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_bench_clients_over_tcp() {
+        let mut cluster = LocalCluster::new();
+        let nodes = start_nodes(&mut cluster, 4).await;
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        for id in ["bench0", "bench1"] {
+            let cfg = WorkloadConfig { operations: 50, max_outstanding: 4, seed: Some(3), ..WorkloadConfig::default() };
+            let client = BenchClient::new(id.to_string(), nodes.clone(), cfg).with_report(tx.clone());
+            cluster.spawn_bench_client(id, client).await.unwrap();
+        }
+        assert_eq!(cluster.addrs().len(), 6);
+        assert_eq!(cluster.addrs().values().map(|a| a.port()).collect::<std::collections::HashSet<_>>().len(), 6, "every actor has its own port");
+
+        let mut clients = vec![];
+        for _ in 0..2 {
+            let report = next_report(&mut rx).await;
+            let count = |op| report.stats.get(&op).map(|s| s.summary().count).unwrap_or(0);
+            assert_eq!(count(OpType::Read) + count(OpType::Update), 50);
+            assert_eq!(report.unknown_responses, 0);
+            clients.push(report.client_id);
+        }
+        clients.sort();
+        assert_eq!(clients, vec!["bench0", "bench1"]);
+        cluster.stop(Duration::from_secs(5)).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_open_loop_client_over_tcp() {
+        let mut cluster = LocalCluster::new();
+        let nodes = start_nodes(&mut cluster, 3).await;
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let cfg = WorkloadConfig { operations: 40, target_ops_per_sec: 200.0, max_outstanding: 4, seed: Some(5), ..WorkloadConfig::default() };
+        let client = BenchClient::new("bench0".to_string(), nodes, cfg).with_report(tx);
+        cluster.spawn_bench_client("bench0", client).await.unwrap();
+
+        let report = next_report(&mut rx).await;
+        assert_eq!(report.stats.values().map(|s| s.summary().count).sum::<u64>(), 40);
+        assert!(report.elapsed >= Duration::from_millis(190), "40 arrivals at 200/s span {:?}", report.elapsed);
+        cluster.stop(Duration::from_secs(5)).await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_timeouts_fire_on_idle_cluster() {
+        // node3 is on the ring but never started; PUTs it should store only complete once the
+        // coordinator times it out and writes to a stand-in, with no other traffic to wake it
+        let mut cluster = LocalCluster::new();
+        let nodes: Vec<String> = (0..4).map(|i| format!("node{}", i)).collect();
+        for name in nodes.iter().take(3) {
+            cluster.spawn_node(DynamoConfig { node_id: name.clone(), nodes: nodes.clone(), n: 3, w: 3, r: 1, timeout_ms: 200, ..DynamoConfig::default() }).await.unwrap();
+        }
+        let live: Vec<String> = nodes[..3].to_vec();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let cfg = WorkloadConfig { operations: 10, read_proportion: 0.0, update_proportion: 1.0, max_outstanding: 1, seed: Some(7), ..WorkloadConfig::default() };
+        let client = BenchClient::new("bench0".to_string(), live, cfg).with_report(tx);
+        cluster.spawn_bench_client("bench0", client).await.unwrap();
+
+        let report = next_report(&mut rx).await;
+        assert_eq!(report.stats.get(&OpType::Update).map(|s| s.summary().count), Some(10));
+        cluster.stop(Duration::from_secs(5)).await;
+    }
+
+    #[tokio::test]
+    async fn test_invalid_spawns_rejected() {
+        let mut cluster = LocalCluster::new();
+        let nodes = start_nodes(&mut cluster, 1).await;
+        let err = cluster.spawn_node(DynamoConfig { node_id: "node0".to_string(), nodes: nodes.clone(), n: 1, w: 1, r: 1, ..DynamoConfig::default() }).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        let err = cluster.spawn_node(DynamoConfig { node_id: "node1".to_string(), nodes, n: 1, w: 1, r: 1, ..DynamoConfig::default() }).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput, "node1 is not in nodes");
+        cluster.stop(Duration::from_secs(5)).await;
+    }
+}
@@ -0,0 +1,129 @@
+// Node Metrics Tests
+// Drives a single DynamoNode as coordinator and checks the counters, histograms and Prometheus export
+
+mod common;
+use common::{three_nodes};
+
+use std::time::Duration;
+use dynamo_new::vector_clock::VectorClock;
+use dynamo_new::versioned_value::{VersionedValue, VersionedValues};
+use std::sync::Arc;
+use dynamo_new::clock::SystemClock;
+use dynamo_new::node::DynamoNode;
+use dynamo_new::messages::{ClientToNode, DynamoNodeIn, DynamoNodeOut, NodeToClient, NodeToNode};
+use dynamo_new::metrics::Histogram;
+use reactor_actor::{ActorProcess, CorrelationId};
+
+// The ask of the PutReq sent to `node`
+fn put_msg_id(out: &[DynamoNodeOut], node: &str) -> CorrelationId {
+    out.iter().find_map(|m| match m {
+        DynamoNodeOut::NodeToNode(NodeToNode::PutReq{ to, msg_id, .. }) if to == node => Some(*msg_id),
+        _ => None,
+    }).expect("coordinator should fan out PutReq")
+}
+
+// The ask of the GetReq sent to `node`
+fn get_msg_id(out: &[DynamoNodeOut], node: &str) -> CorrelationId {
+    out.iter().find_map(|m| match m {
+        DynamoNodeOut::NodeToNode(NodeToNode::GetReq{ to, msg_id, .. }) if to == node => Some(*msg_id),
+        _ => None,
+    }).expect("coordinator should fan out GetReq")
+}
+
+#[cfg(test)]
+mod metrics_tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_and_quantiles() {
+        let mut h = Histogram::default();
+        for _ in 0..98 { h.observe(Duration::from_micros(800)); }
+        h.observe(Duration::from_millis(40));
+        h.observe(Duration::from_secs(10));
+
+        assert_eq!(h.count, 100);
+        assert_eq!(h.quantile_ms(0.5), 1.0);
+        assert_eq!(h.quantile_ms(0.99), 50.0);
+        // overflow bucket reports the last finite bound
+        assert_eq!(h.quantile_ms(1.0), 5000.0);
+    }
+
+    #[test]
+    fn test_put_quorum_updates_metrics() {
+        let mut node = DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock));
+        let out = node.process(DynamoNodeIn::ClientToNode(ClientToNode::ClientPut {
+            key: "k".to_string(), value: "v".to_string(), metadata: vec![], client_addr: "client1".to_string(), request_id: 7,
+        }));
+        assert_eq!(node.metrics().puts_coordinated, 1);
+
+        node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutRsp { from: "nodeA".to_string(), to: "nodeA".to_string(), msg_id: put_msg_id(&out, "nodeA") }));
+        assert_eq!(node.metrics().puts_completed, 0);
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutRsp { from: "nodeB".to_string(), to: "nodeA".to_string(), msg_id: put_msg_id(&out, "nodeB") }));
+        assert!(out.iter().any(|m| matches!(m, DynamoNodeOut::NodeToClient(NodeToClient::ClientPutRsp { request_id: 7, .. }))));
+
+        let m = node.metrics();
+        assert_eq!(m.puts_completed, 1);
+        assert_eq!(m.put_latency.count, 1);
+        assert_eq!(m.put_quorum_wait.count, 1);
+        assert_eq!(m.get_latency.count, 0);
+    }
+
+    #[test]
+    fn test_get_with_siblings_counts_conflict_and_repair() {
+        let mut node = DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock));
+        let out = node.process(DynamoNodeIn::ClientToNode(ClientToNode::ClientGet {
+            key: "k".to_string(), client_addr: "client1".to_string(), request_id: 1,
+        }));
+
+        let mut vc1 = VectorClock::new();
+        vc1.increment("nodeA");
+        let mut vc2 = VectorClock::new();
+        vc2.increment("nodeB");
+        let mut from_a = VersionedValues::new();
+        from_a.add_version(VersionedValue::new("a".to_string(), vc1));
+        let mut from_b = VersionedValues::new();
+        from_b.add_version(VersionedValue::new("b".to_string(), vc2));
+
+        node.process(DynamoNodeIn::NodeToNode(NodeToNode::GetRsp { from: "nodeA".to_string(), to: "nodeA".to_string(), key: "k".to_string(), values: from_a, msg_id: get_msg_id(&out, "nodeA") }));
+        node.process(DynamoNodeIn::NodeToNode(NodeToNode::GetRsp { from: "nodeB".to_string(), to: "nodeA".to_string(), key: "k".to_string(), values: from_b, msg_id: get_msg_id(&out, "nodeB") }));
+
+        let m = node.metrics();
+        assert_eq!(m.gets_completed, 1);
+        assert_eq!(m.gets_with_conflict, 1);
+        // each replica is missing the other's sibling
+        assert_eq!(m.read_repairs, 2);
+        assert_eq!(m.conflict_rate(), 1.0);
+    }
+
+    #[test]
+    fn test_forwarded_requests_are_counted() {
+        let nodes: Vec<String> = (0..8).map(|i| format!("node{}", i)).collect();
+        let mut node = DynamoNode::new("node0".to_string(), nodes, 1, 1, 1, 10, Arc::new(SystemClock));
+        for i in 0..20 {
+            node.process(DynamoNodeIn::ClientToNode(ClientToNode::ClientGet {
+                key: format!("key{}", i), client_addr: "client1".to_string(), request_id: i,
+            }));
+        }
+        let m = node.metrics();
+        assert_eq!(m.gets_forwarded + m.gets_coordinated, 20);
+        assert!(m.gets_forwarded > 0);
+    }
+
+    #[test]
+    fn test_stats_request_and_prometheus_export() {
+        let mut node = DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock));
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::StatsReq { from: "admin".to_string(), to: "nodeA".to_string() }));
+        assert_eq!(out.len(), 1);
+        let stats = match &out[0] {
+            DynamoNodeOut::NodeToNode(NodeToNode::StatsRsp { to, stats, .. }) => { assert_eq!(to, "admin"); stats.clone() }
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(stats.node_id, "nodeA");
+
+        let text = stats.to_prometheus();
+        assert!(text.contains("# TYPE dynamo_put_latency_ms histogram"));
+        assert!(text.contains("dynamo_put_latency_ms_bucket{node=\"nodeA\",le=\"+Inf\"} 0"));
+        assert!(text.contains("dynamo_read_repairs_total{node=\"nodeA\"} 0"));
+        assert!(text.contains("dynamo_keys{node=\"nodeA\"} 0"));
+    }
+}
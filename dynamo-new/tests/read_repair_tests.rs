@@ -0,0 +1,92 @@
+// Read Repair Tests
+// Late responses after the R quorum and probabilistic full-replica repair
+
+mod common;
+use common::{three_nodes, versions};
+
+use dynamo_new::versioned_value::VersionedValues;
+use std::collections::HashMap;
+use std::sync::Arc;
+use dynamo_new::clock::SystemClock;
+use dynamo_new::node::{DynamoNode, ReadRepairConfig};
+use dynamo_new::messages::{ClientToNode, DynamoNodeIn, DynamoNodeOut, NodeToNode, RepairKind};
+use reactor_actor::{ActorProcess, CorrelationId};
+
+// Starts a GET at nodeA and answers it from nodeA and nodeB, reaching R=2; returns the ask sent to each replica
+fn get_with_quorum(node: &mut DynamoNode, from_a: VersionedValues, from_b: VersionedValues) -> HashMap<String, CorrelationId> {
+    let out = node.process(DynamoNodeIn::ClientToNode(ClientToNode::ClientGet { key: "k".to_string(), client_addr: "client1".to_string(), request_id: 1 }));
+    let asks: HashMap<String, CorrelationId> = out.iter().filter_map(|m| match m {
+        DynamoNodeOut::NodeToNode(NodeToNode::GetReq{ to, msg_id, .. }) => Some((to.clone(), *msg_id)),
+        _ => None,
+    }).collect();
+    node.process(get_rsp("nodeA", from_a, asks["nodeA"]));
+    node.process(get_rsp("nodeB", from_b, asks["nodeB"]));
+    asks
+}
+
+fn get_rsp(from: &str, values: VersionedValues, msg_id: CorrelationId) -> DynamoNodeIn {
+    DynamoNodeIn::NodeToNode(NodeToNode::GetRsp { from: from.to_string(), to: "nodeA".to_string(), key: "k".to_string(), values, msg_id })
+}
+
+fn repairs_to(out: &[DynamoNodeOut]) -> Vec<(String, String)> {
+    let mut r: Vec<(String, String)> = out.iter().flat_map(|m| match m {
+        DynamoNodeOut::NodeToNode(NodeToNode::RepairReq{ to, values, kind: RepairKind::ReadRepair, .. }) => values.versions.iter().map(|v| (to.clone(), v.value.clone())).collect(),
+        _ => vec![],
+    }).collect();
+    r.sort();
+    r
+}
+
+#[cfg(test)]
+mod read_repair_tests {
+    use super::*;
+
+    #[test]
+    fn test_late_stale_replica_is_repaired() {
+        let mut node = DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock));
+        let asks = get_with_quorum(&mut node, versions(&[("v1", "nodeA")]), versions(&[("v1", "nodeA")]));
+
+        let out = node.process(get_rsp("nodeC", VersionedValues::new(), asks["nodeC"]));
+        assert_eq!(repairs_to(&out), vec![("nodeC".to_string(), "v1".to_string())]);
+        assert_eq!(node.metrics().late_get_responses, 1);
+        assert_eq!(node.metrics().late_read_repairs, 1);
+
+        // duplicate late response does not trigger another repair
+        let out = node.process(get_rsp("nodeC", VersionedValues::new(), asks["nodeC"]));
+        assert!(repairs_to(&out).is_empty());
+    }
+
+    #[test]
+    fn test_late_replica_with_unseen_version_repairs_earlier_responders() {
+        let mut node = DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock));
+        let asks = get_with_quorum(&mut node, versions(&[("v1", "nodeA")]), versions(&[("v1", "nodeA")]));
+
+        let mut late = versions(&[("v1", "nodeA")]);
+        late.merge(&versions(&[("v2", "nodeC")]));
+        let out = node.process(get_rsp("nodeC", late, asks["nodeC"]));
+        assert_eq!(repairs_to(&out), vec![("nodeA".to_string(), "v2".to_string()), ("nodeB".to_string(), "v2".to_string())]);
+    }
+
+    #[test]
+    fn test_zero_window_drops_late_responses() {
+        let cfg = ReadRepairConfig { late_window_ms: 0, full_repair_chance: 0.0 };
+        let mut node = DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock)).with_read_repair(cfg);
+        let asks = get_with_quorum(&mut node, versions(&[("v1", "nodeA")]), versions(&[("v1", "nodeA")]));
+
+        let out = node.process(get_rsp("nodeC", VersionedValues::new(), asks["nodeC"]));
+        assert!(repairs_to(&out).is_empty());
+        assert_eq!(node.metrics().late_get_responses, 0);
+    }
+
+    #[test]
+    fn test_full_repair_reaches_non_responders() {
+        let cfg = ReadRepairConfig { late_window_ms: 0, full_repair_chance: 1.0 };
+        let mut node = DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock)).with_read_repair(cfg);
+        get_with_quorum(&mut node, versions(&[("v1", "nodeA")]), versions(&[("v1", "nodeA")]));
+
+        // the window has already closed; the next event sweeps it
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::PingReq { from: "nodeB".to_string(), to: "nodeA".to_string() }));
+        assert_eq!(repairs_to(&out), vec![("nodeC".to_string(), "v1".to_string())]);
+        assert_eq!(node.metrics().full_read_repairs, 1);
+    }
+}
@@ -0,0 +1,95 @@
+// Reconfigure Tests
+// Changing W/R and timing parameters on a live DynamoNode through the admin Reconfigure message
+
+mod common;
+use common::{three_nodes};
+
+use std::sync::Arc;
+use dynamo_new::clock::SystemClock;
+use dynamo_new::config::{Tuning, TuningUpdate};
+use dynamo_new::messages::{ClientToNode, DynamoNodeIn, DynamoNodeOut, NodeToClient, NodeToNode};
+use dynamo_new::node::DynamoNode;
+use reactor_actor::ActorProcess;
+
+fn node_a() -> DynamoNode {
+    DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock))
+}
+
+fn reconfigure(node: &mut DynamoNode, update: TuningUpdate, propagate: bool) -> Vec<DynamoNodeOut> {
+    node.process(DynamoNodeIn::NodeToNode(NodeToNode::Reconfigure { from: "admin".to_string(), to: "nodeA".to_string(), update, propagate }))
+}
+
+fn reconfigure_result(out: &[DynamoNodeOut]) -> Result<Tuning, String> {
+    out.iter().find_map(|m| match m {
+        DynamoNodeOut::NodeToNode(NodeToNode::ReconfigureRsp{ to, result, .. }) if to == "admin" => Some(result.clone()),
+        _ => None,
+    }).expect("node should answer the admin")
+}
+
+#[cfg(test)]
+mod reconfigure_tests {
+    use super::*;
+
+    #[test]
+    fn test_update_applies_and_shows_in_stats() {
+        let mut node = node_a();
+        let update = TuningUpdate { w: Some(1), timeout_ms: Some(300), sync_batch: Some(5), ..TuningUpdate::default() };
+        let tuning = reconfigure_result(&reconfigure(&mut node, update, false)).unwrap();
+        assert_eq!(tuning, Tuning { w: 1, r: 2, timeout_ms: 300, ping_interval_ms: 1000, sync_batch: 5 });
+        assert_eq!(node.stats().tuning, tuning);
+        let prom = node.stats().to_prometheus();
+        assert!(prom.contains("dynamo_write_quorum{node=\"nodeA\"} 1"), "{}", prom);
+        assert!(prom.contains("dynamo_timeout_ms{node=\"nodeA\"} 300"), "{}", prom);
+
+        // the next PUT completes on a single ack
+        let out = node.process(DynamoNodeIn::ClientToNode(ClientToNode::ClientPut {
+            key: "k".to_string(), value: "v".to_string(), metadata: vec![], client_addr: "client1".to_string(), request_id: 1,
+        }));
+        let seq = out.iter().find_map(|m| match m {
+            DynamoNodeOut::NodeToNode(NodeToNode::PutReq{ msg_id, .. }) => Some(*msg_id),
+            _ => None,
+        }).unwrap();
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutRsp { from: "nodeB".to_string(), to: "nodeA".to_string(), msg_id: seq }));
+        assert!(out.iter().any(|m| matches!(m, DynamoNodeOut::NodeToClient(NodeToClient::ClientPutRsp{ request_id: 1, .. }))));
+    }
+
+    #[test]
+    fn test_invalid_update_rejected() {
+        let mut node = node_a();
+        let before = node.stats().tuning;
+        let err = reconfigure_result(&reconfigure(&mut node, TuningUpdate { w: Some(4), timeout_ms: Some(100), ..TuningUpdate::default() }, false)).unwrap_err();
+        assert!(err.contains("`W`"), "{}", err);
+        assert_eq!(node.stats().tuning, before, "a rejected update changes nothing");
+
+        let err = reconfigure_result(&reconfigure(&mut node, TuningUpdate { ping_interval_ms: Some(0), ..TuningUpdate::default() }, false)).unwrap_err();
+        assert!(err.contains("ping_interval_ms"), "{}", err);
+    }
+
+    #[test]
+    fn test_propagate_forwards_to_every_other_node() {
+        let mut node = node_a();
+        let update = TuningUpdate { r: Some(3), ..TuningUpdate::default() };
+        let out = reconfigure(&mut node, update.clone(), true);
+        let mut forwarded: Vec<&str> = out.iter().filter_map(|m| match m {
+            DynamoNodeOut::NodeToNode(NodeToNode::Reconfigure{ from, to, update: u, propagate }) => {
+                assert_eq!((from.as_str(), u, *propagate), ("admin", &update, false), "replies go straight to the admin and are not forwarded again");
+                Some(to.as_str())
+            }
+            _ => None,
+        }).collect();
+        forwarded.sort();
+        assert_eq!(forwarded, vec!["nodeB", "nodeC"]);
+        assert_eq!(reconfigure_result(&out).unwrap().r, 3);
+
+        let out = reconfigure(&mut node, update, false);
+        assert!(!out.iter().any(|m| matches!(m, DynamoNodeOut::NodeToNode(NodeToNode::Reconfigure{ .. }))));
+    }
+
+    #[test]
+    fn test_update_uses_config_key_names() {
+        let update: TuningUpdate = serde_json::from_value(serde_json::json!({ "W": 1, "R": 3, "ping_interval_ms": 250 })).unwrap();
+        assert_eq!(update, TuningUpdate { w: Some(1), r: Some(3), ping_interval_ms: Some(250), ..TuningUpdate::default() });
+        assert!(serde_json::from_value::<TuningUpdate>(serde_json::json!({ "N": 5 })).is_err(), "N cannot change at runtime");
+        assert!(TuningUpdate::default().is_empty());
+    }
+}
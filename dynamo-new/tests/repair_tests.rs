@@ -0,0 +1,171 @@
+// Repair Acknowledgement Tests
+// RepairReq/RepairRsp for read repair, hinted-handoff replay and redistribution
+
+mod common;
+use common::{three_nodes, versions, tick};
+
+use std::time::Duration;
+use dynamo_new::vector_clock::VectorClock;
+use std::sync::Arc;
+use dynamo_new::clock::{ManualClock, SystemClock};
+use dynamo_new::node::DynamoNode;
+use dynamo_new::messages::{ClientToNode, DynamoNodeIn, DynamoNodeOut, NodeToNode, RepairKind, RepairTimeout};
+use reactor_actor::{ActorProcess, CorrelationId, Msg, TimerQueue, timer_channel};
+
+// GET at nodeA where nodeA and nodeB hold concurrent siblings, so both get repaired
+fn conflicting_get(node: &mut DynamoNode) -> Vec<DynamoNodeOut> {
+    let out = node.process(DynamoNodeIn::ClientToNode(ClientToNode::ClientGet { key: "k".to_string(), client_addr: "client1".to_string(), request_id: 1 }));
+    let ask = |node: &str| out.iter().find_map(|m| match m {
+        DynamoNodeOut::NodeToNode(NodeToNode::GetReq{ to, msg_id, .. }) if to == node => Some(*msg_id),
+        _ => None,
+    }).unwrap();
+    node.process(DynamoNodeIn::NodeToNode(NodeToNode::GetRsp { from: "nodeA".to_string(), to: "nodeA".to_string(), key: "k".to_string(), values: versions(&[("a", "nodeA")]), msg_id: ask("nodeA") }));
+    node.process(DynamoNodeIn::NodeToNode(NodeToNode::GetRsp { from: "nodeB".to_string(), to: "nodeA".to_string(), key: "k".to_string(), values: versions(&[("b", "nodeB")]), msg_id: ask("nodeB") }))
+}
+
+fn repair_reqs(out: &[DynamoNodeOut]) -> Vec<(String, CorrelationId, RepairKind)> {
+    let mut r: Vec<(String, CorrelationId, RepairKind)> = out.iter().filter_map(|m| match m {
+        DynamoNodeOut::NodeToNode(NodeToNode::RepairReq{ to, repair_id, kind, .. }) => Some((to.clone(), *repair_id, *kind)),
+        _ => None,
+    }).collect();
+    r.sort_by(|a, b| a.0.cmp(&b.0));
+    r
+}
+
+// nodeA with timers whose queue is never run, so the test fires the repair timeouts itself
+fn node_with_timers() -> (DynamoNode, TimerQueue<DynamoNodeIn>) {
+    let (timers, queue) = timer_channel();
+    (DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock)).with_timers(timers), queue)
+}
+
+fn time_out(node: &mut DynamoNode, repairs: &[(String, CorrelationId, RepairKind)]) -> Vec<DynamoNodeOut> {
+    repairs.iter().flat_map(|(_, repair_id, _)| node.process(DynamoNodeIn::RepairTimeout(RepairTimeout { repair_id: *repair_id }))).collect()
+}
+
+#[cfg(test)]
+mod repair_tests {
+    use super::*;
+
+    #[test]
+    fn test_replica_applies_repair_and_acks() {
+        let mut node = DynamoNode::new("nodeB".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock));
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::RepairReq {
+            from: "nodeA".to_string(), to: "nodeB".to_string(), key: "k".to_string(), values: versions(&[("v", "nodeA")]), repair_id: CorrelationId(42), kind: RepairKind::ReadRepair,
+        }));
+        assert_eq!(out.len(), 1);
+        assert!(matches!(&out[0], DynamoNodeOut::NodeToNode(NodeToNode::RepairRsp { to, repair_id: CorrelationId(42), .. }) if to == "nodeA"));
+        assert_eq!(node.stats().keys, 1);
+    }
+
+    #[test]
+    fn test_read_repairs_are_acknowledged() {
+        let mut node = DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock));
+        let repairs = repair_reqs(&conflicting_get(&mut node));
+        assert_eq!(repairs.len(), 2);
+        assert!(repairs.iter().all(|(_, _, kind)| *kind == RepairKind::ReadRepair));
+        assert_eq!(node.stats().pending_repairs, 2);
+
+        for (to, repair_id, _) in repairs {
+            node.process(DynamoNodeIn::NodeToNode(NodeToNode::RepairRsp { from: to, to: "nodeA".to_string(), repair_id }));
+        }
+        assert_eq!(node.metrics().repairs_acked, 2);
+        assert_eq!(node.stats().pending_repairs, 0);
+    }
+
+    #[test]
+    fn test_unacked_repair_is_retried_then_failed() {
+        let (mut node, _queue) = node_with_timers();
+        let first = repair_reqs(&conflicting_get(&mut node));
+        assert_eq!(first.len(), 2);
+
+        let retried = repair_reqs(&time_out(&mut node, &first));
+        assert_eq!(retried.iter().map(|(to, _, kind)| (to, kind)).collect::<Vec<_>>(), first.iter().map(|(to, _, kind)| (to, kind)).collect::<Vec<_>>());
+        assert!(retried.iter().all(|(_, id, _)| first.iter().all(|(_, old, _)| id != old)), "each attempt is a new ask");
+        assert_eq!(node.metrics().repairs_retried, 2);
+        // an attempt is retried once, however often its timeout comes in
+        assert!(time_out(&mut node, &first).is_empty());
+
+        let third = repair_reqs(&time_out(&mut node, &retried));
+        let out = time_out(&mut node, &third);
+        assert!(repair_reqs(&out).is_empty());
+        assert_eq!(node.metrics().repairs_failed, 2);
+        assert_eq!(node.stats().pending_repairs, 0);
+    }
+
+    #[test]
+    fn test_repair_rsp_answers_its_ask() {
+        let (mut node, _queue) = node_with_timers();
+        let (to, repair_id, _) = repair_reqs(&conflicting_get(&mut node)).remove(0);
+        let rsp = DynamoNodeIn::NodeToNode(NodeToNode::RepairRsp { from: to, to: "nodeA".to_string(), repair_id });
+        assert_eq!(rsp.reply_to(), Some(repair_id), "the actor settles the ask before the node sees the reply");
+        let req = DynamoNodeIn::NodeToNode(NodeToNode::RepairReq { from: "nodeB".to_string(), to: "nodeA".to_string(), key: "k".to_string(), values: versions(&[("v", "nodeB")]), repair_id, kind: RepairKind::ReadRepair });
+        assert_eq!(req.reply_to(), None);
+        node.process(rsp);
+        assert_eq!(node.metrics().repairs_acked, 1);
+    }
+
+    #[test]
+    fn test_repairs_time_out_on_the_clock_without_timers() {
+        let clock = ManualClock::new();
+        let mut node = DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(clock.clone()));
+        let first = repair_reqs(&conflicting_get(&mut node));
+        assert_eq!(first.len(), 2);
+        clock.advance(Duration::from_millis(799));
+        assert!(repair_reqs(&tick(&mut node)).is_empty(), "not due yet");
+
+        clock.advance(Duration::from_millis(1));
+        let retried = repair_reqs(&tick(&mut node));
+        assert_eq!(retried.iter().map(|(to, _, _)| to).collect::<Vec<_>>(), first.iter().map(|(to, _, _)| to).collect::<Vec<_>>());
+        // a reply to the attempt that timed out is dropped
+        let (to, repair_id, _) = first[0].clone();
+        node.process(DynamoNodeIn::NodeToNode(NodeToNode::RepairRsp { from: to, to: "nodeA".to_string(), repair_id }));
+        assert_eq!(node.metrics().repairs_acked, 0);
+
+        clock.advance(Duration::from_millis(800));
+        assert_eq!(repair_reqs(&tick(&mut node)).len(), 2);
+        clock.advance(Duration::from_millis(800));
+        assert!(repair_reqs(&tick(&mut node)).is_empty());
+        assert_eq!(node.metrics().repairs_failed, 2);
+        assert_eq!(node.stats().pending_repairs, 0);
+    }
+
+    #[test]
+    fn test_failed_handoff_is_kept_for_next_recovery() {
+        let (mut node, _queue) = node_with_timers();
+        let mut vc = VectorClock::new();
+        vc.increment("nodeB");
+        node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutReq {
+            from: "nodeB".to_string(), to: "nodeA".to_string(), key: "k".to_string(), value: "v".to_string(), clock: vc, ts_ms: 0, msg_id: CorrelationId(1), handoff: Some(vec!["nodeC".to_string()]),
+        }));
+
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::PingRsp { from: "nodeC".to_string(), to: "nodeA".to_string() }));
+        let handoff = repair_reqs(&out);
+        assert_eq!(handoff.len(), 1);
+        assert_eq!((handoff[0].0.as_str(), handoff[0].2), ("nodeC", RepairKind::Handoff));
+
+        let mut attempt = handoff;
+        for _ in 0..3 { attempt = repair_reqs(&time_out(&mut node, &attempt)); }
+        assert!(attempt.is_empty());
+        assert_eq!(node.metrics().repairs_failed, 1);
+        assert!(node.stats().failed.contains(&"nodeC".to_string()));
+
+        // the hint survives, so the next recovery replays it again
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::PingRsp { from: "nodeC".to_string(), to: "nodeA".to_string() }));
+        assert_eq!(repair_reqs(&out).len(), 1);
+    }
+
+    #[test]
+    fn test_redistribution_uses_tracked_repairs() {
+        let mut node = DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(SystemClock));
+        for i in 0..20 {
+            node.process(DynamoNodeIn::NodeToNode(NodeToNode::RepairReq {
+                from: "nodeB".to_string(), to: "nodeA".to_string(), key: format!("key{}", i), values: versions(&[("v", "nodeB")]), repair_id: CorrelationId(i), kind: RepairKind::ReadRepair,
+            }));
+        }
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::AddNode { from: "admin".to_string(), to: "nodeA".to_string(), new_node: "nodeD".to_string() }));
+        let repairs = repair_reqs(&out);
+        assert!(!repairs.is_empty());
+        assert!(repairs.iter().all(|(to, _, kind)| to == "nodeD" && *kind == RepairKind::Redistribution));
+        assert_eq!(node.stats().pending_repairs, repairs.len() as u64);
+    }
+}
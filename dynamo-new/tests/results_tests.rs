@@ -0,0 +1,168 @@
+// Results Tests
+// Structured benchmark results: latency summaries, JSON/CSV output and run comparison
+
+mod common;
+use common::{temp_path};
+
+use std::time::Duration;
+use dynamo_new::bench_client::BenchClient;
+use dynamo_new::messages::{ClientToNode, DynamoClientIn, DynamoClientOut, GeneratorTrigger, NodeToClient};
+use dynamo_new::results::{compare, BenchResults, OpStats, RunConfig, SCHEMA_VERSION};
+use dynamo_new::vector_clock::VectorClock;
+use dynamo_new::workload::WorkloadConfig;
+use reactor_actor::ActorProcess;
+
+// A finished run whose GETs all take `get_us` and PUTs `put_us`
+fn run(run_id: &str, get_us: u64, put_us: u64, elapsed_ms: u64) -> BenchResults {
+    let (mut get, mut put) = (OpStats::new(false), OpStats::new(false));
+    for _ in 0..100 {
+        get.record(Duration::from_micros(get_us));
+        put.record(Duration::from_micros(put_us));
+    }
+    let config = RunConfig { n: Some(3), w: Some(2), r: Some(2), t: Some(10), operations: 200, workload: None };
+    let mut res = BenchResults::new(run_id.to_string(), "test".to_string(), config);
+    res.finish(Duration::from_millis(elapsed_ms), [("get", &get), ("put", &put)]);
+    res
+}
+
+fn regressions(base: &BenchResults, new: &BenchResults) -> Vec<(String, &'static str)> {
+    compare(base, new, 0.1).into_iter().filter(|c| c.regression).map(|c| (c.op, c.metric)).collect()
+}
+
+// Runs a closed-loop bench client against a node that answers every request at once
+fn run_bench_client(client: &mut BenchClient) {
+    let mut pending: Vec<ClientToNode> = client.process(DynamoClientIn::GeneratorTrigger(GeneratorTrigger)).into_iter().map(|DynamoClientOut::ClientToNode(r)| r).collect();
+    while let Some(req) = pending.pop() {
+        let rsp = match req {
+            ClientToNode::ClientPut{ key, request_id, client_addr, .. } => NodeToClient::ClientPutRsp { key, request_id, clock: VectorClock::new(), client_addr },
+            ClientToNode::ClientGet{ key, request_id, client_addr } => NodeToClient::ClientGetRsp { key, request_id, values: vec![], metadata: vec![], client_addr },
+        };
+        pending.extend(client.process(DynamoClientIn::NodeToClient(rsp)).into_iter().map(|DynamoClientOut::ClientToNode(r)| r));
+    }
+}
+
+#[cfg(test)]
+mod results_tests {
+    use super::*;
+
+    #[test]
+    fn test_op_stats_summary_and_histogram() {
+        let mut stats = OpStats::new(true);
+        for us in 1..=1000 {
+            stats.record(Duration::from_micros(us));
+        }
+        stats.record_error();
+        let res = stats.results();
+        assert_eq!(res.latency.count, 1000);
+        assert_eq!(res.errors, 1);
+        assert!((495..=505).contains(&res.latency.p50_us), "p50 {}", res.latency.p50_us);
+        assert!((985..=995).contains(&res.latency.p99_us), "p99 {}", res.latency.p99_us);
+        assert!((999..=1001).contains(&res.latency.max_us));
+        assert_eq!(res.histogram.iter().map(|(_, c)| c).sum::<u64>(), 1000);
+        assert!(res.histogram.windows(2).all(|w| w[0].0 < w[1].0), "buckets ascend");
+        assert_eq!(res.samples_us.len(), 1000);
+
+        assert!(OpStats::new(false).results().samples_us.is_empty());
+        assert!(OpStats::new(false).results().histogram.is_empty());
+    }
+
+    #[test]
+    fn test_merge_combines_clients() {
+        let (mut a, mut b) = (OpStats::new(true), OpStats::new(true));
+        for _ in 0..90 { a.record(Duration::from_micros(100)); }
+        for _ in 0..10 { b.record(Duration::from_micros(10_000)); }
+        b.record_error();
+        a.merge(&b);
+        let res = a.results();
+        assert_eq!(res.latency.count, 100);
+        assert_eq!(res.errors, 1);
+        assert_eq!(res.latency.p50_us, 100);
+        assert!(res.latency.p99_us >= 9_990, "p99 {}", res.latency.p99_us);
+        assert_eq!(res.samples_us.len(), 100);
+    }
+
+    #[test]
+    fn test_finish_computes_throughput() {
+        let res = run("base", 100, 200, 1000);
+        assert_eq!(res.duration_ms, 1000);
+        assert!((res.throughput_ops_per_sec - 200.0).abs() < 1e-6);
+        assert_eq!(res.ops.keys().collect::<Vec<_>>(), vec!["get", "put"]);
+        assert_eq!(res.ops["put"].latency.p50_us, 200);
+    }
+
+    #[test]
+    fn test_json_round_trip_and_schema_check() {
+        let path = temp_path("run").join("basic.json");
+        let res = run("base", 100, 200, 1000);
+        res.write_json(&path).unwrap();
+        assert_eq!(BenchResults::load(&path).unwrap(), res);
+
+        let mut json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        json["schema_version"] = serde_json::json!(SCHEMA_VERSION + 1);
+        std::fs::write(&path, json.to_string()).unwrap();
+        let err = BenchResults::load(&path).unwrap_err();
+        assert!(err.to_string().contains("schema version"), "{}", err);
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_csv_rows_share_one_header() {
+        let path = temp_path("summary.csv");
+        std::fs::remove_file(&path).ok();
+        run("a", 100, 200, 1000).append_csv(&path).unwrap();
+        run("b", 100, 200, 1000).append_csv(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], BenchResults::CSV_HEADER);
+        assert_eq!(lines.iter().filter(|l| l.starts_with("run_id")).count(), 1);
+        assert!(lines[1].starts_with("a,test,3,2,2,10,get,100,0,100.0,100,"), "{}", lines[1]);
+        assert!(lines[4].starts_with("b,test,3,2,2,10,put,"));
+        assert!(lines.iter().all(|l| l.split(',').count() == BenchResults::CSV_HEADER.split(',').count()));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_compare_flags_regressions() {
+        let base = run("base", 100, 200, 1000);
+        assert!(regressions(&base, &run("same", 100, 200, 1000)).is_empty());
+        assert!(regressions(&base, &run("noise", 105, 205, 1050)).is_empty(), "within the threshold");
+        assert!(regressions(&base, &run("faster", 50, 100, 500)).is_empty(), "improvements are not regressions");
+
+        let slow = regressions(&base, &run("slow", 100, 300, 1000));
+        assert!(slow.contains(&("put".to_string(), "p99_us")));
+        assert!(slow.iter().all(|(op, _)| op == "put"), "{:?}", slow);
+
+        let throughput = regressions(&base, &run("stalled", 100, 200, 2000));
+        assert_eq!(throughput, vec![("all".to_string(), "throughput_ops_per_sec")]);
+
+        let mut errors = run("errors", 100, 200, 1000);
+        errors.ops.get_mut("get").unwrap().errors = 1;
+        assert_eq!(regressions(&base, &errors), vec![("get".to_string(), "errors")]);
+    }
+
+    #[test]
+    fn test_bench_client_writes_results() {
+        let json = temp_path("bench_client.json");
+        let csv = temp_path("bench_client.csv");
+        std::fs::remove_file(&csv).ok();
+        let cfg = WorkloadConfig { operations: 20, seed: Some(1), ..WorkloadConfig::default() };
+        let mut client = BenchClient::new("bench1".to_string(), vec!["nodeA".to_string()], cfg.clone())
+            .with_run(Some("run1".to_string()), Some(3), Some(2), Some(2), None)
+            .with_output(Some(json.clone()), Some(csv.clone()), true);
+        run_bench_client(&mut client);
+        assert_eq!(client.completed(), 20);
+
+        let res = BenchResults::load(&json).unwrap();
+        assert_eq!(res.run_id, "run1");
+        assert_eq!(res.source, "bench_client:bench1");
+        assert_eq!((res.config.n, res.config.w, res.config.r, res.config.t), (Some(3), Some(2), Some(2), None));
+        assert_eq!(res.config.workload, Some(cfg));
+        assert_eq!(res.ops.values().map(|o| o.latency.count).sum::<u64>(), 20);
+        assert!(res.ops.keys().all(|op| op == "get" || op == "put"));
+        assert_eq!(res.ops.values().map(|o| o.samples_us.len()).sum::<usize>(), 20);
+        assert_eq!(std::fs::read_to_string(&csv).unwrap().lines().count(), 1 + res.ops.len());
+        std::fs::remove_file(&json).ok();
+        std::fs::remove_file(&csv).ok();
+    }
+}
@@ -0,0 +1,123 @@
+// Client Session Tests
+// Read-your-writes and monotonic reads enforced by the opt-in client session
+
+use dynamo_new::vector_clock::VectorClock;
+use dynamo_new::client::DynamoClient;
+use dynamo_new::session::{Guarantee, Session, SessionCheck, SessionPolicy};
+use dynamo_new::messages::{ClientToNode, DynamoClientIn, DynamoClientOut, GeneratorTrigger, NodeToClient};
+use reactor_actor::ActorProcess;
+
+fn clock(entries: &[(&str, u64)]) -> VectorClock {
+    let mut vc = VectorClock::new();
+    for (n, c) in entries { vc.update(n, *c); }
+    vc
+}
+
+fn put_then_get() -> Vec<ClientToNode> {
+    vec![
+        ClientToNode::ClientPut { key: "k".to_string(), value: "v".to_string(), metadata: vec![], client_addr: "client1".to_string(), request_id: 1 },
+        ClientToNode::ClientGet { key: "k".to_string(), client_addr: "client1".to_string(), request_id: 2 },
+    ]
+}
+
+fn put_rsp(clock: VectorClock) -> DynamoClientIn {
+    DynamoClientIn::NodeToClient(NodeToClient::ClientPutRsp { key: "k".to_string(), request_id: 1, clock, client_addr: "client1".to_string() })
+}
+
+fn get_rsp(metadata: Vec<VectorClock>) -> DynamoClientIn {
+    let values = metadata.iter().map(|_| "v".to_string()).collect();
+    DynamoClientIn::NodeToClient(NodeToClient::ClientGetRsp { key: "k".to_string(), request_id: 2, values, metadata, client_addr: "client1".to_string() })
+}
+
+fn sent_get(out: &[DynamoClientOut]) -> bool {
+    out.iter().any(|m| matches!(m, DynamoClientOut::ClientToNode(ClientToNode::ClientGet { request_id: 2, .. })))
+}
+
+// Drives the client through its PUT so the next request is the GET
+fn client_after_put(policy: SessionPolicy) -> DynamoClient {
+    let mut client = DynamoClient::new("client1".to_string(), vec!["nodeA".to_string()], put_then_get()).with_session(policy);
+    client.process(DynamoClientIn::GeneratorTrigger(GeneratorTrigger));
+    let out = client.process(put_rsp(clock(&[("client1", 1), ("nodeA", 1)])));
+    assert!(sent_get(&out));
+    client
+}
+
+#[cfg(test)]
+mod session_tests {
+    use super::*;
+
+    #[test]
+    fn test_descends() {
+        let a = clock(&[("n1", 1)]);
+        let b = clock(&[("n1", 2), ("n2", 1)]);
+        let c = clock(&[("n2", 3)]);
+        assert!(b.descends(&a));
+        assert!(a.descends(&a));
+        assert!(!a.descends(&b));
+        assert!(!c.descends(&a));
+    }
+
+    #[test]
+    fn test_read_your_writes_check() {
+        let mut s = Session::new(SessionPolicy::Reject);
+        s.record_write("k", &clock(&[("client1", 1), ("nodeA", 1)]));
+        assert_eq!(s.check_read("k", &[]), SessionCheck::Violation(Guarantee::ReadYourWrites));
+        assert_eq!(s.check_read("k", &[clock(&[("client1", 1), ("nodeA", 1)])]), SessionCheck::Ok);
+        // siblings whose combined context covers the write are fine
+        assert_eq!(s.check_read("k", &[clock(&[("client1", 1)]), clock(&[("nodeA", 2)])]), SessionCheck::Ok);
+        assert_eq!(s.check_read("other", &[]), SessionCheck::Ok);
+    }
+
+    #[test]
+    fn test_monotonic_reads_check() {
+        let mut s = Session::new(SessionPolicy::Reject);
+        s.record_read("k", &[clock(&[("nodeA", 2)]), clock(&[("nodeB", 1)])]);
+        assert_eq!(s.check_read("k", &[clock(&[("nodeA", 2)])]), SessionCheck::Violation(Guarantee::MonotonicReads));
+        assert_eq!(s.check_read("k", &[clock(&[("nodeA", 2), ("nodeB", 1)])]), SessionCheck::Ok);
+    }
+
+    #[test]
+    fn test_policy_from_payload() {
+        let p: SessionPolicy = serde_json::from_str(r#"{"policy":"retry"}"#).unwrap();
+        assert_eq!(p, SessionPolicy::Retry { max_retries: 3 });
+        let p: SessionPolicy = serde_json::from_str(r#"{"policy":"retry","max_retries":1}"#).unwrap();
+        assert_eq!(p, SessionPolicy::Retry { max_retries: 1 });
+        let p: SessionPolicy = serde_json::from_str(r#"{"policy":"warn"}"#).unwrap();
+        assert_eq!(p, SessionPolicy::Warn);
+    }
+
+    #[test]
+    fn test_client_retries_stale_read() {
+        let mut client = client_after_put(SessionPolicy::Retry { max_retries: 2 });
+
+        // stale coordinator: GET misses our write, so the client asks again
+        let out = client.process(get_rsp(vec![]));
+        assert!(sent_get(&out));
+        let out = client.process(get_rsp(vec![clock(&[("client1", 1)])]));
+        assert!(sent_get(&out));
+        assert_eq!(client.session_stats().retries, 2);
+
+        // retries exhausted: rejected and the script moves on
+        let out = client.process(get_rsp(vec![]));
+        assert!(out.is_empty());
+        assert_eq!(client.session_stats().violations, 3);
+        assert_eq!(client.session_stats().rejected, 1);
+    }
+
+    #[test]
+    fn test_client_accepts_fresh_read() {
+        let mut client = client_after_put(SessionPolicy::Retry { max_retries: 2 });
+        let out = client.process(get_rsp(vec![clock(&[("client1", 1), ("nodeA", 1)])]));
+        assert!(out.is_empty());
+        assert_eq!(client.session_stats().violations, 0);
+    }
+
+    #[test]
+    fn test_client_warn_policy_accepts_stale_read() {
+        let mut client = client_after_put(SessionPolicy::Warn);
+        let out = client.process(get_rsp(vec![]));
+        assert!(!sent_get(&out));
+        assert_eq!(client.session_stats().violations, 1);
+        assert_eq!(client.session_stats().rejected, 0);
+    }
+}
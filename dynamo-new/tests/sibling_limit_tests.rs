@@ -0,0 +1,171 @@
+// Sibling Limit Tests
+// Capping concurrent versions per key: keep-newest pruning, forced resolution, rejected writes and sibling stats
+
+mod common;
+use common::{three_nodes};
+
+use std::sync::Arc;
+use dynamo_new::clock::ManualClock;
+use dynamo_new::config::DynamoConfig;
+use dynamo_new::messages::{ClientToNode, DynamoNodeIn, DynamoNodeOut, NodeToClient, NodeToNode, RepairKind};
+use dynamo_new::node::DynamoNode;
+use dynamo_new::vector_clock::{ClockOrdering, VectorClock};
+use dynamo_new::versioned_value::{SiblingPolicy, VersionedValue, VersionedValues};
+use reactor_actor::{ActorProcess, CorrelationId};
+
+fn node(max: usize, policy: SiblingPolicy) -> DynamoNode {
+    DynamoNode::new("nodeA".to_string(), three_nodes(), 3, 2, 2, 10, Arc::new(ManualClock::new())).with_sibling_limit(max, policy)
+}
+
+// A version written by `writer` alone, so versions of different writers are concurrent
+fn version(value: &str, writer: &str, ts_ms: u64) -> VersionedValue {
+    let mut vc = VectorClock::new();
+    vc.increment(writer);
+    VersionedValue::new(value.to_string(), vc).with_ts(ts_ms)
+}
+
+fn put(node: &mut DynamoNode, key: &str, v: VersionedValue, msg_id: u64) -> Vec<DynamoNodeOut> {
+    node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutReq { from: "nodeB".to_string(), to: "nodeA".to_string(), key: key.to_string(), value: v.value, clock: v.clock, ts_ms: v.ts_ms, msg_id: CorrelationId(msg_id), handoff: None }))
+}
+
+// The ask and timestamp of the PutReq sent to `to`
+fn put_req(out: &[DynamoNodeOut], to: &str) -> (CorrelationId, u64) {
+    out.iter().find_map(|m| match m {
+        DynamoNodeOut::NodeToNode(NodeToNode::PutReq{ to: sent_to, msg_id, ts_ms, .. }) if sent_to == to => Some((*msg_id, *ts_ms)),
+        _ => None,
+    }).expect("coordinator should fan out PutReq")
+}
+
+fn sorted_values(node: &DynamoNode, key: &str) -> Vec<String> {
+    let mut v: Vec<String> = node.local(key).map(|vs| vs.versions.iter().map(|v| v.value.clone()).collect()).unwrap_or_default();
+    v.sort();
+    v
+}
+
+#[cfg(test)]
+mod sibling_limit_tests {
+    use super::*;
+
+    #[test]
+    fn test_keep_newest_is_independent_of_arrival_order() {
+        let all: Vec<VersionedValue> = (0..6).map(|i| version(&format!("v{}", i), &format!("w{}", i), [30, 10, 50, 20, 50, 40][i])).collect();
+        let mut forward = VersionedValues::new();
+        let mut backward = VersionedValues::new();
+        for v in all.iter() { forward.add_version(v.clone()); }
+        for v in all.iter().rev() { backward.add_version(v.clone()); }
+        assert_eq!(forward.limit_siblings(3, SiblingPolicy::KeepNewest), 3);
+        assert_eq!(backward.limit_siblings(3, SiblingPolicy::KeepNewest), 3);
+        let values = |vs: &VersionedValues| vs.versions.iter().map(|v| v.value.clone()).collect::<Vec<_>>();
+        assert_eq!(values(&forward), vec!["v4", "v2", "v5"], "newest first, equal timestamps ordered by value");
+        assert_eq!(values(&forward), values(&backward));
+        assert_eq!(forward.limit_siblings(0, SiblingPolicy::KeepNewest), 0, "0 means no limit");
+    }
+
+    #[test]
+    fn test_resolve_collapses_into_a_descendant() {
+        let mut vs = VersionedValues::new();
+        for (i, ts) in [5, 9, 7].into_iter().enumerate() { vs.add_version(version(&format!("v{}", i), &format!("w{}", i), ts)); }
+        let before = vs.clone();
+        assert_eq!(vs.limit_siblings(2, SiblingPolicy::Resolve), 2);
+        assert_eq!(vs.versions.len(), 1);
+        assert_eq!((vs.versions[0].value.as_str(), vs.versions[0].ts_ms), ("v1", 9));
+        for v in before.versions.iter() { assert_eq!(vs.versions[0].clock.compare(&v.clock), ClockOrdering::After); }
+        // an old sibling arriving again through repair is now dominated
+        vs.add_version(before.versions[0].clone());
+        assert_eq!(vs.versions.len(), 1);
+    }
+
+    #[test]
+    fn test_node_prunes_oldest_siblings() {
+        let mut node = node(3, SiblingPolicy::KeepNewest);
+        for i in 0..5u64 {
+            let out = put(&mut node, "k", version(&format!("v{}", i), &format!("w{}", i), 100 + i), i + 1);
+            assert!(out.iter().any(|m| matches!(m, DynamoNodeOut::NodeToNode(NodeToNode::PutRsp{ .. }))), "pruning still acks");
+        }
+        assert_eq!(sorted_values(&node, "k"), vec!["v2", "v3", "v4"]);
+        assert_eq!(node.metrics().siblings_dropped, 2);
+        assert_eq!(node.config().max_siblings, 3);
+    }
+
+    #[test]
+    fn test_reject_write_refuses_new_siblings_only() {
+        let mut node = node(2, SiblingPolicy::RejectWrite);
+        put(&mut node, "k", version("a", "w1", 1), 1);
+        put(&mut node, "k", version("b", "w2", 2), 2);
+        let out = put(&mut node, "k", version("c", "w3", 3), 3);
+        assert!(matches!(&out[..], [DynamoNodeOut::NodeToNode(NodeToNode::PutRej{ to, msg_id: CorrelationId(3), .. })] if to == "nodeB"), "{:?}", out);
+        assert_eq!(sorted_values(&node, "k"), vec!["a", "b"]);
+        assert_eq!(node.metrics().writes_rejected, 1);
+
+        // a write that read both siblings replaces them
+        let merged = VectorClock::converge(node.local("k").unwrap().versions.iter().map(|v| v.clock.clone()));
+        let mut clock = merged.clone();
+        clock.increment("w3");
+        let out = put(&mut node, "k", VersionedValue::new("c".to_string(), clock).with_ts(4), 4);
+        assert!(out.iter().any(|m| matches!(m, DynamoNodeOut::NodeToNode(NodeToNode::PutRsp{ msg_id: CorrelationId(4), .. }))));
+        assert_eq!(sorted_values(&node, "k"), vec!["c"]);
+
+        // repairs are never refused
+        let mut vs = VersionedValues::new();
+        vs.add_version(version("d", "w4", 5));
+        vs.add_version(version("e", "w5", 6));
+        node.process(DynamoNodeIn::NodeToNode(NodeToNode::RepairReq { from: "nodeC".to_string(), to: "nodeA".to_string(), key: "k".to_string(), values: vs, repair_id: CorrelationId(1), kind: RepairKind::ReadRepair }));
+        assert_eq!(sorted_values(&node, "k"), vec!["c", "d", "e"]);
+    }
+
+    #[test]
+    fn test_coordinator_fails_rejected_put() {
+        let mut node = node(1, SiblingPolicy::RejectWrite);
+        let out = node.process(DynamoNodeIn::ClientToNode(ClientToNode::ClientPut { key: "k".to_string(), value: "v".to_string(), metadata: vec![], client_addr: "client1".to_string(), request_id: 7 }));
+        let ask = |to: &str| put_req(&out, to).0;
+        assert_eq!(put_req(&out, "nodeA").1, 0, "stamped from the node clock");
+
+        // one refusal leaves two replicas, still enough for W=2
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutRej { from: "nodeB".to_string(), to: "nodeA".to_string(), msg_id: ask("nodeB"), reason: "at limit".to_string() }));
+        assert!(!out.iter().any(|m| matches!(m, DynamoNodeOut::NodeToClient(_))), "{:?}", out);
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutRej { from: "nodeC".to_string(), to: "nodeA".to_string(), msg_id: ask("nodeC"), reason: "at limit".to_string() }));
+        assert!(matches!(&out[..], [DynamoNodeOut::NodeToClient(NodeToClient::ClientPutRejected{ request_id: 7, reason, client_addr, .. })] if reason == "at limit" && client_addr == "client1"), "{:?}", out);
+        assert_eq!(node.metrics().puts_rejected, 1);
+
+        // an ack after the rejection does not answer the client again
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutRsp { from: "nodeA".to_string(), to: "nodeA".to_string(), msg_id: ask("nodeA") }));
+        assert!(!out.iter().any(|m| matches!(m, DynamoNodeOut::NodeToClient(_))));
+        assert_eq!(node.metrics().puts_completed, 0);
+    }
+
+    #[test]
+    fn test_put_refused_by_a_minority_succeeds() {
+        let mut node = node(1, SiblingPolicy::RejectWrite);
+        let out = node.process(DynamoNodeIn::ClientToNode(ClientToNode::ClientPut { key: "k".to_string(), value: "v".to_string(), metadata: vec![], client_addr: "client1".to_string(), request_id: 7 }));
+        node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutRej { from: "nodeB".to_string(), to: "nodeA".to_string(), msg_id: put_req(&out, "nodeB").0, reason: "at limit".to_string() }));
+        let mut answers = vec![];
+        for from in ["nodeA", "nodeC"] {
+            answers.extend(node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutRsp { from: from.to_string(), to: "nodeA".to_string(), msg_id: put_req(&out, from).0 })).into_iter().filter(|m| matches!(m, DynamoNodeOut::NodeToClient(_))));
+        }
+        assert!(matches!(&answers[..], [DynamoNodeOut::NodeToClient(NodeToClient::ClientPutRsp{ request_id: 7, .. })]), "{:?}", answers);
+        assert_eq!((node.metrics().puts_completed, node.metrics().puts_rejected), (1, 0), "nodeB's refusal does not fail a PUT that reached W");
+    }
+
+    #[test]
+    fn test_stats_report_keys_with_most_siblings() {
+        let mut node = node(0, SiblingPolicy::KeepNewest);
+        assert_eq!((node.stats().keys_with_siblings, node.stats().most_siblings), (0, 0));
+        put(&mut node, "single", version("a", "w0", 1), 1);
+        for i in 0..3 { put(&mut node, "three", version(&format!("v{}", i), &format!("w{}", i), 1), 10 + i); }
+        for i in 0..2 { put(&mut node, "two", version(&format!("v{}", i), &format!("w{}", i), 1), 20 + i); }
+        let stats = node.stats();
+        assert_eq!((stats.keys_with_siblings, stats.most_siblings), (2, 3));
+        assert_eq!(stats.sibling_hot_keys, vec![("three".to_string(), 3), ("two".to_string(), 2)]);
+        let prom = stats.to_prometheus();
+        assert!(prom.contains("dynamo_most_siblings{node=\"nodeA\"} 3"), "{}", prom);
+        assert!(prom.contains("dynamo_key_siblings{node=\"nodeA\",key=\"three\"} 3"), "{}", prom);
+    }
+
+    #[test]
+    fn test_sibling_limit_config() {
+        let cfg = DynamoConfig::from_toml_str("max_siblings = 5\nsibling_policy = \"reject_write\"").unwrap();
+        assert_eq!((cfg.max_siblings, cfg.sibling_policy), (5, SiblingPolicy::RejectWrite));
+        assert_eq!(DynamoConfig::default().max_siblings, 0, "unlimited by default");
+        assert!(DynamoConfig::from_toml_str("sibling_policy = \"newest\"").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+// Snapshot Tests
+// Exporting a cluster's stores to a snapshot file and importing it into a fresh cluster
+
+mod common;
+use common::{versions, temp_path};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use dynamo_new::admin::DynamoAdmin;
+use dynamo_new::clock::SystemClock;
+use dynamo_new::messages::{DynamoAdminIn, DynamoNodeIn, DynamoNodeOut, GeneratorTrigger, NodeToNode, RepairKind};
+use dynamo_new::node::{DynamoNode, SNAPSHOT_CHUNK_KEYS};
+use dynamo_new::snapshot::{Snapshot, SNAPSHOT_VERSION};
+use dynamo_new::versioned_value::VersionedValues;
+use reactor_actor::{ActorProcess, CorrelationId, timer_channel};
+
+fn store(node: &mut DynamoNode, key: &str, values: VersionedValues) {
+    node.process(DynamoNodeIn::NodeToNode(NodeToNode::RepairReq { from: "test".to_string(), to: String::new(), key: key.to_string(), values, repair_id: CorrelationId(0), kind: RepairKind::ReadRepair }));
+}
+
+fn sorted_values(vs: &VersionedValues) -> Vec<String> {
+    let mut v: Vec<String> = vs.versions.iter().map(|v| v.value.clone()).collect();
+    v.sort();
+    v
+}
+
+// Four nodes with N=2 and an admin, delivering every message in order until none are left
+struct Cluster { nodes: HashMap<String, DynamoNode>, admin: DynamoAdmin }
+
+impl Cluster {
+    fn new(admin: impl FnOnce(DynamoAdmin) -> DynamoAdmin) -> Self {
+        let ids: Vec<String> = ["nodeA", "nodeB", "nodeC", "nodeD"].iter().map(|s| s.to_string()).collect();
+        let nodes = ids.iter().map(|id| (id.clone(), DynamoNode::new(id.clone(), ids.clone(), 2, 1, 1, 10, Arc::new(SystemClock)))).collect();
+        Self { nodes, admin: admin(DynamoAdmin::new("admin".to_string(), ids, None)) }
+    }
+
+    fn poll(&mut self) {
+        let mut queue: VecDeque<DynamoNodeOut> = self.admin.process(DynamoAdminIn::GeneratorTrigger(GeneratorTrigger)).into();
+        while let Some(msg) = queue.pop_front() {
+            let DynamoNodeOut::NodeToNode(n2n) = msg else { panic!("unexpected client message {:?}", msg) };
+            if let NodeToNode::StatsRsp { .. } = n2n { continue; }
+            let to = DynamoNodeOut::NodeToNode(n2n.clone()).destination().to_string();
+            let out = if to == "admin" { self.admin.process(DynamoAdminIn::NodeToNode(n2n)) } else { self.nodes.get_mut(&to).unwrap().process(DynamoNodeIn::NodeToNode(n2n)) };
+            queue.extend(out);
+        }
+    }
+
+    // Every node's copy of `key`, merged, and how many nodes hold one
+    fn merged(&self, key: &str) -> (VersionedValues, usize) {
+        let mut merged = VersionedValues::new();
+        let mut holders = 0;
+        for node in self.nodes.values() {
+            if let Some(vs) = node.local(key) { merged.merge(vs); holders += 1; }
+        }
+        (merged, holders)
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::*;
+
+    #[test]
+    fn test_file_round_trip() {
+        let path = temp_path("round_trip.jsonl");
+        let mut snap = Snapshot::new();
+        snap.add("nodeA", "k1".to_string(), &versions(&[("a", "nodeA")]));
+        snap.add("nodeB", "k1".to_string(), &versions(&[("b", "nodeB")]));
+        snap.add("nodeB", "k2".to_string(), &versions(&[("c", "nodeB")]));
+        assert_eq!(sorted_values(snap.get("k1").unwrap()), vec!["a", "b"], "replica copies merge into siblings");
+        snap.write(&path).unwrap();
+
+        let (header, loaded) = Snapshot::load(&path).unwrap();
+        assert_eq!((header.version, header.keys), (SNAPSHOT_VERSION, 2));
+        assert_eq!(loaded.nodes(), &["nodeA".to_string(), "nodeB".to_string()]);
+        assert_eq!(loaded.entries().map(|(k, v)| (k.clone(), v.versions.clone())).collect::<Vec<_>>(), snap.entries().map(|(k, v)| (k.clone(), v.versions.clone())).collect::<Vec<_>>());
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_load_rejects_damaged_files() {
+        let path = temp_path("damaged.jsonl");
+        let mut snap = Snapshot::new();
+        snap.add("nodeA", "k1".to_string(), &versions(&[("a", "nodeA")]));
+        snap.add("nodeA", "k2".to_string(), &versions(&[("b", "nodeA")]));
+        snap.write(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+
+        let truncated: Vec<&str> = text.lines().take(2).collect();
+        std::fs::write(&path, truncated.join("\n")).unwrap();
+        let err = Snapshot::load(&path).unwrap_err();
+        assert!(err.to_string().contains("header lists 2 keys but the file holds 1"), "{}", err);
+
+        std::fs::write(&path, text.replacen(&format!("\"version\":{}", SNAPSHOT_VERSION), "\"version\":99", 1)).unwrap();
+        let err = Snapshot::load(&path).unwrap_err();
+        assert!(err.to_string().contains("snapshot version 99"), "{}", err);
+
+        std::fs::write(&path, "{\"event\":\"invoke\"}\n").unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_node_streams_store_in_chunks() {
+        let nodes = vec!["nodeA".to_string()];
+        let mut node = DynamoNode::new("nodeA".to_string(), nodes, 1, 1, 1, 10, Arc::new(SystemClock));
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::SnapshotReq { from: "admin".to_string(), to: "nodeA".to_string() }));
+        assert!(matches!(&out[..], [DynamoNodeOut::NodeToNode(NodeToNode::SnapshotChunk{ entries, last: true, .. })] if entries.is_empty()), "an empty store still answers");
+
+        let keys = SNAPSHOT_CHUNK_KEYS + 10;
+        for i in 0..keys { store(&mut node, &format!("k{:04}", i), versions(&[("v", "nodeA")])); }
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::SnapshotReq { from: "admin".to_string(), to: "nodeA".to_string() }));
+        let chunks: Vec<(usize, bool)> = out.iter().filter_map(|m| match m {
+            DynamoNodeOut::NodeToNode(NodeToNode::SnapshotChunk{ to, entries, last, .. }) if to == "admin" => Some((entries.len(), *last)),
+            _ => None,
+        }).collect();
+        assert_eq!(chunks, vec![(SNAPSHOT_CHUNK_KEYS, false), (10, true)]);
+    }
+
+    #[test]
+    fn test_export_then_import_into_fresh_cluster() {
+        let path = temp_path("cluster.jsonl");
+        let mut source = Cluster::new(|a| a.with_export(path.clone()));
+        for i in 0..100 {
+            let key = format!("user:{}", i);
+            // the same value on two nodes, and concurrent siblings for every tenth key
+            store(source.nodes.get_mut("nodeA").unwrap(), &key, versions(&[("a", "nodeA")]));
+            store(source.nodes.get_mut("nodeC").unwrap(), &key, versions(&[("a", "nodeA")]));
+            if i % 10 == 0 { store(source.nodes.get_mut("nodeB").unwrap(), &key, versions(&[("b", "nodeB")])); }
+        }
+        source.poll();
+        assert!(source.admin.snapshot_done());
+
+        let (header, snap) = Snapshot::load(&path).unwrap();
+        assert_eq!(header.keys, 100);
+        assert_eq!(snap.nodes().len(), 4);
+        assert_eq!(sorted_values(snap.get("user:10").unwrap()), vec!["a", "b"]);
+
+        let mut target = Cluster::new(|a| a.with_import(&snap));
+        target.poll();
+        assert!(target.admin.snapshot_done(), "every key acknowledged");
+        for (key, values) in snap.entries() {
+            let (merged, holders) = target.merged(key);
+            assert_eq!(sorted_values(&merged), sorted_values(values), "{}", key);
+            assert!(merged.versions.iter().all(|v| values.contains(v)), "{} keeps its clocks", key);
+            assert_eq!(holders, 2, "{} written to its N replicas", key);
+        }
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_admin_polling_once_still_retries_the_snapshot() {
+        let (timers, _queue) = timer_channel();
+        let mut admin = DynamoAdmin::new("admin".to_string(), vec!["nodeA".to_string(), "nodeB".to_string()], None).with_export(temp_path("retry.jsonl")).with_timers(timers, 0);
+        let kinds = |out: &[DynamoNodeOut]| out.iter().map(|m| match m { DynamoNodeOut::NodeToNode(NodeToNode::SnapshotReq{ .. }) => "snapshot", DynamoNodeOut::NodeToNode(NodeToNode::StatsReq{ .. }) => "stats", _ => "other" }).collect::<Vec<_>>();
+        let out = admin.process(DynamoAdminIn::GeneratorTrigger(GeneratorTrigger));
+        assert_eq!(kinds(&out), vec!["snapshot", "snapshot", "stats", "stats"]);
+        // the retry timer fires with every chunk still missing
+        let out = admin.process(DynamoAdminIn::GeneratorTrigger(GeneratorTrigger));
+        assert_eq!(kinds(&out), vec!["snapshot", "snapshot"], "stats are polled once, the snapshot until done");
+    }
+}
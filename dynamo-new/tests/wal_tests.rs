@@ -0,0 +1,209 @@
+// WAL Tests
+// Write-ahead logging of replica writes: fsync modes, when acks are sent, and recovery after a crash
+
+mod common;
+use common::{three_nodes, temp_path};
+
+use std::path::{Path, PathBuf};
+use std::cell::Cell;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use dynamo_new::clock::ManualClock;
+use dynamo_new::config::DynamoConfig;
+use dynamo_new::messages::{DynamoNodeIn, DynamoNodeOut, NodeToNode, RepairKind};
+use dynamo_new::node::DynamoNode;
+use dynamo_new::vector_clock::VectorClock;
+use dynamo_new::versioned_value::{VersionedValue, VersionedValues};
+use dynamo_new::wal::{FsyncMode, Wal, WalFile, WalRecord};
+use reactor_actor::{ActorProcess, CorrelationId};
+
+fn config(dir: &Path, fsync: FsyncMode) -> DynamoConfig {
+    DynamoConfig { node_id: "nodeA".to_string(), nodes: three_nodes(), wal_dir: Some(dir.to_path_buf()), wal_fsync: fsync, wal_group_commit_ms: 10, ..DynamoConfig::default() }
+}
+
+fn put(node: &mut DynamoNode, key: &str, value: &str, msg_id: u64, handoff: Option<Vec<String>>) -> Vec<DynamoNodeOut> {
+    let mut vc = VectorClock::new();
+    vc.update("nodeB", msg_id);
+    node.process(DynamoNodeIn::NodeToNode(NodeToNode::PutReq { from: "nodeB".to_string(), to: "nodeA".to_string(), key: key.to_string(), value: value.to_string(), clock: vc, ts_ms: 0, msg_id: CorrelationId(msg_id), handoff }))
+}
+
+fn put_acks(out: &[DynamoNodeOut]) -> Vec<u64> {
+    out.iter().filter_map(|m| match m {
+        DynamoNodeOut::NodeToNode(NodeToNode::PutRsp{ msg_id, .. }) => Some(msg_id.0),
+        _ => None,
+    }).collect()
+}
+
+fn values(node: &DynamoNode, key: &str) -> Vec<String> {
+    node.local(key).map(|vs| vs.versions.iter().map(|v| v.value.clone()).collect()).unwrap_or_default()
+}
+
+// A WAL file whose writes fail once `budget` more bytes have been written, leaving a torn record
+struct FailingFile {
+    file: std::fs::File,
+    budget: Rc<Cell<Option<usize>>>,
+}
+
+impl Read for FailingFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.file.read(buf) }
+}
+
+impl Seek for FailingFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> { self.file.seek(pos) }
+}
+
+impl Write for FailingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.budget.get() {
+            None => self.file.write(buf),
+            Some(0) => { self.budget.set(None); Err(io::Error::new(io::ErrorKind::StorageFull, "disk full")) }
+            Some(n) => { let k = self.file.write(&buf[..n.min(buf.len())])?; self.budget.set(Some(n - k)); Ok(k) }
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> { self.file.flush() }
+}
+
+impl WalFile for FailingFile {
+    fn file_len(&self) -> io::Result<u64> { self.file.file_len() }
+    fn set_len(&self, len: u64) -> io::Result<()> { WalFile::set_len(&self.file, len) }
+    fn sync_data(&self) -> io::Result<()> { WalFile::sync_data(&self.file) }
+}
+
+fn record(key: &str, value: &str) -> WalRecord {
+    let mut vc = VectorClock::new();
+    vc.increment("nodeB");
+    let mut values = VersionedValues::new();
+    values.add_version(VersionedValue::new(value.to_string(), vc));
+    WalRecord { key: key.to_string(), values, hints: vec![] }
+}
+
+#[cfg(test)]
+mod wal_tests {
+    use super::*;
+
+    #[test]
+    fn test_always_acks_durable_writes_and_recovers() {
+        let dir = temp_path("wal_always");
+        let cfg = config(&dir, FsyncMode::Always);
+        let mut node = DynamoNode::open(&cfg, Arc::new(ManualClock::new())).unwrap();
+        assert_eq!(put_acks(&put(&mut node, "k1", "v1", 1, None)), vec![1]);
+        assert_eq!(put_acks(&put(&mut node, "k2", "v2", 2, Some(vec!["nodeC".to_string()]))), vec![2]);
+        assert_eq!((node.metrics().wal_appends, node.metrics().wal_syncs), (2, 2));
+        assert_eq!(node.config().wal_fsync, FsyncMode::Always);
+        drop(node);
+
+        let node = DynamoNode::open(&cfg, Arc::new(ManualClock::new())).unwrap();
+        assert_eq!(values(&node, "k1"), vec!["v1"]);
+        assert_eq!(values(&node, "k2"), vec!["v2"]);
+        assert_eq!(node.stats().failed, vec!["nodeC"], "hints survive the restart");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_group_commit_holds_acks_until_sync() {
+        let dir = temp_path("wal_interval");
+        let clock = ManualClock::new();
+        let mut node = DynamoNode::open(&config(&dir, FsyncMode::Interval), Arc::new(clock.clone())).unwrap();
+        assert!(put_acks(&put(&mut node, "k1", "v1", 1, None)).is_empty());
+        clock.advance(Duration::from_millis(5));
+        assert!(put_acks(&put(&mut node, "k2", "v2", 2, None)).is_empty());
+        // already stored, but the stored copy is not durable yet either
+        let mut vc = VectorClock::new();
+        vc.update("nodeB", 1);
+        let mut vs = VersionedValues::new();
+        vs.add_version(VersionedValue::new("v1".to_string(), vc));
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::RepairReq { from: "nodeC".to_string(), to: "nodeA".to_string(), key: "k1".to_string(), values: vs, repair_id: CorrelationId(9), kind: RepairKind::ReadRepair }));
+        assert!(!out.iter().any(|m| matches!(m, DynamoNodeOut::NodeToNode(NodeToNode::RepairRsp{ .. }))));
+        assert_eq!(node.metrics().wal_syncs, 0);
+
+        clock.advance(Duration::from_millis(5));
+        let out = node.process(DynamoNodeIn::NodeToNode(NodeToNode::StatsReq { from: "admin".to_string(), to: "nodeA".to_string() }));
+        assert_eq!(put_acks(&out), vec![1, 2], "one fsync releases the whole group");
+        assert!(out.iter().any(|m| matches!(m, DynamoNodeOut::NodeToNode(NodeToNode::RepairRsp{ repair_id: CorrelationId(9), .. }))));
+        assert_eq!((node.metrics().wal_appends, node.metrics().wal_syncs), (2, 1));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_none_acks_without_fsync() {
+        let dir = temp_path("wal_none");
+        let cfg = config(&dir, FsyncMode::None);
+        let mut node = DynamoNode::open(&cfg, Arc::new(ManualClock::new())).unwrap();
+        assert_eq!(put_acks(&put(&mut node, "k1", "v1", 1, None)), vec![1]);
+        assert_eq!((node.metrics().wal_appends, node.metrics().wal_syncs), (1, 0));
+        drop(node);
+        assert_eq!(values(&DynamoNode::open(&cfg, Arc::new(ManualClock::new())).unwrap(), "k1"), vec!["v1"]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_crash_mid_append_keeps_complete_writes() {
+        let dir = temp_path("wal_torn");
+        let cfg = config(&dir, FsyncMode::Always);
+        let path = cfg.wal_path().unwrap();
+        let mut node = DynamoNode::open(&cfg, Arc::new(ManualClock::new())).unwrap();
+        put(&mut node, "k1", "v1", 1, None);
+        let complete = std::fs::metadata(&path).unwrap().len();
+        put(&mut node, "k2", "v2", 2, None);
+        drop(node);
+        let full = std::fs::read(&path).unwrap();
+
+        // the node dies at every point of writing the second record
+        for cut in complete..full.len() as u64 {
+            std::fs::write(&path, &full[..cut as usize]).unwrap();
+            let mut node = DynamoNode::open(&cfg, Arc::new(ManualClock::new())).unwrap();
+            assert_eq!(values(&node, "k1"), vec!["v1"], "cut at {}", cut);
+            assert!(values(&node, "k2").is_empty(), "cut at {}", cut);
+            assert_eq!(std::fs::metadata(&path).unwrap().len(), complete, "torn tail truncated");
+            // the restarted node keeps logging after the last complete record
+            assert_eq!(put_acks(&put(&mut node, "k3", "v3", 3, None)), vec![3]);
+            drop(node);
+            let node = DynamoNode::open(&cfg, Arc::new(ManualClock::new())).unwrap();
+            assert_eq!((values(&node, "k1"), values(&node, "k3")), (vec!["v1".to_string()], vec!["v3".to_string()]));
+        }
+
+        // a flipped bit in the last record is caught by its checksum
+        let mut corrupt = full.clone();
+        *corrupt.last_mut().unwrap() ^= 0x40;
+        std::fs::write(&path, &corrupt).unwrap();
+        let node = DynamoNode::open(&cfg, Arc::new(ManualClock::new())).unwrap();
+        assert_eq!(values(&node, "k1"), vec!["v1"]);
+        assert!(values(&node, "k2").is_empty());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_failed_append_does_not_hide_later_ones() {
+        let dir = temp_path("wal_failed_append");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("nodeA.wal");
+        let budget = Rc::new(Cell::new(None));
+        let file = std::fs::OpenOptions::new().read(true).append(true).create(true).open(&path).unwrap();
+        let (mut wal, _) = Wal::from_file(FailingFile { file, budget: budget.clone() }, &path, FsyncMode::Always, Duration::ZERO).unwrap();
+        let now = Instant::now();
+        assert!(wal.append(&record("k1", "v1"), now).unwrap());
+        let complete = std::fs::metadata(&path).unwrap().len();
+
+        // the disk fills up five bytes into the second record
+        budget.set(Some(5));
+        assert!(wal.append(&record("k2", "v2"), now).is_err());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), complete, "torn record cut off");
+        assert!(wal.append(&record("k3", "v3"), now).unwrap());
+        drop(wal);
+
+        let (_, records) = Wal::open(&path, FsyncMode::Always, Duration::ZERO).unwrap();
+        assert_eq!(records.iter().map(|r| r.key.as_str()).collect::<Vec<_>>(), vec!["k1", "k3"]);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_fsync_mode_config() {
+        let cfg = DynamoConfig::from_toml_str("wal_dir = \"/var/lib/dynamo\"\nwal_fsync = \"interval\"\nwal_group_commit_ms = 5").unwrap();
+        assert_eq!((cfg.wal_fsync, cfg.wal_group_commit_ms), (FsyncMode::Interval, 5));
+        assert_eq!(DynamoConfig { node_id: "nodeA".to_string(), ..cfg }.wal_path(), Some(PathBuf::from("/var/lib/dynamo/nodeA.wal")));
+        assert!(DynamoConfig::from_toml_str("wal_fsync = \"sometimes\"").is_err());
+        assert_eq!(DynamoConfig::default().wal_path(), None, "in memory unless wal_dir is set");
+    }
+}
@@ -0,0 +1,182 @@
+// Workload Tests
+// YCSB-style operation mix, key and value-size distributions, and open/closed-loop bench clients
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use dynamo_new::bench_client::BenchClient;
+use dynamo_new::clock::ManualClock;
+use dynamo_new::messages::{ClientToNode, DynamoClientIn, DynamoClientOut, GeneratorTrigger, NodeToClient};
+use dynamo_new::vector_clock::VectorClock;
+use dynamo_new::workload::{KeyDistribution, OpType, ValueSize, Workload, WorkloadConfig, WorkloadOp, Zipfian};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use reactor_actor::ActorProcess;
+
+fn config(operations: usize) -> WorkloadConfig {
+    WorkloadConfig { operations, seed: Some(42), ..WorkloadConfig::default() }
+}
+
+fn key_counts(cfg: WorkloadConfig) -> HashMap<String, usize> {
+    let mut w = Workload::new(cfg, "bench1");
+    let mut counts = HashMap::new();
+    while let Some(op) = w.next_op() {
+        let key = match op { WorkloadOp::Read{ key } | WorkloadOp::Update{ key, .. } => key, WorkloadOp::Scan{ keys } => keys[0].clone() };
+        *counts.entry(key).or_default() += 1;
+    }
+    counts
+}
+
+fn requests(out: &[DynamoClientOut]) -> Vec<ClientToNode> {
+    out.iter().map(|DynamoClientOut::ClientToNode(r)| r.clone()).collect()
+}
+
+// The response a node would send for `req`
+fn answer(client: &mut BenchClient, req: &ClientToNode) -> Vec<DynamoClientOut> {
+    let rsp = match req {
+        ClientToNode::ClientPut{ key, request_id, client_addr, .. } => NodeToClient::ClientPutRsp { key: key.clone(), request_id: *request_id, clock: VectorClock::new(), client_addr: client_addr.clone() },
+        ClientToNode::ClientGet{ key, request_id, client_addr } => NodeToClient::ClientGetRsp { key: key.clone(), request_id: *request_id, values: vec![], metadata: vec![], client_addr: client_addr.clone() },
+    };
+    client.process(DynamoClientIn::NodeToClient(rsp))
+}
+
+fn trigger(client: &mut BenchClient) -> Vec<DynamoClientOut> {
+    client.process(DynamoClientIn::GeneratorTrigger(GeneratorTrigger))
+}
+
+#[cfg(test)]
+mod workload_tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_payload_json() {
+        let cfg: WorkloadConfig = serde_json::from_value(serde_json::json!({
+            "operations": 500, "read_proportion": 0.95, "update_proportion": 0.05, "key_distribution": "zipfian",
+            "value_size": { "dist": "uniform", "min": 10, "max": 100 }, "target_ops_per_sec": 200.0, "max_outstanding": 8
+        })).unwrap();
+        assert_eq!(cfg.operations, 500);
+        assert_eq!(cfg.key_distribution, KeyDistribution::Zipfian);
+        assert_eq!(cfg.value_size, ValueSize::Uniform { min: 10, max: 100 });
+        assert_eq!(cfg.record_count, 100, "unset fields keep their defaults");
+        cfg.validate().unwrap();
+
+        assert!(WorkloadConfig { max_outstanding: 0, ..cfg.clone() }.validate().is_err());
+        assert!(WorkloadConfig { value_size: ValueSize::Uniform { min: 5, max: 1 }, ..cfg }.validate().is_err());
+    }
+
+    #[test]
+    fn test_operation_mix_follows_proportions() {
+        let cfg = WorkloadConfig { read_proportion: 0.7, update_proportion: 0.2, scan_proportion: 0.1, ..config(10_000) };
+        let mut w = Workload::new(cfg, "bench1");
+        let mut counts: HashMap<OpType, usize> = HashMap::new();
+        while let Some(op) = w.next_op() {
+            if let WorkloadOp::Scan{ keys } = &op { assert!((1..=10).contains(&keys.len())); }
+            *counts.entry(op.op_type()).or_default() += 1;
+        }
+        assert_eq!(counts.values().sum::<usize>(), 10_000);
+        assert!((6_700..7_300).contains(&counts[&OpType::Read]));
+        assert!((1_800..2_200).contains(&counts[&OpType::Update]));
+        assert!((800..1_200).contains(&counts[&OpType::Scan]));
+    }
+
+    #[test]
+    fn test_same_seed_same_operations() {
+        let ops = |seed| { let mut w = Workload::new(WorkloadConfig { seed: Some(seed), ..config(100) }, "bench1"); std::iter::from_fn(move || w.next_op()).collect::<Vec<_>>() };
+        assert_eq!(ops(1), ops(1));
+        assert_ne!(ops(1), ops(2));
+    }
+
+    #[test]
+    fn test_zipfian_favours_low_ranks() {
+        let z = Zipfian::new(1_000, 0.99);
+        let mut rng = StdRng::seed_from_u64(7);
+        let samples: Vec<u64> = (0..10_000).map(|_| z.sample(&mut rng)).collect();
+        assert!(samples.iter().all(|s| *s < 1_000));
+        let zeros = samples.iter().filter(|s| **s == 0).count();
+        let tail = samples.iter().filter(|s| **s >= 500).count();
+        assert!(zeros > 1_000, "rank 0 drew {} of 10000", zeros);
+        assert!(zeros > tail);
+    }
+
+    #[test]
+    fn test_key_distributions_skew() {
+        let hottest = |dist| key_counts(WorkloadConfig { key_distribution: dist, record_count: 1_000, ..config(10_000) }).values().copied().max().unwrap();
+        assert!(hottest(KeyDistribution::Uniform) < 40);
+        assert!(hottest(KeyDistribution::Zipfian) > 500);
+
+        // latest: the most recently updated key is the one most likely to be picked next
+        let mut w = Workload::new(WorkloadConfig { key_distribution: KeyDistribution::Latest, record_count: 1_000, ..config(10_000) }, "bench1");
+        let (mut last_update, mut repeats) = (None, 0);
+        while let Some(op) = w.next_op() {
+            let (key, update) = match op { WorkloadOp::Read{ key } => (key, false), WorkloadOp::Update{ key, .. } => (key, true), WorkloadOp::Scan{ .. } => continue };
+            if last_update.as_ref() == Some(&key) { repeats += 1; }
+            if update { last_update = Some(key); }
+        }
+        assert!(repeats > 1_000, "{} of 10000 ops hit the latest update", repeats);
+    }
+
+    #[test]
+    fn test_value_sizes_stay_in_range() {
+        for value_size in [ValueSize::Constant { bytes: 64 }, ValueSize::Uniform { min: 10, max: 20 }, ValueSize::Zipfian { min: 100, max: 200 }] {
+            let mut w = Workload::new(WorkloadConfig { read_proportion: 0.0, update_proportion: 1.0, value_size, ..config(1_000) }, "bench1");
+            while let Some(op) = w.next_op() {
+                let WorkloadOp::Update{ value_len, .. } = op else { panic!("updates only") };
+                match value_size {
+                    ValueSize::Constant{ bytes } => assert_eq!(value_len, bytes),
+                    ValueSize::Uniform{ min, max } | ValueSize::Zipfian{ min, max } => assert!((min..=max).contains(&value_len)),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_closed_loop_keeps_max_outstanding() {
+        let cfg = WorkloadConfig { max_outstanding: 4, ..config(10) };
+        let mut client = BenchClient::new("bench1".to_string(), vec!["nodeA".to_string()], cfg);
+        let mut pending = requests(&trigger(&mut client));
+        assert_eq!(pending.len(), 4);
+        while let Some(req) = pending.pop() {
+            pending.extend(requests(&answer(&mut client, &req)));
+            assert!(client.outstanding() <= 4);
+        }
+        assert_eq!(client.completed(), 10);
+        assert!(client.done_flag().load(std::sync::atomic::Ordering::Relaxed));
+        assert_eq!(client.summary(OpType::Read).count + client.summary(OpType::Update).count, 10);
+    }
+
+    #[test]
+    fn test_open_loop_arrivals_and_coordinated_omission() {
+        let clock = ManualClock::new();
+        let cfg = WorkloadConfig { target_ops_per_sec: 1_000.0, max_outstanding: 2, ..config(100) };
+        let mut client = BenchClient::new("bench1".to_string(), vec!["nodeA".to_string()], cfg).with_clock(Arc::new(clock.clone()));
+        assert_eq!(requests(&trigger(&mut client)).len(), 1, "one arrival is due at start");
+
+        // 10ms later ten more arrivals are due, but only one slot is free
+        clock.advance(Duration::from_millis(10));
+        let sent = requests(&trigger(&mut client));
+        assert_eq!(sent.len(), 1);
+        assert_eq!(client.outstanding(), 2);
+
+        // a completion frees a slot for the oldest queued arrival, timed from when it was due
+        clock.advance(Duration::from_millis(5));
+        let next = requests(&answer(&mut client, &sent[0]));
+        assert_eq!(next.len(), 1);
+        let latency = client.summary(OpType::Read).max_us.max(client.summary(OpType::Update).max_us);
+        assert!(latency >= 14_000, "latency {}us measured from the intended arrival", latency);
+    }
+
+    #[test]
+    fn test_scan_completes_after_every_get() {
+        let cfg = WorkloadConfig { read_proportion: 0.0, update_proportion: 0.0, scan_proportion: 1.0, max_scan_length: 5, ..config(1) };
+        let mut client = BenchClient::new("bench1".to_string(), vec!["nodeA".to_string()], cfg);
+        let gets = requests(&trigger(&mut client));
+        assert!(!gets.is_empty() && gets.len() <= 5);
+        assert!(gets.iter().all(|r| matches!(r, ClientToNode::ClientGet { .. })));
+        for g in gets.iter() {
+            assert_eq!(client.completed(), 0);
+            answer(&mut client, g);
+        }
+        assert_eq!(client.completed(), 1);
+        assert_eq!(client.summary(OpType::Scan).count, 1);
+    }
+}
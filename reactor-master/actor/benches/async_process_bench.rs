@@ -0,0 +1,127 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use bincode::{Decode, Encode};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use futures::future::join_all;
+use reactor_actor::{
+    ActorProcess, AsyncActorProcess, BehaviourBuilder, HasPriority, Msg, NodeComm, RuntimeCtx,
+    codec::BincodeCodec,
+};
+use tokio::sync::mpsc;
+
+const MSGS_PER_ACTOR: u64 = 1_000;
+
+#[derive(Encode, Decode, Debug, Clone)]
+struct Num(u64);
+impl HasPriority for Num {}
+impl Msg for Num {}
+
+struct BlockingSum(u64);
+impl ActorProcess for BlockingSum {
+    type IMsg = Num;
+    type OMsg = Num;
+    fn process(&mut self, input: Num) -> Vec<Num> {
+        self.0 += input.0;
+        vec![]
+    }
+}
+
+struct AsyncSum(u64);
+impl AsyncActorProcess for AsyncSum {
+    type IMsg = Num;
+    type OMsg = Num;
+    async fn process(&mut self, input: Num) -> Vec<Num> {
+        self.0 += input.0;
+        vec![]
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Blocking,
+    Async,
+}
+
+/// A node-local context whose controller is already gone, so the actor only runs its generator.
+fn ctx() -> RuntimeCtx {
+    let (_inst_tx, inst_rx) = mpsc::channel(1);
+    let (req_tx, _req_rx) = mpsc::channel(1);
+    RuntimeCtx::new("bench", NodeComm::new(inst_rx, req_tx))
+}
+
+async fn run_actors(kind: Kind, actors: usize) {
+    let runs = (0..actors).map(|_| {
+        let msgs = (0..MSGS_PER_ACTOR).map(Num);
+        tokio::spawn(async move {
+            match kind {
+                Kind::Blocking => {
+                    BehaviourBuilder::new(BlockingSum(0), BincodeCodec::default())
+                        .generator(msgs)
+                        .build()
+                        .run(ctx())
+                        .await
+                }
+                Kind::Async => {
+                    BehaviourBuilder::new_async(AsyncSum(0), BincodeCodec::default())
+                        .generator(msgs)
+                        .build()
+                        .run(ctx())
+                        .await
+                }
+            }
+        })
+    });
+    for run in join_all(runs).await {
+        run.unwrap().unwrap();
+    }
+}
+
+/// A runtime that records the most threads it had alive at once.
+fn runtime() -> (tokio::runtime::Runtime, Arc<AtomicUsize>) {
+    let live = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let (start_live, stop_live, start_peak) = (live.clone(), live, peak.clone());
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .on_thread_start(move || {
+            let now = start_live.fetch_add(1, Ordering::SeqCst) + 1;
+            start_peak.fetch_max(now, Ordering::SeqCst);
+        })
+        .on_thread_stop(move || {
+            stop_live.fetch_sub(1, Ordering::SeqCst);
+        })
+        .build()
+        .unwrap();
+    (rt, peak)
+}
+
+fn bench_processors(c: &mut Criterion) {
+    let mut group = c.benchmark_group("processor");
+    group.sample_size(10);
+    for actors in [16, 128] {
+        group.throughput(Throughput::Elements(actors as u64 * MSGS_PER_ACTOR));
+        for kind in [Kind::Blocking, Kind::Async] {
+            let (rt, peak) = runtime();
+            group.bench_with_input(
+                BenchmarkId::new(format!("{:?}", kind), actors),
+                &actors,
+                |b, &actors| b.iter(|| rt.block_on(run_actors(kind, actors))),
+            );
+            // generators run as async tasks for both kinds, so the difference is the processors
+            println!(
+                "processor/{:?}/{}: peak threads {}",
+                kind,
+                actors,
+                peak.load(Ordering::SeqCst)
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_processors);
+criterion_main!(benches);
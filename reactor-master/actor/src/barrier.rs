@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use tokio::sync::watch;
+
+/// Rounds reached by an actor's input channels, behind [`crate::ChannelAction::SYNC`].
+pub(crate) struct SyncBarrier {
+    rounds: watch::Sender<HashMap<u64, u16>>,
+    next_seat: AtomicU64,
+}
+
+impl SyncBarrier {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(SyncBarrier {
+            rounds: watch::Sender::new(HashMap::new()),
+            next_seat: AtomicU64::new(0),
+        })
+    }
+
+    /// Registers an input channel for as long as the seat is held. A channel joining late starts
+    /// at the lowest round of the others, so it does not hold back a round already under way.
+    pub(crate) fn join(self: &Arc<Self>) -> BarrierSeat {
+        let id = self.next_seat.fetch_add(1, Ordering::Relaxed);
+        self.rounds.send_modify(|rounds| {
+            let lowest = rounds.values().min().copied().unwrap_or(0);
+            rounds.insert(id, lowest);
+        });
+        BarrierSeat {
+            barrier: self.clone(),
+            id,
+        }
+    }
+}
+
+pub(crate) struct BarrierSeat {
+    barrier: Arc<SyncBarrier>,
+    id: u64,
+}
+
+impl BarrierSeat {
+    /// Marks the channel as having reached `round`, then waits until every registered channel has.
+    pub(crate) async fn sync(&self, round: u16) {
+        self.barrier.rounds.send_modify(|rounds| {
+            let reached = rounds.entry(self.id).or_default();
+            *reached = round.max(*reached);
+        });
+        let mut rounds = self.barrier.rounds.subscribe();
+        // the sender lives in the barrier this seat holds, so it cannot close
+        let _ = rounds
+            .wait_for(|rounds| rounds.values().all(|reached| *reached >= round))
+            .await;
+    }
+}
+
+impl Drop for BarrierSeat {
+    fn drop(&mut self) {
+        // a closed channel no longer holds the others back
+        self.barrier.rounds.send_modify(|rounds| {
+            rounds.remove(&self.id);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    async fn finishes<F: Future>(f: F) -> bool {
+        tokio::time::timeout(Duration::from_millis(50), f)
+            .await
+            .is_ok()
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_every_channel() {
+        let barrier = SyncBarrier::new();
+        let (a, b, c) = (barrier.join(), barrier.join(), barrier.join());
+        let waiting = tokio::spawn(async move {
+            a.sync(1).await;
+            a
+        });
+        // b's round stays reached after its wait gives up
+        assert!(!finishes(b.sync(1)).await);
+        assert!(!waiting.is_finished(), "c has not reached round 1");
+
+        let c_waits = tokio::spawn(async move { c.sync(1).await });
+        assert!(finishes(c_waits).await);
+        assert!(finishes(waiting).await);
+        assert!(finishes(b.sync(1)).await, "round 1 is complete");
+    }
+
+    #[tokio::test]
+    async fn test_closed_and_late_channels() {
+        let barrier = SyncBarrier::new();
+        let (a, b) = (barrier.join(), barrier.join());
+        assert!(!finishes(a.sync(2)).await);
+        drop(b);
+        assert!(
+            finishes(a.sync(2)).await,
+            "a closed channel releases the round"
+        );
+
+        let late = barrier.join();
+        assert!(finishes(a.sync(2)).await, "joins at round 2");
+        assert!(!finishes(a.sync(3)).await);
+        assert!(
+            finishes(async {
+                tokio::join!(a.sync(3), late.sync(3));
+            })
+            .await
+        );
+    }
+}
@@ -7,6 +7,12 @@ pub struct ChaosManager {
     pub msg_duplication_probability: Option<f32>,
 }
 
+impl Default for ChaosManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ChaosManager {
     pub fn new() -> Self {
         ChaosManager {
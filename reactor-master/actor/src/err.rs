@@ -20,6 +20,31 @@ pub enum ActorError {
     RecieverErr(#[from] RecieverErr),
     #[error("Task Join Error: {0}")]
     JoinErr(#[from] JoinError),
+    #[error("The {0} mailbox is full")]
+    MailboxFull(&'static str),
+    #[error("Panicked: {0}")]
+    Panicked(String),
+}
+
+impl ActorError {
+    /// Unwraps a task's result, keeping the message of a panic.
+    pub(crate) fn joined(res: Result<Result<(), ActorError>, JoinError>) -> Result<(), ActorError> {
+        match res {
+            Ok(res) => res,
+            Err(e) if e.is_panic() => {
+                let payload = e.into_panic();
+                let msg = match payload.downcast::<String>() {
+                    Ok(msg) => *msg,
+                    Err(payload) => payload
+                        .downcast_ref::<&str>()
+                        .map_or("non-string panic payload", |msg| msg)
+                        .to_string(),
+                };
+                Err(ActorError::Panicked(msg))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
 }
 
 pub struct DecodeErr;
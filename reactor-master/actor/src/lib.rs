@@ -1,34 +1,47 @@
 // #![feature(log_syntax)]
 
-use std::{borrow::Cow, collections::HashMap, marker::PhantomData};
+use std::{borrow::Cow, collections::HashMap, marker::PhantomData, sync::Arc, time::Duration};
 
 use bincode::{Decode, Encode};
 use err::ActorError;
 use futures::future::join_all;
 pub use inventory as __inventory;
 use reactor_channel::{ReactorChannelTx, reactor_channel};
-use recv::rx;
+use recv::{processor_channel_size, rx};
 use send::tx;
-use tokio::{
-    sync::mpsc::{self},
-    task::JoinHandle,
-};
+use tokio::sync::mpsc::{self};
 use tokio_util::codec::{Decoder, Encoder};
 pub use tracing_shared::setup_shared_logger_ref;
 
+mod barrier;
 mod chaos_manager;
 pub mod codec;
 pub mod err;
+mod mailbox;
 mod node_comm;
+mod process;
 mod reactor_channel;
 mod recv;
+mod reliable;
 mod send;
+mod timer;
 
-pub use node_comm::{Connection, ControlInst, ControlReq, NodeComm};
+pub use mailbox::{MailboxCfg, MailboxMetrics, Overflow, QueueDepth};
+pub use node_comm::{Connection, ControlInst, ControlReq, Exit, NodeComm};
+pub use process::AsyncProcess;
 pub use reactor_channel::{HasPriority, MAX_PRIO};
 pub use reactor_macros::actor;
+pub use reliable::Reliable;
+pub use send::probe_tcp;
+pub use timer::{CorrelationId, TimerCmd, TimerHandle, TimerId, TimerQueue, timer_channel};
 
-use crate::codec::ErrWithMsg;
+use crate::{
+    codec::ErrWithMsg,
+    mailbox::Mailboxes,
+    node_comm::DrainDeadline,
+    process::{ProcIo, Processor},
+    reliable::{Inbound, Reliability},
+};
 
 pub type ActorSpawnCB = fn(RuntimeCtx, HashMap<String, serde_json::Value>);
 
@@ -60,7 +73,13 @@ fn get_registered() -> Vec<String> {
 
 static CHANNEL_SIZE: usize = 1 << 20;
 /// Messages that can flow between the actors.
-pub trait Msg: Send + Sync + std::fmt::Debug + HasPriority + 'static + Clone {}
+pub trait Msg: Send + Sync + std::fmt::Debug + HasPriority + 'static + Clone {
+    /// The ask this message answers, if it is a reply; see [`TimerHandle::ask`]. `#[derive(Msg)]`
+    /// returns the field marked `#[reply_to]`.
+    fn reply_to(&self) -> Option<CorrelationId> {
+        None
+    }
+}
 
 /// Addr of the actors
 pub type ActorAddr = String;
@@ -148,7 +167,9 @@ impl Msg for EmptyMsg {}
 ///   Silently discard the message without processing or forwarding.
 ///
 /// - `SYNC(u16)`:
-///   Perform a synchronization operation with the provided sync identifier (e.g., a sync round or epoch number).
+///   Marks the channel as having reached the given round, and holds the message until every input
+///   channel of the actor has reached it too. The channel reads nothing more meanwhile; a closed
+///   channel no longer counts. This gives round-based protocols a barrier across their inputs.
 ///
 /// - `CLOSE`:
 ///   Signals that the channel should be closed and no more messages will be received from it.
@@ -164,10 +185,12 @@ pub enum ChannelAction {
 #[derive(Debug)]
 enum R2PMsg<T> {
     Msg(T, &'static str),
+    /// From a reliable channel, which applies chaos itself.
+    Reliable(T, &'static str),
     Exit,
-    #[allow(dead_code)]
+    /// Stop once the messages already in the channel are processed, or at the deadline.
+    Drain(tokio::time::Instant),
     AddPrio(mpsc::Receiver<R2PMsg<T>>),
-    #[allow(dead_code)]
     RemoveLowPrio,
     SetMsgDuplication {
         factor: u32,
@@ -184,6 +207,7 @@ impl<T: Clone> Clone for R2PMsg<T> {
     fn clone(&self) -> Self {
         match self {
             R2PMsg::Msg(m, origin) => R2PMsg::Msg(m.clone(), origin),
+            R2PMsg::Reliable(m, origin) => R2PMsg::Reliable(m.clone(), origin),
             _ => panic!("Shouldn't Clone this"),
         }
     }
@@ -193,7 +217,9 @@ impl<T: PartialEq> PartialEq for R2PMsg<T> {
         match (self, other) {
             (R2PMsg::AddPrio(_), _) => panic!("Can't Compare"),
             (R2PMsg::Msg(m1, _), R2PMsg::Msg(m2, _)) => m1.eq(m2),
+            (R2PMsg::Reliable(m1, _), R2PMsg::Reliable(m2, _)) => m1.eq(m2),
             (R2PMsg::Exit, R2PMsg::Exit) => true,
+            (R2PMsg::Drain(d1), R2PMsg::Drain(d2)) => d1 == d2,
             _ => false,
         }
     }
@@ -203,7 +229,9 @@ impl<T: HasPriority> HasPriority for R2PMsg<T> {
     fn priority(&self) -> usize {
         match self {
             R2PMsg::Msg(t, _) => t.priority(),
+            R2PMsg::Reliable(t, _) => t.priority(),
             R2PMsg::Exit => MAX_PRIO,
+            R2PMsg::Drain(_) => MAX_PRIO,
             R2PMsg::AddPrio(_) => MAX_PRIO,
             R2PMsg::RemoveLowPrio => MAX_PRIO,
             R2PMsg::SetMsgLoss { .. } => MAX_PRIO,
@@ -281,6 +309,52 @@ pub trait ActorProcess: Send + 'static {
     ///
     /// A vector of output messages of type [`Self::OMsg`].
     fn process(&mut self, input: Self::IMsg) -> Vec<Self::OMsg>;
+
+    /// Called once the processor stops without failing, after its last message, e.g. to
+    /// persist state.
+    fn on_stop(&mut self) {}
+}
+
+/// An [`ActorProcess`] whose processing may await, e.g. on a timer or another task.
+///
+/// A blocking [`ActorProcess`] holds a thread of the blocking pool for as long as the actor
+/// runs. An `AsyncActorProcess`, selected with [`BehaviourBuilder::new_async`], runs as a task on
+/// the async workers instead, so a node can host many more actors than it has threads. It must
+/// not block: work that does should go through [`tokio::task::spawn_blocking`].
+///
+/// # Example
+/// ```ignore
+/// struct Delayer;
+///
+/// impl AsyncActorProcess for Delayer {
+///     type IMsg = i32;
+///     type OMsg = i32;
+///
+///     async fn process(&mut self, input: i32) -> Vec<i32> {
+///         tokio::time::sleep(Duration::from_millis(10)).await;
+///         vec![input]
+///     }
+/// }
+///
+pub trait AsyncActorProcess: Send + 'static {
+    /// The type of messages this actor accepts as input.
+    type IMsg: Msg;
+
+    /// The type of messages this actor produces as output.
+    type OMsg: Msg;
+
+    /// Processes an input message and returns a list of output messages. Messages are processed
+    /// one at a time, in the order they are received.
+    fn process(
+        &mut self,
+        input: Self::IMsg,
+    ) -> impl std::future::Future<Output = Vec<Self::OMsg>> + Send;
+
+    /// Called once the processor stops without failing, after its last message, e.g. to
+    /// persist state.
+    fn on_stop(&mut self) -> impl std::future::Future<Output = ()> + Send {
+        async {}
+    }
 }
 
 /// The `ActorSend` trait defines how an actor determines the recipients of a message
@@ -339,6 +413,9 @@ impl<M: Msg> ActorSend for NoOpActorSend<M> {
 /// - Retry: retries sending the message, with parameters:
 ///   - attempts: maximum number of retry attempts (None for infinite retries).
 ///   - delay_ms: delay in milliseconds between retry attempts.
+///
+/// A failed write to a TCP destination means the connection broke, so there a retried message
+/// waits for the next connection instead of `delay_ms`, see [`Reconnect`].
 #[derive(Copy, Clone, Debug)]
 pub enum SendErrAction {
     Drop,
@@ -348,6 +425,35 @@ pub enum SendErrAction {
     },
 }
 
+/// How a sender gets a broken TCP connection to a destination back. The destination is resolved
+/// again before each connection attempt, so an actor restarted elsewhere is found at its new
+/// address. Waits between attempts double from `initial_backoff_ms` up to `max_backoff_ms`.
+#[derive(Copy, Clone, Debug)]
+pub struct Reconnect {
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Attempts in a row before the destination is given up and its buffer dropped. Defaults to
+    /// 20, over a minute of waits with the default backoff. `None` keeps trying, also while the
+    /// destination cannot be resolved, so its buffer grows unless
+    /// [`BehaviourBuilder::destination_buffers`] bounds it.
+    pub max_attempts: Option<u16>,
+    /// Last messages written on a connection, written again on the next one. A write only reaches
+    /// the socket buffer, so messages written shortly before a connection broke may be lost; a
+    /// non-zero window delivers them at least once.
+    pub replay_window: usize,
+}
+
+impl Default for Reconnect {
+    fn default() -> Self {
+        Reconnect {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 5_000,
+            max_attempts: Some(20),
+            replay_window: 0,
+        }
+    }
+}
+
 /// The `Behaviour` struct encapsulates the complete behavior of an actor,
 /// including how it receives messages, processes them, sends output,
 /// and optionally generates new messages.
@@ -355,7 +461,8 @@ pub enum SendErrAction {
 /// # Type Parameters
 ///
 /// - `R`: The type implementing the receiving behavior (must implement [`ActorRecv`]).
-/// - `P`: The type implementing the processing behavior (must implement [`ActorProcess`]).
+/// - `P`: The type implementing the processing behavior (an [`ActorProcess`], or an
+///   [`AsyncActorProcess`] wrapped in [`AsyncProcess`]).
 /// - `S`: The type implementing the sending behavior (must implement [`ActorSend`]).
 /// - `M`: The type of message generated internally (e.g., from generators).
 ///
@@ -365,18 +472,29 @@ pub enum SendErrAction {
 /// - `proc`: The core processing logic implementing `ActorProcess`.
 /// - `send`: Optional sender logic implementing `ActorSend`.
 /// - `generators`: A list of internal message generators, producing messages of type `M`.
+/// - `ticks`: Messages of type `M` delivered to the processor periodically.
+/// - `timers`: Queue of the [`TimerHandle`] the processor sets its own timers with.
 /// - `on_send_failure`: Action to take when sending a message fails. Defaults to retrying 5 times with 100ms delay.
+/// - `reconnect`: How broken TCP connections to destinations are reopened, see [`Reconnect`].
+/// - `reliability`: Which outgoing channels are reliable, see [`Reliable`].
 ///
 pub struct Behaviour<R, P, S, M: 'static, MCD> {
     recv: Option<R>,
     proc: P,
     send: Option<S>,
     generators: Vec<Box<dyn Iterator<Item = M> + Send>>,
+    ticks: Vec<(Duration, M)>,
+    timers: Option<TimerQueue<M>>,
     num_prios: u8,
+    prio_weights: Option<Vec<u32>>,
+    mailboxes: Mailboxes,
     master_codec: MCD,
     sub_decoders: Option<SubDecoderStore<M>>,
     receiver_should_adapt: bool,
     on_send_failure: SendErrAction,
+    reconnect: Reconnect,
+    reliability: Reliability,
+    metrics: MailboxMetrics,
 }
 
 pub struct DecoderProvider<M> {
@@ -391,12 +509,18 @@ pub struct BehaviourBuilder<R, P, S, IM: 'static, OM, MCD> {
     proc: P,
     send: Option<S>,
     generators: Vec<Box<dyn Iterator<Item = IM> + Send>>,
+    ticks: Vec<(Duration, IM)>,
+    timers: Option<TimerQueue<IM>>,
     num_prios: u8,
+    prio_weights: Option<Vec<u32>>,
+    mailboxes: Mailboxes,
     master_codec: MCD,
     sub_decoders: Option<SubDecoderStore<IM>>,
     ask_recver_to_adapt: bool,
     m: PhantomData<OM>,
     on_send_failure: SendErrAction,
+    reconnect: Reconnect,
+    reliability: Reliability,
 }
 
 impl<P, IM, OM, MCD> BehaviourBuilder<NoOpActorRecv<IM>, P, NoOpActorSend<OM>, IM, OM, MCD> {
@@ -406,7 +530,15 @@ impl<P, IM, OM, MCD> BehaviourBuilder<NoOpActorRecv<IM>, P, NoOpActorSend<OM>, I
             proc,
             send: None,
             generators: vec![],
+            ticks: vec![],
+            timers: None,
             num_prios: 1,
+            prio_weights: None,
+            mailboxes: Mailboxes {
+                inbox: MailboxCfg::new(CHANNEL_SIZE, Overflow::Block),
+                outbox: None,
+                destinations: None,
+            },
             m: PhantomData,
             master_codec,
             sub_decoders: None,
@@ -415,10 +547,21 @@ impl<P, IM, OM, MCD> BehaviourBuilder<NoOpActorRecv<IM>, P, NoOpActorSend<OM>, I
                 attempts: Some(5),
                 delay_ms: 100,
             },
+            reconnect: Reconnect::default(),
+            reliability: Reliability::default(),
         }
     }
 }
 
+impl<P, IM, OM, MCD>
+    BehaviourBuilder<NoOpActorRecv<IM>, AsyncProcess<P>, NoOpActorSend<OM>, IM, OM, MCD>
+{
+    /// Like [`BehaviourBuilder::new`], for an [`AsyncActorProcess`].
+    pub fn new_async(proc: P, master_codec: MCD) -> Self {
+        BehaviourBuilder::new(AsyncProcess(proc), master_codec)
+    }
+}
+
 impl<R, P, S, IM, OM, MCD> BehaviourBuilder<R, P, S, IM, OM, MCD> {
     pub fn recv<R1>(self, recv: R1) -> BehaviourBuilder<R1, P, S, IM, OM, MCD>
     where
@@ -429,12 +572,18 @@ impl<R, P, S, IM, OM, MCD> BehaviourBuilder<R, P, S, IM, OM, MCD> {
             proc: self.proc,
             send: self.send,
             generators: self.generators,
+            ticks: self.ticks,
+            timers: self.timers,
             num_prios: self.num_prios,
+            prio_weights: self.prio_weights,
+            mailboxes: self.mailboxes,
             m: self.m,
             master_codec: self.master_codec,
             sub_decoders: self.sub_decoders,
             ask_recver_to_adapt: self.ask_recver_to_adapt,
             on_send_failure: self.on_send_failure,
+            reconnect: self.reconnect,
+            reliability: self.reliability,
         }
     }
     pub fn send<S1>(self, send: S1) -> BehaviourBuilder<R, P, S1, IM, OM, MCD>
@@ -446,12 +595,18 @@ impl<R, P, S, IM, OM, MCD> BehaviourBuilder<R, P, S, IM, OM, MCD> {
             proc: self.proc,
             send: Some(send),
             generators: self.generators,
+            ticks: self.ticks,
+            timers: self.timers,
             num_prios: self.num_prios,
+            prio_weights: self.prio_weights,
+            mailboxes: self.mailboxes,
             m: self.m,
             master_codec: self.master_codec,
             sub_decoders: self.sub_decoders,
             ask_recver_to_adapt: self.ask_recver_to_adapt,
             on_send_failure: self.on_send_failure,
+            reconnect: self.reconnect,
+            reliability: self.reliability,
         }
     }
 
@@ -478,11 +633,43 @@ impl<R, P, S, IM, OM, MCD> BehaviourBuilder<R, P, S, IM, OM, MCD> {
         self
     }
 
+    /// Delivers `msg` to the processor every `period`, starting one period after the actor starts.
+    pub fn tick(mut self, period: Duration, msg: IM) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
+        self.ticks.push((period, msg));
+        self
+    }
+
+    /// Serves the timers the processor sets through the [`TimerHandle`] paired with `queue`.
+    pub fn timers(mut self, queue: TimerQueue<IM>) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
+        self.timers = Some(queue);
+        self
+    }
+
     pub fn num_prios(mut self, num: u8) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
         self.num_prios = num;
         self
     }
 
+    /// Uses one priority per weight, like [`Self::num_prios`], but shares the processor between
+    /// them: per round, priority `i` gets up to `weights[i]` messages before lower priorities
+    /// that are waiting get theirs. Without this, a busy high priority starves the lower ones.
+    ///
+    /// # Panics
+    /// If there are fewer than 2 weights, or a weight is 0.
+    pub fn weighted_prios(mut self, weights: Vec<u32>) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
+        assert!(
+            weights.len() > 1 && weights.len() <= u8::MAX as usize,
+            "weighted priorities need 2 to 255 weights"
+        );
+        assert!(
+            weights.iter().all(|w| *w > 0),
+            "priority weights must be positive"
+        );
+        self.num_prios = weights.len() as u8;
+        self.prio_weights = Some(weights);
+        self
+    }
+
     pub fn ask_receiver_to_adapt(mut self) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
         self.ask_recver_to_adapt = true;
         self
@@ -504,17 +691,89 @@ impl<R, P, S, IM, OM, MCD> BehaviourBuilder<R, P, S, IM, OM, MCD> {
         self
     }
 
+    /// Overrides how broken connections to destinations are reopened, see [`Reconnect`].
+    pub fn reconnect(mut self, reconnect: Reconnect) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Makes every channel this actor sends on reliable, see [`Reliable`]. The receivers need no
+    /// setting; they learn it from the channel's handshake.
+    pub fn reliable(mut self, cfg: Reliable) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
+        self.reliability.all = Some(cfg);
+        self
+    }
+
+    /// Makes the channel to `addr` reliable, taking precedence over [`Self::reliable`].
+    pub fn reliable_to(
+        mut self,
+        addr: impl Into<ActorAddr>,
+        cfg: Reliable,
+    ) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
+        self.reliability.to.insert(addr.into(), cfg);
+        self
+    }
+
+    /// Messages each priority of the inbox holds before its overflow policy applies, see
+    /// [`Self::inbox`]. Defaults to 2^20.
+    pub fn inbox_capacity(mut self, capacity: usize) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
+        assert!(capacity > 0, "the inbox needs room for a message");
+        self.mailboxes.inbox.capacity = capacity;
+        self
+    }
+
+    /// Bounds each priority of the inbox, where received messages wait for the processor, and
+    /// sets what the receivers do with a message once it is full. Defaults to 2^20 messages and
+    /// [`Overflow::Block`]. Reliable channels always wait for room, since they acked what they
+    /// forward; so do the generators, the timers and the node's instructions.
+    ///
+    /// With [`Overflow::Fail`], a message that finds the inbox full is refused and counted as
+    /// dropped, and the channel it came on is closed so that its sender's `on_send_failure`
+    /// applies. The actor and its other channels go on.
+    ///
+    /// With [`Overflow::DropOldest`], received messages wait in a queue per priority that the
+    /// receivers own, where the oldest one can still be dropped, and the processor's own queue
+    /// holds a single message per priority.
+    pub fn inbox(mut self, cfg: MailboxCfg) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
+        self.mailboxes.inbox = cfg;
+        self
+    }
+
+    /// Bounds the outbox, where the processor's output waits to be routed. Unbounded by default.
+    pub fn outbox(mut self, cfg: MailboxCfg) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
+        self.mailboxes.outbox = Some(cfg);
+        self
+    }
+
+    /// Bounds each destination's buffer, where routed messages wait to be written. Unbounded by
+    /// default. With [`Overflow::Block`] everywhere, a slow receiver slows this actor down in
+    /// turn instead of growing its memory.
+    pub fn destination_buffers(
+        mut self,
+        cfg: MailboxCfg,
+    ) -> BehaviourBuilder<R, P, S, IM, OM, MCD> {
+        self.mailboxes.destinations = Some(cfg);
+        self
+    }
+
     pub fn build(self) -> Behaviour<R, P, S, IM, MCD> {
         Behaviour {
             recv: self.recv,
             proc: self.proc,
             send: self.send,
             generators: self.generators,
+            ticks: self.ticks,
+            timers: self.timers,
             num_prios: self.num_prios,
+            prio_weights: self.prio_weights,
+            mailboxes: self.mailboxes,
             master_codec: self.master_codec,
             sub_decoders: self.sub_decoders,
             receiver_should_adapt: self.ask_recver_to_adapt,
             on_send_failure: self.on_send_failure,
+            reconnect: self.reconnect,
+            reliability: self.reliability,
+            metrics: MailboxMetrics::default(),
         }
     }
 }
@@ -529,6 +788,14 @@ impl<R, P, S, M, MCD> Behaviour<R, P, S, M, MCD> {
     fn take_generators(&mut self) -> Vec<Box<dyn Iterator<Item = M> + Send>> {
         std::mem::take(&mut self.generators)
     }
+    fn take_timers(&mut self) -> (Option<TimerQueue<M>>, Vec<(Duration, M)>) {
+        (self.timers.take(), std::mem::take(&mut self.ticks))
+    }
+
+    /// Queue depths of the actor, readable while it runs.
+    pub fn metrics(&self) -> MailboxMetrics {
+        self.metrics.clone()
+    }
 }
 
 #[derive(Debug)]
@@ -548,22 +815,53 @@ where
     IM: Msg,
     OM: Msg,
     R: ActorRecv<IMsg = IM>,
-    P: ActorProcess<IMsg = IM, OMsg = OM>,
+    P: Processor<IMsg = IM, OMsg = OM>,
     S: ActorSend<OMsg = OM>,
     MCD: Encoder<OM> + Decoder<Item = IM, Error = std::io::Error> + Send + Sync + Clone + 'static,
     <MCD as Encoder<OM>>::Error: Send + 'static + ErrWithMsg<OM>,
 {
-    pub async fn run(mut self, ctx: RuntimeCtx) -> Result<(), ActorError> {
+    /// Runs the actor until it is stopped or fails, then tells the node controller how it ended.
+    pub async fn run(self, ctx: RuntimeCtx) -> Result<(), ActorError> {
+        let addr = ctx.addr;
+        let supervisor = ctx.node_comm.controller_tx.clone();
+        let result = self.run_to_exit(ctx).await;
+        let exit = match &result {
+            Ok(()) => Exit::Stopped,
+            Err(ActorError::Panicked(msg)) => Exit::Panicked(msg.clone()),
+            Err(e) => Exit::Failed(e.to_string()),
+        };
+        if exit != Exit::Stopped {
+            tracing::error!("[ACTOR][{}] Exited: {:?}", addr, exit);
+        }
+        let exited = ControlReq::Exited {
+            addr: addr.to_string(),
+            exit,
+        };
+        // outside of a node nobody may be listening
+        let _ = supervisor.send(exited).await;
+        result
+    }
+
+    async fn run_to_exit(mut self, ctx: RuntimeCtx) -> Result<(), ActorError> {
         // let my_addr = ctx.addr.to_string();
-        let (p2s_tx, p2s_rx) = mpsc::unbounded_channel::<(OM, &'static str)>();
-        let (r2p_tx, mut r2p_rx) = reactor_channel::<R2PMsg<IM>>(self.num_prios, CHANNEL_SIZE);
+        let mailboxes = self.mailboxes;
+        let (p2s_tx, p2s_rx) = mailbox::mailbox::<(OM, &'static str)>(
+            mailboxes.outbox,
+            self.metrics.outbox_stats(mailboxes.outbox),
+        );
+        let (r2p_tx, mut r2p_rx) =
+            reactor_channel::<R2PMsg<IM>>(self.num_prios, processor_channel_size(mailboxes.inbox));
+        if let Some(weights) = self.prio_weights.take() {
+            r2p_rx.set_weights(weights);
+        }
 
         let (controller_rx, controller_tx) = ctx.node_comm.split();
 
         let reciever = self.take_recv();
         let sender = self.take_send();
         let mut generators = self.take_generators();
-        let mut processor = self.proc;
+        let (timers, ticks) = self.take_timers();
+        let asks = timers.as_ref().map(|t| t.asks());
 
         let gen_handles: Vec<tokio::task::JoinHandle<_>> = generators
             .drain(..)
@@ -573,91 +871,41 @@ where
             })
             .collect();
 
-        let rx_handle = tokio::spawn(rx(
+        let timer_handle = (timers.is_some() || !ticks.is_empty())
+            .then(|| tokio::spawn(timer::run_timers(timers, ticks, r2p_tx.clone())));
+
+        // reliable channels apply the chaos the processor is told to
+        let inbound = Inbound::default();
+        let chaos_manager = inbound.chaos();
+        let drain = Arc::new(DrainDeadline::default());
+        let inbox_stats = self
+            .metrics
+            .inbox_stats(mailboxes.inbox.capacity * self.num_prios as usize);
+        let mut rx_handle = tokio::spawn(rx(
             ctx.addr,
             reciever,
             r2p_tx,
             self.master_codec.clone(),
             self.sub_decoders,
             controller_rx,
+            mailboxes.inbox,
+            inbox_stats.clone(),
+            inbound,
+            drain.clone(),
+        ));
+        // the receivers' queues tell the inbox depth when they may drop the oldest message
+        let proc_stats = match mailboxes.inbox.overflow {
+            Overflow::DropOldest => Arc::default(),
+            _ => inbox_stats,
+        };
+        let mut proc_handle = self.proc.spawn(ProcIo::new(
+            ctx.addr,
+            r2p_rx,
+            p2s_tx,
+            proc_stats,
+            chaos_manager,
+            asks,
         ));
-
-        let addr = ctx.addr;
-        let mut chaos_manager = chaos_manager::ChaosManager::new();
-        let proc_handle: JoinHandle<Result<(), ActorError>> =
-            tokio::task::spawn_blocking(move || -> Result<(), ActorError> {
-                tracing::info!("[ACTOR][{}] Processor Started", addr);
-                loop {
-                    match r2p_rx.recv() {
-                        Some(R2PMsg::Msg(m, origin)) => {
-                            // Dont apply chaos to messages comming from generator
-                            let chaos_out = if origin.is_empty() {
-                                vec![m]
-                            } else {
-                                chaos_manager.apply_chaos(m)
-                            };
-                            let chaos_out_len = chaos_out.len();
-                            if chaos_out_len > 1 {
-                                tracing::warn!(
-                                    "[ACTOR][{}] Message Duplicated {} times",
-                                    addr,
-                                    chaos_out_len
-                                );
-                            } else if chaos_out_len == 0 {
-                                tracing::warn!("[ACTOR][{}] Message Lost", addr);
-                            }
-                            for msg in chaos_out {
-                                let processed = processor.process(msg);
-                                for o in processed {
-                                    p2s_tx.send((o, origin)).map_err(|_| ActorError::P2SErr)?;
-                                }
-                            }
-                        }
-                        Some(R2PMsg::AddPrio(new_rx)) => {
-                            r2p_rx = r2p_rx.add_prio(new_rx);
-                        }
-                        Some(R2PMsg::RemoveLowPrio) => {
-                            r2p_rx = r2p_rx.remove_prio();
-                        }
-                        Some(R2PMsg::Exit) => {
-                            break;
-                        }
-                        Some(R2PMsg::SetMsgDuplication {
-                            factor,
-                            probability,
-                        }) => {
-                            tracing::info!(
-                                "[ACTOR][{}] Setting Msg Duplication: factor={}, probability={}",
-                                addr,
-                                factor,
-                                probability
-                            );
-                            chaos_manager.set_msg_duplication(factor, probability);
-                        }
-                        Some(R2PMsg::SetMsgLoss { probability }) => {
-                            tracing::info!(
-                                "[ACTOR][{}] Setting Msg Loss: probability={}",
-                                addr,
-                                probability
-                            );
-                            chaos_manager.set_msg_loss(probability);
-                        }
-                        Some(R2PMsg::UnsetMsgLoss) => {
-                            tracing::info!("[ACTOR][{}] Unsetting Msg Loss", addr);
-                            chaos_manager.unset_msg_loss();
-                        }
-                        Some(R2PMsg::UnsetMsgDuplication) => {
-                            tracing::info!("[ACTOR][{}] Unsetting Msg Duplication", addr);
-                            chaos_manager.unset_msg_duplication();
-                        }
-                        None => {
-                            break;
-                        }
-                    }
-                }
-                tracing::info!("[ACTOR][{}] Processor Ended", addr);
-                Ok(())
-            });
         let tx_handle = tokio::spawn(tx(
             ctx.addr,
             sender,
@@ -666,10 +914,61 @@ where
             controller_tx,
             self.master_codec,
             self.on_send_failure,
+            mailboxes.destinations,
+            self.reconnect,
+            self.reliability,
+            self.metrics,
+            drain,
         ));
-        rx_handle.await??;
-        proc_handle.await??;
-        tx_handle.await?;
+        // the receiver runs until it is told to stop, so a processor that fails ends it
+        let processed = tokio::select! {
+            received = &mut rx_handle => {
+                // what is still queued is all a draining processor takes in
+                if let Some(h) = &timer_handle {
+                    h.abort();
+                }
+                gen_handles.iter().for_each(|h| h.abort());
+                match ActorError::joined(received) {
+                    Ok(()) => ActorError::joined(proc_handle.await),
+                    Err(e) => {
+                        // a blocking processor cannot be aborted; it ends once its inputs are
+                        // gone, or at its next output once the sender is
+                        tx_handle.abort();
+                        proc_handle.abort();
+                        let _ = proc_handle.await;
+                        Err(e)
+                    }
+                }
+            }
+            processed = &mut proc_handle => {
+                let processed = ActorError::joined(processed);
+                if processed.is_ok() {
+                    ActorError::joined(rx_handle.await)?;
+                } else {
+                    rx_handle.abort();
+                    // its listeners are closed once it is gone, so a restart can bind them again
+                    let _ = rx_handle.await;
+                }
+                processed
+            }
+        };
+        // a timer set far ahead would otherwise outlive the processor
+        if let Some(h) = &timer_handle {
+            h.abort();
+        }
+        if let Err(e) = processed {
+            // nothing of a failed instance may outlive it, or it would go on sending as the
+            // address its restart takes over
+            gen_handles.iter().for_each(|h| h.abort());
+            tx_handle.abort();
+            let _ = tx_handle.await;
+            join_all(gen_handles).await;
+            if let Some(h) = timer_handle {
+                let _ = h.await;
+            }
+            return Err(e);
+        }
+        ActorError::joined(tx_handle.await)?;
         join_all(gen_handles).await;
         Ok(())
     }
@@ -681,7 +980,234 @@ where
     M: Msg + 'static,
 {
     for m in generator {
-        p_tx.send(R2PMsg::Msg(m, "")).await.unwrap();
+        // the processor is gone, so the actor is ending
+        if p_tx.send(R2PMsg::Msg(m, "")).await.is_err() {
+            break;
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use futures::StreamExt;
+    use tokio::net::TcpListener;
+    use tokio_util::codec::FramedRead;
+
+    use super::*;
+    use crate::{codec::BincodeCodec, recv::recv_remote_handshake};
+
+    #[derive(Encode, Decode, Debug, Clone)]
+    struct Num(u64);
+    impl HasPriority for Num {}
+    impl Msg for Num {}
+
+    struct PanicsOn(u64);
+    impl ActorProcess for PanicsOn {
+        type IMsg = Num;
+        type OMsg = Num;
+        fn process(&mut self, input: Num) -> Vec<Num> {
+            assert_ne!(input.0, self.0, "boom");
+            vec![]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panic_is_reported_to_the_controller() {
+        // the controller keeps the actor's instruction channel open, so only the panic ends it
+        let (_inst_tx, inst_rx) = mpsc::channel(1);
+        let (req_tx, mut req_rx) = mpsc::channel(1);
+        let ctx = RuntimeCtx::new("panicky", NodeComm::new(inst_rx, req_tx));
+        let run = BehaviourBuilder::new(PanicsOn(3), BincodeCodec::default())
+            .generator((0..10).map(Num))
+            .build()
+            .run(ctx);
+        let ran = tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .expect("a panicking actor should end");
+        assert!(matches!(ran, Err(ActorError::Panicked(msg)) if msg.contains("boom")));
+        match req_rx.recv().await {
+            Some(ControlReq::Exited {
+                addr,
+                exit: Exit::Panicked(msg),
+            }) => {
+                assert_eq!(addr, "panicky");
+                assert!(msg.contains("boom"));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Emits `n` messages for an input of `n`.
+    struct Fanout(Arc<AtomicBool>);
+    impl ActorProcess for Fanout {
+        type IMsg = Num;
+        type OMsg = Num;
+        fn process(&mut self, input: Num) -> Vec<Num> {
+            (0..input.0).map(Num).collect()
+        }
+        fn on_stop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    struct ToSink;
+    impl ActorSend for ToSink {
+        type OMsg = Num;
+        async fn before_send<'a>(&'a mut self, _output: &Num) -> RouteTo<'a> {
+            RouteTo::from("sink".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_flushes_queued_messages() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (inst_tx, inst_rx) = mpsc::channel(1);
+        let (req_tx, mut req_rx) = mpsc::channel(1);
+        let stopped = Arc::new(AtomicBool::new(false));
+        let ctx = RuntimeCtx::new("drained", NodeComm::new(inst_rx, req_tx));
+        let run = tokio::spawn(
+            BehaviourBuilder::new(Fanout(stopped.clone()), BincodeCodec::default())
+                .send(ToSink)
+                .generator(std::iter::once(Num(50)))
+                .build()
+                .run(ctx),
+        );
+
+        // the messages wait for the sink to resolve, which a hard stop would abort
+        let Some(ControlReq::Resolve { resp_tx, .. }) = req_rx.recv().await else {
+            unreachable!()
+        };
+        inst_tx
+            .send(ControlInst::Drain { deadline_ms: 5000 })
+            .await
+            .unwrap();
+        let _ = resp_tx.send(Connection::Remote(listener.local_addr().unwrap()));
+
+        let (socket, _) = tokio::time::timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .expect("the draining actor should still connect")
+            .unwrap();
+        let (mut rx, _) = socket.into_split();
+        assert_eq!(recv_remote_handshake(&mut rx).await.unwrap().0, "drained");
+        let got: Vec<Num> = FramedRead::new(rx, BincodeCodec::<Num, Num>::default())
+            .map(|msg| msg.unwrap())
+            .collect()
+            .await;
+        assert_eq!(got.len(), 50);
+        run.await.unwrap().unwrap();
+        assert!(stopped.load(Ordering::SeqCst), "on_stop ran");
+        assert!(matches!(
+            req_rx.recv().await,
+            Some(ControlReq::Exited {
+                exit: Exit::Stopped,
+                ..
+            })
+        ));
+    }
+
+    /// Takes longer over each message than the generator does to make one.
+    struct Slow;
+    impl ActorProcess for Slow {
+        type IMsg = Num;
+        type OMsg = Num;
+        fn process(&mut self, _input: Num) -> Vec<Num> {
+            std::thread::sleep(Duration::from_millis(1));
+            vec![]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_stops_generators_and_timers() {
+        let (inst_tx, inst_rx) = mpsc::channel(1);
+        let (req_tx, _req_rx) = mpsc::channel(1);
+        let ctx = RuntimeCtx::new("endless", NodeComm::new(inst_rx, req_tx));
+        let run = tokio::spawn(
+            BehaviourBuilder::new(Slow, BincodeCodec::default())
+                .generator(std::iter::repeat(Num(0)))
+                .tick(Duration::from_millis(1), Num(1))
+                .build()
+                .run(ctx),
+        );
+        inst_tx
+            .send(ControlInst::Drain {
+                deadline_ms: 60_000,
+            })
+            .await
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .expect("the generator and the timer should not keep a draining actor alive")
+            .unwrap()
+            .unwrap();
+    }
+
+    /// Holds a clone of its `Arc` in the processor, the sender or a generator, so the count tells
+    /// how many of them are still alive.
+    struct Holds {
+        _alive: Arc<()>,
+    }
+    impl ActorProcess for Holds {
+        type IMsg = Num;
+        type OMsg = Num;
+        fn process(&mut self, input: Num) -> Vec<Num> {
+            vec![input]
+        }
+    }
+    impl ActorSend for Holds {
+        type OMsg = Num;
+        async fn before_send<'a>(&'a mut self, _output: &Num) -> RouteTo<'a> {
+            RouteTo::Blackhole
+        }
+    }
+    impl Iterator for Holds {
+        type Item = Num;
+        fn next(&mut self) -> Option<Num> {
+            Some(Num(0))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_receiver_leaves_no_task_behind() {
+        let alive = Arc::new(());
+        let (inst_tx, inst_rx) = mpsc::channel(1);
+        let (req_tx, _req_rx) = mpsc::channel(1);
+        let ctx = RuntimeCtx::new("failing", NodeComm::new(inst_rx, req_tx));
+        let run = tokio::spawn(
+            BehaviourBuilder::new(
+                Holds {
+                    _alive: alive.clone(),
+                },
+                BincodeCodec::default(),
+            )
+            .send(Holds {
+                _alive: alive.clone(),
+            })
+            .generator(Holds {
+                _alive: alive.clone(),
+            })
+            .tick(Duration::from_millis(1), Num(1))
+            .inbox_capacity(1)
+            .build()
+            .run(ctx),
+        );
+        // a local channel that closes before its handshake fails the receiver
+        let (_, local_rx) = mpsc::channel(1);
+        inst_tx
+            .send(ControlInst::StartLocalRecv(local_rx))
+            .await
+            .unwrap();
+        let ran = tokio::time::timeout(Duration::from_secs(5), run)
+            .await
+            .expect("a failed receiver ends the actor")
+            .unwrap();
+        assert!(matches!(ran, Err(ActorError::Panicked(_))));
+        assert_eq!(
+            Arc::strong_count(&alive),
+            1,
+            "the processor, the sender and the generator end with the actor"
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+use tokio::sync::Notify;
+
+/// What a bounded mailbox does with a message that arrives while it is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Waits for room, slowing the stage that feeds the mailbox down to the pace of its reader.
+    Block,
+    /// Makes room by dropping the message that has waited longest.
+    DropOldest,
+    /// Drops the arriving message.
+    DropNewest,
+    /// Stops the actor with [`crate::err::ActorError::MailboxFull`]; the inbox refuses the
+    /// message instead, see [`crate::BehaviourBuilder::inbox`].
+    Fail,
+}
+
+/// Capacity and overflow policy of a bounded mailbox.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MailboxCfg {
+    pub capacity: usize,
+    pub overflow: Overflow,
+}
+
+impl MailboxCfg {
+    pub fn new(capacity: usize, overflow: Overflow) -> Self {
+        assert!(capacity > 0, "a mailbox needs room for a message");
+        MailboxCfg { capacity, overflow }
+    }
+}
+
+/// Depth counters of one queue, shared with [`MailboxMetrics`].
+#[derive(Default, Debug)]
+pub(crate) struct QueueStats {
+    capacity: Option<usize>,
+    depth: AtomicUsize,
+    high_water: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+impl QueueStats {
+    fn with_capacity(capacity: Option<usize>) -> Arc<Self> {
+        Arc::new(QueueStats {
+            capacity,
+            ..Default::default()
+        })
+    }
+
+    pub(crate) fn observe(&self, depth: usize) {
+        self.depth.store(depth, Ordering::Relaxed);
+        self.high_water.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    /// Counts a message the queue's policy dropped.
+    pub(crate) fn drop_one(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> QueueDepth {
+        QueueDepth {
+            capacity: self.capacity,
+            depth: self.depth.load(Ordering::Relaxed),
+            high_water: self.high_water.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A point-in-time view of a queue. `capacity` is `None` for an unbounded one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueDepth {
+    pub capacity: Option<usize>,
+    pub depth: usize,
+    pub high_water: usize,
+    pub dropped: u64,
+}
+
+/// Queue depths of an actor, from [`crate::Behaviour::metrics`]. Clones share the counters, so
+/// the handle can be read while the actor runs.
+#[derive(Clone, Debug, Default)]
+pub struct MailboxMetrics {
+    inner: Arc<MetricsInner>,
+}
+
+#[derive(Debug, Default)]
+struct MetricsInner {
+    inbox: Mutex<Arc<QueueStats>>,
+    outbox: Mutex<Arc<QueueStats>>,
+    destinations: Mutex<BTreeMap<String, Arc<QueueStats>>>,
+}
+
+impl MailboxMetrics {
+    /// Messages waiting for the processor, over all priorities.
+    pub fn inbox(&self) -> QueueDepth {
+        self.inner.inbox.lock().unwrap().snapshot()
+    }
+
+    /// Messages the processor produced that the sender has not routed yet.
+    pub fn outbox(&self) -> QueueDepth {
+        self.inner.outbox.lock().unwrap().snapshot()
+    }
+
+    /// Messages waiting to be written to each destination, by address.
+    pub fn destinations(&self) -> Vec<(String, QueueDepth)> {
+        let destinations = self.inner.destinations.lock().unwrap();
+        destinations
+            .iter()
+            .map(|(addr, stats)| (addr.clone(), stats.snapshot()))
+            .collect()
+    }
+
+    pub(crate) fn inbox_stats(&self, capacity: usize) -> Arc<QueueStats> {
+        let stats = QueueStats::with_capacity(Some(capacity));
+        *self.inner.inbox.lock().unwrap() = stats.clone();
+        stats
+    }
+
+    pub(crate) fn outbox_stats(&self, cfg: Option<MailboxCfg>) -> Arc<QueueStats> {
+        let stats = QueueStats::with_capacity(cfg.map(|c| c.capacity));
+        *self.inner.outbox.lock().unwrap() = stats.clone();
+        stats
+    }
+
+    pub(crate) fn destination_stats(&self, addr: &str, cfg: Option<MailboxCfg>) -> Arc<QueueStats> {
+        let stats = QueueStats::with_capacity(cfg.map(|c| c.capacity));
+        let mut destinations = self.inner.destinations.lock().unwrap();
+        destinations.insert(addr.to_string(), stats.clone());
+        stats
+    }
+}
+
+/// Mailbox settings of a behaviour, see [`crate::BehaviourBuilder::inbox`].
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Mailboxes {
+    pub(crate) inbox: MailboxCfg,
+    pub(crate) outbox: Option<MailboxCfg>,
+    pub(crate) destinations: Option<MailboxCfg>,
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    cfg: Option<MailboxCfg>,
+    stats: Arc<QueueStats>,
+    readable: Notify,
+    writable: Notify,
+}
+
+/// Why a message was not queued.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum PushErr<T> {
+    /// The receiver is gone.
+    Closed(T),
+    /// The mailbox is full and its policy is [`Overflow::Fail`].
+    Full(T),
+}
+
+/// Sending half of a mailbox; unbounded when created without a [`MailboxCfg`].
+pub(crate) struct MailboxTx<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub(crate) struct MailboxRx<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub(crate) fn mailbox<T>(
+    cfg: Option<MailboxCfg>,
+    stats: Arc<QueueStats>,
+) -> (MailboxTx<T>, MailboxRx<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::new(),
+            senders: 1,
+            receiver_alive: true,
+        }),
+        cfg,
+        stats,
+        readable: Notify::new(),
+        writable: Notify::new(),
+    });
+    (
+        MailboxTx {
+            shared: shared.clone(),
+        },
+        MailboxRx { shared },
+    )
+}
+
+impl<T> MailboxTx<T> {
+    /// Queues `msg` according to the overflow policy; only [`Overflow::Block`] waits.
+    pub(crate) async fn send(&self, msg: T) -> Result<(), PushErr<T>> {
+        let mut msg = Some(msg);
+        loop {
+            let writable = self.shared.writable.notified();
+            tokio::pin!(writable);
+            writable.as_mut().enable();
+            if self.try_push(&mut msg)? {
+                return Ok(());
+            }
+            writable.await;
+        }
+    }
+
+    /// Like [`Self::send`], for callers outside the async runtime, e.g. a blocking processor.
+    pub(crate) fn send_blocking(&self, msg: T) -> Result<(), PushErr<T>> {
+        futures::executor::block_on(self.send(msg))
+    }
+
+    /// Returns false if the mailbox is full and the message must wait.
+    fn try_push(&self, msg: &mut Option<T>) -> Result<bool, PushErr<T>> {
+        let shared = &self.shared;
+        let mut state = shared.state.lock().unwrap();
+        if !state.receiver_alive {
+            return Err(PushErr::Closed(msg.take().unwrap()));
+        }
+        if let Some(cfg) = shared.cfg
+            && state.queue.len() >= cfg.capacity
+        {
+            match cfg.overflow {
+                Overflow::Block => return Ok(false),
+                Overflow::DropOldest => {
+                    state.queue.pop_front();
+                    shared.stats.drop_one();
+                }
+                Overflow::DropNewest => {
+                    shared.stats.drop_one();
+                    msg.take();
+                    return Ok(true);
+                }
+                Overflow::Fail => return Err(PushErr::Full(msg.take().unwrap())),
+            }
+        }
+        state.queue.push_back(msg.take().unwrap());
+        shared.stats.observe(state.queue.len());
+        drop(state);
+        shared.readable.notify_one();
+        Ok(true)
+    }
+}
+
+impl<T> Clone for MailboxTx<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().senders += 1;
+        MailboxTx {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for MailboxTx<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.shared.readable.notify_one();
+        }
+    }
+}
+
+impl<T> MailboxRx<T> {
+    /// Waits for the next message; `None` once every sender is gone and the queue is empty.
+    pub(crate) async fn recv(&mut self) -> Option<T> {
+        loop {
+            let readable = self.shared.readable.notified();
+            tokio::pin!(readable);
+            readable.as_mut().enable();
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(msg) = state.queue.pop_front() {
+                    self.shared.stats.observe(state.queue.len());
+                    drop(state);
+                    self.shared.writable.notify_one();
+                    return Some(msg);
+                }
+                if state.senders == 0 {
+                    return None;
+                }
+            }
+            readable.await;
+        }
+    }
+}
+
+impl<T> Drop for MailboxRx<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.receiver_alive = false;
+        state.queue.clear();
+        drop(state);
+        // blocked senders wake up to find the receiver gone
+        self.shared.writable.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn bounded(
+        capacity: usize,
+        overflow: Overflow,
+    ) -> (MailboxTx<u32>, MailboxRx<u32>, Arc<QueueStats>) {
+        let stats = QueueStats::with_capacity(Some(capacity));
+        let (tx, rx) = mailbox(Some(MailboxCfg::new(capacity, overflow)), stats.clone());
+        (tx, rx, stats)
+    }
+
+    async fn drain(tx: MailboxTx<u32>, mut rx: MailboxRx<u32>) -> Vec<u32> {
+        drop(tx);
+        let mut out = vec![];
+        while let Some(m) = rx.recv().await {
+            out.push(m);
+        }
+        out
+    }
+
+    #[tokio::test]
+    async fn test_drop_policies() {
+        let (tx, rx, stats) = bounded(2, Overflow::DropOldest);
+        for m in 1..=4 {
+            tx.send(m).await.unwrap();
+        }
+        assert_eq!(stats.snapshot().dropped, 2);
+        assert_eq!(drain(tx, rx).await, vec![3, 4]);
+
+        let (tx, rx, stats) = bounded(2, Overflow::DropNewest);
+        for m in 1..=4 {
+            tx.send(m).await.unwrap();
+        }
+        let depth = stats.snapshot();
+        assert_eq!((depth.depth, depth.high_water, depth.dropped), (2, 2, 2));
+        assert_eq!(drain(tx, rx).await, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_fail_and_closed() {
+        let (tx, rx, _) = bounded(1, Overflow::Fail);
+        tx.send(1).await.unwrap();
+        assert_eq!(tx.send(2).await, Err(PushErr::Full(2)));
+        drop(rx);
+        assert_eq!(tx.send(3).await, Err(PushErr::Closed(3)));
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_room() {
+        let (tx, mut rx, stats) = bounded(2, Overflow::Block);
+        let tx2 = tx.clone();
+        let writer = tokio::spawn(async move {
+            for m in 1..=5 {
+                tx2.send(m).await.unwrap();
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!writer.is_finished(), "the writer waits for room");
+        assert_eq!(stats.snapshot().depth, 2);
+        assert_eq!(rx.recv().await, Some(1));
+        // the writer still holds a sender, so the drain also waits for it to finish
+        assert_eq!(drain(tx, rx).await, vec![2, 3, 4, 5]);
+        writer.await.unwrap();
+        assert_eq!(stats.snapshot().dropped, 0);
+        assert_eq!(stats.snapshot().high_water, 2);
+    }
+
+    #[test]
+    fn test_blocking_sender_is_released_when_receiver_goes() {
+        let (tx, rx, _) = bounded(1, Overflow::Block);
+        tx.send_blocking(1).unwrap();
+        let writer = std::thread::spawn(move || tx.send_blocking(2));
+        std::thread::sleep(Duration::from_millis(20));
+        drop(rx);
+        assert_eq!(writer.join().unwrap(), Err(PushErr::Closed(2)));
+    }
+}
@@ -1,13 +1,24 @@
-use std::{any::Any, net::SocketAddr};
+use std::{any::Any, net::SocketAddr, sync::OnceLock, time::Duration};
 
-use tokio::sync::{mpsc, oneshot};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
 
-use crate::ActorAddr;
+use crate::{ActorAddr, reliable::Hello};
 
 /// Type of channel that is used to send message from one local actor to the other.
 pub type LocalChannelTx = mpsc::Sender<Box<dyn Any + Send>>;
 pub type LocalChannelRx = mpsc::Receiver<Box<dyn Any + Send>>;
 
+/// First message on a local channel: the sender's address, its message type if the receiver
+/// should adapt to it, and the session and ack channel of a reliable channel.
+pub(crate) type LocalHandshake = (
+    String,
+    Option<String>,
+    Option<(Hello, mpsc::UnboundedSender<u64>)>,
+);
+
 #[derive(Debug)]
 pub enum Connection {
     Remote(SocketAddr),
@@ -20,6 +31,19 @@ pub enum ControlReq {
         addr: ActorAddr,
         resp_tx: oneshot::Sender<Connection>,
     },
+    /// Sent once by an actor whose run has ended, so its controller can restart it.
+    Exited { addr: ActorAddr, exit: Exit },
+}
+
+/// How an actor's run ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Exit {
+    /// Stopped by its controller.
+    Stopped,
+    /// A task of the actor panicked, with the panic's message.
+    Panicked(String),
+    /// The actor returned an error.
+    Failed(String),
 }
 
 /// Instructions that are sent by the local controller to the actor
@@ -27,6 +51,12 @@ pub enum ControlInst {
     StartLocalRecv(LocalChannelRx),
     StartTcpRecv(u16),
     Stop,
+    /// Stops gracefully: the actor takes no more input, processes what its inbox holds, calls
+    /// the processor's `on_stop`, and gives its outgoing messages until the deadline, counted
+    /// from now, to be sent. What is still queued then is dropped.
+    Drain {
+        deadline_ms: u64,
+    },
     SetMsgLoss {
         probability: f32,
     },
@@ -43,6 +73,26 @@ pub enum ControlInst {
     UnsetMsgDelay {
         senders: Vec<String>,
     },
+    /// Adds a priority below the lowest; ignored by an actor built with a single priority.
+    AddPrio,
+    /// Removes the lowest priority once it is drained; its messages go to the next lowest.
+    RemoveLowPrio,
+}
+
+/// When a draining actor stops waiting for its outgoing messages to be sent.
+#[derive(Debug, Default)]
+pub(crate) struct DrainDeadline(OnceLock<Instant>);
+
+impl DrainDeadline {
+    /// Starts the drain, or keeps one already started, and returns its deadline.
+    pub(crate) fn start(&self, within: Duration) -> Instant {
+        *self.0.get_or_init(|| Instant::now() + within)
+    }
+
+    /// Set once the actor drains, or `None` while it runs or if it is stopped outright.
+    pub(crate) fn get(&self) -> Option<Instant> {
+        self.0.get().copied()
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,404 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::{task::JoinHandle, time::Instant};
+
+use crate::{
+    ActorProcess, AsyncActorProcess, Msg, R2PMsg,
+    chaos_manager::ChaosManager,
+    err::ActorError,
+    mailbox::{MailboxTx, PushErr, QueueStats},
+    reactor_channel::ReactorChannelRx,
+    timer::Asks,
+};
+
+/// Selects an [`AsyncActorProcess`] in [`crate::BehaviourBuilder::new_async`].
+pub struct AsyncProcess<P>(pub P);
+
+/// How a behaviour drives its processor. Implemented for every [`ActorProcess`], which runs on
+/// the blocking pool, and for [`AsyncProcess`], which runs as a task on the async workers.
+pub trait Processor: Send + 'static {
+    type IMsg: Msg;
+    type OMsg: Msg;
+
+    fn spawn(self, io: ProcIo<Self::IMsg, Self::OMsg>) -> JoinHandle<Result<(), ActorError>>;
+}
+
+impl<P: ActorProcess> Processor for P {
+    type IMsg = P::IMsg;
+    type OMsg = P::OMsg;
+
+    fn spawn(mut self, mut io: ProcIo<P::IMsg, P::OMsg>) -> JoinHandle<Result<(), ActorError>> {
+        tokio::task::spawn_blocking(move || {
+            tracing::info!("[ACTOR][{}] Processor Started", io.addr);
+            while !io.drained() {
+                let next = io.r2p_rx.recv();
+                match io.step(next) {
+                    Step::Process(msgs, origin) => {
+                        for msg in msgs {
+                            for o in self.process(msg) {
+                                io.emitted(io.p2s_tx.send_blocking((o, origin)))?;
+                            }
+                        }
+                    }
+                    Step::Continue => {}
+                    Step::Stop => break,
+                }
+            }
+            self.on_stop();
+            tracing::info!("[ACTOR][{}] Processor Ended", io.addr);
+            Ok(())
+        })
+    }
+}
+
+impl<P: AsyncActorProcess> Processor for AsyncProcess<P> {
+    type IMsg = P::IMsg;
+    type OMsg = P::OMsg;
+
+    fn spawn(self, mut io: ProcIo<P::IMsg, P::OMsg>) -> JoinHandle<Result<(), ActorError>> {
+        let mut processor = self.0;
+        tokio::spawn(async move {
+            tracing::info!("[ACTOR][{}] Processor Started", io.addr);
+            while !io.drained() {
+                let next = io.r2p_rx.recv_async().await;
+                match io.step(next) {
+                    Step::Process(msgs, origin) => {
+                        for msg in msgs {
+                            for o in processor.process(msg).await {
+                                io.emitted(io.p2s_tx.send((o, origin)).await)?;
+                            }
+                        }
+                    }
+                    Step::Continue => {}
+                    Step::Stop => break,
+                }
+            }
+            processor.on_stop().await;
+            tracing::info!("[ACTOR][{}] Processor Ended", io.addr);
+            Ok(())
+        })
+    }
+}
+
+/// The processor's channels and chaos settings, shared by both kinds of processor.
+pub struct ProcIo<IM, OM> {
+    addr: &'static str,
+    r2p_rx: ReactorChannelRx<R2PMsg<IM>>,
+    p2s_tx: MailboxTx<(OM, &'static str)>,
+    inbox: Arc<QueueStats>,
+    chaos_manager: Arc<Mutex<ChaosManager>>,
+    asks: Option<Arc<Asks>>,
+    draining: Option<Instant>,
+}
+
+pub(crate) enum Step<M> {
+    /// Messages to process, all from the same origin.
+    Process(Vec<M>, &'static str),
+    Continue,
+    Stop,
+}
+
+impl<IM: Msg, OM: Msg> ProcIo<IM, OM> {
+    pub(crate) fn new(
+        addr: &'static str,
+        r2p_rx: ReactorChannelRx<R2PMsg<IM>>,
+        p2s_tx: MailboxTx<(OM, &'static str)>,
+        inbox: Arc<QueueStats>,
+        chaos_manager: Arc<Mutex<ChaosManager>>,
+        asks: Option<Arc<Asks>>,
+    ) -> Self {
+        ProcIo {
+            addr,
+            r2p_rx,
+            p2s_tx,
+            inbox,
+            chaos_manager,
+            asks,
+            draining: None,
+        }
+    }
+
+    /// Whether a drain has emptied the processor channel or run out of time; what is left then is
+    /// not processed.
+    fn drained(&self) -> bool {
+        self.draining
+            .is_some_and(|deadline| self.r2p_rx.len() == 0 || Instant::now() >= deadline)
+    }
+
+    /// Whether `msg` may reach the processor: a reply only while its ask is waiting for it.
+    fn expected(&self, msg: &IM) -> bool {
+        let (Some(id), Some(asks)) = (msg.reply_to(), &self.asks) else {
+            return true;
+        };
+        let waiting = asks.settle(id);
+        if !waiting {
+            tracing::warn!(
+                "[ACTOR][{}] Late or repeated reply to {:?} dropped",
+                self.addr,
+                id
+            );
+        }
+        waiting
+    }
+
+    fn emitted(&self, sent: Result<(), PushErr<(OM, &'static str)>>) -> Result<(), ActorError> {
+        match sent {
+            Ok(()) => Ok(()),
+            Err(PushErr::Closed(_)) => Err(ActorError::P2SErr),
+            Err(PushErr::Full(_)) => Err(ActorError::MailboxFull("outbox")),
+        }
+    }
+
+    /// Applies a message from the processor channel, returning what is left to process.
+    pub(crate) fn step(&mut self, next: Option<R2PMsg<IM>>) -> Step<IM> {
+        let addr = self.addr;
+        self.inbox.observe(self.r2p_rx.len());
+        match next {
+            Some(R2PMsg::Msg(m, origin)) => {
+                // Dont apply chaos to messages comming from generator
+                let mut chaos_out = if origin.is_empty() {
+                    vec![m]
+                } else {
+                    self.chaos_manager.lock().unwrap().apply_chaos(m)
+                };
+                let chaos_out_len = chaos_out.len();
+                if chaos_out_len > 1 {
+                    tracing::warn!(
+                        "[ACTOR][{}] Message Duplicated {} times",
+                        addr,
+                        chaos_out_len
+                    );
+                } else if chaos_out_len == 0 {
+                    tracing::warn!("[ACTOR][{}] Message Lost", addr);
+                }
+                chaos_out.retain(|m| self.expected(m));
+                return Step::Process(chaos_out, origin);
+            }
+            // a reliable channel applied chaos before dropping repeats
+            Some(R2PMsg::Reliable(m, origin)) => {
+                let msgs = if self.expected(&m) { vec![m] } else { vec![] };
+                return Step::Process(msgs, origin);
+            }
+            Some(R2PMsg::AddPrio(new_rx)) => {
+                tracing::info!("[ACTOR][{}] Adding Priority", addr);
+                self.r2p_rx.add_prio(new_rx);
+            }
+            Some(R2PMsg::RemoveLowPrio) => {
+                // the channel drops the removed priority itself, once it is drained
+                tracing::info!("[ACTOR][{}] Removing Lowest Priority", addr);
+            }
+            Some(R2PMsg::Exit) | None => return Step::Stop,
+            Some(R2PMsg::Drain(deadline)) => {
+                tracing::info!("[ACTOR][{}] Draining {} messages", addr, self.r2p_rx.len());
+                self.draining = Some(deadline);
+            }
+            Some(R2PMsg::SetMsgDuplication {
+                factor,
+                probability,
+            }) => {
+                tracing::info!(
+                    "[ACTOR][{}] Setting Msg Duplication: factor={}, probability={}",
+                    addr,
+                    factor,
+                    probability
+                );
+                self.chaos_manager
+                    .lock()
+                    .unwrap()
+                    .set_msg_duplication(factor, probability);
+            }
+            Some(R2PMsg::SetMsgLoss { probability }) => {
+                tracing::info!(
+                    "[ACTOR][{}] Setting Msg Loss: probability={}",
+                    addr,
+                    probability
+                );
+                self.chaos_manager.lock().unwrap().set_msg_loss(probability);
+            }
+            Some(R2PMsg::UnsetMsgLoss) => {
+                tracing::info!("[ACTOR][{}] Unsetting Msg Loss", addr);
+                self.chaos_manager.lock().unwrap().unset_msg_loss();
+            }
+            Some(R2PMsg::UnsetMsgDuplication) => {
+                tracing::info!("[ACTOR][{}] Unsetting Msg Duplication", addr);
+                self.chaos_manager.lock().unwrap().unset_msg_duplication();
+            }
+        }
+        Step::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        CorrelationId, HasPriority,
+        mailbox::mailbox,
+        reactor_channel::{ReactorChannelTx, reactor_channel},
+        timer_channel,
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Num(u64);
+    impl HasPriority for Num {}
+    impl Msg for Num {}
+
+    struct Doubler;
+    impl ActorProcess for Doubler {
+        type IMsg = Num;
+        type OMsg = Num;
+        fn process(&mut self, input: Num) -> Vec<Num> {
+            vec![Num(input.0 * 2)]
+        }
+    }
+
+    struct SlowDoubler;
+    impl AsyncActorProcess for SlowDoubler {
+        type IMsg = Num;
+        type OMsg = Num;
+        async fn process(&mut self, input: Num) -> Vec<Num> {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            vec![Num(input.0 * 2)]
+        }
+    }
+
+    async fn run_to_end<P: Processor<IMsg = Num, OMsg = Num>>(
+        proc: P,
+        prios: u8,
+    ) -> (ReactorChannelTx<R2PMsg<Num>>, JoinHandle<Vec<u64>>) {
+        let (r2p_tx, r2p_rx) = reactor_channel(prios, 100);
+        let (p2s_tx, mut p2s_rx) = mailbox(None, Default::default());
+        let handle = proc.spawn(ProcIo::new(
+            "test",
+            r2p_rx,
+            p2s_tx,
+            Default::default(),
+            Default::default(),
+            None,
+        ));
+        let outs = tokio::spawn(async move {
+            let mut outs = vec![];
+            while let Some((Num(n), _)) = p2s_rx.recv().await {
+                outs.push(n);
+            }
+            handle.await.unwrap().unwrap();
+            outs
+        });
+        (r2p_tx, outs)
+    }
+
+    #[tokio::test]
+    async fn test_blocking_and_async_processors_agree() {
+        for prios in [1, 3] {
+            let (tx, sync_outs) = run_to_end(Doubler, prios).await;
+            let (atx, async_outs) = run_to_end(AsyncProcess(SlowDoubler), prios).await;
+            for n in 0..20 {
+                tx.send(R2PMsg::Msg(Num(n), "")).await.unwrap();
+                atx.send(R2PMsg::Msg(Num(n), "")).await.unwrap();
+            }
+            // the processor stops on Exit even with senders still open
+            tx.send(R2PMsg::Exit).await.unwrap();
+            drop(atx);
+            let expected: Vec<u64> = (0..20).map(|n| n * 2).collect();
+            assert_eq!(sync_outs.await.unwrap(), expected);
+            assert_eq!(async_outs.await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_async_processors_share_one_thread() {
+        // on a single-threaded runtime a processor holding its thread would starve the others
+        let mut actors = vec![];
+        for _ in 0..64 {
+            actors.push(run_to_end(AsyncProcess(SlowDoubler), 1).await);
+        }
+        for (i, (tx, _)) in actors.iter().enumerate() {
+            tx.send(R2PMsg::Msg(Num(i as u64), "origin")).await.unwrap();
+        }
+        for (i, (tx, outs)) in actors.into_iter().enumerate() {
+            drop(tx);
+            let outs = tokio::time::timeout(Duration::from_secs(5), outs)
+                .await
+                .expect("async processors should not block each other");
+            assert_eq!(outs.unwrap(), vec![i as u64 * 2]);
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Reply(CorrelationId);
+    impl HasPriority for Reply {}
+    impl Msg for Reply {
+        fn reply_to(&self) -> Option<CorrelationId> {
+            Some(self.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replies_reach_the_processor_once() {
+        let (_r2p_tx, r2p_rx) = reactor_channel(1, 100);
+        let (p2s_tx, _p2s_rx) = mailbox(None, Default::default());
+        let (handle, queue) = timer_channel::<Reply>();
+        let mut io: ProcIo<Reply, Reply> = ProcIo::new(
+            "test",
+            r2p_rx,
+            p2s_tx,
+            Default::default(),
+            Default::default(),
+            Some(queue.asks()),
+        );
+        let id = handle.ask(Duration::from_secs(60), Reply);
+        let processed = |step| match step {
+            Step::Process(msgs, _) => msgs,
+            _ => unreachable!(),
+        };
+
+        let reply = R2PMsg::Msg(Reply(id), "peer");
+        assert_eq!(processed(io.step(Some(reply.clone()))), vec![Reply(id)]);
+        assert!(processed(io.step(Some(reply))).is_empty());
+        assert!(processed(io.step(Some(R2PMsg::Reliable(Reply(id), "peer")))).is_empty());
+        // the ask's timeout is skipped too, as it can no longer settle it
+        assert!(!queue.asks().settle(id));
+    }
+
+    fn forever() -> Instant {
+        Instant::now() + Duration::from_secs(3600)
+    }
+
+    #[tokio::test]
+    async fn test_drain_processes_what_is_queued() {
+        for prios in [1, 3] {
+            let (tx, outs) = run_to_end(Doubler, prios).await;
+            let (atx, async_outs) = run_to_end(AsyncProcess(SlowDoubler), prios).await;
+            for n in 0..20 {
+                tx.send(R2PMsg::Msg(Num(n), "")).await.unwrap();
+                atx.send(R2PMsg::Msg(Num(n), "")).await.unwrap();
+            }
+            // the drain goes ahead of the queued messages, and the processor stops after them
+            // with its senders still open
+            tx.send(R2PMsg::Drain(forever())).await.unwrap();
+            atx.send(R2PMsg::Drain(forever())).await.unwrap();
+            let expected: Vec<u64> = (0..20).map(|n| n * 2).collect();
+            assert_eq!(outs.await.unwrap(), expected);
+            assert_eq!(async_outs.await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_stops_at_its_deadline() {
+        let (tx, outs) = run_to_end(AsyncProcess(SlowDoubler), 1).await;
+        tx.send(R2PMsg::Drain(Instant::now() + Duration::from_millis(20)))
+            .await
+            .unwrap();
+        // messages keep arriving behind the drain, each taking a millisecond to process
+        for n in 0..100 {
+            tx.send(R2PMsg::Msg(Num(n), "")).await.unwrap();
+        }
+        let outs = tokio::time::timeout(Duration::from_secs(5), outs)
+            .await
+            .expect("the processor should stop at the deadline")
+            .unwrap();
+        assert!(outs.len() < 100);
+    }
+}
@@ -1,6 +1,13 @@
+use std::{
+    sync::{Arc, RwLock},
+    task::{Context, Poll},
+};
+
+#[cfg(test)]
+use tokio::sync::mpsc::error::TryRecvError;
 use tokio::sync::mpsc::{
     self, Receiver, Sender,
-    error::{SendError, TryRecvError},
+    error::{SendError, TrySendError},
 };
 
 use crate::R2PMsg;
@@ -22,95 +29,121 @@ impl<T: HasPriority> ReactorChannelTx<T> {
             }
         }
     }
-}
 
-impl<T: HasPriority> ReactorChannelTx<R2PMsg<T>> {
-    #[allow(dead_code)]
-    pub async fn add_prio(self, channel_size: usize) -> ReactorChannelTx<R2PMsg<T>> {
+    /// Number of priorities the channel has now.
+    pub(crate) fn num_prios(&self) -> usize {
         match self {
-            ReactorChannelTx::SingleChannel(tx) => {
-                let (new_tx, new_rx) = mpsc::channel(channel_size);
-                new_tx.send(R2PMsg::AddPrio(new_rx)).await.unwrap();
-                ReactorChannelTx::MultiChannel(PriorityChannelTx {
-                    senders: vec![tx, new_tx],
-                })
-            }
-            ReactorChannelTx::MultiChannel(mut priority_channel_tx) => {
-                let (new_tx, new_rx) = mpsc::channel(channel_size);
-                new_tx.send(R2PMsg::AddPrio(new_rx)).await.unwrap();
-                priority_channel_tx.senders.push(new_tx);
-                ReactorChannelTx::MultiChannel(priority_channel_tx)
+            ReactorChannelTx::SingleChannel(_) => 1,
+            ReactorChannelTx::MultiChannel(priority_channel_tx) => {
+                priority_channel_tx.senders.read().unwrap().len()
             }
         }
     }
-    #[allow(dead_code)]
-    pub fn remove_prio(&mut self) {
+
+    /// Like [`Self::send`], without waiting for room.
+    pub(crate) fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
         match self {
-            ReactorChannelTx::SingleChannel(_) => panic!("Single Channel"),
+            ReactorChannelTx::SingleChannel(tx) => tx.try_send(msg),
             ReactorChannelTx::MultiChannel(priority_channel_tx) => {
-                priority_channel_tx.remove_prio();
-                priority_channel_tx
-                    .send_blocking(R2PMsg::RemoveLowPrio)
-                    .unwrap();
-                if priority_channel_tx.curr_prios() == 1 {
-                    *self =
-                        ReactorChannelTx::SingleChannel(priority_channel_tx.senders.pop().unwrap())
-                }
+                priority_channel_tx.sender_for(&msg).try_send(msg)
             }
         }
     }
 }
 
+impl<T: HasPriority> ReactorChannelTx<R2PMsg<T>> {
+    /// Adds a priority below the current lowest, for every clone of this sender. Returns false for
+    /// a channel built with a single priority, whose clones cannot all be upgraded.
+    pub(crate) async fn add_prio(&self, channel_size: usize) -> Result<bool, SendError<R2PMsg<T>>> {
+        let ReactorChannelTx::MultiChannel(priority_channel_tx) = self else {
+            return Ok(false);
+        };
+        let (new_tx, new_rx) = mpsc::channel(channel_size);
+        priority_channel_tx.send(R2PMsg::AddPrio(new_rx)).await?;
+        priority_channel_tx.senders.write().unwrap().push(new_tx);
+        Ok(true)
+    }
+
+    /// Removes the lowest priority, for every clone of this sender. Its messages then go to the
+    /// next lowest one; those already queued are still delivered. Returns false if one priority
+    /// is left.
+    pub(crate) async fn remove_prio(&self) -> Result<bool, SendError<R2PMsg<T>>> {
+        let ReactorChannelTx::MultiChannel(priority_channel_tx) = self else {
+            return Ok(false);
+        };
+        if !priority_channel_tx.remove_prio() {
+            return Ok(false);
+        }
+        priority_channel_tx.send(R2PMsg::RemoveLowPrio).await?;
+        Ok(true)
+    }
+}
+
 pub enum ReactorChannelRx<T> {
     SingleChannel(Receiver<T>),
     MultiChannel(PriorityChannelRx<T>),
 }
 impl<T> ReactorChannelRx<T> {
+    /// Blocks until a message arrives, so it must not be called on the async workers.
     pub fn recv(&mut self) -> Option<T> {
         match self {
             ReactorChannelRx::SingleChannel(receiver) => receiver.blocking_recv(),
-            ReactorChannelRx::MultiChannel(priority_channel_rx) => loop {
-                match priority_channel_rx.try_recv() {
-                    Ok(msg) => {
-                        return Some(msg);
-                    }
-                    Err(TryRecvError::Empty) => {
-                        continue;
-                    }
-                    Err(TryRecvError::Disconnected) => {
-                        return None;
-                    }
-                }
-            },
+            ReactorChannelRx::MultiChannel(priority_channel_rx) => {
+                futures::executor::block_on(priority_channel_rx.recv())
+            }
+        }
+    }
+
+    /// Number of queued messages, over all priorities.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            ReactorChannelRx::SingleChannel(receiver) => receiver.len(),
+            ReactorChannelRx::MultiChannel(priority_channel_rx) => priority_channel_rx
+                .receivers
+                .iter()
+                .map(|rx| rx.len())
+                .sum(),
         }
     }
 
-    pub(crate) fn add_prio(self, new_rx: Receiver<T>) -> Self {
+    /// Like [`Self::recv`], for processors running on the async workers.
+    pub async fn recv_async(&mut self) -> Option<T> {
         match self {
+            ReactorChannelRx::SingleChannel(receiver) => receiver.recv().await,
+            ReactorChannelRx::MultiChannel(priority_channel_rx) => priority_channel_rx.recv().await,
+        }
+    }
+
+    pub(crate) fn add_prio(&mut self, new_rx: Receiver<T>) {
+        match std::mem::replace(self, Self::empty()) {
             ReactorChannelRx::SingleChannel(receiver) => {
-                ReactorChannelRx::MultiChannel(PriorityChannelRx {
+                *self = ReactorChannelRx::MultiChannel(PriorityChannelRx {
                     receivers: vec![receiver, new_rx],
-                })
+                    fair: None,
+                });
             }
             ReactorChannelRx::MultiChannel(mut priority_channel_rx) => {
                 priority_channel_rx.add_prio(new_rx);
-                ReactorChannelRx::MultiChannel(priority_channel_rx)
+                *self = ReactorChannelRx::MultiChannel(priority_channel_rx);
             }
         }
     }
-    pub(crate) fn remove_prio(self) -> Self {
-        match self {
-            ReactorChannelRx::SingleChannel(_) => panic!("Single Channel"),
-            ReactorChannelRx::MultiChannel(mut priority_channel_rx) => {
-                priority_channel_rx.remove_prio();
-                if priority_channel_rx.curr_prios() == 1 {
-                    ReactorChannelRx::SingleChannel(priority_channel_rx.receivers.pop().unwrap())
-                } else {
-                    ReactorChannelRx::MultiChannel(priority_channel_rx)
-                }
-            }
+
+    /// Switches to the weighted-fair mode, see [`PriorityChannelRx::set_weights`]. A channel with
+    /// a single priority has nothing to share.
+    pub(crate) fn set_weights(&mut self, weights: Vec<u32>) {
+        if let ReactorChannelRx::MultiChannel(priority_channel_rx) = self {
+            priority_channel_rx.set_weights(weights);
         }
     }
+
+    /// Placeholder while the channel is rebuilt in place.
+    fn empty() -> Self {
+        ReactorChannelRx::MultiChannel(PriorityChannelRx {
+            receivers: vec![],
+            fair: None,
+        })
+    }
 }
 
 pub fn reactor_channel<T: HasPriority>(
@@ -134,8 +167,13 @@ pub fn reactor_channel<T: HasPriority>(
         }
 
         (
-            ReactorChannelTx::MultiChannel(PriorityChannelTx { senders }),
-            ReactorChannelRx::MultiChannel(PriorityChannelRx { receivers }),
+            ReactorChannelTx::MultiChannel(PriorityChannelTx {
+                senders: Arc::new(RwLock::new(senders)),
+            }),
+            ReactorChannelRx::MultiChannel(PriorityChannelRx {
+                receivers,
+                fair: None,
+            }),
         )
     }
 }
@@ -143,49 +181,59 @@ pub fn reactor_channel<T: HasPriority>(
 /// Messages that can flow between the actors.
 //pub trait Msg: Send + std::fmt::Debug {}
 pub trait HasPriority {
+    /// Index of the channel the message is queued on, [`MAX_PRIO`] being the most urgent. A
+    /// priority the actor has no channel for falls to its lowest one.
     fn priority(&self) -> usize {
         MAX_PRIO
     }
 }
 
+/// Sending end of a multi-priority channel. Clones share their senders, so a priority added or
+/// removed through one of them applies to all.
 #[derive(Clone)]
 pub struct PriorityChannelTx<T> {
-    senders: Vec<mpsc::Sender<T>>,
+    senders: Arc<RwLock<Vec<mpsc::Sender<T>>>>,
 }
 
 pub struct PriorityChannelRx<T> {
     receivers: Vec<mpsc::Receiver<T>>,
+    fair: Option<FairShare>,
+}
+
+/// Credits of the weighted-fair mode. Each priority takes up to its weight in messages per round,
+/// and a new round starts once no priority with credits left has a message.
+struct FairShare {
+    weights: Vec<u32>,
+    credits: Vec<u32>,
+}
+
+impl FairShare {
+    fn weight(&self, idx: usize) -> u32 {
+        self.weights.get(idx).copied().unwrap_or(1)
+    }
 }
 
 impl<T: HasPriority> PriorityChannelTx<T> {
-    pub async fn send(&self, msg: T) -> Result<(), SendError<T>> {
-        let idx = msg.priority();
-        if let Some(tx) = self.senders.get(idx) {
-            (*tx).send(msg).await
-        } else {
-            Err(SendError(msg))
-        }
+    fn sender_for(&self, msg: &T) -> mpsc::Sender<T> {
+        let senders = self.senders.read().unwrap();
+        senders[msg.priority().min(senders.len() - 1)].clone()
     }
-    pub fn send_blocking(&self, msg: T) -> Result<(), SendError<T>> {
-        let idx = msg.priority();
-        if let Some(tx) = self.senders.get(idx) {
-            (*tx).blocking_send(msg)
-        } else {
-            Err(SendError(msg))
-        }
+
+    pub async fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        self.sender_for(&msg).send(msg).await
     }
 }
 
 impl<T> PriorityChannelTx<T> {
-    pub fn remove_prio(&mut self) {
-        self.senders.pop();
-    }
-    pub fn curr_prios(&self) -> u8 {
-        self.senders.len() as u8
+    /// Stops sending on the lowest priority, unless it is the only one.
+    pub fn remove_prio(&self) -> bool {
+        let mut senders = self.senders.write().unwrap();
+        senders.len() > 1 && senders.pop().is_some()
     }
 }
 
 impl<T> PriorityChannelRx<T> {
+    #[cfg(test)]
     pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
         let mut disconnected_count = 0;
 
@@ -204,16 +252,71 @@ impl<T> PriorityChannelRx<T> {
         }
     }
 
-    pub fn add_prio(&mut self, new_rx: mpsc::Receiver<T>) {
-        self.receivers.push(new_rx);
+    /// Waits for the next message without spinning. Messages come strictly by priority, or by
+    /// weight in the weighted-fair mode.
+    pub async fn recv(&mut self) -> Option<T> {
+        std::future::poll_fn(|cx| self.poll_recv(cx)).await
     }
 
-    fn remove_prio(&mut self) {
-        self.receivers.pop();
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut disconnected = vec![];
+        let mut received = None;
+        // priorities with credits left are served first, then a new round starts with the others
+        'rounds: for new_round in [false, true] {
+            for (idx, rx) in self.receivers.iter_mut().enumerate() {
+                let has_credit = self.fair.as_ref().is_none_or(|f| f.credits[idx] > 0);
+                if has_credit == new_round {
+                    continue;
+                }
+                match rx.poll_recv(cx) {
+                    Poll::Ready(Some(msg)) => {
+                        received = Some((idx, msg, new_round));
+                        break 'rounds;
+                    }
+                    Poll::Ready(None) => disconnected.push(idx),
+                    Poll::Pending => {}
+                }
+            }
+        }
+
+        if let Some((idx, msg, new_round)) = received {
+            if let Some(fair) = &mut self.fair {
+                if new_round {
+                    fair.credits = (0..fair.credits.len()).map(|i| fair.weight(i)).collect();
+                }
+                fair.credits[idx] = fair.credits[idx].saturating_sub(1);
+            }
+            return Poll::Ready(Some(msg));
+        }
+        if disconnected.len() == self.receivers.len() {
+            return Poll::Ready(None);
+        }
+        // a removed priority is dropped once its queued messages are delivered
+        for idx in disconnected.into_iter().rev().filter(|idx| *idx > 0) {
+            self.receivers.remove(idx);
+            if let Some(fair) = &mut self.fair {
+                fair.credits.remove(idx);
+            }
+        }
+        Poll::Pending
     }
 
-    fn curr_prios(&self) -> u8 {
-        self.receivers.len() as u8
+    pub fn add_prio(&mut self, new_rx: mpsc::Receiver<T>) {
+        self.receivers.push(new_rx);
+        if let Some(fair) = &mut self.fair {
+            fair.credits.push(fair.weight(fair.credits.len()));
+        }
+    }
+
+    /// Serves up to `weights[i]` messages of priority `i` per round while lower priorities wait,
+    /// so a busy high priority cannot starve them. Priorities without a weight get 1.
+    pub fn set_weights(&mut self, weights: Vec<u32>) {
+        let mut fair = FairShare {
+            weights,
+            credits: vec![],
+        };
+        fair.credits = (0..self.receivers.len()).map(|i| fair.weight(i)).collect();
+        self.fair = Some(fair);
     }
 }
 
@@ -222,6 +325,7 @@ mod tests {
     use crate::R2PMsg;
 
     use super::*;
+
     fn priority_channel<T: HasPriority>(
         num_prios: u8,
         channel_size: usize,
@@ -236,8 +340,13 @@ mod tests {
         }
 
         (
-            PriorityChannelTx { senders },
-            PriorityChannelRx { receivers },
+            PriorityChannelTx {
+                senders: Arc::new(RwLock::new(senders)),
+            },
+            PriorityChannelRx {
+                receivers,
+                fair: None,
+            },
         )
     }
 
@@ -333,21 +442,121 @@ mod tests {
     // }
     #[tokio::test]
     async fn test_partial_disconnect_behavior() {
-        let (mut tx, mut rx) = priority_channel::<R2PMsg<TestMsg>>(3, 100);
+        let (tx, mut rx) = priority_channel::<R2PMsg<TestMsg>>(3, 100);
         let origin = "origin";
 
-        tx.remove_prio();
+        assert!(tx.remove_prio());
 
         tx.send(R2PMsg::Msg(TestMsg::High, origin)).await.unwrap();
         assert_eq!(rx.try_recv(), Ok(R2PMsg::Msg(TestMsg::High, origin)));
         tx.send(R2PMsg::Msg(TestMsg::Medium, origin)).await.unwrap();
         assert_eq!(rx.try_recv(), Ok(R2PMsg::Msg(TestMsg::Medium, origin)));
-        let msg = R2PMsg::Msg(TestMsg::Low, origin);
-        match tx.send(msg.clone()).await {
-            Ok(_) => panic!(),
-            Err(send_err) => {
-                assert_eq!(send_err.0, msg);
-            }
+        // the removed priority falls to the lowest one left, behind what is already queued there
+        tx.send(R2PMsg::Msg(TestMsg::Medium, origin)).await.unwrap();
+        tx.send(R2PMsg::Msg(TestMsg::Low, origin)).await.unwrap();
+        tx.send(R2PMsg::Msg(TestMsg::High, origin)).await.unwrap();
+        assert_eq!(rx.try_recv(), Ok(R2PMsg::Msg(TestMsg::High, origin)));
+        assert_eq!(rx.try_recv(), Ok(R2PMsg::Msg(TestMsg::Medium, origin)));
+        assert_eq!(rx.try_recv(), Ok(R2PMsg::Msg(TestMsg::Low, origin)));
+
+        assert!(tx.remove_prio());
+        assert!(!tx.remove_prio(), "the last priority stays");
+    }
+
+    fn msg(rx_msg: Option<R2PMsg<TestMsg>>) -> TestMsg {
+        match rx_msg {
+            Some(R2PMsg::Msg(m, _)) => m,
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_blocking_recv_waits_for_messages() {
+        let (tx, mut rx) = reactor_channel::<R2PMsg<TestMsg>>(3, 100);
+        let receiver = std::thread::spawn(move || {
+            let first = rx.recv();
+            (first, rx.recv())
+        });
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!receiver.is_finished(), "nothing was sent yet");
+        futures::executor::block_on(tx.send(R2PMsg::Msg(TestMsg::Low, "origin"))).unwrap();
+        drop(tx);
+        let (first, second) = receiver.join().unwrap();
+        assert_eq!(msg(first), TestMsg::Low);
+        assert_eq!(second, None, "all senders are gone");
+    }
+
+    #[tokio::test]
+    async fn test_recv_is_strict_by_default() {
+        let (tx, mut rx) = priority_channel::<R2PMsg<TestMsg>>(3, 100);
+        for m in [TestMsg::Low, TestMsg::High, TestMsg::Medium, TestMsg::High] {
+            tx.send(R2PMsg::Msg(m, "origin")).await.unwrap();
+        }
+        let mut order = vec![];
+        for _ in 0..4 {
+            order.push(msg(rx.recv().await));
+        }
+        assert_eq!(
+            order,
+            vec![TestMsg::High, TestMsg::High, TestMsg::Medium, TestMsg::Low]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_weighted_fair_does_not_starve() {
+        let (tx, mut rx) = priority_channel::<R2PMsg<TestMsg>>(3, 100);
+        rx.set_weights(vec![3, 1, 1]);
+        for _ in 0..7 {
+            tx.send(R2PMsg::Msg(TestMsg::High, "origin")).await.unwrap();
+        }
+        for _ in 0..2 {
+            tx.send(R2PMsg::Msg(TestMsg::Low, "origin")).await.unwrap();
+        }
+        let mut order = vec![];
+        for _ in 0..9 {
+            order.push(match msg(rx.recv().await) {
+                TestMsg::High => 'H',
+                TestMsg::Medium => 'M',
+                TestMsg::Low => 'L',
+            });
+        }
+        assert_eq!(order.into_iter().collect::<String>(), "HHHLHHHLH");
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove_prio_at_runtime() {
+        let (tx, mut rx) = reactor_channel::<R2PMsg<TestMsg>>(2, 100);
+        let clone = tx.clone();
+        assert!(tx.add_prio(100).await.unwrap());
+        match rx.recv_async().await {
+            Some(R2PMsg::AddPrio(new_rx)) => rx.add_prio(new_rx),
+            other => panic!("unexpected {:?}", other),
         }
+        // the clone sends on the new priority too
+        clone
+            .send(R2PMsg::Msg(TestMsg::Low, "origin"))
+            .await
+            .unwrap();
+        clone
+            .send(R2PMsg::Msg(TestMsg::Medium, "origin"))
+            .await
+            .unwrap();
+        assert_eq!(msg(rx.recv_async().await), TestMsg::Medium);
+
+        assert!(clone.remove_prio().await.unwrap());
+        assert!(matches!(rx.recv_async().await, Some(R2PMsg::RemoveLowPrio)));
+        // queued before the removal, so still delivered
+        assert_eq!(msg(rx.recv_async().await), TestMsg::Low);
+        tx.send(R2PMsg::Msg(TestMsg::Low, "origin")).await.unwrap();
+        assert_eq!(msg(rx.recv_async().await), TestMsg::Low);
+        let idle = std::time::Duration::from_millis(10);
+        assert!(tokio::time::timeout(idle, rx.recv_async()).await.is_err());
+        let ReactorChannelRx::MultiChannel(prx) = &rx else {
+            panic!("stays a priority channel")
+        };
+        assert_eq!(prx.receivers.len(), 2, "the drained priority is dropped");
+
+        let (single, _) = reactor_channel::<R2PMsg<TestMsg>>(1, 100);
+        assert!(!single.add_prio(100).await.unwrap());
     }
 }
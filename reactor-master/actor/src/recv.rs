@@ -6,13 +6,20 @@ use std::{
     time::Duration,
 };
 
-use futures::StreamExt as _;
+use futures::{FutureExt as _, Stream, StreamExt as _, future::BoxFuture};
 use rand::{Rng, SeedableRng as _, rngs::SmallRng};
 use socket2::{Domain, Socket, Type};
 use tokio::{
     io::{AsyncRead, AsyncReadExt as _},
-    net::{TcpListener, tcp::OwnedReadHalf},
-    sync::{Mutex, mpsc, watch},
+    net::{
+        TcpListener,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    sync::{
+        Mutex,
+        mpsc::{self, error::TrySendError},
+        watch,
+    },
     task::JoinSet,
 };
 use tokio_util::{
@@ -23,9 +30,12 @@ use tracing::{error, warn};
 
 use crate::{
     ActorRecv, ChannelAction, Msg, R2PMsg, SubDecoderStore,
+    barrier::{BarrierSeat, SyncBarrier},
     err::{ActorError, RecieverErr},
-    node_comm::{ControlInst, LocalChannelRx},
-    reactor_channel::ReactorChannelTx,
+    mailbox::{self, MailboxCfg, MailboxTx, Overflow, QueueStats},
+    node_comm::{ControlInst, DrainDeadline, LocalChannelRx, LocalHandshake},
+    reactor_channel::{HasPriority as _, ReactorChannelTx},
+    reliable::{AckTx, Hello, Inbound, LocalSeqMsg, Sequenced, Session},
 };
 
 struct BoxedDecoder<M>(
@@ -49,6 +59,99 @@ type ThrottleSignalStore = Arc<Mutex<HashMap<String, watch::Sender<(u64, u64)>>>
 fn any_to_m<M: 'static>(msg: Box<dyn std::any::Any>) -> M {
     *(msg.downcast::<M>().unwrap())
 }
+
+/// The receivers' end of the inbox, which applies its overflow policy to what they forward, see
+/// [`crate::BehaviourBuilder::inbox`].
+#[derive(Clone)]
+pub(crate) struct InboxTx<M> {
+    row_q: ReactorChannelTx<R2PMsg<M>>,
+    overflow: Overflow,
+    stats: Arc<QueueStats>,
+    /// Under [`Overflow::DropOldest`], the queue of each priority in front of the processor.
+    staged: Vec<MailboxTx<R2PMsg<M>>>,
+}
+
+/// Size of each priority of the processor channel. Under [`Overflow::DropOldest`] messages wait
+/// in the receivers' queues instead, where the oldest one can still be dropped.
+pub(crate) fn processor_channel_size(inbox: MailboxCfg) -> usize {
+    match inbox.overflow {
+        Overflow::DropOldest => 1,
+        _ => inbox.capacity,
+    }
+}
+
+impl<M: Msg> InboxTx<M> {
+    fn new(row_q: ReactorChannelTx<R2PMsg<M>>, overflow: Overflow, stats: Arc<QueueStats>) -> Self {
+        InboxTx {
+            row_q,
+            overflow,
+            stats,
+            staged: vec![],
+        }
+    }
+
+    /// An inbox that drops its oldest message, with a queue of `capacity` messages per priority.
+    /// The returned pumps move what is queued on to the processor; each ends once every clone of
+    /// the inbox is gone and its queue is empty. Priorities added later share the lowest queue.
+    fn dropping_oldest(
+        row_q: ReactorChannelTx<R2PMsg<M>>,
+        capacity: usize,
+        stats: Arc<QueueStats>,
+    ) -> (Self, Vec<BoxFuture<'static, ()>>) {
+        let mut inbox = InboxTx::new(row_q.clone(), Overflow::DropOldest, stats.clone());
+        let cfg = MailboxCfg::new(capacity, Overflow::DropOldest);
+        let pumps = (0..row_q.num_prios())
+            .map(|_| {
+                let (staged, mut queued) = mailbox::mailbox(Some(cfg), stats.clone());
+                inbox.staged.push(staged);
+                let row_q = row_q.clone();
+                async move {
+                    while let Some(msg) = queued.recv().await {
+                        if row_q.send(msg).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                .boxed()
+            })
+            .collect();
+        (inbox, pumps)
+    }
+
+    /// Forwards `msg` to the processor. Returns false once the processor is gone, or when the
+    /// inbox is full under [`Overflow::Fail`]: the message is refused and its channel closed, so
+    /// its sender's `on_send_failure` applies while the actor goes on.
+    async fn forward(&self, msg: R2PMsg<M>) -> bool {
+        match self.overflow {
+            Overflow::Block => return self.row_q.send(msg).await.is_ok(),
+            Overflow::DropOldest => {
+                let staged = &self.staged[msg.priority().min(self.staged.len() - 1)];
+                return staged.send(msg).await.is_ok();
+            }
+            Overflow::DropNewest | Overflow::Fail => {}
+        }
+        match self.row_q.try_send(msg) {
+            Ok(()) => true,
+            Err(TrySendError::Closed(_)) => false,
+            Err(TrySendError::Full(_)) if self.overflow == Overflow::DropNewest => {
+                self.stats.drop_one();
+                true
+            }
+            Err(TrySendError::Full(_)) => {
+                self.stats.drop_one();
+                warn!("Inbox full, closing the channel the message came on");
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl<M: Msg> From<ReactorChannelTx<R2PMsg<M>>> for InboxTx<M> {
+    fn from(row_q: ReactorChannelTx<R2PMsg<M>>) -> Self {
+        InboxTx::new(row_q, Overflow::Block, Default::default())
+    }
+}
 /// Spawns tasks to receive messages from incoming network or local control channels,
 /// decode them, and forward them for processing based on channel state.
 ///
@@ -86,6 +189,7 @@ fn any_to_m<M: 'static>(msg: Box<dyn std::any::Any>) -> M {
 ///   Spawns tasks to receive messages from incoming network or local control channels,
 ///   decode them, and forward them for processing based on channel state.
 ///
+#[allow(clippy::too_many_arguments)]
 pub(crate) async fn rx<M, AR, D>(
     my_addr: &'static str,
     reciever: Option<AR>,
@@ -93,6 +197,10 @@ pub(crate) async fn rx<M, AR, D>(
     decoder: D,
     sub_decoders: Option<SubDecoderStore<M>>,
     mut controller_rx: mpsc::Receiver<ControlInst>,
+    inbox_cfg: MailboxCfg,
+    inbox_stats: Arc<QueueStats>,
+    inbound: Inbound,
+    drain: Arc<DrainDeadline>,
 ) -> Result<(), ActorError>
 where
     D: Decoder<Item = M, Error = std::io::Error> + Clone + Send + Sync + 'static,
@@ -105,6 +213,18 @@ where
     let mut local_recv_set = JoinSet::new();
     let channel_state = reciever.map(|reciever| Arc::new(Mutex::new(reciever)));
     let throttle_signals: ThrottleSignalStore = Arc::new(Mutex::new(HashMap::new()));
+    let barrier = SyncBarrier::new();
+    let mut pumps = JoinSet::new();
+    let inbox = if inbox_cfg.overflow == Overflow::DropOldest {
+        let (inbox, staged) =
+            InboxTx::dropping_oldest(p_tx.clone(), inbox_cfg.capacity, inbox_stats);
+        for pump in staged {
+            pumps.spawn(pump);
+        }
+        inbox
+    } else {
+        InboxTx::new(p_tx.clone(), inbox_cfg.overflow, inbox_stats)
+    };
 
     while let Some(msg) = controller_rx.recv().await {
         match msg {
@@ -114,15 +234,17 @@ where
                     cancel_token.clone(),
                     decoder.clone(),
                     sub_decoders,
-                    p_tx.clone(),
+                    inbox.clone(),
                     channel_state.clone(),
                     throttle_signals.clone(),
+                    barrier.clone(),
+                    inbound.clone(),
                 ));
             }
             ControlInst::StartLocalRecv(mut local_rx) => {
                 let remote_addr = local_rx.recv().await.unwrap();
-                let (remote_addr, msg_type) =
-                    *(remote_addr.downcast::<(String, Option<String>)>().unwrap());
+                let (remote_addr, msg_type, reliable) =
+                    *(remote_addr.downcast::<LocalHandshake>().unwrap());
                 let msg_transform = match (sub_decoders, msg_type) {
                     (Some(sub_decoders), Some(msg_type)) => {
                         sub_decoders(&msg_type).unwrap().any_to_m
@@ -145,13 +267,34 @@ where
                             .insert(remote_addr.clone(), throttle_tx);
                         throttle_rx
                     };
+                if let Some((hello, acks)) = reliable {
+                    let session = inbound.open(&remote_addr, hello);
+                    let msgs = futures::stream::poll_fn(move |cx| local_rx.poll_recv(cx)).map(
+                        move |msg| {
+                            let (seq, msg) = *msg.downcast::<LocalSeqMsg>().unwrap();
+                            (seq, msg_transform(msg))
+                        },
+                    );
+                    local_recv_set.spawn(reliable_recv_subtask(
+                        remote_addr,
+                        inbox.clone(),
+                        channel_state.clone(),
+                        msgs,
+                        AckTx::Local(acks),
+                        session,
+                        throttle_rx,
+                        barrier.join(),
+                    ));
+                    continue;
+                }
                 local_recv_set.spawn(local_parent_recv_subtask(
                     remote_addr,
-                    p_tx.clone(),
+                    inbox.clone(),
                     channel_state.clone(),
                     local_rx,
                     msg_transform,
                     throttle_rx,
+                    barrier.join(),
                 ));
             }
             ControlInst::SetMsgDuplication {
@@ -206,6 +349,27 @@ where
                     }
                 }
             }
+            ControlInst::AddPrio => {
+                let added = p_tx
+                    .add_prio(processor_channel_size(inbox_cfg))
+                    .await
+                    .map_err(|_| ActorError::R2PErr)?;
+                if !added {
+                    warn!(
+                        "[ACTOR][{}] Built with a single priority, not adding one",
+                        my_addr
+                    );
+                }
+            }
+            ControlInst::RemoveLowPrio => {
+                let removed = p_tx.remove_prio().await.map_err(|_| ActorError::R2PErr)?;
+                if !removed {
+                    warn!(
+                        "[ACTOR][{}] Only one priority left, not removing it",
+                        my_addr
+                    );
+                }
+            }
             ControlInst::Stop => {
                 cancel_token.cancel();
                 p_tx.send(R2PMsg::Exit)
@@ -213,6 +377,21 @@ where
                     .map_err(|_| ActorError::R2PErr)?;
                 break;
             }
+            ControlInst::Drain { deadline_ms } => {
+                tracing::info!("[ACTOR][{}] Draining within {}ms", my_addr, deadline_ms);
+                let deadline = drain.start(Duration::from_millis(deadline_ms));
+                // no input arrives behind the drain
+                cancel_token.cancel();
+                tcp_server_set.abort_all();
+                local_recv_set.abort_all();
+                // what the receivers queued goes ahead of the drain
+                drop(inbox);
+                while pumps.join_next().await.is_some() {}
+                p_tx.send(R2PMsg::Drain(deadline))
+                    .await
+                    .map_err(|_| ActorError::R2PErr)?;
+                break;
+            }
         }
     }
     tcp_server_set.abort_all();
@@ -221,14 +400,17 @@ where
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn tcp_recv<D, M, AR>(
     port: u16,
     cancel_token: CancellationToken,
     master_decoder: D,
     sub_decoders: Option<SubDecoderStore<M>>,
-    p_tx: ReactorChannelTx<R2PMsg<M>>,
+    inbox: InboxTx<M>,
     cstate: Option<Arc<Mutex<AR>>>,
     throttle_signals: ThrottleSignalStore,
+    barrier: Arc<SyncBarrier>,
+    inbound: Inbound,
 ) -> Result<(), ActorError>
 where
     M: Msg,
@@ -263,10 +445,18 @@ where
                 let (socket, _) = accept_result.map_err(|e| {
                     ActorError::RecieverErr(RecieverErr::TcpStartErr(e))
                 })?;
-                let (mut rx, _) = socket.into_split();
+                let (mut rx, tx) = socket.into_split();
                 // Whenever an actor connects it first needs to tell us its
                 // address, and message type.
-                let (remote_addr, msg_type) = recv_remote_handshake(&mut rx).await;
+                // a peer that hangs up mid-handshake only loses its own connection
+                let (remote_addr, msg_type, hello) = match recv_remote_handshake(&mut rx).await {
+                    Ok(handshake) => handshake,
+                    Err(e) => {
+                        warn!("Dropping a connection without a handshake: {}", e);
+                        continue;
+                    }
+                };
+                let session = hello.map(|hello| inbound.open(&remote_addr, hello));
 
                 match (sub_decoders, msg_type){
                     (Some(sub_decoders), Some(msg_type)) => {
@@ -274,12 +464,15 @@ where
                         let boxed_decoder = BoxedDecoder(decoder);
                         let (throttle_tx, throttle_rx) = watch::channel((0,0));
                         throttle_signals.lock().await.insert(remote_addr.clone(), throttle_tx);
-                        remote_recv_set.spawn(remote_parent_recv_subtask(
+                        remote_recv_set.spawn(remote_subtask(
                             remote_addr,
-                            p_tx.clone(),
+                            inbox.clone(),
                             cstate.clone(),
-                            FramedRead::new(rx, boxed_decoder),
-                            throttle_rx
+                            (rx, tx),
+                            boxed_decoder,
+                            session,
+                            throttle_rx,
+                            barrier.join(),
                         ));
                     },
                     (None, Some(_)) => {
@@ -288,12 +481,15 @@ where
                     _ => {
                         let (throttle_tx, throttle_rx) = watch::channel((0,0));
                         throttle_signals.lock().await.insert(remote_addr.clone(), throttle_tx);
-                        remote_recv_set.spawn(remote_parent_recv_subtask(
+                        remote_recv_set.spawn(remote_subtask(
                             remote_addr,
-                            p_tx.clone(),
+                            inbox.clone(),
                             cstate.clone(),
-                            FramedRead::new(rx, master_decoder.clone()),
-                            throttle_rx
+                            (rx, tx),
+                            master_decoder.clone(),
+                            session,
+                            throttle_rx,
+                            barrier.join(),
                         ));
 
                     },
@@ -305,34 +501,94 @@ where
     Ok(())
 }
 
-/// Receive actor name and message type
-async fn recv_remote_handshake(rx: &mut OwnedReadHalf) -> (String, Option<String>) {
+/// Receive actor name, message type, and whether the channel is reliable
+pub(crate) async fn recv_remote_handshake(
+    rx: &mut OwnedReadHalf,
+) -> std::io::Result<(String, Option<String>, Option<Hello>)> {
     // Step 1: Read 4 bytes as the length prefix
-    let size = rx.read_u32().await.unwrap();
+    let size = rx.read_u32().await?;
     // Step 2: Allocate a buffer of that size
     let mut buf = vec![0u8; size as usize];
     // Step 3: Read exactly that many bytes into the buffer
-    rx.read_exact(&mut buf).await.unwrap();
+    rx.read_exact(&mut buf).await?;
     // Step 4: Convert to String
-    let actor_addr = String::from_utf8(buf).unwrap();
+    let actor_addr = String::from_utf8(buf).map_err(std::io::Error::other)?;
 
-    let size = rx.read_u32().await.unwrap();
-    if size == 0 {
-        return (actor_addr, None);
-    }
-    let mut buf = vec![0u8; size as usize];
-    rx.read_exact(&mut buf).await.unwrap();
-    let message_type = String::from_utf8(buf).unwrap();
+    let size = rx.read_u32().await?;
+    let message_type = if size == 0 {
+        None
+    } else {
+        let mut buf = vec![0u8; size as usize];
+        rx.read_exact(&mut buf).await?;
+        Some(String::from_utf8(buf).map_err(std::io::Error::other)?)
+    };
+
+    let hello = if rx.read_u8().await? == 1 {
+        Some(Hello {
+            session: rx.read_u64().await?,
+            base: rx.read_u64().await?,
+        })
+    } else {
+        None
+    };
+
+    Ok((actor_addr, message_type, hello))
+}
 
-    (actor_addr, Some(message_type))
+/// Receives from a TCP sender, through [`reliable_recv_subtask`] if the sender opened a session.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn remote_subtask<M, AR, D>(
+    parent_addr: String,
+    inbox: InboxTx<M>,
+    cstate: Option<Arc<Mutex<AR>>>,
+    (rx, tx): (OwnedReadHalf, OwnedWriteHalf),
+    decoder: D,
+    session: Option<Session>,
+    throttle_signal: watch::Receiver<(u64, u64)>,
+    seat: BarrierSeat,
+) -> BoxFuture<'static, ()>
+where
+    AR: ActorRecv<IMsg = M> + 'static,
+    D: Decoder<Item = M, Error = std::io::Error> + Send + 'static,
+    M: Msg,
+{
+    let Some(session) = session else {
+        // acks are the only thing a receiver writes
+        drop(tx);
+        let framed_reader = FramedRead::new(rx, decoder);
+        return remote_parent_recv_subtask(
+            parent_addr,
+            inbox,
+            cstate,
+            framed_reader,
+            throttle_signal,
+            seat,
+        )
+        .boxed();
+    };
+    // like the plain subtask, stop at the first frame that fails to decode
+    let msgs = FramedRead::new(rx, Sequenced(decoder))
+        .scan((), |_, frame| futures::future::ready(frame.ok()));
+    reliable_recv_subtask(
+        parent_addr,
+        inbox,
+        cstate,
+        msgs,
+        AckTx::Tcp(tx),
+        session,
+        throttle_signal,
+        seat,
+    )
+    .boxed()
 }
 
 async fn remote_parent_recv_subtask<M, AR, D, RX>(
     parent_addr: String,
-    row_q: ReactorChannelTx<R2PMsg<M>>,
+    inbox: InboxTx<M>,
     cstate: Option<Arc<Mutex<AR>>>,
     mut framed_reader: FramedRead<RX, D>,
     mut throttle_signal: watch::Receiver<(u64, u64)>,
+    seat: BarrierSeat,
 ) where
     AR: ActorRecv<IMsg = M>,
     D: Decoder<Item = M>,
@@ -365,17 +621,20 @@ async fn remote_parent_recv_subtask<M, AR, D, RX>(
                         ChannelAction::DROP => {
                             continue;
                         }
-                        ChannelAction::SYNC(_) => todo!(),
+                        ChannelAction::SYNC(round) => seat.sync(round).await,
                         ChannelAction::CLOSE => {
                             break;
                         }
                     }
-                    if row_q.send(R2PMsg::Msg(msg, parent_addr)).await.is_err() {
+                    if !inbox.forward(R2PMsg::Msg(msg, parent_addr)).await {
                         break;
                     }
-                } else if row_q.send(R2PMsg::Msg(msg, parent_addr)).await.is_err() {
+                } else if !inbox.forward(R2PMsg::Msg(msg, parent_addr)).await {
                     break;
                 }
+            } else {
+                // connection closed or framing lost
+                break;
             }
         }
 
@@ -386,11 +645,12 @@ async fn remote_parent_recv_subtask<M, AR, D, RX>(
 
 async fn local_parent_recv_subtask<M, AR>(
     parent_addr: String,
-    row_q: ReactorChannelTx<R2PMsg<M>>,
+    inbox: InboxTx<M>,
     after_recv: Option<Arc<Mutex<AR>>>,
     mut local_rx: LocalChannelRx,
     msg_transform: fn(Box<dyn std::any::Any>) -> M,
     mut throttle_signal: watch::Receiver<(u64, u64)>,
+    seat: BarrierSeat,
 ) where
     M: Msg + 'static,
     AR: ActorRecv<IMsg = M>,
@@ -425,21 +685,372 @@ async fn local_parent_recv_subtask<M, AR>(
                             ChannelAction::DROP => {
                                 continue;
                             }
-                            ChannelAction::SYNC(_) => todo!(),
+                            ChannelAction::SYNC(round) => seat.sync(round).await,
                             ChannelAction::CLOSE => {
                                 break;
                             }
                         }
-                        if row_q.send(R2PMsg::Msg(msg, parent_addr)).await.is_err() {
+                        if !inbox.forward(R2PMsg::Msg(msg, parent_addr)).await {
                             break;
                         }
-                    } else if row_q.send(R2PMsg::Msg(msg, parent_addr)).await.is_err() {
+                    } else if !inbox.forward(R2PMsg::Msg(msg, parent_addr)).await {
                         break;
                     }
+                } else {
+                    // every sender of the channel is gone
+                    break;
+                }
+            }
+
+        }
+    }
+    tracing::info!("[ACTOR] SubRx Ended");
+}
+
+/// Like [`remote_parent_recv_subtask`], for a reliable channel. Chaos applies first, then
+/// repeats are dropped, and every frame is answered with the session's cumulative ack.
+#[allow(clippy::too_many_arguments)]
+async fn reliable_recv_subtask<M, AR, S>(
+    parent_addr: String,
+    inbox: InboxTx<M>,
+    cstate: Option<Arc<Mutex<AR>>>,
+    mut msgs: S,
+    mut acks: AckTx,
+    session: Session,
+    mut throttle_signal: watch::Receiver<(u64, u64)>,
+    seat: BarrierSeat,
+) where
+    AR: ActorRecv<IMsg = M>,
+    S: Stream<Item = (u64, M)> + Unpin,
+    M: Msg,
+{
+    tracing::info!("[ACTOR] SubRx Started (Reliable)");
+    let parent_addr = parent_addr.leak();
+    let mut throttle_range = (0, 0);
+    let mut rng = SmallRng::from_seed([0; 32]);
+    'recv: loop {
+        tokio::select! {
+        _ = throttle_signal.changed() => {
+            throttle_range = *throttle_signal.borrow_and_update();
+        }
+        msg = msgs.next() => {
+            let Some((seq, msg)) = msg else {
+                // connection closed or framing lost
+                break;
+            };
+            if throttle_range != (0,0){
+                let throttle_ms: u64 = rng.random_range(throttle_range.0..=throttle_range.1);
+                warn!("Throttling messages from {parent_addr} for {throttle_ms}ms");
+                tokio::time::sleep(Duration::from_millis(throttle_ms)).await;
+            }
+            for msg in session.chaos(msg) {
+                if !session.accept(seq) {
+                    continue;
+                }
+                if let Some(cstate) = cstate.as_ref() {
+                    let action = cstate.lock().await.after_recv(parent_addr, &msg).await;
+                    match action {
+                        ChannelAction::PASS => {}
+                        ChannelAction::PANIC => {
+                            panic!()
+                        }
+                        // the actor chose to drop it, so it still counts as delivered
+                        ChannelAction::DROP => {
+                            continue;
+                        }
+                        ChannelAction::SYNC(round) => seat.sync(round).await,
+                        ChannelAction::CLOSE => {
+                            break 'recv;
+                        }
+                    }
+                }
+                // acked input waits for room whatever the inbox's policy
+                if inbox.row_q.send(R2PMsg::Reliable(msg, parent_addr)).await.is_err() {
+                    break 'recv;
                 }
             }
+            // a newer session of the sender took over
+            let Some(next) = session.next() else {
+                break;
+            };
+            if !acks.send(next).await {
+                break;
+            }
+        }
 
         }
     }
     tracing::info!("[ACTOR] SubRx Ended");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HasPriority, mailbox::MailboxMetrics, reactor_channel::reactor_channel};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Num(u64);
+    impl HasPriority for Num {}
+    impl Msg for Num {}
+
+    /// Messages of 100 and up end round `n / 100`.
+    struct Rounds;
+    impl ActorRecv for Rounds {
+        type IMsg = Num;
+        async fn after_recv(&mut self, _worker_id: &str, input: &Num) -> ChannelAction {
+            if input.0 >= 100 {
+                ChannelAction::SYNC((input.0 / 100) as u16)
+            } else {
+                ChannelAction::PASS
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_holds_channel_until_all_reach_round() {
+        let (p_tx, mut p_rx) = reactor_channel::<R2PMsg<Num>>(1, 100);
+        let cstate = Some(Arc::new(Mutex::new(Rounds)));
+        let barrier = SyncBarrier::new();
+        let mut inputs = vec![];
+        for addr in ["a", "b"] {
+            let (tx, rx) = mpsc::channel(100);
+            let (throttle_tx, throttle_rx) = watch::channel((0, 0));
+            tokio::spawn(local_parent_recv_subtask(
+                addr.to_string(),
+                p_tx.clone().into(),
+                cstate.clone(),
+                rx,
+                any_to_m::<Num>,
+                throttle_rx,
+                barrier.join(),
+            ));
+            // throttle senders are kept so the subtasks do not see them close
+            inputs.push((tx, throttle_tx));
+        }
+        let send = |i: usize, n: u64| {
+            let tx = inputs[i].0.clone();
+            async move { tx.send(Box::new(Num(n))).await.unwrap() }
+        };
+        let mut next = async || match p_rx.recv_async().await {
+            Some(R2PMsg::Msg(Num(n), _)) => n,
+            other => unreachable!("unexpected {:?}", other),
+        };
+
+        send(0, 1).await;
+        send(0, 100).await;
+        send(0, 2).await;
+        send(1, 3).await;
+        assert_eq!(next().await, 1);
+        assert_eq!(next().await, 3);
+        let idle = Duration::from_millis(50);
+        assert!(
+            tokio::time::timeout(idle, next()).await.is_err(),
+            "a waits for b to reach round 1"
+        );
+
+        send(1, 101).await;
+        let mut released = vec![next().await, next().await, next().await];
+        released.sort();
+        assert_eq!(released, vec![2, 100, 101]);
+    }
+
+    #[tokio::test]
+    async fn test_closed_local_input_releases_sync() {
+        let (p_tx, mut p_rx) = reactor_channel::<R2PMsg<Num>>(1, 100);
+        let cstate = Some(Arc::new(Mutex::new(Rounds)));
+        let barrier = SyncBarrier::new();
+        let mut inputs = vec![];
+        let mut subtasks = vec![];
+        for addr in ["a", "b"] {
+            let (tx, rx) = mpsc::channel(100);
+            let (throttle_tx, throttle_rx) = watch::channel((0, 0));
+            subtasks.push(tokio::spawn(local_parent_recv_subtask(
+                addr.to_string(),
+                p_tx.clone().into(),
+                cstate.clone(),
+                rx,
+                any_to_m::<Num>,
+                throttle_rx,
+                barrier.join(),
+            )));
+            inputs.push((tx, throttle_tx));
+        }
+        let idle = Duration::from_millis(50);
+
+        inputs[0].0.send(Box::new(Num(100))).await.unwrap();
+        assert!(
+            tokio::time::timeout(idle, p_rx.recv_async()).await.is_err(),
+            "a waits for b to reach round 1"
+        );
+
+        let (b_tx, _b_throttle) = inputs.pop().unwrap();
+        drop(b_tx);
+        let released = tokio::time::timeout(idle, p_rx.recv_async()).await;
+        assert_eq!(
+            released.expect("a closed channel releases the round"),
+            Some(R2PMsg::Msg(Num(100), "a"))
+        );
+        tokio::time::timeout(idle, subtasks.pop().unwrap())
+            .await
+            .expect("the receive loop spins on a closed channel")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_full_inbox_drops_newest() {
+        let (p_tx, mut p_rx) = reactor_channel::<R2PMsg<Num>>(1, 1);
+        let metrics = MailboxMetrics::default();
+        let inbox = InboxTx::new(p_tx, Overflow::DropNewest, metrics.inbox_stats(1));
+        let (tx, rx) = mpsc::channel(100);
+        let (_throttle_tx, throttle_rx) = watch::channel((0, 0));
+        let subtask = tokio::spawn(local_parent_recv_subtask(
+            "a".to_string(),
+            inbox,
+            None::<Arc<Mutex<Rounds>>>,
+            rx,
+            any_to_m::<Num>,
+            throttle_rx,
+            SyncBarrier::new().join(),
+        ));
+        for n in 1..=3 {
+            tx.send(Box::new(Num(n))).await.unwrap();
+        }
+        drop(tx);
+        subtask.await.unwrap();
+
+        assert_eq!(p_rx.recv_async().await, Some(R2PMsg::Msg(Num(1), "a")));
+        assert_eq!(p_rx.recv_async().await, None);
+        assert_eq!(metrics.inbox().dropped, 2);
+    }
+
+    #[tokio::test]
+    async fn test_full_inbox_drops_oldest() {
+        let cfg = MailboxCfg::new(2, Overflow::DropOldest);
+        let (p_tx, mut p_rx) = reactor_channel::<R2PMsg<Num>>(1, processor_channel_size(cfg));
+        let metrics = MailboxMetrics::default();
+        let (controller_tx, controller_rx) = mpsc::channel(1);
+        let receiving = tokio::spawn(rx(
+            "me",
+            None::<Rounds>,
+            p_tx,
+            NumDecoder,
+            None,
+            controller_rx,
+            cfg,
+            metrics.inbox_stats(2),
+            Inbound::default(),
+            Arc::new(DrainDeadline::default()),
+        ));
+        let (tx, local_rx): (_, LocalChannelRx) = mpsc::channel(100);
+        let handshake: LocalHandshake = ("a".to_string(), None, None);
+        tx.send(Box::new(handshake)).await.unwrap();
+        controller_tx
+            .send(ControlInst::StartLocalRecv(local_rx))
+            .await
+            .unwrap();
+        for n in 1..=10 {
+            tx.send(Box::new(Num(n))).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!receiving.is_finished());
+
+        controller_tx
+            .send(ControlInst::Drain { deadline_ms: 1000 })
+            .await
+            .unwrap();
+        let mut received = vec![];
+        let drained = loop {
+            match p_rx.recv_async().await {
+                Some(R2PMsg::Msg(Num(n), _)) => received.push(n),
+                other => break other,
+            }
+        };
+        assert!(matches!(drained, Some(R2PMsg::Drain(_))));
+        // the processor's channel and the pump hold one each, and the queue the newest two
+        assert!(received.len() <= 4, "kept {:?}", received);
+        assert!(received.is_sorted());
+        assert!(received.ends_with(&[9, 10]), "kept {:?}", received);
+        assert_eq!(metrics.inbox().dropped, 10 - received.len() as u64);
+        receiving.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_full_inbox_refuses_the_sender() {
+        let (p_tx, mut p_rx) = reactor_channel::<R2PMsg<Num>>(1, 1);
+        let metrics = MailboxMetrics::default();
+        let (controller_tx, controller_rx) = mpsc::channel(1);
+        let receiving = tokio::spawn(rx(
+            "me",
+            None::<Rounds>,
+            p_tx,
+            NumDecoder,
+            None,
+            controller_rx,
+            MailboxCfg::new(1, Overflow::Fail),
+            metrics.inbox_stats(1),
+            Inbound::default(),
+            Arc::new(DrainDeadline::default()),
+        ));
+        let connect = |name: &str| {
+            let (tx, local_rx): (_, LocalChannelRx) = mpsc::channel(100);
+            let handshake: LocalHandshake = (name.to_string(), None, None);
+            tx.try_send(Box::new(handshake)).unwrap();
+            (tx, ControlInst::StartLocalRecv(local_rx))
+        };
+        let (a_tx, start_a) = connect("a");
+        controller_tx.send(start_a).await.unwrap();
+        a_tx.send(Box::new(Num(1))).await.unwrap();
+        a_tx.send(Box::new(Num(2))).await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(1), a_tx.closed())
+            .await
+            .expect("the refused sender's channel is closed");
+        assert_eq!(metrics.inbox().dropped, 1);
+        assert!(!receiving.is_finished(), "the actor goes on");
+
+        assert_eq!(p_rx.recv_async().await, Some(R2PMsg::Msg(Num(1), "a")));
+        let (b_tx, start_b) = connect("b");
+        controller_tx.send(start_b).await.unwrap();
+        b_tx.send(Box::new(Num(3))).await.unwrap();
+        assert_eq!(p_rx.recv_async().await, Some(R2PMsg::Msg(Num(3), "b")));
+    }
+
+    /// Eight little-endian bytes per `Num`.
+    #[derive(Clone)]
+    struct NumDecoder;
+    impl Decoder for NumDecoder {
+        type Item = Num;
+        type Error = std::io::Error;
+        fn decode(
+            &mut self,
+            src: &mut tokio_util::bytes::BytesMut,
+        ) -> Result<Option<Num>, std::io::Error> {
+            if src.len() < 8 {
+                return Ok(None);
+            }
+            let n = src.split_to(8);
+            Ok(Some(Num(u64::from_le_bytes(n[..].try_into().unwrap()))))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_remote_recv_ends_when_the_connection_closes() {
+        let (p_tx, mut p_rx) = reactor_channel::<R2PMsg<Num>>(1, 100);
+        let (_throttle_tx, throttle_rx) = watch::channel((0, 0));
+        // one message, then end of stream as on a hung-up connection
+        let wire: &'static [u8] = &[7, 0, 0, 0, 0, 0, 0, 0];
+        let subtask = tokio::spawn(remote_parent_recv_subtask(
+            "peer".to_string(),
+            p_tx.into(),
+            None::<Arc<Mutex<Rounds>>>,
+            FramedRead::new(wire, NumDecoder),
+            throttle_rx,
+            SyncBarrier::new().join(),
+        ));
+        assert_eq!(p_rx.recv_async().await, Some(R2PMsg::Msg(Num(7), "peer")));
+        tokio::time::timeout(Duration::from_secs(1), subtask)
+            .await
+            .expect("the receive loop spins on a closed connection")
+            .unwrap();
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use tokio::{io::AsyncWriteExt, net::tcp::OwnedWriteHalf, sync::mpsc};
+use tokio_util::{
+    bytes::{Buf, BufMut, BytesMut},
+    codec::{Decoder, Encoder},
+};
+
+use crate::{ActorAddr, chaos_manager::ChaosManager};
+
+/// Settings of a reliable channel, see [`crate::BehaviourBuilder::reliable`].
+///
+/// Messages on a reliable channel carry sequence numbers. The receiver acknowledges the ones it
+/// put in its inbox and drops repeats, and the sender writes again whatever stays unacknowledged,
+/// also over a new connection after a reconnect. Each message reaches the receiver's inbox once
+/// and in the order it was sent, even under message loss and duplication chaos.
+#[derive(Clone, Copy, Debug)]
+pub struct Reliable {
+    /// Time without a new ack after which every unacknowledged message is written again.
+    pub retransmit_ms: u64,
+    /// Messages written and not yet acknowledged before the sender waits for acks.
+    pub max_in_flight: usize,
+}
+
+impl Default for Reliable {
+    fn default() -> Self {
+        Reliable {
+            retransmit_ms: 200,
+            max_in_flight: 1024,
+        }
+    }
+}
+
+/// Which channels of a behaviour are reliable.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Reliability {
+    pub(crate) all: Option<Reliable>,
+    pub(crate) to: HashMap<ActorAddr, Reliable>,
+}
+
+impl Reliability {
+    pub(crate) fn of(&self, addr: &str) -> Option<Reliable> {
+        self.to.get(addr).copied().or(self.all)
+    }
+}
+
+/// Opens a reliable channel, after the sender's address in the handshake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Hello {
+    /// Picked by the sender when it starts, so a restarted sender does not look like a repeat.
+    pub(crate) session: u64,
+    /// The first sequence number the connection carries.
+    pub(crate) base: u64,
+}
+
+/// Sending side of a reliable channel, kept across connections.
+pub(crate) struct Outbound<M> {
+    session: u64,
+    next_seq: u64,
+    unacked: VecDeque<(u64, M)>,
+}
+
+impl<M: Clone> Outbound<M> {
+    pub(crate) fn new() -> Self {
+        Outbound {
+            session: rand::random(),
+            next_seq: 0,
+            unacked: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn hello(&self) -> Hello {
+        Hello {
+            session: self.session,
+            base: self.unacked.front().map_or(self.next_seq, |(seq, _)| *seq),
+        }
+    }
+
+    /// Keeps `msg` until it is acknowledged and returns its sequence number.
+    pub(crate) fn push(&mut self, msg: M) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.unacked.push_back((seq, msg));
+        seq
+    }
+
+    /// Forgets the messages before `next`, returning whether there were any.
+    pub(crate) fn ack(&mut self, next: u64) -> bool {
+        let before = self.unacked.len();
+        while self.unacked.front().is_some_and(|(seq, _)| *seq < next) {
+            self.unacked.pop_front();
+        }
+        self.unacked.len() < before
+    }
+
+    pub(crate) fn in_flight(&self) -> usize {
+        self.unacked.len()
+    }
+
+    pub(crate) fn unacked(&self) -> Vec<(u64, M)> {
+        self.unacked.iter().cloned().collect()
+    }
+}
+
+/// Receiving side of the reliable channels of an actor. Sessions outlive connections, so a
+/// sender that reconnects continues where it left off. Only the latest session of each sender is
+/// kept: a new one means the sender restarted, and the old one cannot resume.
+#[derive(Clone, Default)]
+pub(crate) struct Inbound {
+    next: Arc<Mutex<HashMap<(String, u64), u64>>>,
+    chaos: Arc<Mutex<ChaosManager>>,
+}
+
+impl Inbound {
+    /// The chaos settings, shared with the processor that sets them.
+    pub(crate) fn chaos(&self) -> Arc<Mutex<ChaosManager>> {
+        self.chaos.clone()
+    }
+
+    pub(crate) fn open(&self, sender: &str, hello: Hello) -> Session {
+        let key = (sender.to_string(), hello.session);
+        let mut next = self.next.lock().unwrap();
+        if !next.contains_key(&key) {
+            next.retain(|(from, _), _| from != sender);
+            next.insert(key.clone(), hello.base);
+        }
+        drop(next);
+        Session {
+            inbound: self.clone(),
+            key,
+        }
+    }
+}
+
+/// One sender's session, on one of its connections.
+pub(crate) struct Session {
+    inbound: Inbound,
+    key: (String, u64),
+}
+
+impl Session {
+    /// Loses or duplicates `msg` as the actor's chaos settings say. On a reliable channel chaos
+    /// stands in for the network, so it applies before repeats are dropped.
+    pub(crate) fn chaos<M: Clone>(&self, msg: M) -> Vec<M> {
+        self.inbound.chaos.lock().unwrap().apply_chaos(msg)
+    }
+
+    /// Whether `seq` is the next message of the session. Repeats and messages after a gap are
+    /// refused; the sender writes the gap and what follows it again. Everything is refused once a
+    /// newer session of the sender replaced this one.
+    pub(crate) fn accept(&self, seq: u64) -> bool {
+        let mut next = self.inbound.next.lock().unwrap();
+        match next.get_mut(&self.key) {
+            Some(next) if seq == *next => {
+                *next += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The cumulative ack: every message before it is in the inbox. `None` once a newer session of
+    /// the sender replaced this one.
+    pub(crate) fn next(&self) -> Option<u64> {
+        self.inbound.next.lock().unwrap().get(&self.key).copied()
+    }
+}
+
+/// Where a receiver writes its acks back to the sender.
+pub(crate) enum AckTx {
+    Tcp(OwnedWriteHalf),
+    Local(mpsc::UnboundedSender<u64>),
+}
+
+impl AckTx {
+    /// Returns false once the sender is gone.
+    pub(crate) async fn send(&mut self, next: u64) -> bool {
+        match self {
+            AckTx::Tcp(tx) => tx.write_u64(next).await.is_ok(),
+            AckTx::Local(tx) => tx.send(next).is_ok(),
+        }
+    }
+}
+
+/// A message of a reliable channel in a local channel; the message stays boxed so the receiver
+/// can convert it like any other.
+pub(crate) type LocalSeqMsg = (u64, Box<dyn std::any::Any + Send>);
+
+/// Puts a sequence number in front of each frame of the wrapped codec.
+#[derive(Clone)]
+pub(crate) struct Sequenced<C>(pub(crate) C);
+
+impl<M, C: Encoder<M>> Encoder<(u64, M)> for Sequenced<C> {
+    type Error = C::Error;
+
+    fn encode(&mut self, (seq, msg): (u64, M), dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let start = dst.len();
+        dst.put_u64(seq);
+        self.0.encode(msg, dst).inspect_err(|_| dst.truncate(start))
+    }
+}
+
+impl<C: Decoder> Decoder for Sequenced<C> {
+    type Item = (u64, C::Item);
+    type Error = C::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < 8 {
+            return Ok(None);
+        }
+        // the wrapped codec only sees the bytes after the sequence number, which stays in front
+        // until its frame is complete
+        let mut frame = src.split_off(8);
+        let decoded = self
+            .0
+            .decode(&mut frame)
+            .map(|msg| msg.map(|msg| (src.get_u64(), msg)));
+        src.unsplit(frame);
+        decoded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::BincodeCodec;
+
+    #[test]
+    fn test_sequenced_frames_split_anywhere() {
+        let mut codec = Sequenced(BincodeCodec::<String, String>::default());
+        let mut wire = BytesMut::new();
+        for (seq, msg) in [(7, "seven"), (8, "eight")] {
+            assert!(codec.encode((seq, msg.to_string()), &mut wire).is_ok());
+        }
+
+        let mut src = BytesMut::new();
+        let mut got = vec![];
+        for byte in wire {
+            src.put_u8(byte);
+            while let Some(item) = codec.decode(&mut src).unwrap() {
+                got.push(item);
+            }
+        }
+        assert_eq!(got, vec![(7, "seven".into()), (8, "eight".into())]);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn test_sessions_refuse_repeats_and_gaps() {
+        let inbound = Inbound::default();
+        let hello = Hello {
+            session: 1,
+            base: 5,
+        };
+        let first = inbound.open("a", hello);
+        assert!(!first.accept(6), "a gap");
+        assert!(first.accept(5));
+        assert!(!first.accept(5), "a repeat");
+        drop(first);
+
+        // a reconnect resumes the session, a restarted sender starts a new one
+        let again = inbound.open("a", hello);
+        assert_eq!(again.next(), Some(6));
+        assert!(again.accept(6));
+        let restarted = inbound.open(
+            "a",
+            Hello {
+                session: 2,
+                base: 0,
+            },
+        );
+        assert!(restarted.accept(0));
+    }
+
+    #[test]
+    fn test_new_session_replaces_the_senders_old_ones() {
+        let inbound = Inbound::default();
+        let hello = |session| Hello { session, base: 0 };
+        let old = inbound.open("a", hello(1));
+        let other = inbound.open("b", hello(1));
+        for session in 2..10 {
+            assert!(inbound.open("a", hello(session)).accept(0));
+        }
+        assert_eq!(
+            inbound.next.lock().unwrap().len(),
+            2,
+            "one session per sender"
+        );
+        assert!(!old.accept(0), "a replaced session refuses everything");
+        assert_eq!(old.next(), None);
+        assert_eq!(other.next(), Some(0));
+        assert_eq!(
+            inbound.open("a", hello(9)).next(),
+            Some(1),
+            "the latest one resumes"
+        );
+    }
+}
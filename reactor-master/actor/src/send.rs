@@ -1,36 +1,57 @@
-use std::{any::Any, collections::HashMap, time::Duration};
+use std::{
+    any::Any,
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::Duration,
+};
 
 use futures::SinkExt as _;
 use tokio::{
-    io::{AsyncWrite, AsyncWriteExt},
-    net::TcpStream,
+    io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpStream, ToSocketAddrs, tcp::OwnedWriteHalf},
     sync::mpsc,
-    task::JoinSet,
+    task::{JoinHandle, JoinSet},
+    time::Instant,
 };
 use tokio_util::codec::{Encoder, FramedWrite};
 
 use crate::{
-    ActorAddr, ActorSend, Msg, RouteTo, SendErrAction,
+    ActorAddr, ActorSend, Msg, Reconnect, RouteTo, SendErrAction,
     codec::ErrWithMsg,
-    node_comm::{Connection, ControlReq},
+    err::ActorError,
+    mailbox::{MailboxCfg, MailboxMetrics, MailboxRx, MailboxTx, PushErr, mailbox},
+    node_comm::{Connection, ControlReq, DrainDeadline, LocalChannelTx, LocalHandshake},
+    reliable::{Hello, LocalSeqMsg, Outbound, Reliability, Reliable, Sequenced},
 };
 
-#[allow(clippy::type_complexity)]
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 pub(crate) async fn tx<M, E, BS>(
     my_addr: &'static str,
     before_send: Option<BS>,
     ask_receiver_to_adapt: bool,
-    mut p_rx: mpsc::UnboundedReceiver<(M, &'static str)>,
+    mut p_rx: MailboxRx<(M, &'static str)>,
     controller_tx: mpsc::Sender<ControlReq>,
     codec: E,
     on_send_failure: SendErrAction,
-) where
+    dest_cfg: Option<MailboxCfg>,
+    reconnect: Reconnect,
+    reliability: Reliability,
+    metrics: MailboxMetrics,
+    drain: Arc<DrainDeadline>,
+) -> Result<(), ActorError>
+where
     M: Msg,
     BS: ActorSend<OMsg = M>,
     E: Encoder<M> + 'static + Send + Clone,
     E::Error: Send + 'static + ErrWithMsg<M>,
 {
-    let mut addr_to_buff: HashMap<ActorAddr, mpsc::UnboundedSender<M>> = HashMap::new();
+    let mut addr_to_buff: HashMap<ActorAddr, MailboxTx<M>> = HashMap::new();
+    let dests = Destinations {
+        cfg: dest_cfg,
+        reconnect,
+        reliability: &reliability,
+        metrics: &metrics,
+    };
     let mut sub_senders = JoinSet::new();
     tracing::info!("[ACTOR][{}] Tx Started", my_addr);
 
@@ -39,28 +60,36 @@ pub(crate) async fn tx<M, E, BS>(
             let receivers: RouteTo<'_> = before_send.before_send(&m).await;
             match receivers {
                 RouteTo::Blackhole => {}
-                RouteTo::Reply => send_msg(
-                    my_addr,
-                    ask_receiver_to_adapt,
-                    controller_tx.clone(),
-                    codec.clone(),
-                    &mut addr_to_buff,
-                    &mut sub_senders,
-                    origin,
-                    m,
-                    on_send_failure,
-                ),
-                RouteTo::Single(send_to) => send_msg(
-                    my_addr,
-                    ask_receiver_to_adapt,
-                    controller_tx.clone(),
-                    codec.clone(),
-                    &mut addr_to_buff,
-                    &mut sub_senders,
-                    &send_to,
-                    m,
-                    on_send_failure,
-                ),
+                RouteTo::Reply => {
+                    send_msg(
+                        my_addr,
+                        ask_receiver_to_adapt,
+                        controller_tx.clone(),
+                        codec.clone(),
+                        &mut addr_to_buff,
+                        &mut sub_senders,
+                        origin,
+                        m,
+                        on_send_failure,
+                        &dests,
+                    )
+                    .await?
+                }
+                RouteTo::Single(send_to) => {
+                    send_msg(
+                        my_addr,
+                        ask_receiver_to_adapt,
+                        controller_tx.clone(),
+                        codec.clone(),
+                        &mut addr_to_buff,
+                        &mut sub_senders,
+                        &send_to,
+                        m,
+                        on_send_failure,
+                        &dests,
+                    )
+                    .await?
+                }
                 RouteTo::Multiple(receivers) => {
                     let num_receivers = receivers.len();
                     if num_receivers == 0 {
@@ -77,7 +106,9 @@ pub(crate) async fn tx<M, E, BS>(
                             addr,
                             m.clone(),
                             on_send_failure,
-                        );
+                            &dests,
+                        )
+                        .await?;
                     }
                     send_msg(
                         my_addr,
@@ -89,40 +120,65 @@ pub(crate) async fn tx<M, E, BS>(
                         &receivers[num_receivers - 1],
                         m,
                         on_send_failure,
-                    );
+                        &dests,
+                    )
+                    .await?;
                 }
             }
         }
     } else {
         while p_rx.recv().await.is_some() {}
     }
+    if let Some(deadline) = drain.get() {
+        // with their buffers closed, the senders end once they have sent what they hold
+        drop(addr_to_buff);
+        let flushed = tokio::time::timeout_at(deadline, async {
+            while sub_senders.join_next().await.is_some() {}
+        })
+        .await;
+        if flushed.is_err() {
+            tracing::warn!(
+                "[ACTOR][{}] {} destinations not flushed by the drain deadline",
+                my_addr,
+                sub_senders.len()
+            );
+        }
+    }
     sub_senders.abort_all();
     tracing::info!("[ACTOR][{}] Tx Ended", my_addr);
+    Ok(())
+}
+
+/// Settings for the per-destination buffers and connections that [`send_msg`] creates.
+struct Destinations<'a> {
+    cfg: Option<MailboxCfg>,
+    reconnect: Reconnect,
+    reliability: &'a Reliability,
+    metrics: &'a MailboxMetrics,
 }
 
 #[allow(clippy::too_many_arguments)]
 #[inline(always)]
-fn send_msg<M, E>(
+async fn send_msg<M, E>(
     my_addr: &'static str,
     ask_receiver_to_adapt: bool,
     controller_tx: mpsc::Sender<ControlReq>,
     codec: E,
-    addr_to_buff: &mut HashMap<String, mpsc::UnboundedSender<M>>,
+    addr_to_buff: &mut HashMap<String, MailboxTx<M>>,
     sub_senders: &mut JoinSet<()>,
     addr: &str,
     m: M,
     on_send_failure: SendErrAction,
-) where
+    dests: &Destinations<'_>,
+) -> Result<(), ActorError>
+where
     M: Msg,
     E: Encoder<M> + 'static + Send + Clone,
     E::Error: Send + 'static + ErrWithMsg<M>,
 {
-    if let Some(sender) = addr_to_buff.get(addr) {
-        if let Err(e) = sender.send(m) {
-            log::error!("[ACTOR] Failed to send message to {}: {}", addr, e);
-        }
-    } else {
-        let (tx, rx) = mpsc::unbounded_channel::<M>();
+    if !addr_to_buff.contains_key(addr) {
+        let stats = dests.metrics.destination_stats(addr, dests.cfg);
+        let (tx, rx) = mailbox::<M>(dests.cfg, stats);
         sub_senders.spawn(sender_task(
             my_addr,
             ask_receiver_to_adapt,
@@ -131,104 +187,360 @@ fn send_msg<M, E>(
             codec,
             controller_tx,
             on_send_failure,
+            dests.reconnect,
+            dests.reliability.of(addr),
         ));
-        let _ = tx.send(m);
         addr_to_buff.insert(addr.to_string(), tx);
     }
+    match addr_to_buff[addr].send(m).await {
+        Ok(()) => Ok(()),
+        Err(PushErr::Closed(_)) => {
+            log::error!("[ACTOR] Failed to send message to {}: sender closed", addr);
+            Ok(())
+        }
+        Err(PushErr::Full(_)) => Err(ActorError::MailboxFull("destination")),
+    }
+}
+
+/// Why a connection to a destination stopped being used.
+enum Ended {
+    /// Every sender is gone and the buffer is drained.
+    Drained,
+    /// Writing failed, so the destination has to be resolved and connected again.
+    Broken,
+}
+
+/// Waits between connection attempts, doubling up to [`Reconnect::max_backoff_ms`].
+struct Backoff {
+    cfg: Reconnect,
+    attempts: u16,
+    next_ms: u64,
+}
+
+impl Backoff {
+    fn new(cfg: Reconnect) -> Self {
+        Backoff {
+            cfg,
+            attempts: 0,
+            next_ms: cfg.initial_backoff_ms,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.attempts = 0;
+        self.next_ms = self.cfg.initial_backoff_ms;
+    }
+
+    /// Returns false once [`Reconnect::max_attempts`] is used up.
+    async fn wait(&mut self) -> bool {
+        if let Some(max) = self.cfg.max_attempts
+            && self.attempts >= max
+        {
+            return false;
+        }
+        self.attempts += 1;
+        tokio::time::sleep(Duration::from_millis(self.next_ms)).await;
+        self.next_ms = (self.next_ms * 2).min(self.cfg.max_backoff_ms);
+        true
+    }
+}
+
+/// The connection under a reliable channel.
+trait Link<M> {
+    /// Returns false if the connection broke.
+    async fn write(&mut self, seq: u64, msg: M) -> bool;
+    /// Waits for the next cumulative ack; `None` once the connection broke.
+    async fn ack(&mut self) -> Option<u64>;
+}
+
+struct TcpLink<C> {
+    framed_writer: FramedWrite<OwnedWriteHalf, Sequenced<C>>,
+    acks: mpsc::UnboundedReceiver<u64>,
+    ack_reader: JoinHandle<()>,
+}
+
+impl<C> TcpLink<C> {
+    /// Opens a reliable channel on a new connection; `None` if the handshake fails.
+    async fn open<M: Msg>(
+        my_addr: &'static str,
+        ask_receiver_to_adapt: bool,
+        stream: TcpStream,
+        encoder: C,
+        hello: Hello,
+    ) -> Option<Self> {
+        let (mut ack_rx, mut tx) = stream.into_split();
+        let decoder_name = std::any::type_name::<M>().to_string();
+        let handshake = send_remote_handshake(
+            &mut tx,
+            my_addr,
+            decoder_name,
+            ask_receiver_to_adapt,
+            Some(hello),
+        );
+        if let Err(e) = handshake.await {
+            log::warn!("[ACTOR] {} Failed to send handshake: {}", my_addr, e);
+            return None;
+        }
+        let (ack_tx, acks) = mpsc::unbounded_channel();
+        // the receiver writes acks back on the same connection
+        let ack_reader = tokio::spawn(async move {
+            while let Ok(next) = ack_rx.read_u64().await {
+                if ack_tx.send(next).is_err() {
+                    break;
+                }
+            }
+        });
+        Some(TcpLink {
+            framed_writer: FramedWrite::new(tx, Sequenced(encoder)),
+            acks,
+            ack_reader,
+        })
+    }
+}
+
+impl<C> Drop for TcpLink<C> {
+    fn drop(&mut self) {
+        self.ack_reader.abort();
+    }
+}
+
+impl<M, C: Encoder<M>> Link<M> for TcpLink<C> {
+    async fn write(&mut self, seq: u64, msg: M) -> bool {
+        self.framed_writer.send((seq, msg)).await.is_ok()
+    }
+
+    async fn ack(&mut self) -> Option<u64> {
+        self.acks.recv().await
+    }
+}
+
+struct LocalLink {
+    tx: LocalChannelTx,
+    acks: mpsc::UnboundedReceiver<u64>,
+}
+
+impl LocalLink {
+    async fn open<M>(
+        my_addr: &'static str,
+        ask_receiver_to_adapt: bool,
+        tx: LocalChannelTx,
+        hello: Hello,
+    ) -> Self {
+        let (ack_tx, acks) = mpsc::unbounded_channel();
+        let decoder_name = std::any::type_name::<M>().to_string();
+        let reliable = Some((hello, ack_tx));
+        send_local_handshake(&tx, my_addr, decoder_name, ask_receiver_to_adapt, reliable).await;
+        LocalLink { tx, acks }
+    }
+}
+
+impl<M: Send + 'static> Link<M> for LocalLink {
+    async fn write(&mut self, seq: u64, msg: M) -> bool {
+        let msg: LocalSeqMsg = (seq, Box::new(msg));
+        self.tx.send(Box::new(msg)).await.is_ok()
+    }
+
+    async fn ack(&mut self) -> Option<u64> {
+        self.acks.recv().await
+    }
+}
+
+/// Writes what is unacknowledged from earlier connections, then the buffered messages, until
+/// the buffer is drained and everything is acknowledged or the connection breaks.
+async fn reliable_sender<M: Msg>(
+    my_addr: &'static str,
+    mut link: impl Link<M>,
+    rx: &mut MailboxRx<M>,
+    outbound: &mut Outbound<M>,
+    cfg: Reliable,
+) -> Ended {
+    log::info!("[ACTOR] SubTx Started (Reliable)");
+    let retransmit = Duration::from_millis(cfg.retransmit_ms);
+    let mut open = true;
+    let mut resend = true;
+    let mut deadline = Instant::now();
+    loop {
+        if resend {
+            for (seq, msg) in outbound.unacked() {
+                if !link.write(seq, msg).await {
+                    return Ended::Broken;
+                }
+            }
+            resend = false;
+            deadline = Instant::now() + retransmit;
+        }
+        if !open && outbound.in_flight() == 0 {
+            break;
+        }
+        tokio::select! {
+            ack = link.ack() => match ack {
+                Some(next) => {
+                    if outbound.ack(next) {
+                        deadline = Instant::now() + retransmit;
+                    }
+                }
+                None => return Ended::Broken,
+            },
+            msg = rx.recv(), if open && outbound.in_flight() < cfg.max_in_flight => match msg {
+                Some(msg) => {
+                    if outbound.in_flight() == 0 {
+                        deadline = Instant::now() + retransmit;
+                    }
+                    let seq = outbound.push(msg.clone());
+                    if !link.write(seq, msg).await {
+                        return Ended::Broken;
+                    }
+                }
+                None => open = false,
+            },
+            _ = tokio::time::sleep_until(deadline), if outbound.in_flight() > 0 => {
+                log::warn!(
+                    "[ACTOR] {} No ack for {}ms, writing {} messages again",
+                    my_addr,
+                    cfg.retransmit_ms,
+                    outbound.in_flight()
+                );
+                resend = true;
+            }
+        }
+    }
+    log::info!("[ACTOR] SubTx Ended");
+    Ended::Drained
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn sender_task<M, E>(
     my_addr: &'static str,
     ask_receiver_to_adapt: bool,
     send_addr: ActorAddr,
-    rx: mpsc::UnboundedReceiver<M>,
+    mut rx: MailboxRx<M>,
     encoder: E,
     controller_tx: mpsc::Sender<ControlReq>,
     on_send_failure: SendErrAction,
+    reconnect: Reconnect,
+    reliable: Option<Reliable>,
 ) where
     M: Msg,
-    E: Encoder<M> + 'static + Send,
+    E: Encoder<M> + 'static + Send + Clone,
     E::Error: Send + 'static + ErrWithMsg<M>,
 {
+    /// Writes `pending` and then the buffered messages until the buffer is drained or a write
+    /// fails. On failure, `pending` is left with what the next connection has to write first:
+    /// the replay window, the failed message unless `on_send_failure` gives it up, and whatever
+    /// was still pending. Each pending message carries the failed writes it has seen.
+    #[allow(clippy::too_many_arguments)]
     async fn remote_sender<C, M>(
         my_addr: &'static str,
         ask_receiver_to_adapt: bool,
         mut tx: impl AsyncWrite + Unpin,
-        mut rx: mpsc::UnboundedReceiver<M>,
+        rx: &mut MailboxRx<M>,
         encoder: C,
         on_send_failure: SendErrAction,
-    ) where
+        replay_window: usize,
+        pending: &mut VecDeque<(M, u16)>,
+    ) -> Ended
+    where
         M: Msg,
         C::Error: Send + 'static + ErrWithMsg<M>,
         C: Encoder<M> + 'static + Send,
     {
         log::info!("[ACTOR] SubTx Started");
         let decoder_name = std::any::type_name::<M>().to_string();
-        send_remote_handshake(&mut tx, my_addr, decoder_name, ask_receiver_to_adapt).await;
+        if let Err(e) =
+            send_remote_handshake(&mut tx, my_addr, decoder_name, ask_receiver_to_adapt, None).await
+        {
+            log::warn!("[ACTOR] {} Failed to send handshake: {}", my_addr, e);
+            return Ended::Broken;
+        }
         let mut framed_writer = FramedWrite::new(tx, encoder);
+        let keep_copy = replay_window > 0 || matches!(on_send_failure, SendErrAction::Retry { .. });
+        let mut window = VecDeque::with_capacity(replay_window);
 
-        while let Some(msg) = rx.recv().await {
-            let Err(e) = framed_writer.send(msg).await else {
-                continue;
+        loop {
+            let (msg, failures) = match pending.pop_front() {
+                Some(next) => next,
+                None => match rx.recv().await {
+                    Some(msg) => (msg, 0),
+                    None => break,
+                },
             };
-            let mut failed_msg = e.into_inner().unwrap();
-            match on_send_failure {
-                SendErrAction::Drop => {
-                    log::warn!("[ACTOR] {} Failed to send message, dropping", my_addr);
-                }
-                SendErrAction::Retry {
-                    attempts: retries_opt,
-                    delay_ms,
-                } => {
-                    let mut attempts = 0;
-                    loop {
-                        if let Some(retries) = retries_opt
-                            && attempts >= retries
-                        {
-                            log::error!(
-                                "[ACTOR] {} Failed to send message, retries exhausted",
-                                my_addr
-                            );
-                            break;
-                        }
-
-                        attempts += 1;
-
-                        if let Err(err_msg) = framed_writer.send(failed_msg).await {
-                            failed_msg = err_msg.into_inner().unwrap();
-
-                            log::warn!(
-                                "[ACTOR] {} Failed to send message, retries: {}",
-                                my_addr,
-                                attempts
-                            );
-                            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                            continue;
-                        } else {
-                            break;
+            // an io error gives the message back only if it failed to encode
+            let copy = keep_copy.then(|| msg.clone());
+            let failed = match framed_writer.send(msg).await {
+                Ok(()) => {
+                    if replay_window > 0 {
+                        if window.len() == replay_window {
+                            window.pop_front();
                         }
+                        window.extend(copy);
                     }
+                    continue;
                 }
-            }
+                Err(e) => e.into_inner().or(copy),
+            };
+
+            let retried = match (on_send_failure, failed) {
+                (SendErrAction::Drop, _) | (_, None) => {
+                    log::warn!("[ACTOR] {} Failed to send message, dropping", my_addr);
+                    None
+                }
+                (
+                    SendErrAction::Retry {
+                        attempts: Some(retries),
+                        ..
+                    },
+                    Some(_),
+                ) if failures >= retries => {
+                    log::error!(
+                        "[ACTOR] {} Failed to send message, retries exhausted",
+                        my_addr
+                    );
+                    None
+                }
+                (SendErrAction::Retry { .. }, Some(msg)) => {
+                    log::warn!(
+                        "[ACTOR] {} Failed to send message, retries: {}",
+                        my_addr,
+                        failures + 1
+                    );
+                    Some((msg, failures + 1))
+                }
+            };
+            let rest = std::mem::take(pending);
+            pending.extend(window.into_iter().map(|msg| (msg, 0)));
+            pending.extend(retried);
+            pending.extend(rest);
+            log::info!("[ACTOR] SubTx Ended, connection broken");
+            return Ended::Broken;
         }
         log::info!("[ACTOR] SubTx Ended");
+        Ended::Drained
     }
 
     /// - `tx`: Channel to the destination actor.
     /// - `rx`: Internal channel for messages of type `M`, specific to this actor instance.
     /// - Note: `tx` and `rx` are independent — `rx` collects messages locally between helper functions and
     ///   `tx` delivers them to the actor. They don’t correlate directly.
+    /// - `pending`: Messages left over from a broken TCP connection, delivered first.
     async fn local_sender<M: std::fmt::Debug + Send + 'static + Clone>(
         my_addr: &'static str,
         ask_receiver_to_adapt: bool,
         tx: mpsc::Sender<Box<dyn Any + Send>>,
-        mut rx: mpsc::UnboundedReceiver<M>,
+        mut rx: MailboxRx<M>,
         on_send_failure: SendErrAction,
+        mut pending: VecDeque<(M, u16)>,
     ) {
         log::info!("[ACTOR] SubTx Started (Local)");
         let decoder_name = std::any::type_name::<M>().to_string();
-        send_local_handshake(&tx, my_addr, decoder_name, ask_receiver_to_adapt).await;
-        while let Some(msg) = rx.recv().await {
+        send_local_handshake(&tx, my_addr, decoder_name, ask_receiver_to_adapt, None).await;
+        loop {
+            let msg = match pending.pop_front() {
+                Some((msg, _)) => msg,
+                None => match rx.recv().await {
+                    Some(msg) => msg,
+                    None => break,
+                },
+            };
             let Err(e) = tx.send(Box::new(msg)).await else {
                 continue;
             };
@@ -275,6 +587,11 @@ async fn sender_task<M, E>(
     }
 
     let mut attempts = 0;
+    let mut backoff = Backoff::new(reconnect);
+    // messages carried over from a broken connection
+    let mut pending = VecDeque::new();
+    let mut outbound = reliable.map(|cfg| (cfg, Outbound::new()));
+    let mut connected = false;
     loop {
         let (c_tx, c_rx) = tokio::sync::oneshot::channel();
         log::debug!("[ACTOR] Sending Resolve request for address: {}", send_addr);
@@ -287,76 +604,141 @@ async fn sender_task<M, E>(
             .unwrap();
 
         match c_rx.await.unwrap() {
-            Connection::Remote(socket_addr) => {
-                let tx = loop {
-                    match TcpStream::connect(socket_addr).await {
-                        Ok(s) => {
-                            let (_, tx) = s.into_split();
-                            break tx;
+            Connection::Remote(socket_addr) => match TcpStream::connect(socket_addr).await {
+                Ok(s) => {
+                    connected = true;
+                    backoff.reset();
+                    let ended = match outbound.as_mut() {
+                        Some((cfg, outbound)) => {
+                            let link = TcpLink::open::<M>(
+                                my_addr,
+                                ask_receiver_to_adapt,
+                                s,
+                                encoder.clone(),
+                                outbound.hello(),
+                            )
+                            .await;
+                            match link {
+                                Some(link) => {
+                                    reliable_sender(my_addr, link, &mut rx, outbound, *cfg).await
+                                }
+                                None => Ended::Broken,
+                            }
                         }
-                        Err(_) => {
-                            tokio::time::sleep(Duration::from_millis(500)).await;
-                            todo!();
+                        None => {
+                            let (_, tx) = s.into_split();
+                            remote_sender(
+                                my_addr,
+                                ask_receiver_to_adapt,
+                                tx,
+                                &mut rx,
+                                encoder.clone(),
+                                on_send_failure,
+                                reconnect.replay_window,
+                                &mut pending,
+                            )
+                            .await
                         }
+                    };
+                    if let Ended::Drained = ended {
+                        break;
                     }
-                };
-
-                remote_sender(
-                    my_addr,
-                    ask_receiver_to_adapt,
-                    tx,
-                    rx,
-                    encoder,
-                    on_send_failure,
-                )
-                .await;
-                break;
-            }
+                    log::warn!(
+                        "[ACTOR] {} Connection to {} broke, reconnecting",
+                        my_addr,
+                        send_addr
+                    );
+                }
+                Err(e) => {
+                    log::warn!(
+                        "[ACTOR] {} Failed to connect to {} at {}: {}",
+                        my_addr,
+                        send_addr,
+                        socket_addr,
+                        e
+                    );
+                }
+            },
             Connection::Local(write_half) => {
-                local_sender(
+                let Some((cfg, outbound)) = outbound.as_mut() else {
+                    local_sender(
+                        my_addr,
+                        ask_receiver_to_adapt,
+                        write_half,
+                        rx,
+                        on_send_failure,
+                        pending,
+                    )
+                    .await;
+                    break;
+                };
+                connected = true;
+                backoff.reset();
+                let link = LocalLink::open::<M>(
                     my_addr,
                     ask_receiver_to_adapt,
                     write_half,
-                    rx,
-                    on_send_failure,
+                    outbound.hello(),
                 )
                 .await;
-                break;
-            }
-            Connection::CouldntResolve => match on_send_failure {
-                SendErrAction::Drop => {
-                    log::warn!(
-                        "[ACTOR] {} Failed to resolve {}, dropping",
-                        my_addr,
-                        send_addr
-                    );
+                if let Ended::Drained =
+                    reliable_sender(my_addr, link, &mut rx, outbound, *cfg).await
+                {
                     break;
                 }
-                SendErrAction::Retry {
-                    attempts: retries_opt,
-                    delay_ms,
-                } => {
-                    if let Some(retries) = retries_opt
-                        && attempts >= retries
-                    {
-                        log::error!(
-                            "[ACTOR] {} Failed to resolve {}, retries exhausted",
+                log::warn!(
+                    "[ACTOR] {} Channel to {} closed, reconnecting",
+                    my_addr,
+                    send_addr
+                );
+            }
+            // a destination that went away may take a while to be resolvable again
+            Connection::CouldntResolve if connected => {}
+            Connection::CouldntResolve => {
+                match on_send_failure {
+                    SendErrAction::Drop => {
+                        log::warn!(
+                            "[ACTOR] {} Failed to resolve {}, dropping",
                             my_addr,
                             send_addr
                         );
                         break;
                     }
-                    attempts += 1;
-                    log::warn!(
-                        "[ACTOR] {} Failed to resolve {}, retrying {}",
-                        my_addr,
-                        send_addr,
-                        attempts,
-                    );
-                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                    SendErrAction::Retry {
+                        attempts: retries_opt,
+                        delay_ms,
+                    } => {
+                        if let Some(retries) = retries_opt
+                            && attempts >= retries
+                        {
+                            log::error!(
+                                "[ACTOR] {} Failed to resolve {}, retries exhausted",
+                                my_addr,
+                                send_addr
+                            );
+                            break;
+                        }
+                        attempts += 1;
+                        log::warn!(
+                            "[ACTOR] {} Failed to resolve {}, retrying {}",
+                            my_addr,
+                            send_addr,
+                            attempts,
+                        );
+                        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                    }
                 }
-            },
+                continue;
+            }
         };
+        if !backoff.wait().await {
+            log::error!(
+                "[ACTOR] {} Failed to reach {} again, attempts exhausted",
+                my_addr,
+                send_addr
+            );
+            break;
+        }
     }
 }
 
@@ -365,31 +747,313 @@ async fn send_remote_handshake(
     my_name: &str,
     type_name: String,
     ask_receiver_to_adapt: bool,
-) {
+    hello: Option<Hello>,
+) -> std::io::Result<()> {
     let bytes = my_name.as_bytes();
     let len = bytes.len();
-    tx.write_u32(len as u32).await.unwrap();
-    tx.write_all(bytes).await.unwrap();
+    tx.write_u32(len as u32).await?;
+    tx.write_all(bytes).await?;
 
     if ask_receiver_to_adapt {
         let bytes = type_name.as_bytes();
         let len = bytes.len();
-        tx.write_u32(len as u32).await.unwrap();
-        tx.write_all(bytes).await.unwrap();
+        tx.write_u32(len as u32).await?;
+        tx.write_all(bytes).await?;
     } else {
-        tx.write_u32(0).await.unwrap();
+        tx.write_u32(0).await?;
     }
+
+    match hello {
+        Some(hello) => {
+            tx.write_u8(1).await?;
+            tx.write_u64(hello.session).await?;
+            tx.write_u64(hello.base).await?;
+        }
+        None => tx.write_u8(0).await?,
+    }
+    Ok(())
+}
+
+/// Connects to the actor listening on `addr` and introduces itself as `name` on a plain channel,
+/// then hangs up without sending anything. Lets a caller wait until an actor's TCP listener is up.
+pub async fn probe_tcp(addr: impl ToSocketAddrs, name: &str) -> std::io::Result<()> {
+    let mut stream = TcpStream::connect(addr).await?;
+    send_remote_handshake(&mut stream, name, String::new(), false, None).await
 }
 async fn send_local_handshake(
     tx: &mpsc::Sender<Box<dyn Any + Send>>,
     my_name: &str,
     type_name: String,
     ask_receiver_to_adapt: bool,
+    reliable: Option<(Hello, mpsc::UnboundedSender<u64>)>,
 ) {
-    let to_send = if ask_receiver_to_adapt {
-        (my_name.to_string(), Some(type_name.to_string()))
+    let to_send: LocalHandshake = if ask_receiver_to_adapt {
+        (my_name.to_string(), Some(type_name.to_string()), reliable)
     } else {
-        (my_name.to_string(), None)
+        (my_name.to_string(), None, reliable)
     };
     tx.send(Box::new(to_send)).await.unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use bincode::{Decode, Encode};
+    use futures::StreamExt as _;
+    use tokio::{
+        net::{TcpListener, tcp::OwnedReadHalf},
+        sync::{Mutex, watch},
+    };
+    use tokio_util::codec::FramedRead;
+
+    use super::*;
+    use crate::{
+        HasPriority, NoOpActorRecv, R2PMsg,
+        barrier::SyncBarrier,
+        codec::BincodeCodec,
+        reactor_channel::{ReactorChannelTx, reactor_channel},
+        recv::{recv_remote_handshake, remote_subtask},
+        reliable::Inbound,
+    };
+
+    #[derive(Encode, Decode, Debug, Clone, PartialEq)]
+    struct Num(u64);
+    impl HasPriority for Num {}
+    impl Msg for Num {}
+
+    async fn accept(listener: &TcpListener) -> FramedRead<OwnedReadHalf, BincodeCodec<Num, Num>> {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (mut rx, _) = socket.into_split();
+        assert_eq!(recv_remote_handshake(&mut rx).await.unwrap().0, "sender");
+        FramedRead::new(rx, BincodeCodec::default())
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_and_replays_after_receiver_restart() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let socket_addr = listener.local_addr().unwrap();
+        let resolves = Arc::new(AtomicUsize::new(0));
+        let (controller_tx, mut controller_rx) = mpsc::channel(8);
+        let counted = resolves.clone();
+        tokio::spawn(async move {
+            while let Some(ControlReq::Resolve { resp_tx, .. }) = controller_rx.recv().await {
+                counted.fetch_add(1, Ordering::SeqCst);
+                let _ = resp_tx.send(Connection::Remote(socket_addr));
+            }
+        });
+
+        let (tx, rx) = mailbox(None, Default::default());
+        tokio::spawn(sender_task(
+            "sender",
+            false,
+            "receiver".to_string(),
+            rx,
+            BincodeCodec::<Num, Num>::default(),
+            controller_tx,
+            SendErrAction::Retry {
+                attempts: Some(3),
+                delay_ms: 0,
+            },
+            Reconnect {
+                initial_backoff_ms: 10,
+                max_backoff_ms: 50,
+                max_attempts: None,
+                replay_window: 4,
+            },
+            None,
+        ));
+
+        let mut first = accept(&listener).await;
+        for n in 0..3 {
+            tx.send(Num(n)).await.unwrap();
+            assert_eq!(first.next().await.unwrap().unwrap(), Num(n));
+        }
+        // the receiver crashes; writes go on until the sender notices
+        drop(first);
+        let writer = tokio::spawn(async move {
+            for n in 3.. {
+                tx.send(Num(n)).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        });
+        let mut second = tokio::time::timeout(Duration::from_secs(5), accept(&listener))
+            .await
+            .expect("the sender reconnects");
+        assert_eq!(resolves.load(Ordering::SeqCst), 2, "resolved again");
+
+        // replays may repeat messages the first connection got, but none written after it are lost
+        let mut next = 0;
+        while next < 10 {
+            let Num(n) = second.next().await.unwrap().unwrap();
+            assert!(n <= next, "expected {} or a replay, got {}", next, n);
+            next = next.max(n + 1);
+        }
+        writer.abort();
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_a_destination_that_stays_unresolvable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let socket_addr = listener.local_addr().unwrap();
+        let (controller_tx, mut controller_rx) = mpsc::channel(8);
+        tokio::spawn(async move {
+            let mut first = true;
+            while let Some(ControlReq::Resolve { resp_tx, .. }) = controller_rx.recv().await {
+                // the receiver is gone for good after its first connection
+                let connection = if std::mem::take(&mut first) {
+                    Connection::Remote(socket_addr)
+                } else {
+                    Connection::CouldntResolve
+                };
+                let _ = resp_tx.send(connection);
+            }
+        });
+
+        let (tx, rx) = mailbox(None, Default::default());
+        let sender = tokio::spawn(sender_task(
+            "sender",
+            false,
+            "receiver".to_string(),
+            rx,
+            BincodeCodec::<Num, Num>::default(),
+            controller_tx,
+            SendErrAction::Drop,
+            Reconnect {
+                initial_backoff_ms: 1,
+                max_backoff_ms: 1,
+                ..Reconnect::default()
+            },
+            None,
+        ));
+
+        let mut first = accept(&listener).await;
+        tx.send(Num(0)).await.unwrap();
+        assert_eq!(first.next().await.unwrap().unwrap(), Num(0));
+        drop(first);
+        let writer = tokio::spawn(async move {
+            for n in 1.. {
+                if tx.send(Num(n)).await.is_err() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        });
+        tokio::time::timeout(Duration::from_secs(5), sender)
+            .await
+            .expect("the sender gives up by default")
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(1), writer)
+            .await
+            .expect("the destination's buffer is closed")
+            .unwrap();
+    }
+
+    /// Resolves every address to `socket_addr`.
+    fn controller(socket_addr: std::net::SocketAddr) -> mpsc::Sender<ControlReq> {
+        let (controller_tx, mut controller_rx) = mpsc::channel(8);
+        tokio::spawn(async move {
+            while let Some(ControlReq::Resolve { resp_tx, .. }) = controller_rx.recv().await {
+                let _ = resp_tx.send(Connection::Remote(socket_addr));
+            }
+        });
+        controller_tx
+    }
+
+    /// Accepts a reliable connection and receives from it like an actor would.
+    async fn serve(
+        listener: &TcpListener,
+        inbound: &Inbound,
+        p_tx: &ReactorChannelTx<R2PMsg<Num>>,
+    ) -> (JoinHandle<()>, watch::Sender<(u64, u64)>) {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (mut rx, tx) = socket.into_split();
+        let (addr, _, hello) = recv_remote_handshake(&mut rx).await.unwrap();
+        let session = inbound.open(&addr, hello.expect("a reliable channel"));
+        let (throttle_tx, throttle_rx) = watch::channel((0, 0));
+        let subtask = tokio::spawn(remote_subtask(
+            addr,
+            p_tx.clone().into(),
+            None::<Arc<Mutex<NoOpActorRecv<Num>>>>,
+            (rx, tx),
+            BincodeCodec::<Num, Num>::default(),
+            Some(session),
+            throttle_rx,
+            SyncBarrier::new().join(),
+        ));
+        (subtask, throttle_tx)
+    }
+
+    #[tokio::test]
+    async fn test_reliable_channel_survives_chaos_and_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (tx, rx) = mailbox(None, Default::default());
+        tokio::spawn(sender_task(
+            "sender",
+            false,
+            "receiver".to_string(),
+            rx,
+            BincodeCodec::<Num, Num>::default(),
+            controller(listener.local_addr().unwrap()),
+            SendErrAction::Drop,
+            Reconnect {
+                initial_backoff_ms: 10,
+                max_backoff_ms: 50,
+                max_attempts: None,
+                replay_window: 0,
+            },
+            Some(Reliable {
+                retransmit_ms: 20,
+                max_in_flight: 8,
+            }),
+        ));
+        let inbound = Inbound::default();
+        {
+            let chaos = inbound.chaos();
+            let mut chaos = chaos.lock().unwrap();
+            chaos.set_msg_loss(0.3);
+            chaos.set_msg_duplication(3, 0.3);
+        }
+        let (p_tx, mut p_rx) = reactor_channel::<R2PMsg<Num>>(1, 1000);
+        for n in 0..60 {
+            tx.send(Num(n)).await.unwrap();
+        }
+
+        let mut next = async || {
+            let msg = tokio::time::timeout(Duration::from_secs(5), p_rx.recv_async()).await;
+            match msg.expect("retransmitted in time") {
+                Some(R2PMsg::Reliable(Num(n), "sender")) => n,
+                other => unreachable!("unexpected {:?}", other),
+            }
+        };
+        let (first, _throttle) = serve(&listener, &inbound, &p_tx).await;
+        for n in 0..20 {
+            assert_eq!(next().await, n);
+        }
+        // the connection breaks; the session carries on over the next one
+        first.abort();
+        let (_second, _throttle) = serve(&listener, &inbound, &p_tx).await;
+        for n in 20..60 {
+            assert_eq!(next().await, n);
+        }
+        let extra = tokio::time::timeout(Duration::from_millis(100), p_rx.recv_async()).await;
+        assert!(extra.is_err(), "delivered once");
+    }
+
+    #[tokio::test]
+    async fn test_probe_is_a_plain_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        probe_tcp(listener.local_addr().unwrap(), "probe")
+            .await
+            .unwrap();
+        let (socket, _) = listener.accept().await.unwrap();
+        let (mut rx, _) = socket.into_split();
+        let (name, message_type, hello) = recv_remote_handshake(&mut rx).await.unwrap();
+        assert_eq!(name, "probe");
+        assert!(message_type.is_none() && hello.is_none());
+        assert_eq!(rx.read_u8().await.ok(), None, "then hangs up");
+    }
+}
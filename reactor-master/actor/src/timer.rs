@@ -0,0 +1,391 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use bincode::{Decode, Encode};
+use futures::StreamExt;
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::Instant,
+};
+use tokio_util::time::{DelayQueue, delay_queue};
+
+use crate::{HasPriority, R2PMsg, reactor_channel::ReactorChannelTx};
+
+/// Identifies a timer set through a [`TimerHandle`], so it can be cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerId(u64);
+
+/// Correlates a request with its reply, see [`TimerHandle::ask`]. Requests and replies carry it
+/// in the application's own messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Encode, Decode)]
+pub struct CorrelationId(pub u64);
+
+/// Asks of an actor still waiting for their reply or their timeout.
+#[derive(Debug, Default)]
+pub(crate) struct Asks(Mutex<HashSet<CorrelationId>>);
+
+impl Asks {
+    /// Returns whether the ask was still waiting, so only the first of its reply and its timeout
+    /// gets through.
+    pub(crate) fn settle(&self, id: CorrelationId) -> bool {
+        self.0.lock().unwrap().remove(&id)
+    }
+}
+
+/// What a [`TimerHandle`] asked of its [`TimerQueue`], for serving the timers outside a
+/// behaviour; see [`TimerQueue::try_next`].
+pub enum TimerCmd<M> {
+    /// Deliver `msg` after `delay`, then every `period` if set. If `ask` is set, the timer is the
+    /// timeout of that ask and fires only if [`TimerQueue::settle`] still finds it waiting.
+    Set {
+        id: TimerId,
+        delay: Duration,
+        period: Option<Duration>,
+        msg: M,
+        ask: Option<CorrelationId>,
+    },
+    /// Forget the timer `id`, if it has not fired for the last time yet.
+    Cancel(TimerId),
+}
+
+/// Lets an actor schedule messages to itself, e.g. from [`crate::ActorProcess::process`].
+///
+/// Created with [`timer_channel`]; the matching [`TimerQueue`] is given to
+/// [`crate::BehaviourBuilder::timers`]. Timers fire on the runtime, and their messages reach the
+/// processor through the same channel as received messages, without chaos applied. Timers set
+/// before the actor starts are armed when it does. Clones share the same timers.
+pub struct TimerHandle<M> {
+    cmds: UnboundedSender<TimerCmd<M>>,
+    next_id: Arc<AtomicU64>,
+    asks: Arc<Asks>,
+}
+
+impl<M> Clone for TimerHandle<M> {
+    fn clone(&self) -> Self {
+        TimerHandle {
+            cmds: self.cmds.clone(),
+            next_id: self.next_id.clone(),
+            asks: self.asks.clone(),
+        }
+    }
+}
+
+impl<M> std::fmt::Debug for TimerHandle<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimerHandle").finish_non_exhaustive()
+    }
+}
+
+impl<M> TimerHandle<M> {
+    /// Delivers `msg` once, after `delay`.
+    pub fn set_once(&self, delay: Duration, msg: M) -> TimerId {
+        self.set(delay, None, msg)
+    }
+
+    /// Delivers `msg` every `period`, starting one period from now. If the actor falls behind,
+    /// missed ticks are skipped rather than delivered in a burst.
+    pub fn set_periodic(&self, period: Duration, msg: M) -> TimerId {
+        self.set(period, Some(period), msg)
+    }
+
+    /// Starts an ask, returning the id to put in the request. Unless a message answering it (see
+    /// [`crate::Msg::reply_to`]) arrives within `deadline`, `on_timeout(id)` is delivered instead.
+    /// The processor gets exactly one of the two; a reply after the timeout, or a repeated one, is
+    /// dropped before it.
+    pub fn ask(
+        &self,
+        deadline: Duration,
+        on_timeout: impl FnOnce(CorrelationId) -> M,
+    ) -> CorrelationId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let ask = CorrelationId(id);
+        self.asks.0.lock().unwrap().insert(ask);
+        let _ = self.cmds.send(TimerCmd::Set {
+            id: TimerId(id),
+            delay: deadline,
+            period: None,
+            msg: on_timeout(ask),
+            ask: Some(ask),
+        });
+        ask
+    }
+
+    /// Stops a timer. A firing already handed to the processor is still delivered.
+    pub fn cancel(&self, id: TimerId) {
+        // the actor has stopped; there is nothing left to cancel
+        let _ = self.cmds.send(TimerCmd::Cancel(id));
+    }
+
+    fn set(&self, delay: Duration, period: Option<Duration>, msg: M) -> TimerId {
+        let id = TimerId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let _ = self.cmds.send(TimerCmd::Set {
+            id,
+            delay,
+            period,
+            msg,
+            ask: None,
+        });
+        id
+    }
+}
+
+/// Receiving end of a [`timer_channel`], served by the actor it is given to.
+pub struct TimerQueue<M> {
+    cmds: UnboundedReceiver<TimerCmd<M>>,
+    asks: Arc<Asks>,
+}
+
+impl<M> TimerQueue<M> {
+    pub(crate) fn asks(&self) -> Arc<Asks> {
+        self.asks.clone()
+    }
+
+    /// Takes the next command without waiting. Only for running a processor by hand, e.g. on
+    /// simulated time: the caller then fires the timers itself, and settles asks with
+    /// [`TimerQueue::settle`] as the behaviour would.
+    pub fn try_next(&mut self) -> Option<TimerCmd<M>> {
+        self.cmds.try_recv().ok()
+    }
+
+    /// Settles the ask `id` for a reply or a timeout and returns whether it was still waiting.
+    /// Only what settles it first should reach the processor.
+    pub fn settle(&self, id: CorrelationId) -> bool {
+        self.asks.settle(id)
+    }
+}
+
+/// Creates a [`TimerHandle`] for the actor's processor and the [`TimerQueue`] for its behaviour.
+pub fn timer_channel<M>() -> (TimerHandle<M>, TimerQueue<M>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let asks = Arc::new(Asks::default());
+    (
+        TimerHandle {
+            cmds: tx,
+            next_id: Arc::new(AtomicU64::new(0)),
+            asks: asks.clone(),
+        },
+        TimerQueue { cmds: rx, asks },
+    )
+}
+
+/// A pending timer: its key in the delay queue, its period if it repeats, its message, and the
+/// ask it times out, if any.
+type Timer<M> = (delay_queue::Key, Option<Duration>, M, Option<CorrelationId>);
+
+/// Fires the builder's `ticks` and the timers set through `queue` into the processor channel.
+/// Ends when the processor is gone, or when every handle is dropped and no timer is left.
+pub(crate) async fn run_timers<M: HasPriority + Clone>(
+    queue: Option<TimerQueue<M>>,
+    ticks: Vec<(Duration, M)>,
+    p_tx: ReactorChannelTx<R2PMsg<M>>,
+) {
+    let mut delays: DelayQueue<TimerId> = DelayQueue::new();
+    let mut timers: HashMap<TimerId, Timer<M>> = HashMap::new();
+    // ids counted down from the top, so they never meet those handed out by handles
+    for (i, (period, msg)) in ticks.into_iter().enumerate() {
+        let id = TimerId(u64::MAX - i as u64);
+        timers.insert(id, (delays.insert(id, period), Some(period), msg, None));
+    }
+    let asks = queue.as_ref().map(|q| q.asks());
+    let mut cmds = queue.map(|q| q.cmds);
+    let mut handles_open = cmds.is_some();
+    loop {
+        tokio::select! {
+            cmd = async { cmds.as_mut()?.recv().await }, if handles_open => match cmd {
+                Some(TimerCmd::Set { id, delay, period, msg, ask }) => {
+                    let key = delays.insert(id, delay);
+                    timers.insert(id, (key, period, msg, ask));
+                }
+                Some(TimerCmd::Cancel(id)) => {
+                    if let Some((key, ..)) = timers.remove(&id) {
+                        delays.remove(&key);
+                    }
+                }
+                None => handles_open = false,
+            },
+            Some(expired) = delays.next(), if !delays.is_empty() => {
+                let id = *expired.get_ref();
+                let Some((_, period, msg, ask)) = timers.remove(&id) else { continue };
+                // an answered ask's timeout is left to expire here rather than cancelled
+                if let (Some(ask), Some(asks)) = (ask, &asks) && !asks.settle(ask) {
+                    continue;
+                }
+                let fired = match period {
+                    Some(period) => {
+                        let next = (expired.deadline() + period).max(Instant::now());
+                        let key = delays.insert_at(id, next);
+                        timers.insert(id, (key, Some(period), msg.clone(), None));
+                        msg
+                    }
+                    None => msg,
+                };
+                if p_tx.send(R2PMsg::Msg(fired, "")).await.is_err() {
+                    break;
+                }
+            },
+            else => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reactor_channel::{MAX_PRIO, ReactorChannelTx};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum TestMsg {
+        Once,
+        Tick,
+        TimedOut(CorrelationId),
+    }
+
+    impl HasPriority for TestMsg {
+        fn priority(&self) -> usize {
+            MAX_PRIO
+        }
+    }
+
+    async fn next(rx: &mut mpsc::Receiver<R2PMsg<TestMsg>>) -> TestMsg {
+        match rx.recv().await {
+            Some(R2PMsg::Msg(m, origin)) => {
+                assert_eq!(origin, "", "timer messages carry no origin");
+                m
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_once_periodic_and_cancel() {
+        let (tx, mut rx) = mpsc::channel(100);
+        let (handle, queue) = timer_channel::<TestMsg>();
+        let start = Instant::now();
+        let tick = handle.set_periodic(Duration::from_millis(40), TestMsg::Tick);
+        handle.set_once(Duration::from_millis(60), TestMsg::Once);
+        let task = tokio::spawn(run_timers(
+            Some(queue),
+            vec![],
+            ReactorChannelTx::SingleChannel(tx),
+        ));
+
+        assert_eq!(next(&mut rx).await, TestMsg::Tick);
+        assert_eq!(next(&mut rx).await, TestMsg::Once);
+        assert_eq!(next(&mut rx).await, TestMsg::Tick);
+        assert!(start.elapsed() >= Duration::from_millis(80));
+
+        handle.cancel(tick);
+        let cancelled = handle.set_once(Duration::from_millis(10), TestMsg::Once);
+        handle.cancel(cancelled);
+        drop(handle);
+        // nothing is left, so the task ends without firing again
+        task.await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ticks_without_handle() {
+        let (tx, mut rx) = mpsc::channel(100);
+        let ticks = vec![
+            (Duration::from_millis(10), TestMsg::Tick),
+            (Duration::from_millis(60), TestMsg::Once),
+        ];
+        let task = tokio::spawn(run_timers(None, ticks, ReactorChannelTx::SingleChannel(tx)));
+        let mut ticked = 0;
+        while next(&mut rx).await == TestMsg::Tick {
+            ticked += 1;
+        }
+        assert!(
+            ticked >= 2,
+            "fast tick fired {} times before the slow one",
+            ticked
+        );
+        assert_eq!(next(&mut rx).await, TestMsg::Tick, "both keep firing");
+        assert!(!task.is_finished(), "ticks keep firing");
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_stops_when_processor_is_gone() {
+        let (tx, rx) = mpsc::channel(100);
+        let (handle, queue) = timer_channel::<TestMsg>();
+        handle.set_periodic(Duration::from_millis(5), TestMsg::Tick);
+        drop(rx);
+        let task = tokio::spawn(run_timers(
+            Some(queue),
+            vec![],
+            ReactorChannelTx::SingleChannel(tx),
+        ));
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("timer task should end")
+            .unwrap();
+        // the handle outlives the actor without panicking
+        handle.set_once(Duration::from_millis(1), TestMsg::Once);
+    }
+
+    #[tokio::test]
+    async fn test_ask_times_out_unless_answered() {
+        let (tx, mut rx) = mpsc::channel(100);
+        let (handle, queue) = timer_channel::<TestMsg>();
+        let asks = queue.asks();
+        let answered = handle.ask(Duration::from_millis(10), TestMsg::TimedOut);
+        let unanswered = handle.ask(Duration::from_millis(20), TestMsg::TimedOut);
+        assert_ne!(answered, unanswered);
+        // the processor settles an ask when its reply comes in
+        assert!(asks.settle(answered));
+        let task = tokio::spawn(run_timers(
+            Some(queue),
+            vec![],
+            ReactorChannelTx::SingleChannel(tx),
+        ));
+
+        assert_eq!(next(&mut rx).await, TestMsg::TimedOut(unanswered));
+        assert!(
+            !asks.settle(unanswered),
+            "a reply after the timeout is late"
+        );
+        drop(handle);
+        task.await.unwrap();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_queue_served_by_hand() {
+        let (handle, mut queue) = timer_channel::<TestMsg>();
+        let once = handle.set_once(Duration::from_millis(10), TestMsg::Once);
+        let ask = handle.ask(Duration::from_millis(20), TestMsg::TimedOut);
+        handle.cancel(once);
+
+        match queue.try_next() {
+            Some(TimerCmd::Set {
+                id,
+                delay,
+                period: None,
+                msg: TestMsg::Once,
+                ask: None,
+            }) => {
+                assert_eq!((id, delay), (once, Duration::from_millis(10)));
+            }
+            _ => panic!("expected the one-shot timer"),
+        }
+        match queue.try_next() {
+            Some(TimerCmd::Set {
+                msg: TestMsg::TimedOut(id),
+                ask: Some(asked),
+                ..
+            }) => assert_eq!((id, asked), (ask, ask)),
+            _ => panic!("expected the ask's timeout"),
+        }
+        assert!(matches!(queue.try_next(), Some(TimerCmd::Cancel(id)) if id == once));
+        assert!(queue.try_next().is_none());
+        assert!(queue.settle(ask), "the reply settles it first");
+        assert!(!queue.settle(ask), "so its timeout is dropped");
+    }
+}
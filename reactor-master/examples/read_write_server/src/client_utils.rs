@@ -1,7 +1,10 @@
 use reactor_actor::{Msg, RouteTo};
+use reactor_macros::{DefaultPrio, Msg as DeriveMsg};
 use std::marker::PhantomData;
 
-#[derive(Default, Debug, PartialEq, bincode::Encode, bincode::Decode, Clone)]
+#[derive(
+    Default, Debug, PartialEq, bincode::Encode, bincode::Decode, Clone, DeriveMsg, DefaultPrio,
+)]
 pub struct GeneratorOut;
 
 // //////////////////////////////////////////////////////////////////////////////
@@ -13,7 +13,9 @@ use reactor_macros::msg_converter;
 )]
 pub struct WriteOut;
 
-#[derive(Default, Debug, PartialEq, bincode::Encode, bincode::Decode, Clone)]
+#[derive(
+    Default, Debug, PartialEq, bincode::Encode, bincode::Decode, Clone, DeriveMsg, DefaultPrio,
+)]
 pub struct WriteAck;
 
 msg_converter! {
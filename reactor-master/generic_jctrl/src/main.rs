@@ -15,6 +15,7 @@ use serde::Deserialize;
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 use tokio::signal;
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -29,6 +30,10 @@ pub struct JobManifest {
 #[command(name = "Job Controller", about = "Run reactor Job controller")]
 pub struct Cli {
     pub job_manifest: PathBuf,
+    /// On Ctrl-C, give the actors this long to process their queued input and send what they
+    /// hold, instead of stopping them at once
+    #[arg(long)]
+    pub drain_ms: Option<u64>,
 }
 
 #[tokio::main]
@@ -48,18 +53,29 @@ async fn main() {
     jc.start_job(ops).await;
     jc.chaos_scheduler().await;
     let _ = signal::ctrl_c().await;
-    jc.stop_job().await;
+    match cli.drain_ms {
+        Some(ms) => jc.drain_job(Duration::from_millis(ms)).await,
+        None => jc.stop_job().await,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use reactor_jobm::placement::{
-        CrashOp, Factor, MsgDelayOp, MsgDuplicationOp, MsgLossOp, Probability,
+        CrashOp, Factor, MsgDelayOp, MsgDuplicationOp, MsgLossOp, Probability, RestartPolicy,
     };
     use serde_json::json;
     use std::collections::HashMap;
 
+    #[test]
+    fn test_cli_drain_is_opt_in() {
+        let cli = Cli::parse_from(["jctrl", "job.toml"]);
+        assert_eq!(cli.drain_ms, None);
+        let cli = Cli::parse_from(["jctrl", "job.toml", "--drain-ms", "5000"]);
+        assert_eq!(cli.drain_ms, Some(5000));
+    }
+
     #[test]
     fn test_manual_placement_manager_parse() {
         let toml_data = r#"
@@ -91,6 +107,7 @@ port = 3000
   nodename = "node1"
   actor_name = "ponger"
   replicas = 3
+  restart = { policy = "backoff", initial_backoff_ms = 100, max_backoff_ms = 1000, max_restarts = 5 }
   connection = { hashed = ["addr1", "addr2"] }
   op_args = { height = 1080, width = 1920, source = { type = "rtsp", url = "rtsp://example.com/stream" } }
 "#;
@@ -140,6 +157,7 @@ port = 3000
                                 senders: vec!["ponger".into()],
                             }),
                         }),
+                        restart: None,
                     }],
                 ),
                 (
@@ -163,6 +181,11 @@ port = 3000
                         ]),
                         replicas: Some(3),
                         chaos: None,
+                        restart: Some(RestartPolicy::Backoff {
+                            initial_backoff_ms: 100,
+                            max_backoff_ms: 1000,
+                            max_restarts: 5,
+                        }),
                     }],
                 ),
             ]),
@@ -190,5 +213,15 @@ port = 3000
             vec!["ponger1", "ponger2", "ponger3"]
         );
         assert!(ponger_list.iter().all(|p| p.nodename == "node1"));
+        assert!(
+            ponger_list.iter().all(|p| matches!(
+                p.restart,
+                Some(RestartPolicy::Backoff {
+                    max_restarts: 5,
+                    ..
+                })
+            )),
+            "replicas keep the restart policy"
+        );
     }
 }
@@ -12,8 +12,8 @@ use reactor_client::{
     self,
     apis::configuration::Configuration,
     models::{
-        DisableMsgDelayRequest, MsgDelayRequest, MsgDuplicationRequest, MsgLossRequest,
-        RemoteActorInfo, SpawnArgs,
+        DisableMsgDelayRequest, DrainActorRequest, MsgDelayRequest, MsgDuplicationRequest,
+        MsgLossRequest, RemoteActorInfo, SpawnArgs,
     },
 };
 
@@ -176,6 +176,7 @@ impl NodeHandle {
             operator_name: logical_op.name.clone(),
             lib_name: logical_op.lib_name.clone(),
             payload: physical_op.payload.clone(),
+            restart: physical_op.restart.clone().map(Box::new),
         };
         self.actor_spawn_args
             .insert(physical_op.actor_name.clone(), spawn_args.clone());
@@ -239,11 +240,38 @@ impl NodeHandle {
         }
     }
 
-    async fn stop_all_actors(self) {
+    /// Stops the node's actors, first draining them within `drain` if one is given.
+    async fn stop_all_actors(self, drain: Option<Duration>) {
+        if let Some(deadline) = drain {
+            self.drain_all_actors(deadline).await;
+        }
         reactor_client::apis::default_api::stop_all_actors(&self.client_config)
             .await
             .unwrap();
     }
+
+    // a drained actor leaves the node's list at once, so the hard stop after it only reaches
+    // actors the drain missed
+    async fn drain_all_actors(&self, deadline: Duration) {
+        let drains: Vec<_> = self
+            .actors
+            .iter()
+            .map(|actor| async move {
+                let drained = reactor_client::apis::default_api::drain_actor(
+                    &self.client_config,
+                    DrainActorRequest {
+                        actor_name: actor.name.clone(),
+                        deadline_ms: deadline.as_millis() as i64,
+                    },
+                )
+                .await;
+                if let Err(e) = drained {
+                    log::warn!("Failed to drain {} on {}: {e}", actor.name, self.hostname);
+                }
+            })
+            .collect();
+        join_all(drains).await;
+    }
 }
 
 pub struct JobController<PM> {
@@ -317,9 +345,19 @@ impl<PM: PlacementManager> JobController<PM> {
         }
     }
 
-    pub async fn stop_job(mut self) {
+    pub async fn stop_job(self) {
+        self.stop_nodes(None).await;
+    }
+
+    /// Like [`Self::stop_job`], but each actor first processes its queued input and gets up to
+    /// `deadline` to send what it holds. Actors that fail to drain are stopped outright.
+    pub async fn drain_job(self, deadline: Duration) {
+        self.stop_nodes(Some(deadline)).await;
+    }
+
+    async fn stop_nodes(mut self, drain: Option<Duration>) {
         while let Some((_, node_handle)) = self.nodes.pop_first() {
-            node_handle.stop_all_actors().await;
+            node_handle.stop_all_actors(drain).await;
         }
     }
 
@@ -334,3 +372,180 @@ impl<PM: PlacementManager> JobController<PM> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    };
+
+    use axum::{Json, Router, extract::State, routing::post};
+    use bincode::{Decode, Encode};
+    use reactor_actor::{
+        ActorProcess, ActorSend, BehaviourBuilder, Connection, ControlInst, ControlReq,
+        HasPriority, Msg, NodeComm, RouteTo, RuntimeCtx, codec::BincodeCodec,
+    };
+    use tokio::{
+        net::TcpListener,
+        sync::{Notify, mpsc},
+    };
+
+    use super::*;
+    use crate::placement::ManualPlacementManager;
+
+    #[derive(Encode, Decode, Debug, Clone)]
+    struct Num(u64);
+    impl HasPriority for Num {}
+    impl Msg for Num {}
+
+    /// Emits `n` messages for an input of `n`.
+    struct Fanout;
+    impl ActorProcess for Fanout {
+        type IMsg = Num;
+        type OMsg = Num;
+        fn process(&mut self, input: Num) -> Vec<Num> {
+            (0..input.0).map(Num).collect()
+        }
+    }
+
+    struct ToSink;
+    impl ActorSend for ToSink {
+        type OMsg = Num;
+        async fn before_send<'a>(&'a mut self, _output: &Num) -> RouteTo<'a> {
+            RouteTo::from("sink".to_string())
+        }
+    }
+
+    struct Count(Arc<AtomicU64>);
+    impl ActorProcess for Count {
+        type IMsg = Num;
+        type OMsg = Num;
+        fn process(&mut self, _input: Num) -> Vec<Num> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            vec![]
+        }
+    }
+
+    /// A node controller that runs the job's one actor in this process. It holds back where
+    /// `sink` is until the actor is drained, so the actor's output is still queued then.
+    #[derive(Clone)]
+    struct MockNode {
+        actor: Arc<Mutex<Option<mpsc::Sender<ControlInst>>>>,
+        drained: Arc<Notify>,
+        sink: mpsc::Sender<ControlInst>,
+    }
+
+    async fn start_actor(
+        State(node): State<MockNode>,
+        Json(args): Json<SpawnArgs>,
+    ) -> Json<RemoteActorInfo> {
+        let (inst_tx, inst_rx) = mpsc::channel(8);
+        let (req_tx, mut req_rx) = mpsc::channel(8);
+        let ctx = RuntimeCtx::new(
+            args.actor_name.clone().leak(),
+            NodeComm::new(inst_rx, req_tx),
+        );
+        tokio::spawn(
+            BehaviourBuilder::new(Fanout, BincodeCodec::default())
+                .send(ToSink)
+                .generator(std::iter::once(Num(50)))
+                .build()
+                .run(ctx),
+        );
+        let resolver = node.clone();
+        tokio::spawn(async move {
+            while let Some(req) = req_rx.recv().await {
+                let ControlReq::Resolve { resp_tx, .. } = req else {
+                    continue;
+                };
+                resolver.drained.notified().await;
+                let (write_half, read_half) = mpsc::channel(1 << 10);
+                resolver
+                    .sink
+                    .send(ControlInst::StartLocalRecv(read_half))
+                    .await
+                    .unwrap();
+                let _ = resp_tx.send(Connection::Local(write_half));
+            }
+        });
+        *node.actor.lock().unwrap() = Some(inst_tx);
+        Json(RemoteActorInfo::new(String::new(), args.actor_name, 0))
+    }
+
+    // like the node, a drained actor leaves the node's list at once
+    async fn drain_actor(State(node): State<MockNode>, Json(req): Json<DrainActorRequest>) {
+        let actor = node.actor.lock().unwrap().take();
+        if let Some(actor) = actor {
+            let deadline_ms = req.deadline_ms as u64;
+            actor
+                .send(ControlInst::Drain { deadline_ms })
+                .await
+                .unwrap();
+            node.drained.notify_one();
+        }
+    }
+
+    async fn stop_all_actors(State(node): State<MockNode>) {
+        let actor = node.actor.lock().unwrap().take();
+        if let Some(actor) = actor {
+            let _ = actor.send(ControlInst::Stop).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_job_delivers_queued_output() {
+        let received = Arc::new(AtomicU64::new(0));
+        let (sink, sink_rx) = mpsc::channel(8);
+        let (sink_req_tx, _sink_req_rx) = mpsc::channel(8);
+        tokio::spawn(
+            BehaviourBuilder::new(Count(received.clone()), BincodeCodec::default())
+                .build()
+                .run(RuntimeCtx::new("sink", NodeComm::new(sink_rx, sink_req_tx))),
+        );
+        let node = MockNode {
+            actor: Arc::default(),
+            drained: Arc::default(),
+            sink,
+        };
+        let app = Router::new()
+            .route("/start_actor", post(start_actor))
+            .route("/drain_actor", post(drain_actor))
+            .route("/stop_all_actors", post(stop_all_actors))
+            .with_state(node);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let op = LogicalOp {
+            name: "fanout".to_string(),
+            lib_name: "lib".to_string(),
+        };
+        let pm = ManualPlacementManager::new(
+            HashMap::from([(
+                "fanout".to_string(),
+                vec![PhysicalOp {
+                    nodename: "node1".to_string(),
+                    actor_name: "fanout".to_string(),
+                    replicas: None,
+                    chaos: None,
+                    restart: None,
+                    payload: HashMap::new(),
+                }],
+            )]),
+            None,
+        );
+        let mut jc = JobController::new(pm);
+        jc.register_node("node1", "127.0.0.1", port);
+        jc.start_job(vec![op]).await;
+        jc.drain_job(Duration::from_secs(5)).await;
+
+        let delivered = tokio::time::timeout(Duration::from_secs(5), async {
+            while received.load(Ordering::SeqCst) < 50 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        assert!(delivered.is_ok(), "the drained actor sends what it holds");
+    }
+}
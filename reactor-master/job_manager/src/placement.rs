@@ -4,6 +4,8 @@ use std::{
     iter,
 };
 
+pub use reactor_client::models::RestartPolicy;
+
 pub type Hostname = &'static str;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -180,6 +182,8 @@ pub struct PhysicalOp {
     pub actor_name: String,
     pub replicas: Option<u32>,
     pub chaos: Option<ChaosMap>,
+    /// What the node does when the actor panics or fails; see the node's `RestartPolicy`.
+    pub restart: Option<RestartPolicy>,
     #[serde(flatten)]
     pub payload: HashMap<String, serde_json::Value>,
 }
@@ -222,6 +226,7 @@ impl ManualPlacementManager {
                             payload: phys_op.payload.clone(),
                             replicas: None,
                             chaos: phys_op.chaos.clone(),
+                            restart: phys_op.restart.clone(),
                         });
                     }
                 } else {
@@ -3,7 +3,7 @@ use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
 use syn::ItemFn;
 use syn::{
-    DeriveInput, Ident, Result, Token, Type, bracketed,
+    Data, DeriveInput, Fields, Ident, Index, Member, Result, Token, Type, bracketed,
     parse::{Parse, ParseStream},
     parse_macro_input,
     punctuated::Punctuated,
@@ -32,16 +32,86 @@ pub fn auto_default_priority(input: TokenStream) -> TokenStream {
     })
 }
 
-#[proc_macro_derive(Msg)]
+/// Implements `reactor_actor::Msg`. A field marked `#[reply_to]`, of type
+/// `reactor_actor::CorrelationId`, makes the message a reply to that ask; in an enum, each
+/// variant may mark one.
+#[proc_macro_derive(Msg, attributes(reply_to))]
 pub fn auto_msg(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     let name = &input.ident;
 
+    let reply_to = match reply_to_arms(&input.data) {
+        Ok(arms) => arms,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    if reply_to.is_empty() {
+        return TokenStream::from(quote! {
+            impl reactor_actor::Msg for #name {}
+        });
+    }
     TokenStream::from(quote! {
-        impl reactor_actor::Msg for #name {}
+        impl reactor_actor::Msg for #name {
+            #[allow(unreachable_patterns)]
+            fn reply_to(&self) -> ::std::option::Option<reactor_actor::CorrelationId> {
+                match self {
+                    #(#reply_to,)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
     })
 }
 
+/// One match arm for every struct or variant with a `#[reply_to]` field.
+fn reply_to_arms(data: &Data) -> Result<Vec<TokenStream2>> {
+    let mut arms = vec![];
+    match data {
+        Data::Struct(data) => {
+            if let Some(field) = reply_to_field(&data.fields)? {
+                arms.push(quote! { Self { #field: id, .. } => ::std::option::Option::Some(*id) });
+            }
+        }
+        Data::Enum(data) => {
+            for variant in data.variants.iter() {
+                if let Some(field) = reply_to_field(&variant.fields)? {
+                    let ident = &variant.ident;
+                    arms.push(
+                        quote! { Self::#ident { #field: id, .. } => ::std::option::Option::Some(*id) },
+                    );
+                }
+            }
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "Msg cannot be derived for unions",
+            ));
+        }
+    }
+    Ok(arms)
+}
+
+/// The member marked `#[reply_to]` among `fields`, if any.
+fn reply_to_field(fields: &Fields) -> Result<Option<Member>> {
+    let mut marked = fields
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| f.attrs.iter().any(|a| a.path().is_ident("reply_to")));
+    let Some((i, field)) = marked.next() else {
+        return Ok(None);
+    };
+    if let Some((_, again)) = marked.next() {
+        return Err(syn::Error::new_spanned(
+            again,
+            "only one field can be marked #[reply_to]",
+        ));
+    }
+    Ok(Some(match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(Index::from(i)),
+    }))
+}
+
 #[derive(Debug)]
 struct MsgConverters {
     pub unions: Vec<UnionDef>,
@@ -203,7 +273,13 @@ fn generate_enum(union: &UnionDef) -> TokenStream2 {
             #(#variants(#variants)),*
         }
         impl ::reactor_actor::HasPriority for #enum_name {}
-        impl ::reactor_actor::Msg for #enum_name {}
+        impl ::reactor_actor::Msg for #enum_name {
+            fn reply_to(&self) -> ::std::option::Option<::reactor_actor::CorrelationId> {
+                match self {
+                    #(#enum_name::#variants(v) => ::reactor_actor::Msg::reply_to(v)),*
+                }
+            }
+        }
     };
     // Implement From<Variant> for Enum
     let impl_from_variant = variants.iter().map(|variant| {
@@ -0,0 +1,117 @@
+#[cfg(test)]
+mod tests {
+    use reactor_actor::{CorrelationId, Msg};
+    use reactor_macros::msg_converter;
+
+    #[derive(
+        Debug,
+        Clone,
+        bincode::Encode,
+        bincode::Decode,
+        ::reactor_macros::DefaultPrio,
+        ::reactor_macros::Msg,
+    )]
+    pub struct Tick;
+
+    #[derive(
+        Debug,
+        Clone,
+        bincode::Encode,
+        bincode::Decode,
+        ::reactor_macros::DefaultPrio,
+        ::reactor_macros::Msg,
+    )]
+    pub struct Pong {
+        pub from: String,
+        #[reply_to]
+        pub ping: CorrelationId,
+    }
+
+    #[derive(
+        Debug,
+        Clone,
+        bincode::Encode,
+        bincode::Decode,
+        ::reactor_macros::DefaultPrio,
+        ::reactor_macros::Msg,
+    )]
+    pub struct Ack(u32, #[reply_to] CorrelationId);
+
+    #[derive(
+        Debug,
+        Clone,
+        bincode::Encode,
+        bincode::Decode,
+        ::reactor_macros::DefaultPrio,
+        ::reactor_macros::Msg,
+    )]
+    pub enum Peer {
+        Req {
+            id: CorrelationId,
+        },
+        Rsp {
+            #[reply_to]
+            id: CorrelationId,
+        },
+        Nack(String, #[reply_to] CorrelationId),
+    }
+
+    msg_converter! {
+       Unions: [
+           ClientIn = Pong, Ack, Peer, Tick;
+       ];
+    }
+
+    #[test]
+    fn test_derived_reply_to() {
+        assert_eq!(Tick.reply_to(), None);
+        let pong = Pong {
+            from: "server".to_string(),
+            ping: CorrelationId(1),
+        };
+        assert_eq!(pong.reply_to(), Some(CorrelationId(1)));
+        assert_eq!(Ack(7, CorrelationId(2)).reply_to(), Some(CorrelationId(2)));
+        assert_eq!(
+            Peer::Req {
+                id: CorrelationId(3)
+            }
+            .reply_to(),
+            None,
+            "requests answer nothing"
+        );
+        assert_eq!(
+            Peer::Rsp {
+                id: CorrelationId(4)
+            }
+            .reply_to(),
+            Some(CorrelationId(4))
+        );
+        assert_eq!(
+            Peer::Nack("busy".to_string(), CorrelationId(5)).reply_to(),
+            Some(CorrelationId(5))
+        );
+    }
+
+    #[test]
+    fn test_unions_pass_reply_to_through() {
+        assert_eq!(ClientIn::from(Tick).reply_to(), None);
+        assert_eq!(
+            ClientIn::from(Ack(7, CorrelationId(2))).reply_to(),
+            Some(CorrelationId(2))
+        );
+        assert_eq!(
+            ClientIn::from(Peer::Rsp {
+                id: CorrelationId(4)
+            })
+            .reply_to(),
+            Some(CorrelationId(4))
+        );
+        assert_eq!(
+            ClientIn::from(Peer::Req {
+                id: CorrelationId(3)
+            })
+            .reply_to(),
+            None
+        );
+    }
+}
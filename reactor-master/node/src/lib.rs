@@ -1,16 +1,15 @@
 //! One Node Controller task will be spawned on each physical nodes.
 use core::panic;
 use op_lib_manager::OpLibrary;
-use reactor_actor::{Connection, ControlInst, ControlReq, NodeComm, RuntimeCtx};
+use reactor_actor::{Connection, ControlInst, ControlReq, Exit, NodeComm, RuntimeCtx};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use tokio::sync::{
-    mpsc::{self, Sender, UnboundedReceiver, channel, unbounded_channel},
+    mpsc::{self, Sender, UnboundedReceiver, UnboundedSender, channel, unbounded_channel},
     oneshot,
 };
 use tracing::{error, info, warn};
 use tracing_shared::SharedLogger;
-// use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[cfg(feature = "dynop")]
 use code_gen::CodeGenerator;
@@ -19,6 +18,8 @@ use lib_builder::LibBuilder;
 use serde_json::Value;
 #[cfg(not(feature = "dynop"))]
 use std::path::PathBuf;
+#[cfg(feature = "dynop")]
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[cfg(feature = "dynop")]
 pub mod code_gen;
@@ -27,6 +28,8 @@ pub mod lib_builder;
 mod rpc;
 use rpc::webserver;
 mod op_lib_manager;
+mod supervisor;
+use supervisor::{RestartPolicy, Supervisor};
 
 pub type NodeAddr = &'static str;
 // pub type ActorSpawnCB = fn(RuntimeCtx, HashMap<String, serde_json::Value>);
@@ -65,6 +68,7 @@ pub(crate) enum JobControllerReq {
         op_name: String,
         resp_tx: oneshot::Sender<Option<SpawnResult>>,
         payload: HashMap<String, Value>,
+        restart: RestartPolicy,
     },
     RemoteActorAdded {
         addr: ActorAddr,
@@ -73,7 +77,17 @@ pub(crate) enum JobControllerReq {
     StopActor {
         addr: ActorAddr,
     },
+    DrainActor {
+        addr: ActorAddr,
+        deadline_ms: u64,
+    },
     StopAllActors,
+    AddPrio {
+        actor_name: ActorAddr,
+    },
+    RemoveLowPrio {
+        actor_name: ActorAddr,
+    },
     MsgLoss {
         actor_name: ActorAddr,
         probability: f32,
@@ -105,6 +119,16 @@ pub(crate) enum JobControllerReq {
 
 struct LocalActor {
     handle: Sender<ControlInst>,
+    spawn: ActorSpawn,
+    supervisor: Supervisor,
+}
+
+/// What a local actor was spawned from, to re-create it after a failure.
+struct ActorSpawn {
+    lib_name: LibName,
+    op_name: String,
+    payload: HashMap<String, Value>,
+    port: u16,
 }
 struct RemoteActor {
     remote_actor_addr: SocketAddr,
@@ -177,18 +201,25 @@ async fn actor_control_loop(
     let mut local_actors: HashMap<ActorAddr, LocalActor> = HashMap::new();
     let mut remote_actors: HashMap<ActorAddr, RemoteActor> = HashMap::new();
     let (actor_control_tx, mut actor_control_rx) = channel(20);
+    let (restart_tx, mut restart_rx) = unbounded_channel();
     let mut port: u16 = 6000;
 
     loop {
         tokio::select! {
             req = actor_control_rx.recv() => {
                 match req {
+                    Some(ControlReq::Exited { addr, exit }) => {
+                        handle_actor_exit(addr, exit, &mut local_actors, &restart_tx);
+                    },
                     Some(req) => {
                         handle_actor_req(req, &local_actors, &remote_actors).await;
                     },
                     None => break,
                 }
             }
+            Some(addr) = restart_rx.recv() => {
+                restart_actor(addr, &op_lib, &mut local_actors, &actor_control_tx).await;
+            }
             req = job_control_rx.recv() => {
                 match req {
                     Some(req) => {
@@ -202,7 +233,6 @@ async fn actor_control_loop(
     }
 }
 
-#[cfg(not(feature = "dynop"))]
 #[tracing::instrument(skip(req, op_lib, local_actors, remote_actors, actor_contrl_tx))]
 async fn handle_job_req(
     req: JobControllerReq,
@@ -215,40 +245,36 @@ async fn handle_job_req(
     use tracing::info;
 
     match req {
+        #[cfg(feature = "dynop")]
+        JobControllerReq::RegisterOps { .. } => {
+            unreachable!("libraries are registered by the dynamic operator controller")
+        }
         JobControllerReq::SpawnActor {
             addr,
             op_name,
             resp_tx,
             lib_name,
             payload,
+            restart,
         } => {
-            info!(target: "serving spawn actor", addr, op_name, lib_name, ?payload);
-            let (control_tx, control_rx) = channel(20);
-
-            let lib = op_lib.get_lib(&lib_name);
-            unsafe {
-                use reactor_actor::ActorSpawnCB;
-
-                let shared_logger: libloading::Symbol<SetupSharedLogger> =
-                    lib.get(b"setup_shared_logger_ref").unwrap();
-                let logger = SharedLogger::new();
-                shared_logger(logger);
-                let op: libloading::Symbol<ActorSpawnCB> = lib.get(op_name.as_bytes()).unwrap();
-                op(
-                    RuntimeCtx::new(
-                        addr.clone().leak(),
-                        NodeComm::new(control_rx, actor_contrl_tx.clone()),
-                    ),
-                    payload,
-                );
-                resp_tx.send(Some(SpawnResult { port })).unwrap();
-                control_tx
-                    .send(ControlInst::StartTcpRecv(port))
-                    .await
-                    .unwrap();
-                info!(target: "actor spawned", port);
-                local_actors.insert(addr, LocalActor { handle: control_tx });
-            }
+            info!(target: "serving spawn actor", addr, op_name, lib_name, ?payload, ?restart);
+            let spawn = ActorSpawn {
+                lib_name,
+                op_name,
+                payload,
+                port,
+            };
+            let control_tx = spawn_local(&addr, &spawn, op_lib, actor_contrl_tx).await;
+            resp_tx.send(Some(SpawnResult { port })).unwrap();
+            info!(target: "actor spawned", port);
+            local_actors.insert(
+                addr,
+                LocalActor {
+                    handle: control_tx,
+                    spawn,
+                    supervisor: Supervisor::new(restart),
+                },
+            );
         }
         JobControllerReq::RemoteActorAdded { addr, sock_addr } => {
             info!(target: "serving remote actor added", addr, ?sock_addr);
@@ -262,14 +288,33 @@ async fn handle_job_req(
         JobControllerReq::StopActor { addr } => {
             if let Some(actor) = local_actors.remove(&addr) {
                 info!(target: "stopping actor", addr);
-                actor.handle.send(ControlInst::Stop).await.unwrap();
+                // an actor waiting for its restart is already down
+                let _ = actor.handle.send(ControlInst::Stop).await;
+            }
+        }
+        JobControllerReq::DrainActor { addr, deadline_ms } => {
+            if let Some(actor) = local_actors.remove(&addr) {
+                info!(target: "draining actor", addr, deadline_ms);
+                let _ = actor.handle.send(ControlInst::Drain { deadline_ms }).await;
             }
         }
         JobControllerReq::StopAllActors => {
             info!(target: "serving stop all actors", total_actors=local_actors.len());
             for (name, actor) in local_actors.drain() {
                 info!(target: "stopping actor", name);
-                actor.handle.send(ControlInst::Stop).await.unwrap();
+                let _ = actor.handle.send(ControlInst::Stop).await;
+            }
+        }
+        JobControllerReq::AddPrio { actor_name } => {
+            if let Some(actor) = local_actors.get(&actor_name) {
+                info!(target: "adding priority", actor_name);
+                send_control(&actor_name, actor, ControlInst::AddPrio).await;
+            }
+        }
+        JobControllerReq::RemoveLowPrio { actor_name } => {
+            if let Some(actor) = local_actors.get(&actor_name) {
+                info!(target: "removing lowest priority", actor_name);
+                send_control(&actor_name, actor, ControlInst::RemoveLowPrio).await;
             }
         }
         JobControllerReq::MsgDuplication {
@@ -279,14 +324,15 @@ async fn handle_job_req(
         } => {
             if let Some(actor) = local_actors.get(&actor_name) {
                 info!(target: "setting msg duplication", actor_name, factor, probability);
-                actor
-                    .handle
-                    .send(ControlInst::SetMsgDuplication {
+                send_control(
+                    &actor_name,
+                    actor,
+                    ControlInst::SetMsgDuplication {
                         factor,
                         probability,
-                    })
-                    .await
-                    .unwrap();
+                    },
+                )
+                .await;
             }
         }
         JobControllerReq::MsgLoss {
@@ -295,11 +341,7 @@ async fn handle_job_req(
         } => {
             if let Some(actor) = local_actors.get(&actor_name) {
                 info!(target: "setting msg loss", actor_name, probability);
-                actor
-                    .handle
-                    .send(ControlInst::SetMsgLoss { probability })
-                    .await
-                    .unwrap();
+                send_control(&actor_name, actor, ControlInst::SetMsgLoss { probability }).await;
             }
         }
         JobControllerReq::MsgDelay {
@@ -309,30 +351,27 @@ async fn handle_job_req(
         } => {
             if let Some(actor) = local_actors.get(&actor_name) {
                 info!(target: "setting msg delay", actor_name, ?senders);
-                actor
-                    .handle
-                    .send(ControlInst::SetMsgDelay {
+                send_control(
+                    &actor_name,
+                    actor,
+                    ControlInst::SetMsgDelay {
                         delay_range_ms,
                         senders,
-                    })
-                    .await
-                    .unwrap();
+                    },
+                )
+                .await;
             }
         }
         JobControllerReq::DisableMsgLoss { actor_name } => {
             if let Some(actor) = local_actors.get(&actor_name) {
                 info!(target: "disabling msg loss", actor_name);
-                actor.handle.send(ControlInst::UnsetMsgLoss).await.unwrap();
+                send_control(&actor_name, actor, ControlInst::UnsetMsgLoss).await;
             }
         }
         JobControllerReq::DisableMsgDuplication { actor_name } => {
             if let Some(actor) = local_actors.get(&actor_name) {
                 info!(target: "disabling msg duplication", actor_name);
-                actor
-                    .handle
-                    .send(ControlInst::UnsetMsgDuplication)
-                    .await
-                    .unwrap();
+                send_control(&actor_name, actor, ControlInst::UnsetMsgDuplication).await;
             }
         }
         JobControllerReq::DisableMsgDelay {
@@ -341,11 +380,7 @@ async fn handle_job_req(
         } => {
             if let Some(actor) = local_actors.get(&actor_name) {
                 info!(target: "disabling msg loss", actor_name);
-                actor
-                    .handle
-                    .send(ControlInst::UnsetMsgDelay { senders })
-                    .await
-                    .unwrap();
+                send_control(&actor_name, actor, ControlInst::UnsetMsgDelay { senders }).await;
             }
         }
         JobControllerReq::GetStatus { resp_tx } => {
@@ -367,6 +402,99 @@ async fn handle_job_req(
     }
 }
 
+/// Hands a control instruction to a local actor. An actor waiting for its restart has no one
+/// reading its control channel, so the instruction is skipped.
+async fn send_control(addr: &str, actor: &LocalActor, inst: ControlInst) {
+    if actor.handle.send(inst).await.is_err() {
+        warn!(target: "actor down, skipping control", addr);
+    }
+}
+
+/// Creates a local actor from its spawn payload and starts its TCP listener, returning its
+/// control channel.
+async fn spawn_local(
+    addr: &ActorAddr,
+    spawn: &ActorSpawn,
+    op_lib: &OpLibrary,
+    actor_contrl_tx: &Sender<ControlReq>,
+) -> Sender<ControlInst> {
+    let (control_tx, control_rx) = channel(20);
+
+    let lib = op_lib.get_lib(&spawn.lib_name);
+    unsafe {
+        use reactor_actor::ActorSpawnCB;
+
+        let shared_logger: libloading::Symbol<SetupSharedLogger> =
+            lib.get(b"setup_shared_logger_ref").unwrap();
+        let logger = SharedLogger::new();
+        shared_logger(logger);
+        let op: libloading::Symbol<ActorSpawnCB> = lib.get(spawn.op_name.as_bytes()).unwrap();
+        op(
+            RuntimeCtx::new(
+                addr.clone().leak(),
+                NodeComm::new(control_rx, actor_contrl_tx.clone()),
+            ),
+            spawn.payload.clone(),
+        );
+    }
+    control_tx
+        .send(ControlInst::StartTcpRecv(spawn.port))
+        .await
+        .unwrap();
+    control_tx
+}
+
+/// Schedules the restart of a local actor that panicked or failed, or forgets it once its
+/// policy gives up on it.
+#[tracing::instrument(skip(local_actors, restart_tx))]
+fn handle_actor_exit(
+    addr: ActorAddr,
+    exit: Exit,
+    local_actors: &mut HashMap<ActorAddr, LocalActor>,
+    restart_tx: &UnboundedSender<ActorAddr>,
+) {
+    // a stopped actor is no longer listed, and an actor spawned again under the same name
+    // is still running
+    let Some(actor) = local_actors.get_mut(&addr) else {
+        return;
+    };
+    if exit == Exit::Stopped || !actor.handle.is_closed() {
+        return;
+    }
+    match actor.supervisor.next_restart() {
+        Some(delay) => {
+            warn!(target: "restarting actor", addr, ?exit, ?delay);
+            let restart_tx = restart_tx.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let _ = restart_tx.send(addr);
+            });
+        }
+        None => {
+            error!(target: "actor down", addr, ?exit);
+            local_actors.remove(&addr);
+        }
+    }
+}
+
+#[tracing::instrument(skip(op_lib, local_actors, actor_contrl_tx))]
+async fn restart_actor(
+    addr: ActorAddr,
+    op_lib: &OpLibrary,
+    local_actors: &mut HashMap<ActorAddr, LocalActor>,
+    actor_contrl_tx: &Sender<ControlReq>,
+) {
+    // stopped, or spawned again, while waiting for the restart
+    let Some(actor) = local_actors.get_mut(&addr) else {
+        return;
+    };
+    if !actor.handle.is_closed() {
+        return;
+    }
+    actor.handle = spawn_local(&addr, &actor.spawn, op_lib, actor_contrl_tx).await;
+    info!(target: "actor restarted", addr, port = actor.spawn.port);
+}
+
 #[tracing::instrument(skip(local_actors, remote_actors, req))]
 async fn handle_actor_req(
     req: ControlReq,
@@ -379,12 +507,16 @@ async fn handle_actor_req(
             if let Some(local) = local_actors.get(&addr) {
                 info!(target: "resolved", addr="local");
                 let (write_half, read_half) = mpsc::channel(1 << 10);
-                local
+                let started = local
                     .handle
                     .send(ControlInst::StartLocalRecv(read_half))
-                    .await
-                    .unwrap();
-                resp_tx.send(Connection::Local(write_half)).unwrap();
+                    .await;
+                if started.is_ok() {
+                    resp_tx.send(Connection::Local(write_half)).unwrap();
+                } else {
+                    // down, the sender tries again after its restart
+                    let _ = resp_tx.send(Connection::CouldntResolve);
+                }
             } else if let Some(local) = remote_actors.get(&addr) {
                 info!(target: "resolved", addr=?local.remote_actor_addr);
                 resp_tx
@@ -394,6 +526,9 @@ async fn handle_actor_req(
                 let _ = resp_tx.send(Connection::CouldntResolve);
             }
         }
+        ControlReq::Exited { addr, exit } => {
+            info!(target: "actor exited", addr, ?exit);
+        }
     }
 }
 
@@ -436,21 +571,42 @@ async fn actor_control_loop<CG: CodeGenerator + Send>(
     let mut remote_actors: HashMap<ActorAddr, RemoteActor> = HashMap::new();
     let mut libs = OpLibrary::default();
     let (actor_control_tx, mut actor_control_rx) = channel(20);
+    let (restart_tx, mut restart_rx) = unbounded_channel();
+    let mut port: u16 = 6000;
 
     loop {
         tokio::select! {
             req = actor_control_rx.recv() => {
                 match req {
+                    Some(ControlReq::Exited { addr, exit }) => {
+                        handle_actor_exit(addr, exit, &mut local_actors, &restart_tx);
+                    },
                     Some(req) => {
                         handle_actor_req(req, &local_actors, &remote_actors).await;
                     },
                     None => break,
                 }
             }
+            Some(addr) = restart_rx.recv() => {
+                restart_actor(addr, &libs, &mut local_actors, &actor_control_tx).await;
+            }
             req = job_control_rx.recv() => {
                 match req {
+                    Some(JobControllerReq::RegisterOps {
+                        args,
+                        resp_tx,
+                        lib_name,
+                    }) => {
+                        log::info!("[Node] Registering Op from lib: {lib_name}");
+                        let (code, deps) = code_gen.generate(args);
+                        let lib = LibBuilder::build(code, deps).unwrap();
+                        libs.add_lib(lib_name.to_string(), lib);
+                        resp_tx.send(Some(RegisterResult {})).unwrap();
+                    },
+                    // spawned actors are restarted like those of a static node
                     Some(req) => {
-                        handle_job_req(req, &code_gen, &mut libs, &mut local_actors, &mut remote_actors, &actor_control_tx).await;
+                        handle_job_req(req, &libs, &mut local_actors, &mut remote_actors, &actor_control_tx, port).await;
+                        port += 1;
                     },
                     None => break,
                 }
@@ -459,81 +615,87 @@ async fn actor_control_loop<CG: CodeGenerator + Send>(
     }
 }
 
-#[cfg(feature = "dynop")]
-async fn handle_job_req<CG: CodeGenerator + Send>(
-    req: JobControllerReq,
-    code_gen: &CG,
-    op_lib: &mut OpLibrary,
-    local_actors: &mut HashMap<ActorAddr, LocalActor>,
-    remote_actors: &mut HashMap<ActorAddr, RemoteActor>,
-    actor_contrl_tx: &Sender<ControlReq>,
-) {
-    match req {
-        JobControllerReq::RegisterOps {
-            args,
-            resp_tx,
-            lib_name,
-        } => {
-            log::info!("[Node] Registering Op from lib: {lib_name}");
-            let (code, deps) = code_gen.generate(args);
-            let lib = LibBuilder::build(code, deps).unwrap();
-            op_lib.add_lib(lib_name.to_string(), lib);
-            resp_tx.send(Some(RegisterResult {})).unwrap();
-        }
-        JobControllerReq::SpawnActor {
-            addr,
-            op_name,
-            resp_tx,
-            lib_name,
-            payload,
-        } => {
-            log::info!("[Node] Spawing Actor {addr} with op: {op_name}");
-            let (control_tx, control_rx) = channel(20);
-
-            let lib = op_lib.get_lib(&lib_name);
-            unsafe {
-                let shared_logger: libloading::Symbol<SetupSharedLogger> =
-                    lib.get(b"setup_shared_logger_ref").unwrap();
-                let logger = SharedLogger::new();
-                shared_logger(logger);
-                let op: libloading::Symbol<ActorSpawnCB> = lib.get(op_name.as_bytes()).unwrap();
-                op(
-                    addr,
-                    NodeComm {
-                        controller_rx: control_rx,
-                        controller_tx: actor_contrl_tx.clone(),
-                    },
-                    payload,
-                );
-                let port: u16 = 6000;
-                resp_tx.send(Some(SpawnResult { port })).unwrap();
-                control_tx
-                    .send(ControlInst::StartTcpRecv(port))
-                    .await
-                    .unwrap();
-                local_actors.insert(addr, LocalActor { handle: control_tx });
-            }
-        }
-        JobControllerReq::RemoteActorAdded { addr, sock_addr } => {
-            log::info!("[Node] Remote Actor {addr} Added");
-            remote_actors.insert(
-                addr,
-                RemoteActor {
-                    remote_actor_addr: sock_addr,
-                },
-            );
-        }
-        JobControllerReq::StopActor { addr } => {
-            if let Some(actor) = local_actors.remove(&addr) {
-                log::info!("[Node] Stopping Actor {addr}");
-                actor.handle.send(ControlInst::Stop).await.unwrap();
-            }
-        }
-        JobControllerReq::StopAllActors => {
-            for (name, actor) in local_actors.drain() {
-                log::info!("[Node] Stopping Actor {name}");
-                actor.handle.send(ControlInst::Stop).await.unwrap();
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_control_skips_an_actor_waiting_for_its_restart() {
+        // the actor's tasks are gone, as after a panic
+        let (handle, _) = channel(1);
+        let actor = LocalActor {
+            handle,
+            spawn: ActorSpawn {
+                lib_name: "lib".to_string(),
+                op_name: "op".to_string(),
+                payload: HashMap::new(),
+                port: 6000,
+            },
+            supervisor: Supervisor::new(RestartPolicy::Backoff {
+                initial_backoff_ms: 60_000,
+                max_backoff_ms: 60_000,
+                max_restarts: 1,
+            }),
+        };
+        let mut local_actors = HashMap::from([("actor".to_string(), actor)]);
+        let (restart_tx, mut restart_rx) = unbounded_channel();
+        handle_actor_exit(
+            "actor".to_string(),
+            Exit::Panicked("boom".to_string()),
+            &mut local_actors,
+            &restart_tx,
+        );
+        assert!(local_actors.contains_key("actor"), "waits out its backoff");
+
+        let actor_name = || "actor".to_string();
+        let reqs = vec![
+            JobControllerReq::AddPrio {
+                actor_name: actor_name(),
+            },
+            JobControllerReq::RemoveLowPrio {
+                actor_name: actor_name(),
+            },
+            JobControllerReq::MsgLoss {
+                actor_name: actor_name(),
+                probability: 0.5,
+            },
+            JobControllerReq::MsgDuplication {
+                actor_name: actor_name(),
+                factor: 2,
+                probability: 0.5,
+            },
+            JobControllerReq::MsgDelay {
+                actor_name: actor_name(),
+                delay_range_ms: (1, 2),
+                senders: vec![],
+            },
+            JobControllerReq::DisableMsgLoss {
+                actor_name: actor_name(),
+            },
+            JobControllerReq::DisableMsgDuplication {
+                actor_name: actor_name(),
+            },
+            JobControllerReq::DisableMsgDelay {
+                actor_name: actor_name(),
+                senders: vec![],
+            },
+        ];
+        let (actor_control_tx, _actor_control_rx) = channel(1);
+        for req in reqs {
+            handle_job_req(
+                req,
+                &OpLibrary::default(),
+                &mut local_actors,
+                &mut HashMap::new(),
+                &actor_control_tx,
+                6001,
+            )
+            .await;
         }
+        assert!(local_actors.contains_key("actor"));
+        assert!(
+            restart_rx.try_recv().is_err(),
+            "the restart is still pending"
+        );
     }
 }
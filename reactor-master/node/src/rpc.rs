@@ -24,7 +24,7 @@ use utoipa::{OpenApi, ToSchema};
 #[cfg(feature = "swagger")]
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::JobControllerReq;
+use crate::{JobControllerReq, supervisor::RestartPolicy};
 
 #[derive(Clone)]
 struct AppState {
@@ -38,6 +38,9 @@ pub(crate) struct SpawnArgs {
     pub operator_name: String,
     pub lib_name: String,
     pub payload: HashMap<String, Value>,
+    /// What to do when the actor panics or fails; it is not restarted by default.
+    #[serde(default)]
+    pub restart: RestartPolicy,
 }
 
 #[cfg_attr(feature = "swagger", derive(ToSchema))]
@@ -54,6 +57,14 @@ pub(crate) struct RemoteActorInfo {
 //     pub actor_name: String,
 // }
 
+#[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DrainActorRequest {
+    pub actor_name: String,
+    /// Time the actor gets to send its outgoing messages, from the request on.
+    pub deadline_ms: u64,
+}
+
 #[cfg_attr(feature = "swagger", derive(utoipa::ToSchema))]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MsgLossRequest {
@@ -159,6 +170,7 @@ async fn start_actor(
             op_name: args.operator_name,
             lib_name: args.lib_name,
             payload: args.payload,
+            restart: args.restart,
         })
         .unwrap();
     let status = rx.await.unwrap();
@@ -258,6 +270,36 @@ async fn stop_actor(
     )
 }
 
+#[cfg_attr(feature="swagger", utoipa::path(
+    post,
+    path = "/drain_actor",
+    request_body(
+        content = DrainActorRequest,
+        description = "Actor to stop once its inbox is processed and its messages are sent",
+        content_type = "application/json"
+    ),
+    responses(
+        (status = 200, description = "Actor drain initiated")
+    )
+))]
+async fn drain_actor(
+    State(state): State<Arc<AppState>>,
+    Json(drain_request): Json<DrainActorRequest>,
+) -> impl IntoResponse {
+    state
+        .clone()
+        .tx
+        .send(JobControllerReq::DrainActor {
+            addr: drain_request.actor_name.clone(),
+            deadline_ms: drain_request.deadline_ms,
+        })
+        .unwrap();
+    (
+        axum::http::StatusCode::OK,
+        format!("Actor {} Draining!", drain_request.actor_name),
+    )
+}
+
 #[cfg_attr(feature="swagger", utoipa::path(
     post,
     path = "/stop_all_actors",
@@ -274,6 +316,48 @@ async fn stop_all_actors(State(state): State<Arc<AppState>>) -> impl IntoRespons
     (axum::http::StatusCode::OK, "Actors Stopped!")
 }
 
+#[cfg_attr(feature="swagger", utoipa::path(
+    post,
+    path = "/add_prio",
+    responses(
+        (status = 200, description = "Priority added below the actor's lowest")
+    )
+))]
+async fn add_prio(
+    State(state): State<Arc<AppState>>,
+    Json(actor_addr): Json<String>,
+) -> impl IntoResponse {
+    state
+        .clone()
+        .tx
+        .send(JobControllerReq::AddPrio {
+            actor_name: actor_addr,
+        })
+        .unwrap();
+    (axum::http::StatusCode::OK, "Priority Added!")
+}
+
+#[cfg_attr(feature="swagger", utoipa::path(
+    post,
+    path = "/remove_low_prio",
+    responses(
+        (status = 200, description = "Actor's lowest priority removed once drained")
+    )
+))]
+async fn remove_low_prio(
+    State(state): State<Arc<AppState>>,
+    Json(actor_addr): Json<String>,
+) -> impl IntoResponse {
+    state
+        .clone()
+        .tx
+        .send(JobControllerReq::RemoveLowPrio {
+            actor_name: actor_addr,
+        })
+        .unwrap();
+    (axum::http::StatusCode::OK, "Priority Removed!")
+}
+
 #[cfg_attr(feature="swagger", utoipa::path(
     post,
     path = "/set_duplication",
@@ -443,7 +527,10 @@ async fn get_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     actor_added,
     register_lib,
     stop_actor,
+    drain_actor,
     stop_all_actors,
+    add_prio,
+    remove_low_prio,
     set_duplication,
     set_msg_loss,
     set_msg_delay,
@@ -462,7 +549,10 @@ pub async fn webserver(job_control_tx: UnboundedSender<JobControllerReq>, port:
         .route("/actor_added", post(actor_added))
         .route("/register_lib", post(register_lib))
         .route("/stop_actor", post(stop_actor))
+        .route("/drain_actor", post(drain_actor))
         .route("/stop_all_actors", post(stop_all_actors))
+        .route("/add_prio", post(add_prio))
+        .route("/remove_low_prio", post(remove_low_prio))
         .route("/set_duplication", post(set_duplication))
         .route("/set_msg_loss", post(set_msg_loss))
         .route("/set_msg_delay", post(set_msg_delay))
@@ -516,3 +606,45 @@ pub async fn webserver(job_control_tx: UnboundedSender<JobControllerReq>, port:
         .unwrap();
     axum::serve(listener, app).await.unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use reactor_client::apis::{configuration::Configuration, default_api};
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_priority_routes_reach_the_controller() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let (tx, mut rx) = unbounded_channel();
+        tokio::spawn(webserver(tx, port));
+        let config = Configuration {
+            base_path: format!("http://127.0.0.1:{port}"),
+            ..Default::default()
+        };
+
+        // the server is up once its first request goes through
+        let mut tries = 0;
+        while let Err(e) = default_api::add_prio(&config, "actor").await {
+            tries += 1;
+            assert!(tries < 100, "the node never served /add_prio: {e}");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(matches!(
+            rx.recv().await,
+            Some(JobControllerReq::AddPrio { actor_name }) if actor_name == "actor"
+        ));
+        default_api::remove_low_prio(&config, "actor")
+            .await
+            .unwrap();
+        assert!(matches!(
+            rx.recv().await,
+            Some(JobControllerReq::RemoveLowPrio { actor_name }) if actor_name == "actor"
+        ));
+    }
+}
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "swagger")]
+use utoipa::ToSchema;
+
+/// What the node controller does when one of its actors panics or fails. An actor that was
+/// stopped is never restarted.
+#[cfg_attr(feature = "swagger", derive(ToSchema))]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub(crate) enum RestartPolicy {
+    /// The actor is dropped from the node.
+    #[default]
+    Never,
+    /// The actor is re-created at once, however often it fails.
+    Always,
+    /// The actor is re-created after a delay doubling from `initial_backoff_ms` up to
+    /// `max_backoff_ms`, and dropped after `max_restarts` restarts.
+    Backoff {
+        initial_backoff_ms: u64,
+        max_backoff_ms: u64,
+        max_restarts: u32,
+    },
+}
+
+/// Restarts left to an actor under its policy.
+#[derive(Debug)]
+pub(crate) struct Supervisor {
+    policy: RestartPolicy,
+    restarts: u32,
+}
+
+impl Supervisor {
+    pub(crate) fn new(policy: RestartPolicy) -> Self {
+        Supervisor {
+            policy,
+            restarts: 0,
+        }
+    }
+
+    /// The delay before the next restart, or `None` once the actor should stay down.
+    pub(crate) fn next_restart(&mut self) -> Option<Duration> {
+        let delay = match self.policy {
+            RestartPolicy::Never => return None,
+            RestartPolicy::Always => Duration::ZERO,
+            RestartPolicy::Backoff {
+                initial_backoff_ms,
+                max_backoff_ms,
+                max_restarts,
+            } => {
+                if self.restarts >= max_restarts {
+                    return None;
+                }
+                let delay_ms = initial_backoff_ms.saturating_mul(1 << self.restarts.min(32));
+                Duration::from_millis(delay_ms.min(max_backoff_ms))
+            }
+        };
+        self.restarts += 1;
+        Some(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_never_and_always() {
+        let mut never = Supervisor::new(RestartPolicy::Never);
+        assert_eq!(never.next_restart(), None);
+
+        let mut always = Supervisor::new(RestartPolicy::Always);
+        for _ in 0..100 {
+            assert_eq!(always.next_restart(), Some(Duration::ZERO));
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_its_cap_then_gives_up() {
+        let mut supervisor = Supervisor::new(RestartPolicy::Backoff {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+            max_restarts: 6,
+        });
+        let delays: Vec<_> = std::iter::from_fn(|| supervisor.next_restart())
+            .map(|d| d.as_millis())
+            .collect();
+        assert_eq!(delays, vec![100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(supervisor.next_restart(), None, "stays down");
+    }
+
+    #[test]
+    fn test_backoff_does_not_overflow() {
+        let mut supervisor = Supervisor::new(RestartPolicy::Backoff {
+            initial_backoff_ms: u64::MAX / 2,
+            max_backoff_ms: u64::MAX,
+            max_restarts: 40,
+        });
+        let delays: Vec<_> = std::iter::from_fn(|| supervisor.next_restart()).collect();
+        assert_eq!(delays.len(), 40);
+        assert_eq!(delays[39], Duration::from_millis(u64::MAX));
+    }
+}
@@ -20,6 +20,20 @@ pub enum ActorAddedError {
     UnknownValue(serde_json::Value),
 }
 
+/// struct for typed errors of method [`add_prio`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AddPrioError {
+    UnknownValue(serde_json::Value),
+}
+
+/// struct for typed errors of method [`drain_actor`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DrainActorError {
+    UnknownValue(serde_json::Value),
+}
+
 /// struct for typed errors of method [`get_status`]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -36,6 +50,13 @@ pub enum RegisterLibError {
     UnknownValue(serde_json::Value),
 }
 
+/// struct for typed errors of method [`remove_low_prio`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RemoveLowPrioError {
+    UnknownValue(serde_json::Value),
+}
+
 /// struct for typed errors of method [`set_duplication`]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -135,6 +156,76 @@ pub async fn actor_added(
     }
 }
 
+pub async fn add_prio(
+    configuration: &configuration::Configuration,
+    body: &str,
+) -> Result<(), Error<AddPrioError>> {
+    // add a prefix to parameters to efficiently prevent name collisions
+    let p_body = body;
+
+    let uri_str = format!("{}/add_prio", configuration.base_path);
+    let mut req_builder = configuration
+        .client
+        .request(reqwest::Method::POST, &uri_str);
+
+    if let Some(ref user_agent) = configuration.user_agent {
+        req_builder = req_builder.header(reqwest::header::USER_AGENT, user_agent.clone());
+    }
+    req_builder = req_builder.json(&p_body);
+
+    let req = req_builder.build()?;
+    let resp = configuration.client.execute(req).await?;
+
+    let status = resp.status();
+
+    if !status.is_client_error() && !status.is_server_error() {
+        Ok(())
+    } else {
+        let content = resp.text().await?;
+        let entity: Option<AddPrioError> = serde_json::from_str(&content).ok();
+        Err(Error::ResponseError(ResponseContent {
+            status,
+            content,
+            entity,
+        }))
+    }
+}
+
+pub async fn drain_actor(
+    configuration: &configuration::Configuration,
+    drain_actor_request: models::DrainActorRequest,
+) -> Result<(), Error<DrainActorError>> {
+    // add a prefix to parameters to efficiently prevent name collisions
+    let p_drain_actor_request = drain_actor_request;
+
+    let uri_str = format!("{}/drain_actor", configuration.base_path);
+    let mut req_builder = configuration
+        .client
+        .request(reqwest::Method::POST, &uri_str);
+
+    if let Some(ref user_agent) = configuration.user_agent {
+        req_builder = req_builder.header(reqwest::header::USER_AGENT, user_agent.clone());
+    }
+    req_builder = req_builder.json(&p_drain_actor_request);
+
+    let req = req_builder.build()?;
+    let resp = configuration.client.execute(req).await?;
+
+    let status = resp.status();
+
+    if !status.is_client_error() && !status.is_server_error() {
+        Ok(())
+    } else {
+        let content = resp.text().await?;
+        let entity: Option<DrainActorError> = serde_json::from_str(&content).ok();
+        Err(Error::ResponseError(ResponseContent {
+            status,
+            content,
+            entity,
+        }))
+    }
+}
+
 pub async fn get_status(
     configuration: &configuration::Configuration,
 ) -> Result<models::StatusResponse, Error<GetStatusError>> {
@@ -209,6 +300,41 @@ pub async fn register_lib(
     }
 }
 
+pub async fn remove_low_prio(
+    configuration: &configuration::Configuration,
+    body: &str,
+) -> Result<(), Error<RemoveLowPrioError>> {
+    // add a prefix to parameters to efficiently prevent name collisions
+    let p_body = body;
+
+    let uri_str = format!("{}/remove_low_prio", configuration.base_path);
+    let mut req_builder = configuration
+        .client
+        .request(reqwest::Method::POST, &uri_str);
+
+    if let Some(ref user_agent) = configuration.user_agent {
+        req_builder = req_builder.header(reqwest::header::USER_AGENT, user_agent.clone());
+    }
+    req_builder = req_builder.json(&p_body);
+
+    let req = req_builder.build()?;
+    let resp = configuration.client.execute(req).await?;
+
+    let status = resp.status();
+
+    if !status.is_client_error() && !status.is_server_error() {
+        Ok(())
+    } else {
+        let content = resp.text().await?;
+        let entity: Option<RemoveLowPrioError> = serde_json::from_str(&content).ok();
+        Err(Error::ResponseError(ResponseContent {
+            status,
+            content,
+            entity,
+        }))
+    }
+}
+
 pub async fn set_duplication(
     configuration: &configuration::Configuration,
     msg_duplication_request: models::MsgDuplicationRequest,
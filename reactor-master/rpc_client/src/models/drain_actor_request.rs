@@ -0,0 +1,30 @@
+/*
+ * reactor-node
+ *
+ * No description provided (generated by Openapi Generator https://github.com/openapitools/openapi-generator)
+ *
+ * The version of the OpenAPI document: 0.1.0
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DrainActorRequest {
+    #[serde(rename = "actor_name")]
+    pub actor_name: String,
+    /// Time the actor gets to send its outgoing messages, from the request on.
+    #[serde(rename = "deadline_ms")]
+    pub deadline_ms: i64,
+}
+
+impl DrainActorRequest {
+    pub fn new(actor_name: String, deadline_ms: i64) -> DrainActorRequest {
+        DrainActorRequest {
+            actor_name,
+            deadline_ms,
+        }
+    }
+}
@@ -1,5 +1,7 @@
 pub mod disable_msg_delay_request;
 pub use self::disable_msg_delay_request::DisableMsgDelayRequest;
+pub mod drain_actor_request;
+pub use self::drain_actor_request::DrainActorRequest;
 pub mod msg_delay_request;
 pub use self::msg_delay_request::MsgDelayRequest;
 pub mod msg_duplication_request;
@@ -10,6 +12,8 @@ pub mod registration_args;
 pub use self::registration_args::RegistrationArgs;
 pub mod remote_actor_info;
 pub use self::remote_actor_info::RemoteActorInfo;
+pub mod restart_policy;
+pub use self::restart_policy::RestartPolicy;
 pub mod spawn_args;
 pub use self::spawn_args::SpawnArgs;
 pub mod status_response;
@@ -0,0 +1,32 @@
+/*
+ * reactor-node
+ *
+ * No description provided (generated by Openapi Generator https://github.com/openapitools/openapi-generator)
+ *
+ * The version of the OpenAPI document: 0.1.0
+ *
+ * Generated by: https://openapi-generator.tech
+ */
+
+use crate::models;
+use serde::{Deserialize, Serialize};
+
+/// RestartPolicy : What the node controller does when one of its actors panics or fails. An actor that was stopped is never restarted.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "policy")]
+pub enum RestartPolicy {
+    #[serde(rename = "never")]
+    #[default]
+    Never,
+    #[serde(rename = "always")]
+    Always,
+    #[serde(rename = "backoff")]
+    Backoff {
+        #[serde(rename = "initial_backoff_ms")]
+        initial_backoff_ms: i64,
+        #[serde(rename = "max_backoff_ms")]
+        max_backoff_ms: i64,
+        #[serde(rename = "max_restarts")]
+        max_restarts: i32,
+    },
+}
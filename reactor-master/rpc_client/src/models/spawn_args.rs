@@ -21,6 +21,8 @@ pub struct SpawnArgs {
     pub operator_name: String,
     #[serde(rename = "payload")]
     pub payload: std::collections::HashMap<String, serde_json::Value>,
+    #[serde(rename = "restart", skip_serializing_if = "Option::is_none")]
+    pub restart: Option<Box<models::RestartPolicy>>,
 }
 
 impl SpawnArgs {
@@ -35,6 +37,7 @@ impl SpawnArgs {
             lib_name,
             operator_name,
             payload,
+            restart: None,
         }
     }
 }